@@ -0,0 +1,17 @@
+#![no_main]
+
+use alfis_core::Block;
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    if let Ok(block) = Block::from_untrusted_bytes(data) {
+        // The node uses these before the block is checked by the chain
+        let _ = block.is_genesis();
+        let _ = block.is_better_than(&block);
+        let _ = block.as_bytes();
+        let _ = block.as_bytes_compact();
+        if let Some(transaction) = &block.transaction {
+            let _ = transaction.get_domain_data();
+        }
+    }
+});
@@ -0,0 +1,11 @@
+#![no_main]
+
+use alfis_core::Transaction;
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|json: &str| {
+    if let Ok(transaction) = Transaction::from_untrusted_json(json) {
+        let _ = transaction.get_domain_data();
+        let _ = transaction.to_string();
+    }
+});
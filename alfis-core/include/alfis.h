@@ -0,0 +1,41 @@
+/*
+ * C API of ALFIS to resolve its names, the library (libalfis_ffi.so, alfis_ffi.dll) is built by:
+ * cargo build -p alfis-ffi --release
+ */
+#ifndef ALFIS_H
+#define ALFIS_H
+
+#include <stddef.h>
+#include <stdint.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+/* Wrong arguments, like NULL pointers or bad address */
+#define ALFIS_ERROR_ARGS (-1)
+/* Records don't fit in the buffer */
+#define ALFIS_ERROR_BUFFER (-2)
+/* Node didn't answer or database can't be opened */
+#define ALFIS_ERROR_RESOLVE (-3)
+/* Unexpected error inside the library, like a damaged database */
+#define ALFIS_ERROR_INTERNAL (-4)
+
+/* Resolve names by DNS server of the node at this address, like "127.0.0.1:53", it is the default. Returns 0 or error */
+int alfis_use_node(const char *addr);
+
+/* Resolve names from the database of a node, like "/var/lib/alfis/blockchain.db", it is opened read-only. Returns 0 or error */
+int alfis_open_db(const char *path);
+
+/*
+ * Resolves the name and writes data of found records of this type (1 for A, 28 for AAAA and so on) to out_buf,
+ * as text separated by '\n' and ended by NUL, like "10.0.0.1\n10.0.0.2".
+ * Returns count of records, 0 if nothing is found, or negative ALFIS_ERROR_* code.
+ */
+int alfis_resolve(const char *name, uint16_t type, char *out_buf, size_t out_len);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif /* ALFIS_H */
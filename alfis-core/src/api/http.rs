@@ -0,0 +1,166 @@
+//! Minimal HTTP/1.1 for the API: one request per connection, bodies only with `Content-Length`.
+
+use std::collections::HashMap;
+use std::io::{self, BufRead, BufReader, Error, ErrorKind, Read, Write};
+use std::net::{SocketAddr, TcpStream};
+use std::time::Duration;
+
+use serde::Serialize;
+
+/// Maximal size of request body that we accept
+pub const MAX_BODY: usize = 65536;
+/// Maximal count of headers in request
+const MAX_HEADERS: usize = 64;
+/// Timeout of requests that we send to our API from command line
+const CLIENT_TIMEOUT: Duration = Duration::from_secs(10);
+
+#[derive(Debug, PartialEq)]
+pub struct Request {
+    pub method: String,
+    /// Path without query string
+    pub path: String,
+    /// Query string without `?`
+    pub query: String,
+    /// Names of headers are in lower case
+    pub headers: HashMap<String, String>,
+    pub body: Vec<u8>
+}
+
+impl Request {
+    pub fn read<R: BufRead>(reader: &mut R) -> io::Result<Request> {
+        let mut line = String::new();
+        reader.read_line(&mut line)?;
+        let mut parts = line.split_whitespace();
+        let (method, target) = match (parts.next(), parts.next(), parts.next()) {
+            (Some(method), Some(target), Some(version)) if version.starts_with("HTTP/1.") => (method.to_owned(), target),
+            _ => return Err(Error::new(ErrorKind::InvalidData, "wrong request line"))
+        };
+        let (path, query) = match target.split_once('?') {
+            Some((path, query)) => (path.to_owned(), query.to_owned()),
+            None => (target.to_owned(), String::new())
+        };
+
+        let mut headers = HashMap::new();
+        loop {
+            let mut line = String::new();
+            if reader.read_line(&mut line)? == 0 {
+                return Err(Error::new(ErrorKind::UnexpectedEof, "no end of headers"));
+            }
+            let line = line.trim_end();
+            if line.is_empty() {
+                break;
+            }
+            if headers.len() >= MAX_HEADERS {
+                return Err(Error::new(ErrorKind::InvalidData, "too many headers"));
+            }
+            if let Some((name, value)) = line.split_once(':') {
+                headers.insert(name.trim().to_lowercase(), value.trim().to_owned());
+            }
+        }
+
+        let length = match headers.get("content-length") {
+            Some(length) => length.parse::<usize>().map_err(|_| Error::new(ErrorKind::InvalidData, "wrong content length"))?,
+            None => 0
+        };
+        if length > MAX_BODY {
+            return Err(Error::new(ErrorKind::InvalidData, "body is too big"));
+        }
+        let mut body = vec![0u8; length];
+        reader.read_exact(&mut body)?;
+        Ok(Request { method, path, query, headers, body })
+    }
+
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers.get(name).map(|value| value.as_str())
+    }
+
+    /// Returns the value of parameter from query string, values are not URL-decoded
+    pub fn param(&self, name: &str) -> Option<&str> {
+        self.query.split('&').find_map(|pair| pair.split_once('=').filter(|(key, _)| *key == name).map(|(_, value)| value))
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub struct Response {
+    pub status: u16,
+    pub content_type: &'static str,
+    pub body: Vec<u8>
+}
+
+impl Response {
+    pub fn json<T: Serialize>(status: u16, value: &T) -> Self {
+        Response { status, content_type: "application/json", body: serde_json::to_vec(value).unwrap() }
+    }
+
+    pub fn error(status: u16, text: &str) -> Self {
+        Response::json(status, &serde_json::json!({ "error": text }))
+    }
+
+    pub fn write<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        write!(writer, "HTTP/1.1 {} {}\r\n", self.status, reason(self.status))?;
+        write!(writer, "Content-Type: {}\r\nContent-Length: {}\r\n", self.content_type, self.body.len())?;
+        // Dashboards can read our data from any page, but POST needs JSON content type and so a preflight that we don't allow
+        write!(writer, "Access-Control-Allow-Origin: *\r\nConnection: close\r\n\r\n")?;
+        writer.write_all(&self.body)?;
+        writer.flush()
+    }
+}
+
+/// Sends request with JSON body to the API of running node, returns status and body of its response
+pub fn send_request(addr: &SocketAddr, method: &str, path: &str, body: &str) -> io::Result<(u16, String)> {
+    let mut stream = TcpStream::connect_timeout(addr, CLIENT_TIMEOUT)?;
+    stream.set_read_timeout(Some(CLIENT_TIMEOUT))?;
+    write!(stream, "{} {} HTTP/1.1\r\nHost: {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}", method, path, addr, body.len(), body)?;
+    stream.flush()?;
+    let mut text = String::new();
+    BufReader::new(stream).read_to_string(&mut text)?;
+    let status = text.split_whitespace().nth(1).and_then(|status| status.parse().ok());
+    match (status, text.split_once("\r\n\r\n")) {
+        (Some(status), Some((_, body))) => Ok((status, body.to_owned())),
+        _ => Err(Error::new(ErrorKind::InvalidData, "wrong response"))
+    }
+}
+
+fn reason(status: u16) -> &'static str {
+    match status {
+        200 => "OK",
+        202 => "Accepted",
+        400 => "Bad Request",
+        404 => "Not Found",
+        405 => "Method Not Allowed",
+        415 => "Unsupported Media Type",
+        503 => "Service Unavailable",
+        _ => "Unknown"
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::BufReader;
+
+    use crate::api::http::{Request, Response};
+
+    #[test]
+    fn test_request() {
+        let data = "POST /transactions?x=1 HTTP/1.1\r\nHost: localhost\r\nContent-Type: application/json\r\nContent-Length: 2\r\n\r\n{}";
+        let request = Request::read(&mut BufReader::new(data.as_bytes())).unwrap();
+        assert_eq!(request.method, "POST");
+        assert_eq!(request.path, "/transactions");
+        assert_eq!(request.param("x"), Some("1"));
+        assert_eq!(request.param("y"), None);
+        assert_eq!(request.header("content-type"), Some("application/json"));
+        assert_eq!(request.body, b"{}");
+
+        assert!(Request::read(&mut BufReader::new("GET /\r\n\r\n".as_bytes())).is_err());
+        assert!(Request::read(&mut BufReader::new("GET / HTTP/1.1\r\nContent-Length: 1000000\r\n\r\n".as_bytes())).is_err());
+    }
+
+    #[test]
+    fn test_response() {
+        let mut data = Vec::new();
+        Response::error(404, "not found").write(&mut data).unwrap();
+        let text = String::from_utf8(data).unwrap();
+        assert!(text.starts_with("HTTP/1.1 404 Not Found\r\n"));
+        assert!(text.ends_with("\r\n\r\n{\"error\":\"not found\"}"));
+    }
+}
@@ -0,0 +1,292 @@
+//! REST API for web dashboards and scripts, it is described by OpenAPI spec at `/openapi.json`:
+//! * `GET /domains/{name}` returns owner, state and data of the domain;
+//! * `GET /domains/{name}/history` returns all transactions of the domain;
+//! * `GET /domains/{name}/proof` returns the block with the last transaction of the domain and several blocks after it,
+//!   light clients check them by `alfis-verify`;
+//! * `GET /blocks` returns the last blocks, or blocks before `?before=height`;
+//! * `GET /blocks/{height}` returns the block;
+//! * `GET /zones` returns zones with counts of their alive domains;
+//! * `GET /rules` returns states of new rules that are activated by signals of miners;
+//! * `POST /transactions` takes domain transaction prepared by its owner (like from "Export for a friend to mine") and mines it;
+//! * `GET /events` is a WebSocket that streams events of the node;
+//! * `GET /explorer` is a web page to browse the chain by this API, if `explorer` is enabled in settings;
+//! * `GET /stats/queries` returns timings of DB queries, if `query_stats` is enabled in settings.
+
+use std::collections::BTreeMap;
+use std::io::{BufReader, Read};
+use std::net::{TcpListener, TcpStream};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+use chrono::Utc;
+#[allow(unused_imports)]
+use log::{debug, error, info, trace, warn};
+use serde::Serialize;
+
+use crate::api::http::{Request, Response, MAX_BODY};
+use crate::blockchain::activation::{window_start, RuleState, RULES};
+use crate::blockchain::transaction::{DomainData, DomainState};
+use crate::blockchain::types::MineResult;
+use crate::light::PROOF_BLOCKS;
+use crate::{Block, Bytes, Chain, ChainRef, Context, Miner, Transaction};
+
+pub mod http;
+pub mod websocket;
+
+/// OpenAPI 3 description of the API
+pub const OPENAPI_SPEC: &str = include_str!("openapi.json");
+/// Page of block explorer, it uses only this API
+const EXPLORER_PAGE: &str = include_str!("explorer.html");
+/// How many blocks we give in one list
+pub const MAX_BLOCKS: u64 = 50;
+/// How long we wait for slow clients
+const TIMEOUT: Duration = Duration::from_secs(10);
+
+/// What we know about the domain
+#[derive(Serialize)]
+pub struct DomainInfo {
+    pub name: String,
+    pub owner: Bytes,
+    pub state: DomainState,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub data: Option<DomainData>
+}
+
+/// State of a new rule, see `blockchain::activation`
+#[derive(Serialize)]
+pub struct RuleInfo {
+    pub name: &'static str,
+    pub bit: u8,
+    pub state: RuleState,
+    /// Height of the first block in this state
+    pub since: u64,
+    /// How many blocks of the current window signal the rule
+    pub signalled: u64,
+    pub window: u64
+}
+
+/// One transaction from the history of domain
+#[derive(Serialize)]
+pub struct DomainChange {
+    pub index: u64,
+    pub timestamp: i64,
+    pub owner: Bytes,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub data: Option<DomainData>
+}
+
+/// Zone with the count of its domains that are not expired
+#[derive(Serialize)]
+pub struct ZoneInfo {
+    pub name: String,
+    pub yggdrasil: bool,
+    pub domains: usize
+}
+
+/// Starts HTTP server for the API if it is enabled in settings
+pub fn start_api_server(context: Arc<Mutex<Context>>, miner: Arc<Mutex<Miner>>) {
+    let listen = context.lock().unwrap().settings.api.listen.clone();
+    if listen.is_empty() {
+        return;
+    }
+    let listener = match TcpListener::bind(&listen) {
+        Ok(listener) => listener,
+        Err(e) => {
+            error!("Unable to start API server on {}: {}", &listen, e);
+            return;
+        }
+    };
+    info!("API server listens on http://{}", &listen);
+    let _ = thread::Builder::new().name(String::from("api")).spawn(move || {
+        for stream in listener.incoming().flatten() {
+            let context = Arc::clone(&context);
+            let miner = Arc::clone(&miner);
+            let _ = thread::Builder::new().name(String::from("api-client")).spawn(move || {
+                handle_client(stream, &context, &miner);
+            });
+        }
+    });
+}
+
+fn handle_client(stream: TcpStream, context: &Arc<Mutex<Context>>, miner: &Arc<Mutex<Miner>>) {
+    let _ = stream.set_read_timeout(Some(TIMEOUT));
+    let mut writer = match stream.try_clone() {
+        Ok(writer) => writer,
+        Err(_) => return
+    };
+    // Headers can't make the request much bigger than the body
+    let mut reader = BufReader::new(stream.take((MAX_BODY * 2) as u64));
+    let response = match Request::read(&mut reader) {
+        Ok(request) if request.path == "/events" && websocket::is_upgrade(&request) => {
+            websocket::serve_events(&request, writer);
+            return;
+        }
+        Ok(request) => {
+            debug!("API request {} {}", &request.method, &request.path);
+            route(&request, context, miner)
+        }
+        Err(e) => Response::error(400, &e.to_string())
+    };
+    let _ = response.write(&mut writer);
+}
+
+fn route(request: &Request, context: &Arc<Mutex<Context>>, miner: &Arc<Mutex<Miner>>) -> Response {
+    let parts: Vec<&str> = request.path.trim_matches('/').split('/').collect();
+    let chain = ChainRef::new(Arc::clone(context));
+    match (request.method.as_str(), parts.as_slice()) {
+        ("GET", ["openapi.json"]) => Response { status: 200, content_type: "application/json", body: OPENAPI_SPEC.as_bytes().to_vec() },
+        ("GET", ["explorer"]) => get_explorer(context),
+        ("GET", ["domains", name]) => chain.read(|chain| get_domain(chain, name)),
+        ("GET", ["domains", name, "history"]) => chain.read(|chain| get_domain_history(chain, name)),
+        ("GET", ["domains", name, "proof"]) => chain.read(|chain| get_domain_proof(chain, name)),
+        ("GET", ["blocks"]) => chain.read(|chain| get_blocks(chain, request.param("before"))),
+        ("GET", ["blocks", height]) => chain.read(|chain| get_block(chain, height)),
+        ("GET", ["zones"]) => chain.read(get_zones),
+        ("GET", ["rules"]) => chain.read(get_rules),
+        ("POST", ["transactions"]) => post_transaction(request, miner),
+        ("GET", ["events"]) => Response::error(400, "use WebSocket to get events"),
+        ("GET", ["stats", "queries"]) => chain.read(get_query_stats),
+        (_, ["openapi.json"]) | (_, ["explorer"]) | (_, ["domains", _]) | (_, ["domains", _, "history"]) | (_, ["domains", _, "proof"]) | (_, ["blocks"]) | (_, ["blocks", _]) |
+        (_, ["zones"]) | (_, ["rules"]) | (_, ["transactions"]) | (_, ["events"]) | (_, ["stats", "queries"]) => Response::error(405, "method not allowed"),
+        _ => Response::error(404, "not found")
+    }
+}
+
+fn get_domain(chain: &Chain, name: &str) -> Response {
+    match get_domain_info(chain, name) {
+        Some(info) => Response::json(200, &info),
+        None => Response::error(404, "domain not found")
+    }
+}
+
+pub fn get_domain_info(chain: &Chain, name: &str) -> Option<DomainInfo> {
+    let name = name.to_lowercase();
+    match chain.get_domain_transaction_and_state(&name) {
+        (Some(transaction), state) => {
+            let data = transaction.get_domain_data();
+            Some(DomainInfo { name, owner: transaction.signing, state, data })
+        }
+        (None, _) => None
+    }
+}
+
+fn get_explorer(context: &Arc<Mutex<Context>>) -> Response {
+    if !context.lock().unwrap().settings.api.explorer {
+        return Response::error(404, "explorer is disabled");
+    }
+    Response { status: 200, content_type: "text/html; charset=utf-8", body: EXPLORER_PAGE.as_bytes().to_vec() }
+}
+
+fn get_query_stats(chain: &Chain) -> Response {
+    if !chain.is_timing_queries() {
+        return Response::error(404, "query stats are disabled");
+    }
+    Response::json(200, &chain.get_query_stats())
+}
+
+fn get_rules(chain: &Chain) -> Response {
+    let index = chain.get_height() + 1;
+    let window = chain.get_kind().activation_window();
+    let rules: Vec<RuleInfo> = RULES.iter()
+        .map(|rule| {
+            let (state, since) = chain.get_rule_state(rule, index);
+            let signalled = chain.count_signals(rule.mask(), window_start(index, window), index);
+            RuleInfo { name: rule.name, bit: rule.bit, state, since, signalled, window }
+        })
+        .collect();
+    Response::json(200, &rules)
+}
+
+fn get_domain_history(chain: &Chain, name: &str) -> Response {
+    let history: Vec<DomainChange> = chain.get_domain_history(&name.to_lowercase())
+        .into_iter()
+        .map(|(index, timestamp, transaction)| {
+            let data = transaction.get_domain_data();
+            DomainChange { index, timestamp, owner: transaction.signing, data }
+        })
+        .collect();
+    if history.is_empty() {
+        return Response::error(404, "domain not found");
+    }
+    Response::json(200, &history)
+}
+
+fn get_domain_proof(chain: &Chain, name: &str) -> Response {
+    let index = match chain.get_domain_history(&name.to_lowercase()).first() {
+        Some((index, _, _)) => *index,
+        None => return Response::error(404, "domain not found")
+    };
+    let last = (index + PROOF_BLOCKS).min(chain.get_height());
+    match (index..=last).map(|index| chain.get_block(index)).collect::<Option<Vec<Block>>>() {
+        Some(blocks) => Response::json(200, &blocks),
+        None => Response::error(404, "blocks are pruned")
+    }
+}
+
+fn get_blocks(chain: &Chain, before: Option<&str>) -> Response {
+    let before = match before.map(|before| before.parse::<u64>()) {
+        None => chain.get_height() + 1,
+        Some(Ok(before)) => before.min(chain.get_height() + 1),
+        Some(Err(_)) => return Response::error(400, "wrong height")
+    };
+    let first = before.saturating_sub(MAX_BLOCKS).max(1);
+    let blocks: Vec<Block> = (first..before).rev().filter_map(|index| chain.get_block(index)).collect();
+    Response::json(200, &blocks)
+}
+
+fn get_zones(chain: &Chain) -> Response {
+    let mut counts: BTreeMap<String, usize> = BTreeMap::new();
+    for transaction in chain.get_alive_domains(Utc::now().timestamp()) {
+        if let Some(data) = transaction.get_domain_data() {
+            *counts.entry(data.zone).or_default() += 1;
+        }
+    }
+    let zones: Vec<ZoneInfo> = chain.get_zones()
+        .iter()
+        .filter(|zone| !zone.name.is_empty())
+        .map(|zone| ZoneInfo { name: zone.name.clone(), yggdrasil: zone.yggdrasil, domains: counts.get(&zone.name).cloned().unwrap_or_default() })
+        .collect();
+    Response::json(200, &zones)
+}
+
+fn get_block(chain: &Chain, height: &str) -> Response {
+    let height = match height.parse::<u64>() {
+        Ok(height) => height,
+        Err(_) => return Response::error(400, "wrong height")
+    };
+    match chain.get_block(height) {
+        Some(block) => Response::json(200, &block),
+        None => Response::error(404, "block not found")
+    }
+}
+
+fn post_transaction(request: &Request, miner: &Arc<Mutex<Miner>>) -> Response {
+    // Browsers can't send JSON to other sites without preflight, so web pages can't make us mine
+    if !request.header("content-type").unwrap_or_default().starts_with("application/json") {
+        return Response::error(415, "content type must be application/json");
+    }
+    let transaction = match std::str::from_utf8(&request.body).ok().map(Transaction::from_untrusted_json) {
+        Some(Ok(transaction)) => transaction,
+        Some(Err(e)) => return Response::error(400, &format!("wrong transaction: {}", e)),
+        None => return Response::error(400, "wrong transaction")
+    };
+    match miner.lock().unwrap().add_transaction(transaction) {
+        MineResult::Fine => Response::json(202, &serde_json::json!({ "result": "mining" })),
+        MineResult::WrongKey => Response::error(503, "no key to sign the block"),
+        result => Response::error(400, &format!("{:?}", result))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::api::OPENAPI_SPEC;
+
+    #[test]
+    fn test_openapi_spec() {
+        let spec: serde_json::Value = serde_json::from_str(OPENAPI_SPEC).unwrap();
+        for path in ["/domains/{name}", "/domains/{name}/history", "/domains/{name}/proof", "/blocks", "/blocks/{height}", "/zones", "/rules", "/transactions", "/events", "/explorer", "/stats/queries"] {
+            assert!(spec["paths"].get(path).is_some());
+        }
+    }
+}
@@ -0,0 +1,163 @@
+//! WebSocket endpoint `/events` that streams events of the node as JSON objects with `type` field,
+//! so that dashboards and bots don't need to poll the API. We only send, messages from clients are ignored.
+
+use std::io::Write;
+use std::net::TcpStream;
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::sync::Mutex;
+use std::time::Duration;
+
+#[allow(unused_imports)]
+use log::{debug, error, info, trace, warn};
+use serde::Serialize;
+use sha1::{Digest, Sha1};
+
+use crate::api::http::Request;
+use crate::event::Event;
+use crate::eventbus::register;
+
+/// Magic string from RFC 6455 to make `Sec-WebSocket-Accept`
+const WEBSOCKET_GUID: &str = "258EAFA5-E914-47DA-95CA-C5AB0DC11B65";
+/// We send pings this often to find closed connections
+const PING_PERIOD: Duration = Duration::from_secs(30);
+const PING_FRAME: [u8; 2] = [0x89, 0x00];
+
+/// Events that we give to clients, their names are in `type` field
+#[derive(Serialize, Debug, PartialEq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ApiEvent {
+    NewBlock { index: u64, hash: String },
+    DomainConfirmed { index: u64, identity: String },
+    DomainExpiring { identity: String, owner: String, expire: i64 },
+    DomainConflict { index: u64, identity: String, owner: String, key: String },
+    NetworkStatus { blocks: u64, domains: i64, keys: i64, nodes: usize },
+    PeerConnected { addr: String, inbound: bool },
+    PeerDisconnected { addr: String },
+    SyncProgress { have: u64, height: u64 },
+    SyncFinished,
+    ClockSkew { offset: i64 },
+    MinerStarted,
+    MinerStopped { success: bool },
+    MinerProgress { speed: u64, target_diff: u32, eta: u64 }
+}
+
+impl ApiEvent {
+    /// Returns None for internal events
+    pub fn from_event(event: &Event) -> Option<Self> {
+        let event = match event.clone() {
+            Event::NewBlock { index, hash } => ApiEvent::NewBlock { index, hash },
+            Event::DomainConfirmed { index, identity } => ApiEvent::DomainConfirmed { index, identity },
+            Event::DomainExpiring { identity, owner, expire } => ApiEvent::DomainExpiring { identity, owner, expire },
+            Event::DomainConflict { index, identity, owner, key } => ApiEvent::DomainConflict { index, identity, owner, key },
+            Event::NetworkStatus { blocks, domains, keys, nodes } => ApiEvent::NetworkStatus { blocks, domains, keys, nodes },
+            Event::PeerConnected { addr, inbound } => ApiEvent::PeerConnected { addr, inbound },
+            Event::PeerDisconnected { addr } => ApiEvent::PeerDisconnected { addr },
+            Event::SyncProgress { have, height } => ApiEvent::SyncProgress { have, height },
+            Event::SyncFinished => ApiEvent::SyncFinished,
+            Event::ClockSkew { offset } => ApiEvent::ClockSkew { offset },
+            Event::MinerStarted => ApiEvent::MinerStarted,
+            Event::MinerStopped { success, .. } => ApiEvent::MinerStopped { success },
+            Event::MinerProgress { speed, target_diff, eta } => ApiEvent::MinerProgress { speed, target_diff, eta },
+            _ => return None
+        };
+        Some(event)
+    }
+}
+
+/// Checks if the client wants to open WebSocket
+pub fn is_upgrade(request: &Request) -> bool {
+    request.method == "GET" && request.header("upgrade").map(|value| value.eq_ignore_ascii_case("websocket")).unwrap_or(false)
+}
+
+/// Answers the handshake and sends events to the client until it disconnects
+pub fn serve_events(request: &Request, mut stream: TcpStream) {
+    let key = match request.header("sec-websocket-key") {
+        Some(key) => key,
+        None => return
+    };
+    let answer = format!("HTTP/1.1 101 Switching Protocols\r\nUpgrade: websocket\r\nConnection: Upgrade\r\nSec-WebSocket-Accept: {}\r\n\r\n", accept_key(key));
+    if stream.write_all(answer.as_bytes()).is_err() {
+        return;
+    }
+    let _ = stream.set_write_timeout(Some(PING_PERIOD));
+    debug!("Client subscribed to events");
+
+    // Listeners of event bus must be Sync
+    let (sender, receiver) = mpsc::channel();
+    let sender = Mutex::new(sender);
+    register(move |_uuid, e| {
+        if matches!(e, Event::ActionQuit) {
+            return false;
+        }
+        match ApiEvent::from_event(&e) {
+            // The channel is closed when the client is gone, then we remove this listener
+            Some(event) => sender.lock().unwrap().send(serde_json::to_string(&event).unwrap()).is_ok(),
+            None => true
+        }
+    });
+
+    loop {
+        let frame = match receiver.recv_timeout(PING_PERIOD) {
+            Ok(text) => text_frame(&text),
+            Err(RecvTimeoutError::Timeout) => PING_FRAME.to_vec(),
+            Err(RecvTimeoutError::Disconnected) => break
+        };
+        if stream.write_all(&frame).is_err() {
+            break;
+        }
+    }
+    debug!("Client unsubscribed from events");
+}
+
+fn accept_key(key: &str) -> String {
+    let mut digest = Sha1::new();
+    digest.update(key.as_bytes());
+    digest.update(WEBSOCKET_GUID.as_bytes());
+    base64::encode(digest.finalize())
+}
+
+/// Makes unmasked text frame, as servers send them
+fn text_frame(text: &str) -> Vec<u8> {
+    let length = text.len();
+    let mut frame = Vec::with_capacity(length + 10);
+    frame.push(0x81);
+    if length < 126 {
+        frame.push(length as u8);
+    } else if length <= u16::MAX as usize {
+        frame.push(126);
+        frame.extend_from_slice(&(length as u16).to_be_bytes());
+    } else {
+        frame.push(127);
+        frame.extend_from_slice(&(length as u64).to_be_bytes());
+    }
+    frame.extend_from_slice(text.as_bytes());
+    frame
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::api::websocket::{accept_key, text_frame, ApiEvent};
+    use crate::event::Event;
+
+    #[test]
+    fn test_accept_key() {
+        // Example from RFC 6455
+        assert_eq!(accept_key("dGhlIHNhbXBsZSBub25jZQ=="), "s3pPLMBiTxaQ9kYGzzhZRbK+xOo=");
+    }
+
+    #[test]
+    fn test_text_frame() {
+        assert_eq!(text_frame("hi"), vec![0x81, 2, b'h', b'i']);
+        let frame = text_frame(&"a".repeat(300));
+        assert_eq!(&frame[..4], &[0x81, 126, 1, 44]);
+        assert_eq!(frame.len(), 304);
+    }
+
+    #[test]
+    fn test_events() {
+        let event = ApiEvent::from_event(&Event::NewBlock { index: 5, hash: String::from("AB") }).unwrap();
+        assert_eq!(serde_json::to_string(&event).unwrap(), r#"{"type":"new_block","index":5,"hash":"AB"}"#);
+        assert_eq!(serde_json::to_string(&ApiEvent::SyncFinished).unwrap(), r#"{"type":"sync_finished"}"#);
+        assert!(ApiEvent::from_event(&Event::ActionQuit).is_none());
+    }
+}
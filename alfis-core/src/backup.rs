@@ -0,0 +1,356 @@
+//! Scheduled backups of key files to a directory from settings, and to an optional secondary directory,
+//! like a mounted USB drive. Every backup is a directory with copies of key files and a manifest
+//! with their hashes, so we can check that the backup is not damaged.
+//! To move the node to another computer there is [`NodeArchive`], one encrypted file with config,
+//! key files and known peers. The chain is not there, the node downloads it again.
+
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+use chrono::{TimeZone, Utc};
+#[allow(unused_imports)]
+use log::{debug, error, info, trace, warn};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::blockchain::types::KnownPeer;
+use crate::keystore::ContainerCrypto;
+use crate::settings::Backup;
+use crate::{from_hex, to_hex, Context, Keystore};
+
+/// Names of backup directories start with this
+const BACKUP_PREFIX: &str = "keys-";
+const MANIFEST_NAME: &str = "manifest.json";
+/// How often we check if it is time to make a new backup
+const CHECK_PERIOD: Duration = Duration::from_secs(3600);
+const ARCHIVE_VERSION: u32 = 1;
+
+/// Description of one backup, the files are stored near it
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+struct Manifest {
+    timestamp: i64,
+    files: Vec<BackupFile>
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+struct BackupFile {
+    /// Name of the copy in backup directory
+    name: String,
+    /// Original path of the key file
+    source: String,
+    sha256: String,
+    encrypted: bool
+}
+
+/// Node archive as it is saved to file, the contents are encrypted by password like in key containers
+#[derive(Serialize, Deserialize, Debug)]
+struct EncryptedArchive {
+    version: u32,
+    crypto: ContainerCrypto
+}
+
+/// Everything that is needed to restore the node on another computer, except the chain
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+pub struct NodeArchive {
+    pub timestamp: i64,
+    pub config: String,
+    pub keys: Vec<ArchivedFile>,
+    pub peers: Vec<KnownPeer>
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+pub struct ArchivedFile {
+    /// Path of the file as it is in settings
+    pub path: String,
+    /// Contents in hex
+    pub data: String
+}
+
+impl NodeArchive {
+    /// Collects config, key files and known peers of the node
+    pub fn collect(context: &Context, config_name: &str) -> io::Result<Self> {
+        let config = fs::read_to_string(config_name)?;
+        let mut keys = Vec::new();
+        for path in get_key_files(context) {
+            let data = fs::read(&path)?;
+            keys.push(ArchivedFile { path, data: to_hex(&data) });
+        }
+        Ok(NodeArchive { timestamp: Utc::now().timestamp(), config, keys, peers: context.chain.get_all_peers() })
+    }
+
+    pub fn save(&self, path: &Path, password: &str) -> io::Result<()> {
+        let data = serde_json::to_vec(self)?;
+        let archive = EncryptedArchive { version: ARCHIVE_VERSION, crypto: ContainerCrypto::encrypt(password, &data) };
+        fs::write(path, serde_json::to_string_pretty(&archive)?)
+    }
+
+    pub fn load(path: &Path, password: &str) -> Result<Self, String> {
+        let text = fs::read_to_string(path).map_err(|e| e.to_string())?;
+        let archive: EncryptedArchive = serde_json::from_str(&text).map_err(|_| String::from("it is not a backup of node"))?;
+        if archive.version != ARCHIVE_VERSION || !archive.crypto.is_supported() {
+            return Err(format!("unsupported version {} of backup", archive.version));
+        }
+        let data = archive.crypto.decrypt(password).ok_or_else(|| String::from("wrong password or damaged file"))?;
+        serde_json::from_slice(&data).map_err(|e| format!("damaged contents: {}", e))
+    }
+
+    /// Writes config to `config_name` and key files to their paths, returns count of key files.
+    /// If some file exists with other contents nothing is written, so the node is never restored by half.
+    pub fn restore_files(&self, config_name: &str) -> Result<usize, String> {
+        let mut files = vec![(config_name.to_owned(), self.config.as_bytes().to_vec())];
+        for key in &self.keys {
+            let data = from_hex(&key.data).map_err(|_| format!("key file {} is damaged", &key.path))?;
+            files.push((key.path.clone(), data));
+        }
+        for (path, data) in &files {
+            if let Ok(existing) = fs::read(path) {
+                if &existing != data {
+                    return Err(format!("file {} exists and differs from backup, move it away first", path));
+                }
+            }
+        }
+        for (path, data) in &files {
+            if let Some(parent) = Path::new(path).parent() {
+                fs::create_dir_all(parent).map_err(|e| format!("{}: {}", parent.display(), e))?;
+            }
+            fs::write(path, data).map_err(|e| format!("{}: {}", path, e))?;
+        }
+        Ok(self.keys.len())
+    }
+}
+
+/// Warns if there is no recent backup of our keys, and starts a thread that makes backups by schedule
+pub fn start_backups(context: Arc<Mutex<Context>>) {
+    let (settings, files) = {
+        let context = context.lock().unwrap();
+        (context.settings.backup.clone(), get_key_files(&context))
+    };
+    if !files.is_empty() {
+        check_recent_backup(&settings, Utc::now().timestamp());
+    }
+    if settings.dir.is_empty() {
+        return;
+    }
+    let _ = thread::Builder::new().name(String::from("backup")).spawn(move || loop {
+        let files = get_key_files(&context.lock().unwrap());
+        if !files.is_empty() {
+            let now = Utc::now().timestamp();
+            for dir in get_backup_dirs(&settings) {
+                let last = latest_backup(&dir).map(|(_, manifest)| manifest.timestamp).unwrap_or(0);
+                if now - last < settings.period as i64 * 3600 {
+                    continue;
+                }
+                match make_backup(&dir, &files, now) {
+                    Ok(path) => match verify_backup(&path) {
+                        Ok(count) => info!("Backup of {} key files is saved to {}", count, path.display()),
+                        Err(e) => error!("Backup in {} is damaged: {}", path.display(), e)
+                    },
+                    Err(e) => error!("Error making backup of keys in {}: {}", dir.display(), e)
+                }
+                remove_old_backups(&dir, settings.keep);
+            }
+        }
+        thread::sleep(CHECK_PERIOD);
+    });
+}
+
+/// Logs a warning if no backup directory has an intact backup newer than `warn_days`
+fn check_recent_backup(settings: &Backup, now: i64) {
+    if settings.dir.is_empty() {
+        warn!("Backups of keys are disabled, set 'dir' in [backup] section of config to make them automatically");
+        return;
+    }
+    let mut recent = false;
+    for dir in get_backup_dirs(settings) {
+        if let Some((path, manifest)) = latest_backup(&dir) {
+            match verify_backup(&path) {
+                Ok(_) => recent |= is_recent(manifest.timestamp, now, settings.warn_days),
+                Err(e) => warn!("Last backup in {} is damaged: {}", path.display(), e)
+            }
+        }
+    }
+    if !recent {
+        warn!("There is no backup of your keys for last {} days!", settings.warn_days);
+    }
+}
+
+fn is_recent(timestamp: i64, now: i64, days: u64) -> bool {
+    now - timestamp < days as i64 * 86400
+}
+
+/// Returns paths of loaded keys and of existing key files from settings
+fn get_key_files(context: &Context) -> Vec<String> {
+    let mut files: Vec<String> = context.get_keystores().iter()
+        .map(|keystore| keystore.get_path().to_owned())
+        .filter(|path| !path.is_empty())
+        .collect();
+    for path in &context.settings.key_files {
+        if Path::new(path).is_file() && !files.contains(path) {
+            files.push(path.clone());
+        }
+    }
+    files
+}
+
+/// Returns main backup directory and secondary one, if it is available now
+fn get_backup_dirs(settings: &Backup) -> Vec<PathBuf> {
+    let mut result = Vec::new();
+    if !settings.dir.is_empty() {
+        result.push(PathBuf::from(&settings.dir));
+    }
+    if !settings.secondary.is_empty() {
+        // We don't create it, as it can be a mount point of removable drive
+        let path = PathBuf::from(&settings.secondary);
+        if path.is_dir() {
+            result.push(path);
+        } else {
+            debug!("Secondary backup directory {} is not available", path.display());
+        }
+    }
+    result
+}
+
+/// Copies key files to a new backup directory inside `dir` and writes manifest with their hashes
+fn make_backup(dir: &Path, files: &[String], timestamp: i64) -> io::Result<PathBuf> {
+    let time = Utc.timestamp_opt(timestamp, 0).unwrap();
+    let path = dir.join(format!("{}{}", BACKUP_PREFIX, time.format("%Y%m%d-%H%M%S")));
+    fs::create_dir_all(&path)?;
+    let mut manifest = Manifest { timestamp, files: Vec::new() };
+    for (index, source) in files.iter().enumerate() {
+        let data = fs::read(source)?;
+        let file_name = Path::new(source).file_name().map(|name| name.to_string_lossy().to_string()).unwrap_or_default();
+        // Key files from different directories can have the same names
+        let name = format!("{}-{}", index, file_name);
+        fs::write(path.join(&name), &data)?;
+        let encrypted = Keystore::is_encrypted_file(source);
+        if !encrypted {
+            warn!("Key file {} is not encrypted, so is its backup. Save it with password to protect it", source);
+        }
+        manifest.files.push(BackupFile { name, source: source.clone(), sha256: to_hex(&Sha256::digest(&data)), encrypted });
+    }
+    fs::write(path.join(MANIFEST_NAME), serde_json::to_string_pretty(&manifest).unwrap())?;
+    Ok(path)
+}
+
+fn read_manifest(path: &Path) -> Option<Manifest> {
+    serde_json::from_str(&fs::read_to_string(path.join(MANIFEST_NAME)).ok()?).ok()
+}
+
+/// Checks hashes of all files in backup, returns count of files
+fn verify_backup(path: &Path) -> Result<usize, String> {
+    let manifest = read_manifest(path).ok_or_else(|| String::from("no manifest"))?;
+    for file in &manifest.files {
+        let data = fs::read(path.join(&file.name)).map_err(|e| format!("{}: {}", &file.name, e))?;
+        if to_hex(&Sha256::digest(&data)) != file.sha256 {
+            return Err(format!("{}: wrong hash", &file.name));
+        }
+    }
+    Ok(manifest.files.len())
+}
+
+/// Returns paths of backups in `dir` sorted from the oldest to the newest
+fn list_backups(dir: &Path) -> Vec<PathBuf> {
+    let mut result: Vec<PathBuf> = match fs::read_dir(dir) {
+        Ok(entries) => entries.flatten()
+            .map(|entry| entry.path())
+            .filter(|path| path.is_dir() && path.file_name().map(|name| name.to_string_lossy().starts_with(BACKUP_PREFIX)).unwrap_or(false))
+            .collect(),
+        Err(_) => Vec::new()
+    };
+    result.sort();
+    result
+}
+
+fn latest_backup(dir: &Path) -> Option<(PathBuf, Manifest)> {
+    list_backups(dir).into_iter().rev().find_map(|path| read_manifest(&path).map(|manifest| (path, manifest)))
+}
+
+/// Leaves only `keep` newest backups in `dir`
+fn remove_old_backups(dir: &Path, keep: usize) {
+    let backups = list_backups(dir);
+    let count = backups.len().saturating_sub(keep.max(1));
+    for path in backups.into_iter().take(count) {
+        match fs::remove_dir_all(&path) {
+            Ok(_) => debug!("Removed old backup {}", path.display()),
+            Err(e) => warn!("Error removing old backup {}: {}", path.display(), e)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use crate::backup::{is_recent, latest_backup, list_backups, make_backup, remove_old_backups, verify_backup, NodeArchive};
+    use crate::{Chain, Context, Settings};
+
+    #[test]
+    fn test_backups() {
+        let dir = std::env::temp_dir().join("alfis-backup-test");
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let key_file = dir.join("key1.toml");
+        fs::write(&key_file, "secret = \"test\"").unwrap();
+        let files = vec![key_file.to_string_lossy().to_string()];
+
+        let now = 1_700_000_000;
+        for i in 0..3 {
+            let path = make_backup(&dir, &files, now + i).unwrap();
+            assert_eq!(verify_backup(&path), Ok(1));
+        }
+        let (path, manifest) = latest_backup(&dir).unwrap();
+        assert_eq!(manifest.timestamp, now + 2);
+        assert!(!manifest.files[0].encrypted);
+
+        remove_old_backups(&dir, 2);
+        assert_eq!(list_backups(&dir).len(), 2);
+
+        fs::write(path.join(&manifest.files[0].name), "secret = \"changed\"").unwrap();
+        assert!(verify_backup(&path).is_err());
+
+        assert!(is_recent(now, now + 86400, 7));
+        assert!(!is_recent(now, now + 86400 * 8, 7));
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_node_archive() {
+        let dir = std::env::temp_dir().join(format!("alfis-archive-test-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let key_file = dir.join("keys").join("key1.toml");
+        fs::create_dir_all(key_file.parent().unwrap()).unwrap();
+        fs::write(&key_file, "secret = \"test\"").unwrap();
+        let config_name = dir.join("alfis.toml").to_string_lossy().to_string();
+        fs::write(&config_name, "data_dir = \"data\"").unwrap();
+
+        let mut settings = Settings::default();
+        settings.key_files = vec![key_file.to_string_lossy().to_string()];
+        let chain = Chain::new(&settings, ":memory:");
+        chain.add_peer_success("10.0.0.1:4244");
+        let context = Context::new(String::from("test"), settings, Vec::new(), chain);
+        let archive = NodeArchive::collect(&context, &config_name).unwrap();
+        assert_eq!(archive.keys.len(), 1);
+        assert_eq!(archive.peers.len(), 1);
+
+        let path = dir.join("node.backup");
+        archive.save(&path, "password").unwrap();
+        assert!(NodeArchive::load(&path, "wrong").is_err());
+        let loaded = NodeArchive::load(&path, "password").unwrap();
+        assert_eq!(loaded, archive);
+
+        // Files that are the same as in backup are fine, changed ones are not overwritten
+        assert_eq!(loaded.restore_files(&config_name), Ok(1));
+        fs::write(&key_file, "secret = \"other\"").unwrap();
+        assert!(loaded.restore_files(&config_name).is_err());
+        fs::remove_dir_all(key_file.parent().unwrap()).unwrap();
+        fs::remove_file(&config_name).unwrap();
+        assert_eq!(loaded.restore_files(&config_name), Ok(1));
+        assert_eq!(fs::read_to_string(&key_file).unwrap(), "secret = \"test\"");
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,134 @@
+//! Activation of new rules of blocks by signals of miners, like BIP9 in Bitcoin.
+//! Every rule has its bit in `BLOCK_SIGNAL_MASK` part of block version, and miners set it while the rule is started
+//! and their node knows it. Blocks are counted by windows, when enough blocks of a window have the bit, the rule is
+//! locked in, and it is active from the start of the next window. Nodes ignore signal bits that they don't know,
+//! so blocks of new miners are good for old nodes too.
+
+use serde::Serialize;
+
+use crate::commons::{ACTIVATION_THRESHOLD, BLOCK_SIGNAL_MASK};
+
+/// First bit of `BLOCK_SIGNAL_MASK`
+const SIGNAL_SHIFT: u32 = BLOCK_SIGNAL_MASK.trailing_zeros();
+
+/// New rule of blocks that is enabled when most of miners are ready
+#[derive(Clone, Debug, PartialEq)]
+pub struct Rule {
+    pub name: &'static str,
+    /// Number of bit in `BLOCK_SIGNAL_MASK`, from 0 to 7
+    pub bit: u8,
+    /// Miners start to signal from the first window that starts at this height
+    pub start: u64,
+    /// If the rule is not locked in by this height, it fails
+    pub timeout: u64
+}
+
+impl Rule {
+    pub fn mask(&self) -> u32 {
+        1 << (SIGNAL_SHIFT + self.bit as u32)
+    }
+}
+
+/// Limits how many new domains one key can register in a zone, see `ChainKind::registration_limit`
+pub const REGISTRATION_LIMIT_RULE: &str = "registration_limit";
+/// Freed domains go to the most difficult block of auction, see `ChainKind::auction_time`
+pub const AUCTION_RULE: &str = "auction";
+/// Difficulty of domain blocks is adjusted by the time between previous domain blocks
+pub const DIFFICULTY_ADJUST_RULE: &str = "difficulty_adjust";
+/// Blocks are hashed by Argon2id instead of Blakeout, with `BLOCK_FLAG_ARGON2` in version
+pub const ARGON2_RULE: &str = "argon2";
+
+/// Rules that this node knows. Bits of failed and active rules can be reused after some time.
+pub const RULES: &[Rule] = &[
+    Rule { name: REGISTRATION_LIMIT_RULE, bit: 0, start: 0, timeout: u64::MAX },
+    Rule { name: AUCTION_RULE, bit: 1, start: 0, timeout: u64::MAX },
+    Rule { name: DIFFICULTY_ADJUST_RULE, bit: 2, start: 0, timeout: u64::MAX },
+    Rule { name: ARGON2_RULE, bit: 3, start: 0, timeout: u64::MAX }
+];
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RuleState {
+    Defined,
+    /// Miners signal the bit
+    Started,
+    /// Enough miners are ready, the rule is active from the next window
+    LockedIn,
+    Active,
+    Failed
+}
+
+/// Height of the first block of the window that contains block `index`, windows start from block 1
+pub fn window_start(index: u64, window: u64) -> u64 {
+    index.saturating_sub(1) / window * window + 1
+}
+
+/// Gets state of the rule in the window with block `index`, and the height since which it is in that state.
+/// `count(from, to)` gives how many blocks from `from` to `to` (not including) have the bit of the rule.
+pub fn rule_state<F: Fn(u64, u64) -> u64>(rule: &Rule, index: u64, window: u64, count: F) -> (RuleState, u64) {
+    let mut state = RuleState::Defined;
+    let mut since = 1;
+    let mut start = 1 + window;
+    while start <= index {
+        if matches!(state, RuleState::Active | RuleState::Failed) {
+            break;
+        }
+        let next = next_state(rule, state, start, window, &count);
+        if next != state {
+            state = next;
+            since = start;
+        }
+        start += window;
+    }
+    (state, since)
+}
+
+/// Gets state of the rule in the window that starts at block `start`, by its state in the previous window
+pub fn next_state<F: Fn(u64, u64) -> u64>(rule: &Rule, state: RuleState, start: u64, window: u64, count: F) -> RuleState {
+    match state {
+        RuleState::Defined if start >= rule.start => RuleState::Started,
+        RuleState::Defined => RuleState::Defined,
+        RuleState::Started => {
+            if count(start - window, start) * 100 >= window * ACTIVATION_THRESHOLD {
+                RuleState::LockedIn
+            } else if start >= rule.timeout {
+                RuleState::Failed
+            } else {
+                RuleState::Started
+            }
+        }
+        RuleState::LockedIn => RuleState::Active,
+        RuleState::Active | RuleState::Failed => state
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::blockchain::activation::{rule_state, window_start, Rule, RuleState};
+    use crate::commons::BLOCK_SIGNAL_MASK;
+
+    const RULE: Rule = Rule { name: "test", bit: 1, start: 15, timeout: 60 };
+
+    #[test]
+    fn test_rule_state() {
+        assert_eq!(RULE.mask() & BLOCK_SIGNAL_MASK, RULE.mask());
+        assert_eq!(window_start(1, 10), 1);
+        assert_eq!(window_start(10, 10), 1);
+        assert_eq!(window_start(11, 10), 11);
+
+        // Blocks from 31 signal the bit
+        let count = |from: u64, to: u64| (from.max(31)..to).count() as u64;
+        assert_eq!(rule_state(&RULE, 10, 10, count), (RuleState::Defined, 1));
+        assert_eq!(rule_state(&RULE, 25, 10, count), (RuleState::Started, 21));
+        assert_eq!(rule_state(&RULE, 40, 10, count), (RuleState::Started, 21));
+        assert_eq!(rule_state(&RULE, 41, 10, count), (RuleState::LockedIn, 41));
+        assert_eq!(rule_state(&RULE, 51, 10, count), (RuleState::Active, 51));
+        assert_eq!(rule_state(&RULE, 1000, 10, count), (RuleState::Active, 51));
+
+        // Nobody signals
+        let count = |_: u64, _: u64| 0;
+        assert_eq!(rule_state(&RULE, 59, 10, count), (RuleState::Started, 21));
+        assert_eq!(rule_state(&RULE, 61, 10, count), (RuleState::Failed, 61));
+        assert_eq!(rule_state(&RULE, 1000, 10, count), (RuleState::Failed, 61));
+    }
+}
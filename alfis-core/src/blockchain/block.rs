@@ -0,0 +1,250 @@
+extern crate serde;
+extern crate serde_json;
+
+use std::cell::RefCell;
+use std::fmt::Debug;
+
+use serde::{Deserialize, Serialize};
+
+use crate::blockchain::hash_utils::{hash_difficulty, key_hash_difficulty};
+use crate::blockchain::transaction::TransactionType;
+use crate::blockchain::types::DecodeError;
+use crate::bytes::Bytes;
+use crate::commons::MAX_BLOCK_SIZE;
+use crate::Transaction;
+
+#[derive(Clone, Serialize, Deserialize, PartialEq, Debug)]
+pub struct Block {
+    pub index: u64,
+    pub timestamp: i64,
+    pub version: u32,
+    pub difficulty: u32,
+    pub random: u32,
+    pub nonce: u64,
+    #[serde(default, skip_serializing_if = "Bytes::is_zero")]
+    pub hash: Bytes,
+    #[serde(default, skip_serializing_if = "Bytes::is_zero")]
+    pub prev_block_hash: Bytes,
+    #[serde(default, skip_serializing_if = "Bytes::is_zero")]
+    pub pub_key: Bytes,
+    #[serde(default, skip_serializing_if = "Bytes::is_zero")]
+    pub signature: Bytes,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub transaction: Option<Transaction>,
+    #[serde(default, skip)]
+    hash_good: RefCell<bool>
+}
+
+impl Block {
+    pub fn new(transaction: Option<Transaction>, pub_key: Bytes, prev_block_hash: Bytes, difficulty: u32) -> Self {
+        Block {
+            index: 0,
+            timestamp: 0,
+            version: 0,
+            difficulty,
+            random: 0,
+            nonce: 0,
+            transaction,
+            prev_block_hash,
+            hash: Bytes::default(),
+            pub_key,
+            signature: Bytes::default(),
+            hash_good: RefCell::new(false)
+        }
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    pub fn from_all_params(index: u64, timestamp: i64, version: u32, difficulty: u32, random: u32, nonce: u64, prev_block_hash: Bytes, hash: Bytes, pub_key: Bytes, signature: Bytes, transaction: Option<Transaction>) -> Self {
+        Block {
+            index,
+            timestamp,
+            version,
+            difficulty,
+            random,
+            nonce,
+            transaction,
+            prev_block_hash,
+            hash,
+            pub_key,
+            signature,
+            hash_good: RefCell::new(false)
+        }
+    }
+
+    pub fn from_bytes(data: &[u8]) -> serde_cbor::Result<Self> {
+        serde_cbor::from_slice(data)
+    }
+
+    /// Parses block received from network, it checks sizes of all fields and never panics.
+    /// Hashes, signatures and everything else are checked later by the chain.
+    pub fn from_untrusted_bytes(data: &[u8]) -> Result<Self, DecodeError> {
+        if data.len() > MAX_BLOCK_SIZE {
+            return Err(DecodeError::TooBig { size: data.len() });
+        }
+        let block: Block = serde_cbor::from_slice(data).map_err(|e| DecodeError::Malformed(e.to_string()))?;
+        // Indexes are stored as i64 in DB
+        if block.index == 0 || block.index > i64::MAX as u64 {
+            return Err(DecodeError::WrongField("index"));
+        }
+        if block.timestamp < 0 {
+            return Err(DecodeError::WrongField("timestamp"));
+        }
+        if block.hash.length() != 32 {
+            return Err(DecodeError::WrongField("hash"));
+        }
+        // Only the origin block has no previous one
+        if block.prev_block_hash.length() != 32 && !(block.index == 1 && block.prev_block_hash.is_empty()) {
+            return Err(DecodeError::WrongField("prev_block_hash"));
+        }
+        if block.pub_key.length() != 32 {
+            return Err(DecodeError::WrongField("pub_key"));
+        }
+        if block.signature.length() != 64 {
+            return Err(DecodeError::WrongField("signature"));
+        }
+        if let Some(transaction) = &block.transaction {
+            transaction.check_fields()?;
+        }
+        Ok(block)
+    }
+
+    pub fn is_genesis(&self) -> bool {
+        self.index == 1 &&
+            matches!(Transaction::get_type(&self.transaction), TransactionType::Origin) &&
+            self.prev_block_hash == Bytes::default()
+    }
+
+    /// Key that signed the transaction of this block, it owns the domain.
+    /// Friends can mine transactions of owners, old transactions were signed by the key of block.
+    pub fn get_signing(&self) -> Option<&Bytes> {
+        let transaction = self.transaction.as_ref()?;
+        match transaction.signing.is_empty() {
+            true => Some(&self.pub_key),
+            false => Some(&transaction.signing)
+        }
+    }
+
+    pub fn is_hash_good(&self) -> bool {
+        *self.hash_good.borrow()
+    }
+
+    pub fn set_hash_good(&self, good: bool) {
+        *self.hash_good.borrow_mut() = good;
+    }
+
+    /// Serializes block to CBOR for network
+    pub fn as_bytes(&self) -> Vec<u8> {
+        serde_cbor::to_vec(&self).unwrap()
+    }
+
+    /// Serializes block to bincode format for hashing.
+    pub fn as_bytes_compact(&self) -> Vec<u8> {
+        bincode::serialize(&self).unwrap()
+    }
+
+    /// Checks if this block is superior than the other
+    pub fn is_better_than(&self, other: &Block) -> bool {
+        if self.transaction.is_some() && other.transaction.is_none() {
+            return true;
+        }
+        let hash_diff = hash_difficulty(self.hash.as_slice()) + key_hash_difficulty(self.hash.as_slice());
+        let my_diff = (hash_diff << 16) + (self.hash.get_tail_u64() % 0xFFFF) as u32;
+        let hash_diff = hash_difficulty(other.hash.as_slice()) + key_hash_difficulty(other.hash.as_slice());
+        let it_diff = (hash_diff << 16) + (other.hash.get_tail_u64() % 0xFFFF) as u32;
+
+        if my_diff > it_diff {
+            return true;
+        }
+
+        if my_diff == it_diff && self.nonce != other.nonce {
+            return self.nonce < other.nonce;
+        }
+
+        false
+    }
+}
+
+impl From<&Block> for alfis_verify::Block {
+    fn from(block: &Block) -> Self {
+        alfis_verify::Block {
+            index: block.index,
+            timestamp: block.timestamp,
+            version: block.version,
+            difficulty: block.difficulty,
+            random: block.random,
+            nonce: block.nonce,
+            hash: block.hash.to_vec(),
+            prev_block_hash: block.prev_block_hash.to_vec(),
+            pub_key: block.pub_key.to_vec(),
+            signature: block.signature.to_vec(),
+            transaction: block.transaction.as_ref().map(|transaction| transaction.into())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::blockchain::types::DecodeError;
+    use crate::{Block, Bytes, Transaction, CLASS_DOMAIN, MAX_BLOCK_SIZE};
+
+    fn signed_block() -> Block {
+        let mut block = Block::new(None, Bytes::from_bytes(&[7u8; 32]), Bytes::from_bytes(&[1u8; 32]), 16);
+        block.index = 5;
+        block.timestamp = 1_600_000_000;
+        block.hash = Bytes::from_bytes(&[4u8; 32]);
+        block.signature = Bytes::from_bytes(&[3u8; 64]);
+        block
+    }
+
+    #[test]
+    fn test_untrusted_bytes() {
+        let block = signed_block();
+        let bytes = block.as_bytes();
+        assert_eq!(Block::from_untrusted_bytes(&bytes), Ok(block.clone()));
+        // Garbage and every cut of a good block
+        assert!(matches!(Block::from_untrusted_bytes(&[0xFF; 100]), Err(DecodeError::Malformed(_))));
+        for len in 0..bytes.len() {
+            assert!(Block::from_untrusted_bytes(&bytes[..len]).is_err());
+        }
+        assert_eq!(Block::from_untrusted_bytes(&[0u8; MAX_BLOCK_SIZE + 1]), Err(DecodeError::TooBig { size: MAX_BLOCK_SIZE + 1 }));
+
+        let mut wrong = block.clone();
+        wrong.index = u64::MAX;
+        assert_eq!(Block::from_untrusted_bytes(&wrong.as_bytes()), Err(DecodeError::WrongField("index")));
+        let mut wrong = block.clone();
+        wrong.signature = Bytes::from_bytes(&[3u8; 8]);
+        assert_eq!(Block::from_untrusted_bytes(&wrong.as_bytes()), Err(DecodeError::WrongField("signature")));
+        let mut wrong = block.clone();
+        wrong.prev_block_hash = Bytes::default();
+        assert_eq!(Block::from_untrusted_bytes(&wrong.as_bytes()), Err(DecodeError::WrongField("prev_block_hash")));
+        let mut wrong = block;
+        wrong.transaction = Some(Transaction::from_str(String::from("example.ygg"), String::from("unknown"), String::new(), Bytes::from_bytes(&[2u8; 32]), Bytes::default()));
+        assert_eq!(Block::from_untrusted_bytes(&wrong.as_bytes()), Err(DecodeError::WrongField("class")));
+    }
+
+    #[test]
+    fn test_untrusted_json() {
+        let transaction = Transaction::from_str(String::from("example.ygg"), String::from(CLASS_DOMAIN), String::from("{}"), Bytes::from_bytes(&[2u8; 32]), Bytes::from_bytes(&[5u8; 32]));
+        let json = transaction.to_string();
+        assert_eq!(Transaction::from_untrusted_json(&json), Ok(transaction));
+        for json in ["", "{", "[]", r#"{"class":"domain","signing":"ZZ"}"#, r#"{"class":"domain","signing":"é"}"#, r#"{"class":"domain","signing":" A"}"#] {
+            assert!(matches!(Transaction::from_untrusted_json(json), Err(DecodeError::Malformed(_))));
+        }
+        assert_eq!(Transaction::from_untrusted_json(r#"{"class":"domain","signing":"AB"}"#), Err(DecodeError::WrongField("identity")));
+    }
+
+    #[test]
+    fn test_compact_bytes() {
+        // Blocks are checked by alfis-verify, it must hash the same bytes as we do
+        let mut block = Block::new(None, Bytes::from_bytes(&[7u8; 32]), Bytes::from_bytes(&[1u8; 32]), 16);
+        block.index = 5;
+        block.timestamp = 1_600_000_000;
+        block.nonce = 12345;
+        assert_eq!(alfis_verify::compact_bytes(&(&block).into()), block.as_bytes_compact());
+
+        let transaction = Transaction::from_str(String::from("example.ygg"), String::from(CLASS_DOMAIN), String::from("{}"), Bytes::from_bytes(&[2u8; 32]), Bytes::default());
+        block.transaction = Some(transaction);
+        block.signature = Bytes::from_bytes(&[3u8; 64]);
+        assert_eq!(alfis_verify::compact_bytes(&(&block).into()), block.as_bytes_compact());
+    }
+}
@@ -0,0 +1,2150 @@
+use std::cell::RefCell;
+use std::cmp::{max, min};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fs;
+use std::ops::Deref;
+use std::path::Path;
+use std::sync::Arc;
+use std::time::Instant;
+
+use chrono::Utc;
+#[allow(unused_imports)]
+use log::{debug, error, info, trace, warn};
+use sqlite::{State, Statement};
+use lazy_static::lazy_static;
+
+use crate::blockchain::activation::{next_state, rule_state, window_start, Rule, RuleState, ARGON2_RULE, AUCTION_RULE, DIFFICULTY_ADJUST_RULE, REGISTRATION_LIMIT_RULE, RULES};
+use crate::blockchain::db::{Db, QueryStats};
+use crate::blockchain::hash_utils::*;
+use crate::blockchain::plugins::{get_class_handler, truncate_classes};
+use crate::blockchain::transaction::{DomainData, DomainState};
+use crate::blockchain::types::BlockQuality::*;
+use crate::blockchain::types::MineResult::*;
+use crate::blockchain::types::{BlockQuality, KnownPeer, MineResult, Options, OwnedDomain, ZoneData};
+use crate::commons::constants::*;
+use crate::commons::ChainKind;
+use crate::event::Event;
+use crate::eventbus::post;
+use crate::keystore::check_public_key_strength;
+use crate::settings::Settings;
+use crate::{check_domain, check_record_comment, get_domain_zone, is_yggdrasil_record, Block, Bytes, Keystore, Transaction, from_hex};
+use rand::prelude::IteratorRandom;
+
+const TEMP_DB_NAME: &str = ":memory:";
+const SQL_CREATE_TABLES: &str = include_str!("data/create_db.sql");
+const ZONES_TXT: &str = include_str!("data/zones.txt");
+const SQL_ADD_BLOCK: &str = "INSERT INTO blocks (id, timestamp, version, difficulty, random, nonce, 'transaction',\
+                          prev_block_hash, hash, pub_key, signature) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?);";
+const SQL_GET_LAST_BLOCK: &str = "SELECT * FROM blocks ORDER BY id DESC LIMIT 1;";
+const SQL_TRUNCATE_BLOCKS: &str = "DELETE FROM blocks WHERE id >= ?;";
+const SQL_TRUNCATE_DOMAINS: &str = "DELETE FROM domains WHERE id >= ?;";
+const SQL_TRUNCATE_IDENTITIES: &str = "DELETE FROM identities WHERE id >= ?;";
+
+const SQL_ADD_DOMAIN: &str = "INSERT INTO domains (id, timestamp, identity, confirmation, data, signing, encryption) VALUES (?, ?, ?, ?, ?, ?, ?)";
+const SQL_GET_BLOCK_BY_ID: &str = "SELECT * FROM blocks WHERE id=? LIMIT 1;";
+// Pruned blocks have NULL instead of transaction, they are full blocks too
+const SQL_GET_LAST_FULL_BLOCK: &str = "SELECT * FROM blocks WHERE id < ? AND `transaction` IS NOT '' ORDER BY id DESC LIMIT 1;";
+const SQL_GET_LAST_FULL_BLOCK_FOR_KEY: &str = "SELECT * FROM blocks WHERE id < ? AND `transaction` IS NOT '' AND pub_key = ? ORDER BY id DESC LIMIT 1;";
+const SQL_PRUNE_BLOCKS: &str = "UPDATE blocks SET `transaction` = NULL WHERE id > 1 AND id <= ? AND `transaction` <> '';";
+const SQL_GET_PRUNED_HEIGHT: &str = "SELECT MAX(id) FROM blocks WHERE `transaction` IS NULL;";
+const SQL_GET_DOMAIN_OWNER_BY_ID: &str = "SELECT signing, timestamp FROM domains WHERE id < ? AND identity = ? ORDER BY id DESC LIMIT 1;";
+const SQL_GET_DOMAIN_BY_ID: &str = "SELECT * FROM domains WHERE identity = ? AND id < ? ORDER BY id DESC LIMIT 1;";
+const SQL_GET_DOMAIN_HISTORY: &str = "SELECT id, timestamp, confirmation, data, signing, encryption FROM domains WHERE identity = ? ORDER BY id DESC;";
+const SQL_GET_DOMAINS_BY_KEY: &str = "SELECT domains.id, domains.timestamp, domains.identity, data FROM identities JOIN domains ON domains.id = identities.id \
+                                      WHERE identities.signing = ? ORDER BY domains.id;";
+const SQL_GET_LAST_DOMAINS: &str = "SELECT domains.timestamp, domains.identity, confirmation, data, domains.signing, encryption FROM identities JOIN domains ON domains.id = identities.id;";
+const SQL_GET_DOMAINS_COUNT: &str = "SELECT count(identity) FROM identities;";
+const SQL_GET_USERS_COUNT: &str = "SELECT count(DISTINCT pub_key) FROM blocks;";
+const SQL_GET_USER_BLOCK_COUNT: &str = "SELECT count(pub_key) FROM blocks WHERE pub_key = ? AND id < ?";
+const SQL_GET_DOMAIN_TIMESTAMPS: &str = "SELECT timestamp FROM domains WHERE id < ? ORDER BY id DESC LIMIT ?;";
+const SQL_GET_NEW_DOMAINS_OF_KEY: &str = "SELECT data FROM domains WHERE id >= ? AND id < ? AND signing = ? \
+                                          AND NOT EXISTS (SELECT 1 FROM domains AS old WHERE old.identity = domains.identity AND old.id < domains.id);";
+const SQL_GET_DOMAIN_BIDS: &str = "SELECT domains.timestamp, blocks.difficulty FROM domains JOIN blocks ON blocks.id = domains.id WHERE identity = ? AND domains.id < ? ORDER BY domains.id DESC;";
+const SQL_COUNT_SIGNALS: &str = "SELECT count(id) FROM blocks WHERE id >= ? AND id < ? AND (version & ?) <> 0;";
+const SQL_GET_DOMAIN_UPDATE_TIME: &str = "SELECT domains.timestamp FROM blocks JOIN domains ON blocks.id = domains.id WHERE difficulty >= 23 AND identity = ? ORDER BY domains.id DESC LIMIT 1;";
+
+const SQL_QUICK_CHECK: &str = "PRAGMA quick_check;";
+const SQL_GET_DB_SIZE: &str = "SELECT page_count * page_size FROM pragma_page_count(), pragma_page_size();";
+const SQL_INTEGRITY_CHECK: &str = "PRAGMA integrity_check;";
+const SQL_REINDEX: &str = "REINDEX;";
+
+const SQL_GET_OPTIONS: &str = "SELECT * FROM options;";
+const SQL_CHECK_TABLES: &str = "SELECT 1 FROM blocks, domains LIMIT 1;";
+
+// The last block and owner of every domain, so that we don't search all domains for them
+const SQL_CREATE_IDENTITIES: &str = "CREATE TABLE IF NOT EXISTS identities ('identity' BINARY NOT NULL PRIMARY KEY, 'id' BIGINT NOT NULL, 'signing' BINARY NOT NULL); \
+                                     CREATE INDEX IF NOT EXISTS owners ON identities ('signing');";
+const SQL_HAS_IDENTITIES: &str = "SELECT EXISTS (SELECT 1 FROM identities);";
+const SQL_FILL_IDENTITIES: &str = "INSERT OR IGNORE INTO identities (identity, id, signing) \
+                                   SELECT identity, id, signing FROM domains WHERE id IN (SELECT MAX(id) FROM domains GROUP BY identity);";
+const SQL_UPDATE_IDENTITY: &str = "INSERT OR REPLACE INTO identities (identity, id, signing) VALUES (?, ?, ?);";
+
+const SQL_CREATE_PEERS: &str = "CREATE TABLE IF NOT EXISTS peers ('addr' TEXT NOT NULL PRIMARY KEY, 'last_seen' BIGINT NOT NULL, 'success' INTEGER NOT NULL, 'failure' INTEGER NOT NULL);";
+const SQL_PEER_SUCCESS: &str = "INSERT INTO peers (addr, last_seen, success, failure) VALUES (?, ?, 1, 0) \
+                                ON CONFLICT(addr) DO UPDATE SET last_seen = excluded.last_seen, success = success + 1;";
+const SQL_PEER_FAILURE: &str = "UPDATE peers SET failure = failure + 1 WHERE addr = ?;";
+const SQL_GET_PEERS: &str = "SELECT addr FROM peers WHERE last_seen > ? AND failure <= success + 10 ORDER BY last_seen DESC LIMIT ?;";
+const SQL_GET_ALL_PEERS: &str = "SELECT addr, last_seen, success, failure FROM peers ORDER BY last_seen DESC;";
+const SQL_RESTORE_PEER: &str = "INSERT INTO peers (addr, last_seen, success, failure) VALUES (?, ?, ?, ?) \
+                                ON CONFLICT(addr) DO UPDATE SET last_seen = MAX(last_seen, excluded.last_seen), \
+                                success = MAX(success, excluded.success), failure = MIN(failure, excluded.failure);";
+const SQL_CREATE_BANS: &str = "CREATE TABLE IF NOT EXISTS bans ('ip' TEXT NOT NULL PRIMARY KEY, 'until' BIGINT NOT NULL, 'reason' TEXT);";
+const SQL_ADD_BAN: &str = "INSERT OR REPLACE INTO bans (ip, until, reason) VALUES (?, ?, ?);";
+const SQL_REMOVE_BAN: &str = "DELETE FROM bans WHERE ip = ?;";
+const SQL_GET_BANS: &str = "SELECT ip FROM bans WHERE until > ?;";
+const SQL_DELETE_OLD_PEERS: &str = "DELETE FROM peers WHERE last_seen <= ? OR failure > success + 10;";
+
+lazy_static! {
+    static ref WRONG_HASHES: Vec<Bytes> = vec![
+        Bytes::from_bytes(&from_hex("5B2D63CD8BD854B23A34A49AD850BF520BDD8D4514F9B20A3DF01430A59F0000").unwrap()),
+        Bytes::from_bytes(&from_hex("4448E0582878FCB982C0DDAFEB441A03A30FB62FA6ECD1EA4D51C29A30980000").unwrap())
+    ];
+}
+
+/// Max possible block index
+const MAX: u64 = i64::MAX as u64;
+
+pub struct Chain {
+    origin: Bytes,
+    /// Last blocks are shared with callers, blocks can be big with many records
+    last_block: Option<Arc<Block>>,
+    last_full_block: Option<Arc<Block>>,
+    max_height: u64,
+    /// Main or test chain, they have different difficulties and lifetime of domains
+    kind: ChainKind,
+    /// How many last blocks keep their transactions, 0 if we keep all
+    prune_blocks: u64,
+    /// Blocks up to this height may have no transactions
+    pruned_height: u64,
+    db: Db,
+    db_name: String,
+    zones: Vec<ZoneData>,
+    signers: RefCell<SignersCache>,
+    /// States of `RULES` with heights since them, by the first block of window, every window is counted once
+    rule_states: RefCell<BTreeMap<u64, Vec<(RuleState, u64)>>>
+}
+
+impl Chain {
+    pub fn new(settings: &Settings, db_name: &str) -> Self {
+        let origin = settings.get_origin();
+
+        let db = Db::open(db_name, settings.api.query_stats).expect("Unable to open blockchain DB");
+        let zones = Self::load_zones();
+        let prune_blocks = settings.get_prune_blocks();
+        let mut chain = Chain { origin, last_block: None, last_full_block: None, max_height: 0, kind: settings.chain, prune_blocks, pruned_height: 0, db, db_name: db_name.to_owned(), zones, signers: SignersCache::new(), rule_states: RefCell::new(BTreeMap::new()) };
+        chain.init_db();
+        chain
+    }
+
+    /// Opens database of a node only to read domains and blocks from it, while the node may work with it.
+    /// Nothing is created, migrated or cleared, so the database must be made by the node before.
+    pub fn open_read_only(settings: &Settings, db_name: &str) -> sqlite::Result<Self> {
+        let db = Db::open_read_only(db_name)?;
+        // Fails at once if this is not a database of a node
+        db.execute(SQL_CHECK_TABLES)?;
+        let zones = Self::load_zones();
+        let mut chain = Chain { origin: settings.get_origin(), last_block: None, last_full_block: None, max_height: 0, kind: settings.chain, prune_blocks: 0, pruned_height: 0, db, db_name: db_name.to_owned(), zones, signers: SignersCache::new(), rule_states: RefCell::new(BTreeMap::new()) };
+        chain.load_state();
+        Ok(chain)
+    }
+
+    /// Reads options from DB or initializes and writes them to DB if not found
+    fn init_db(&mut self) {
+        let options = self.get_options();
+        if !self.origin.is_zero() && !options.origin.is_empty() && self.origin.to_string() != options.origin {
+            self.clear_db();
+        }
+        #[allow(clippy::absurd_extreme_comparisons)]
+        if options.version < DB_VERSION {
+            self.migrate_db(options.version, DB_VERSION);
+        }
+
+        self.load_state();
+        self.init_identities();
+        self.db.execute(SQL_CREATE_PEERS).expect("Error creating peers table");
+        self.db.execute(SQL_CREATE_BANS).expect("Error creating bans table");
+    }
+
+    /// Loads last blocks and pruned height from DB
+    fn load_state(&mut self) {
+        if let Some(block) = self.load_last_block() {
+            // Cache some info
+            let block = Arc::new(block);
+            self.last_block = Some(Arc::clone(&block));
+            if block.transaction.is_some() {
+                self.last_full_block = Some(block);
+            } else {
+                self.last_full_block = self.get_last_full_block(MAX, None);
+            }
+        }
+        self.pruned_height = self.load_pruned_height();
+    }
+
+    /// Creates index of domains by identity, and fills it from domains of older versions
+    fn init_identities(&mut self) {
+        self.db.execute(SQL_CREATE_IDENTITIES).expect("Error creating identities table");
+        let mut statement = self.db.prepare(SQL_HAS_IDENTITIES).unwrap();
+        let empty = match statement.next().unwrap() {
+            State::Row => statement.read::<i64>(0).unwrap() == 0,
+            State::Done => true
+        };
+        drop(statement);
+        if empty {
+            self.db.execute(SQL_FILL_IDENTITIES).expect("Error filling identities table");
+        }
+    }
+
+    pub fn check_chain(&mut self, count: u64) {
+        let height = self.get_height();
+        let start = if height > count {
+            info!("Checking last {} blocks...", count);
+            height - count + 1
+        } else {
+            info!("Local blockchain height is {}, starting full blockchain check...", height);
+            1
+        };
+        // Transactions of pruned blocks can't be checked, and signing blocks need their full block
+        let start = match self.pruned_height {
+            0 => start,
+            pruned => max(start, min(pruned + BLOCK_SIGNERS_MIN + 1, height))
+        };
+        let mut last_block: Option<Arc<Block>> = None;
+        let mut last_full_block: Option<Arc<Block>> = None;
+        if start > 1 {
+            last_block = self.get_block_header(start - 1).map(Arc::new);
+            if let Some(last) = &last_block {
+                last_full_block = match &last.transaction {
+                    None => self.get_last_full_block(last.index, None),
+                    Some(_) => Some(Arc::clone(last))
+                };
+            }
+        }
+
+        for id in start..=height {
+            debug!("Checking block {}", id);
+            let block = self.get_block(id);
+            match block {
+                None => {
+                    error!("Block {} is missing in database", id);
+                    info!("Truncating database from block {}...", id);
+                    if let Err(e) = self.truncate_db_from_block(id) {
+                        error!("{}", e);
+                        panic!("Error truncating database! Please, delete 'blockchain.db' and restart.");
+                    }
+                    break;
+                }
+                Some(block) => {
+                    if block.index == 1 {
+                        // Without origin in config we take any chain, like the test one
+                        if !self.origin.is_zero() && block.hash != self.origin {
+                            panic!("Loaded DB is not of origin {:?}! Please, delete 'blockchain.db' and restart.", &self.origin);
+                        }
+                        debug!("Block {} with hash {:?} is good!", block.index, &block.hash);
+                        last_block = Some(Arc::new(block));
+                        continue;
+                    }
+
+                    if WRONG_HASHES.contains(&block.hash)  {
+                        error!("Block {} has bad hash:\n{:?}", block.index, &block);
+                        info!("Truncating database from block {}...", block.index);
+                        if let Err(e) = self.truncate_db_from_block(block.index) {
+                            error!("{}", e);
+                            panic!("Error truncating database! Please, delete 'blockchain.db' and restart.");
+                        }
+                        break;
+                    }
+
+                    //let last = self.last_block.clone().unwrap();
+                    if self.check_block(&block, last_block.as_deref(), last_full_block.as_deref()) != Good {
+                        error!("Block {} is bad:\n{:?}", block.index, &block);
+                        info!("Truncating database from block {}...", block.index);
+                        match self.truncate_db_from_block(block.index) {
+                            Ok(_) => {}
+                            Err(e) => {
+                                error!("{}", e);
+                                panic!("Error truncating database! Please, delete 'blockchain.db' and restart.");
+                            }
+                        }
+                        break;
+                    }
+                    debug!("Block {} with hash {:?} is good!", block.index, &block.hash);
+                    let block = Arc::new(block);
+                    if block.transaction.is_some() {
+                        self.last_full_block = Some(Arc::clone(&block));
+                        last_full_block = Some(Arc::clone(&block));
+                    }
+                    last_block = Some(block);
+                }
+            }
+        }
+        self.last_block = self.load_last_block().map(Arc::new);
+        self.last_full_block = self.get_last_full_block(MAX, None);
+        debug!("Last block after chain check: {:?}", &self.last_block);
+    }
+
+    /// Checks database file by SQLite and that last `count` blocks follow each other.
+    /// The first broken block is removed with all blocks after it, they will be downloaded from other nodes again.
+    /// Full check is slower, it checks indexes too. Returns false if the database file is damaged and can't be used.
+    pub fn check_db(&mut self, count: u64, full: bool) -> bool {
+        let sql = if full { SQL_INTEGRITY_CHECK } else { SQL_QUICK_CHECK };
+        let errors = self.get_db_errors(sql);
+        if !errors.is_empty() {
+            warn!("Database has errors, rebuilding indexes:\n{}", errors.join("\n"));
+            if let Err(e) = self.db.execute(SQL_REINDEX) {
+                error!("Error rebuilding indexes: {}", e);
+            }
+            let errors = self.get_db_errors(sql);
+            if !errors.is_empty() {
+                error!("Database is damaged:\n{}", errors.join("\n"));
+                return false;
+            }
+        }
+
+        if let Some(index) = self.find_broken_block(count) {
+            warn!("Block {} is missing or doesn't follow previous block", index);
+            info!("Truncating database from block {}...", index);
+            if let Err(e) = self.truncate_db_from_block(index) {
+                error!("Error truncating database: {}", e);
+                return false;
+            }
+            self.signers.borrow_mut().clear();
+            self.last_block = self.load_last_block().map(Arc::new);
+            self.last_full_block = self.get_last_full_block(MAX, None);
+            self.pruned_height = self.load_pruned_height();
+            info!("Blockchain height is {} now, other blocks will be downloaded again", self.get_height());
+        }
+        true
+    }
+
+    /// Runs SQLite check and returns found problems
+    fn get_db_errors(&self, sql: &'static str) -> Vec<String> {
+        let mut statement = match self.db.prepare(sql) {
+            Ok(statement) => statement,
+            Err(e) => return vec![e.to_string()]
+        };
+        let mut errors = Vec::new();
+        loop {
+            match statement.next() {
+                Ok(State::Row) => {
+                    let message = statement.read::<String>(0).unwrap_or_default();
+                    if message != "ok" {
+                        errors.push(message);
+                    }
+                }
+                Ok(State::Done) => break,
+                Err(e) => {
+                    errors.push(e.to_string());
+                    break;
+                }
+            }
+        }
+        errors
+    }
+
+    /// Finds the first of last `count` blocks that is missing, has wrong hash or is not linked to previous block
+    fn find_broken_block(&self, count: u64) -> Option<u64> {
+        let height = self.get_height();
+        // The block before checked ones is taken only to check the link of the first one
+        let start = max(1, height.saturating_sub(count));
+        let mut prev: Option<Block> = None;
+        for index in start..=height {
+            let block = match self.get_block_header(index) {
+                Some(block) => block,
+                None => return Some(index)
+            };
+            let linked = match &prev {
+                Some(prev) => block.prev_block_hash == prev.hash,
+                None => index > 1 || block.prev_block_hash.is_zero()
+            };
+            if block.index != index || !linked || !check_block_hash(&block) {
+                return Some(index);
+            }
+            prev = Some(block);
+        }
+        None
+    }
+
+    fn truncate_db_from_block(&mut self, index: u64) -> sqlite::Result<State> {
+        self.clear_rule_states(index);
+        let mut statement = self.db.prepare(SQL_TRUNCATE_BLOCKS)?;
+        statement.bind(1, index as i64)?;
+        statement.next()?;
+
+        let mut statement = self.db.prepare(SQL_TRUNCATE_DOMAINS)?;
+        statement.bind(1, index as i64)?;
+        statement.next()?;
+
+        // Domains of removed blocks get their previous blocks back
+        let mut statement = self.db.prepare(SQL_TRUNCATE_IDENTITIES)?;
+        statement.bind(1, index as i64)?;
+        statement.next()?;
+        drop(statement);
+        self.db.execute(SQL_FILL_IDENTITIES)?;
+        truncate_classes(index);
+        Ok(State::Done)
+    }
+
+    fn load_last_block(&mut self) -> Option<Block> {
+        match self.db.prepare(SQL_GET_LAST_BLOCK) {
+            Ok(mut statement) => {
+                let mut result = None;
+                while statement.next().unwrap() == State::Row {
+                    match Self::get_block_from_statement(&mut statement) {
+                        None => {
+                            error!("Something wrong with block in DB!");
+                            panic!();
+                        }
+                        Some(block) => {
+                            debug!("Loaded last block: {:?}", &block);
+                            result = Some(block);
+                            break;
+                        }
+                    }
+                }
+                result
+            }
+            Err(e) => {
+                info!("No blockchain database found. Creating new. {}", e);
+                self.db.execute(SQL_CREATE_TABLES).expect("Error creating DB tables");
+                None
+            }
+        }
+    }
+
+    fn migrate_db(&mut self, from: u32, to: u32) {
+        debug!("Migrating DB from {} to {}", from, to);
+    }
+
+    fn clear_db(&mut self) {
+        warn!("Clearing DB");
+        // We cannot close DB connection and recreate file,
+        // therefore we switch our db to temporary file, delete main DB and switch back.
+        // I know that this is a crutch, but this way I don't need to use Option<db> :)
+        let timing = self.db.is_timing();
+        self.db = Db::open(TEMP_DB_NAME, timing).expect("Unable to open temporary blockchain DB");
+        let file = Path::new(&self.db_name);
+        if fs::remove_file(&file).is_err() {
+            panic!("Unable to remove database!");
+        }
+        self.db = Db::open(&self.db_name, timing).expect("Unable to open blockchain DB");
+        let file = Path::new(TEMP_DB_NAME);
+        let _ = fs::remove_file(&file).is_err();
+    }
+
+    fn get_options(&self) -> Options {
+        let mut options = Options::empty();
+        if let Ok(mut statement) = self.db.prepare(SQL_GET_OPTIONS) {
+            while let State::Row = statement.next().unwrap() {
+                let name = statement.read::<String>(0).unwrap();
+                let value = statement.read::<String>(1).unwrap();
+                match name.as_ref() {
+                    "origin" => options.origin = value,
+                    "version" => options.version = value.parse().unwrap(),
+                    _ => {}
+                }
+            }
+        }
+        options
+    }
+
+    pub fn add_block(&mut self, block: Block) {
+        debug!("Adding block:\n{:?}", &block);
+        let index = block.index;
+        let block = Arc::new(block);
+        self.last_block = Some(Arc::clone(&block));
+        if block.transaction.is_some() {
+            self.last_full_block = Some(Arc::clone(&block));
+        }
+        if self.add_block_to_table(&block).is_ok() {
+            if let (Some(transaction), Some(signing)) = (&block.transaction, block.get_signing()) {
+                self.add_transaction_to_table(index, block.timestamp, transaction, signing).expect("Error adding transaction");
+            }
+            post(Event::NewBlock { index, hash: block.hash.to_string() });
+            if block.transaction.is_none() {
+                self.post_domain_confirmed(index);
+            }
+            if self.prune_blocks > 0 && index % PRUNE_INTERVAL == 0 {
+                self.prune();
+            }
+            if index % self.kind.activation_window() == 0 {
+                self.log_rule_changes(index + 1);
+            }
+        }
+    }
+
+    /// Tells about rules that change their state from the next window, and about signals of rules that we don't know
+    fn log_rule_changes(&self, index: u64) {
+        for rule in RULES {
+            let (state, since) = self.get_rule_state(rule, index);
+            if since == index {
+                info!("Rule '{}' is {:?} starting from block {}", rule.name, state, index);
+            }
+        }
+        let window = self.kind.activation_window();
+        let unknown = RULES.iter().fold(BLOCK_SIGNAL_MASK, |mask, rule| mask & !rule.mask());
+        if self.count_signals(unknown, index.saturating_sub(window), index) * 100 >= window * ACTIVATION_THRESHOLD {
+            warn!("Most of miners are ready for rules that this version doesn't know, please update ALFIS");
+        }
+    }
+
+    /// State of the rule for block at this height, and the height of the first block in that state
+    pub fn get_rule_state(&self, rule: &Rule, index: u64) -> (RuleState, u64) {
+        let window = self.kind.activation_window();
+        match RULES.iter().position(|r| r == rule) {
+            Some(position) => self.get_rule_states(window_start(index, window))[position],
+            None => rule_state(rule, index, window, |from, to| self.count_signals(rule.mask(), from, to))
+        }
+    }
+
+    /// States of all `RULES` in the window that starts at block `start`, counted from the last known window
+    fn get_rule_states(&self, start: u64) -> Vec<(RuleState, u64)> {
+        let window = self.kind.activation_window();
+        let mut cache = self.rule_states.borrow_mut();
+        if let Some(states) = cache.get(&start) {
+            return states.clone();
+        }
+        let (mut current, mut states) = match cache.range(..start).next_back() {
+            Some((current, states)) => (*current, states.clone()),
+            None => (1, vec![(RuleState::Defined, 1); RULES.len()])
+        };
+        // Signals of the previous window don't change only when all its blocks are in DB
+        let complete = self.get_height() + 1;
+        while current < start {
+            current += window;
+            for (rule, (state, since)) in RULES.iter().zip(states.iter_mut()) {
+                let next = next_state(rule, *state, current, window, |from, to| self.count_signals(rule.mask(), from, to));
+                if next != *state {
+                    *state = next;
+                    *since = current;
+                }
+            }
+            if current <= complete {
+                cache.insert(current, states.clone());
+            }
+        }
+        states
+    }
+
+    /// Forgets states of rules in windows after this block, when blocks are removed or replaced
+    fn clear_rule_states(&self, index: u64) {
+        self.rule_states.borrow_mut().retain(|start, _| *start <= index);
+    }
+
+    /// True if the rule with this name is active for block at this height
+    pub fn is_rule_active(&self, name: &str, index: u64) -> bool {
+        RULES.iter().any(|rule| rule.name == name && self.get_rule_state(rule, index).0 == RuleState::Active)
+    }
+
+    /// Bits of rules that wait for signals, miners add them to version of new blocks
+    pub fn get_signal_bits(&self, index: u64) -> u32 {
+        RULES.iter()
+            .filter(|rule| self.get_rule_state(rule, index).0 == RuleState::Started)
+            .fold(0, |bits, rule| bits | rule.mask())
+    }
+
+    /// Version of new block at this height: PoW algorithm by active rules and bits of rules that wait for signals
+    pub fn get_block_version(&self, index: u64) -> u32 {
+        block_version(self.is_rule_active(ARGON2_RULE, index)) | self.get_signal_bits(index)
+    }
+
+    /// Counts blocks from `from` to `to` (not including) that have any of these bits in version
+    pub fn count_signals(&self, mask: u32, from: u64, to: u64) -> u64 {
+        let mut statement = self.db.prepare(SQL_COUNT_SIGNALS).unwrap();
+        statement.bind(1, from as i64).expect("Error in bind");
+        statement.bind(2, to as i64).expect("Error in bind");
+        statement.bind(3, mask as i64).expect("Error in bind");
+        if let State::Row = statement.next().unwrap() {
+            return statement.read::<i64>(0).unwrap() as u64;
+        }
+        0
+    }
+
+    /// Drops transactions of blocks older than `prune_blocks`, their domains stay in domains table
+    fn prune(&mut self) {
+        let height = self.get_height();
+        if height <= self.prune_blocks {
+            return;
+        }
+        let before = height - self.prune_blocks;
+        let result = self.db.prepare(SQL_PRUNE_BLOCKS).and_then(|mut statement| {
+            statement.bind(1, before as i64)?;
+            statement.next()
+        });
+        match result {
+            Ok(_) => {
+                debug!("Pruned {} blocks up to {}", self.db.change_count(), before);
+                self.pruned_height = self.load_pruned_height();
+            }
+            Err(e) => warn!("Error pruning blocks: {}", e)
+        }
+    }
+
+    fn load_pruned_height(&self) -> u64 {
+        match self.db.prepare(SQL_GET_PRUNED_HEIGHT) {
+            Ok(mut statement) => match statement.next() {
+                Ok(State::Row) => statement.read::<Option<i64>>(0).unwrap_or(None).unwrap_or(0) as u64,
+                _ => 0
+            },
+            Err(_) => 0
+        }
+    }
+
+    /// True if we keep transactions only of last blocks, and can't give old blocks to others
+    pub fn is_pruned(&self) -> bool {
+        self.prune_blocks > 0
+    }
+
+    /// Blocks up to this height may have no transactions, `get_block` doesn't give them
+    pub fn get_pruned_height(&self) -> u64 {
+        self.pruned_height
+    }
+
+    pub fn get_kind(&self) -> ChainKind {
+        self.kind
+    }
+
+    /// Tells everybody that the last domain got enough signatures
+    fn post_domain_confirmed(&self, index: u64) {
+        if let Some(block) = &self.last_full_block {
+            if index - block.index != BLOCK_SIGNERS_MIN {
+                return;
+            }
+            if let Some(transaction) = &block.transaction {
+                if transaction.class == CLASS_DOMAIN {
+                    post(Event::DomainConfirmed { index: block.index, identity: transaction.identity.to_string() });
+                }
+            }
+        }
+    }
+
+    /// Checks and adds consequent blocks in one DB transaction.
+    /// Stops at the first block that is not good and returns it with the rest of blocks.
+    pub fn add_blocks(&mut self, blocks: Vec<Block>) -> Vec<Block> {
+        let count = blocks.len();
+        let start = Instant::now();
+        let mut blocks = blocks.into_iter();
+        let mut rest = Vec::new();
+        if let Err(e) = self.db.execute("BEGIN TRANSACTION;") {
+            warn!("Error starting DB transaction: {}", e);
+        }
+        for block in blocks.by_ref() {
+            if self.check_new_block(&block) != Good {
+                rest.push(block);
+                break;
+            }
+            self.add_block(block);
+        }
+        if let Err(e) = self.db.execute("COMMIT;") {
+            warn!("Error committing DB transaction: {}", e);
+        }
+        rest.extend(blocks);
+        // To measure sync speed
+        debug!("Added {} blocks in {} ms", count - rest.len(), start.elapsed().as_millis());
+        rest
+    }
+
+    pub fn replace_block(&mut self, block: Block) -> sqlite::Result<()> {
+        info!("Replacing block {} with:\n{:?}", block.index, &block);
+        self.signers.borrow_mut().clear();
+        self.truncate_db_from_block(block.index)?;
+        self.add_block(block);
+        Ok(())
+    }
+
+    pub fn get_sign_block(&self, keys: &[Keystore]) -> Option<(Block, Keystore)> {
+        if self.get_height() < self.kind.signers_start() {
+            trace!("Too early to start block signings");
+            return None;
+        }
+        if keys.is_empty() {
+            trace!("We can't sign blocks without keys");
+            return None;
+        }
+        if self.get_height() < self.get_max_height() {
+            trace!("No signing while syncing");
+            return None;
+        }
+
+        let block = match self.last_full_block {
+            None => {
+                return None;
+            }
+            Some(ref block) => block.clone()
+        };
+        // TODO maybe make some config option to mine signing blocks above?
+        let sign_count = self.get_height() - block.index;
+        if sign_count >= BLOCK_SIGNERS_MIN {
+            trace!("Block {} has enough signing blocks", block.index);
+            return None;
+        }
+        if let Some(block) = &self.last_block {
+            if block.timestamp + 60 > Utc::now().timestamp() {
+                info!("Waiting for other blocks before signing.");
+                return None;
+            }
+        }
+        let (last_hash, last_index) = match &self.last_block {
+            Some(block) => (block.hash.clone(), block.index),
+            None => { return None; }
+        };
+
+        let signers: HashSet<Bytes> = self.get_block_signers(&block).into_iter().collect();
+        let mut rng = rand::thread_rng();
+        let keystore = keys
+            .iter()
+            .filter(|keystore| signers.contains(&keystore.get_public()))
+            .filter(|keystore| {
+                for index in block.index..=self.get_height() {
+                    let b = self.get_block_header(index).unwrap();
+                    if b.pub_key == keystore.get_public() {
+                        debug!("We already mined signing block for block {} by {:?}", block.index, &b.pub_key);
+                        return false;
+                    }
+                }
+                true
+            })
+            .choose(&mut rng);
+        if let Some(keystore) = keystore {
+            info!("We have an honor to mine signing block!");
+            let mut block = Block::new(None, Bytes::default(), last_hash, self.kind.signer_difficulty());
+            block.index = last_index + 1;
+            return Some((block, keystore.clone()));
+        }
+        if !signers.is_empty() {
+            info!("Signing block must be mined by other nodes");
+        }
+        None
+    }
+
+    pub fn update_sign_block_for_mining(&self, mut block: Block) -> Option<Block> {
+        if let Some(full_block) = &self.last_full_block {
+            let sign_count = self.get_height() - full_block.index;
+            if sign_count >= BLOCK_SIGNERS_MIN {
+                return None;
+            }
+            if let Some(last) = &self.last_block {
+                block.index = last.index + 1;
+                block.prev_block_hash = last.hash.clone();
+                return Some(block);
+            }
+        }
+        None
+    }
+
+    pub fn is_waiting_signers(&self) -> bool {
+        if self.get_height() < self.kind.signers_start() {
+            return false;
+        }
+        if let Some(full_block) = &self.last_full_block {
+            let sign_count = self.get_height() - full_block.index;
+            if sign_count < BLOCK_SIGNERS_MIN {
+                return true;
+            }
+        }
+
+        false
+    }
+
+    /// Adds block to blocks table
+    fn add_block_to_table(&mut self, block: &Block) -> sqlite::Result<State> {
+        let mut statement = self.db.prepare(SQL_ADD_BLOCK)?;
+        statement.bind(1, block.index as i64)?;
+        statement.bind(2, block.timestamp as i64)?;
+        statement.bind(3, block.version as i64)?;
+        statement.bind(4, block.difficulty as i64)?;
+        statement.bind(5, block.random as i64)?;
+        statement.bind(6, block.nonce as i64)?;
+        match &block.transaction {
+            None => {
+                statement.bind(7, "")?;
+            }
+            Some(transaction) => {
+                statement.bind(7, transaction.to_string().as_str())?;
+            }
+        }
+        statement.bind(8, block.prev_block_hash.as_slice())?;
+        statement.bind(9, block.hash.as_slice())?;
+        statement.bind(10, block.pub_key.as_slice())?;
+        statement.bind(11, block.signature.as_slice())?;
+        statement.next()
+    }
+
+    /// Adds transaction to transactions table, `signing` is the owner of domain
+    fn add_transaction_to_table(&mut self, index: u64, timestamp: i64, t: &Transaction, signing: &Bytes) -> sqlite::Result<State> {
+        let sql = match t.class.as_ref() {
+            CLASS_DOMAIN => SQL_ADD_DOMAIN,
+            CLASS_ORIGIN => return Ok(State::Done),
+            class => return match get_class_handler(class) {
+                Some(handler) => {
+                    handler.apply(self, index, timestamp, t);
+                    Ok(State::Done)
+                }
+                None => Err(sqlite::Error { code: None, message: None })
+            }
+        };
+
+        let mut statement = self.db.prepare(sql)?;
+        statement.bind(1, index as i64)?;
+        statement.bind(2, timestamp)?;
+        statement.bind(3, t.identity.as_slice())?;
+        statement.bind(4, t.confirmation.as_slice())?;
+        statement.bind(5, t.data.as_ref() as &str)?;
+        statement.bind(6, signing.as_slice())?;
+        statement.bind(7, t.encryption.as_slice())?;
+        statement.next()?;
+
+        let mut statement = self.db.prepare(SQL_UPDATE_IDENTITY)?;
+        statement.bind(1, t.identity.as_slice())?;
+        statement.bind(2, index as i64)?;
+        statement.bind(3, signing.as_slice())?;
+        statement.next()
+    }
+
+    /// Gets the whole block, pruned blocks are not given
+    pub fn get_block(&self, index: u64) -> Option<Block> {
+        self.load_block(index, false)
+    }
+
+    /// Gets the block even if its transaction was pruned, for checks that need only headers of blocks
+    fn get_block_header(&self, index: u64) -> Option<Block> {
+        self.load_block(index, true)
+    }
+
+    fn load_block(&self, index: u64, allow_pruned: bool) -> Option<Block> {
+        match self.db.prepare(SQL_GET_BLOCK_BY_ID) {
+            Ok(mut statement) => {
+                statement.bind(1, index as i64).expect("Error in bind");
+                if statement.next().unwrap() == State::Row {
+                    if !allow_pruned && statement.kind(6) == sqlite::Type::Null {
+                        return None;
+                    }
+                    return match Self::get_block_from_statement(&mut statement) {
+                        None => {
+                            error!("Something wrong with block in DB!");
+                            None
+                        }
+                        Some(block) => {
+                            //trace!("Loaded block: {:?}", &block);
+                            Some(block)
+                        }
+                    };
+                }
+                None
+            }
+            Err(_) => {
+                warn!("Can't find requested block {}", index);
+                None
+            }
+        }
+    }
+
+    /// Gets last block that has a Transaction within
+    pub fn get_last_full_block(&self, before: u64, pub_key: Option<&[u8]>) -> Option<Arc<Block>> {
+        if let Some(block) = &self.last_full_block {
+            if block.index < before {
+                match pub_key {
+                    None => {
+                        return Some(Arc::clone(block));
+                    }
+                    Some(key) => {
+                        if block.pub_key.deref().eq(key) {
+                            return Some(Arc::clone(block));
+                        }
+                    }
+                }
+            }
+        }
+
+        let mut statement = match pub_key {
+            None => {
+                let mut statement = self.db.prepare(SQL_GET_LAST_FULL_BLOCK).expect("Unable to prepare");
+                statement.bind(1, before as i64).expect("Unable to bind");
+                statement
+            }
+            Some(pub_key) => {
+                let mut statement = self.db.prepare(SQL_GET_LAST_FULL_BLOCK_FOR_KEY).expect("Unable to prepare");
+                statement.bind(1, before as i64).expect("Unable to bind");
+                statement.bind(2, pub_key).expect("Unable to bind");
+                statement
+            }
+        };
+        if statement.next().unwrap() == State::Row {
+            return match Self::get_block_from_statement(&mut statement) {
+                None => {
+                    error!("Something wrong with block in DB!");
+                    None
+                }
+                Some(block) => {
+                    //trace!("Got last full block: {:?}", &block);
+                    Some(Arc::new(block))
+                }
+            };
+        }
+        None
+    }
+
+    pub fn can_mine_domain(&self, height: u64, domain: &str, pub_key: &Bytes) -> MineResult {
+        let name = domain.to_lowercase();
+        if !check_domain(&name, true) {
+            return WrongName;
+        }
+        let zone = get_domain_zone(&name);
+        if !self.is_available_zone(&zone) {
+            return WrongZone;
+        }
+
+        let identity_hash = hash_identity(&name, None);
+        let time = Utc::now().timestamp();
+        let (transaction, state) = self.get_domain_transaction_and_state(&name);
+        if let Some(transaction) = transaction {
+            let owner = transaction.signing.eq(pub_key);
+            match state {
+                DomainState::NotFound => {}
+                DomainState::Alive { .. } => if !owner && self.get_bid_difficulty(&identity_hash, height + 1, time).is_none() {
+                    return NotOwned;
+                },
+                DomainState::Expired { .. } => if !owner {
+                    return NotOwned;
+                },
+                DomainState::Free { .. } => {}
+            }
+        }
+        match self.can_mine_identity(&identity_hash, height, time, pub_key) {
+            Fine if self.is_registration_limited(&identity_hash, &zone, pub_key, height + 1) => Limited,
+            result => result
+        }
+    }
+
+    /// Checks if we can mine prepared domain transaction with our key, the domain will belong to the `signing` key of transaction
+    pub fn can_mine_transaction(&self, transaction: &Transaction, pub_key: &Bytes) -> MineResult {
+        if transaction.class != CLASS_DOMAIN {
+            return WrongData;
+        }
+        let data = match transaction.get_domain_data() {
+            Some(data) => data,
+            None => return WrongData
+        };
+        if data.records.len() > MAX_RECORDS {
+            return WrongData;
+        }
+        if data.records.iter().any(|r| r.get_ttl() > MAX_RECORD_TTL) {
+            return WrongData;
+        }
+        if data.comments.len() > data.records.len() || data.comments.iter().any(|c| check_record_comment(c).is_err()) {
+            return WrongData;
+        }
+        if !self.is_available_zone(&data.zone) {
+            return WrongZone;
+        }
+        let yggdrasil_only = self.zones.iter().any(|z| z.name == data.zone && z.yggdrasil);
+        if yggdrasil_only && !data.records.iter().all(is_yggdrasil_record) {
+            return WrongData;
+        }
+        let height = self.get_height();
+        let time = Utc::now().timestamp();
+        if !self.is_id_available(height, time, &transaction.identity, pub_key) && self.get_bid_difficulty(&transaction.identity, height + 1, time).is_none() {
+            return NotOwned;
+        }
+        match self.can_mine_identity(&transaction.identity, height, time, pub_key) {
+            Fine if self.is_registration_limited(&transaction.identity, &data.zone, &transaction.signing, height + 1) => Limited,
+            result => result
+        }
+    }
+
+    fn can_mine_identity(&self, identity_hash: &Bytes, height: u64, time: i64, pub_key: &Bytes) -> MineResult {
+        if let Some(last) = self.get_last_full_block(height, Some(pub_key)) {
+            // If this domain/identity is new
+            let want_new_domain = !self.is_domain_in_blockchain(height, &identity_hash);
+            // And the user hasn't mined a domain in previous 24h, then we allow her to mine
+            let time = last.timestamp + self.kind.new_domains_interval() - time;
+            if want_new_domain && time > 0 {
+                return Cooldown { time };
+            }
+        }
+        Fine
+    }
+
+    /// Checks if this identity is free or is owned by the same pub_key
+    pub fn is_id_available(&self, height: u64, time: i64, identity: &Bytes, public_key: &Bytes) -> bool {
+        let (transaction, state) = self.get_identity_transaction_and_state(identity, height, time);
+        if transaction.is_none() {
+            return true;
+        }
+        if transaction.unwrap().signing.eq(public_key) {
+            return true;
+        }
+        match state {
+            DomainState::NotFound => true,
+            DomainState::Alive { .. } => false,
+            DomainState::Expired { .. } => false,
+            DomainState::Free { .. } => true
+        }
+    }
+
+    /// Difficulty that block `index` needs to take this identity from the leader of auction, if the domain was freed recently.
+    /// Freed domains go to the most difficult block that is mined during `auction_time`, not to the first one.
+    pub fn get_bid_difficulty(&self, identity: &Bytes, index: u64, time: i64) -> Option<u32> {
+        if !self.is_rule_active(AUCTION_RULE, index) {
+            return None;
+        }
+        let mut statement = self.db.prepare(SQL_GET_DOMAIN_BIDS).unwrap();
+        statement.bind(1, identity.as_slice()).expect("Error in bind");
+        statement.bind(2, index as i64).expect("Error in bind");
+        let mut bids = Vec::new();
+        while let State::Row = statement.next().unwrap() {
+            bids.push((statement.read::<i64>(0).unwrap(), statement.read::<i64>(1).unwrap() as u32));
+        }
+        let (leader_time, leader_difficulty) = *bids.first()?;
+        let free_after = self.kind.domain_lifetime() + self.kind.domain_renew_time();
+        for pair in bids.windows(2) {
+            let (newer, older) = (pair[0].0, pair[1].0);
+            // The newer block took the domain when it was free, the auction started then
+            if newer > older + free_after {
+                let end = older + free_after + self.kind.auction_time();
+                return if leader_time <= end && time <= end { Some(leader_difficulty + 1) } else { None };
+            }
+        }
+        None
+    }
+
+    /// True if this identity is new, and the `signing` key of its transaction has registered enough new domains
+    /// in the zone before block `index`. Domains are counted by owners, not by miners, as friends can mine them.
+    pub fn is_registration_limited(&self, identity: &Bytes, zone: &str, signing: &Bytes, index: u64) -> bool {
+        if self.is_domain_in_blockchain(index, identity) {
+            return false;
+        }
+        self.get_registrations_left(zone, signing, index) == Some(0)
+    }
+
+    /// How many new domains the signing key can register in the zone in block `index`, None if there is no limit yet
+    pub fn get_registrations_left(&self, zone: &str, signing: &Bytes, index: u64) -> Option<u64> {
+        if !self.is_rule_active(REGISTRATION_LIMIT_RULE, index) {
+            return None;
+        }
+        let from = index.saturating_sub(self.kind.registration_window());
+        let count = self.count_new_domains(zone, signing, from, index);
+        Some(self.kind.registration_limit(zone).saturating_sub(count))
+    }
+
+    /// Counts domains of the zone that were registered first by transactions signed by this key, from `from` to `to` (not including)
+    fn count_new_domains(&self, zone: &str, signing: &Bytes, from: u64, to: u64) -> u64 {
+        let mut statement = self.db.prepare(SQL_GET_NEW_DOMAINS_OF_KEY).unwrap();
+        statement.bind(1, from as i64).expect("Error in bind");
+        statement.bind(2, to as i64).expect("Error in bind");
+        statement.bind(3, signing.as_slice()).expect("Error in bind");
+        let mut count = 0;
+        while let State::Row = statement.next().unwrap() {
+            let data = statement.read::<String>(0).unwrap();
+            if let Ok(data) = serde_json::from_str::<DomainData>(&data) {
+                if data.zone == zone {
+                    count += 1;
+                }
+            }
+        }
+        count
+    }
+
+    pub fn get_zones(&self) -> &Vec<ZoneData> {
+        &self.zones
+    }
+
+    fn load_zones() -> Vec<ZoneData> {
+        let mut result: Vec<ZoneData> = Vec::new();
+        let zones_text = ZONES_TXT.replace("\r", "");
+        let zones: Vec<_> = zones_text.split('\n').collect();
+        for zone in zones {
+            let yggdrasil = zone == "ygg" || zone == "anon";
+            result.push(ZoneData { name: zone.to_owned(), yggdrasil })
+        }
+        result
+    }
+
+    pub fn get_zones_hash() -> Bytes {
+        Bytes::from_bytes(hash_sha256(ZONES_TXT.as_bytes()).as_slice())
+    }
+
+    /// Checks if some zone exists in our blockchain
+    pub fn is_available_zone(&self, zone: &str) -> bool {
+        for z in &self.zones {
+            if z.name == zone {
+                return true;
+            }
+        }
+
+        false
+    }
+
+    /// Checks if some id exists in our blockchain
+    pub fn is_domain_in_blockchain(&self, height: u64, id: &Bytes) -> bool {
+        // Checking for existing domain in DB
+        let mut statement = self.db.prepare(SQL_GET_DOMAIN_OWNER_BY_ID).unwrap();
+        statement.bind(1, height as i64).expect("Error in bind");
+        statement.bind(2, id.as_slice()).expect("Error in bind");
+        if let State::Row = statement.next().unwrap() {
+            // If there is such an ID
+            return true;
+        }
+        false
+    }
+
+    pub fn get_domain_renewal_time(&self, time: i64, identity_hash: &Bytes) -> Option<i64> {
+        let mut statement = self.db.prepare(SQL_GET_DOMAIN_UPDATE_TIME).unwrap();
+        statement.bind(1, identity_hash.as_slice()).expect("Error in bind");
+        if let State::Row = statement.next().unwrap() {
+            let timestamp = statement.read::<i64>(0).unwrap();
+            if timestamp < time - self.kind.domain_lifetime() {
+                // This domain is too old
+                return None;
+            }
+            return Some(timestamp);
+        }
+        None
+    }
+
+    pub fn get_domain_update_time(&self, identity_hash: &Bytes, height: u64, time: i64) -> Option<i64> {
+        let mut statement = self.db.prepare(SQL_GET_DOMAIN_BY_ID).unwrap();
+        statement.bind(1, identity_hash.as_slice()).expect("Error in bind");
+        statement.bind(2, height as i64 ).expect("Error in bind");
+        if let State::Row = statement.next().unwrap() {
+            let timestamp = statement.read::<i64>(1).unwrap();
+            if timestamp < time - self.kind.domain_lifetime() {
+                // This domain is too old
+                return None;
+            }
+            return Some(timestamp);
+        }
+        None
+    }
+
+    pub fn get_identity_transaction_and_state(&self, identity_hash: &Bytes, height: u64, time: i64) -> (Option<Transaction>, DomainState) {
+        let mut statement = self.db.prepare(SQL_GET_DOMAIN_BY_ID).unwrap();
+        statement.bind(1, identity_hash.as_slice()).expect("Error in bind");
+        statement.bind(2, height as i64).expect("Error in bind");
+        if let State::Row = statement.next().unwrap() {
+            let timestamp = statement.read::<i64>(1).unwrap();
+            // Determine current state of the domain
+            let (lifetime, renew_time) = (self.kind.domain_lifetime(), self.kind.domain_renew_time());
+            let state = if timestamp + lifetime >= time {
+                DomainState::Alive { renewed_time: timestamp, until: timestamp + lifetime }
+            } else if timestamp + lifetime + renew_time >= time {
+                DomainState::Expired { renewed_time: timestamp, until: timestamp + lifetime + renew_time }
+            } else {
+                DomainState::Free { renewed_time: timestamp }
+            };
+            let identity = Bytes::from_bytes(&statement.read::<Vec<u8>>(2).unwrap());
+            let confirmation = Bytes::from_bytes(&statement.read::<Vec<u8>>(3).unwrap());
+            let class = String::from(CLASS_DOMAIN);
+            let data = statement.read::<String>(4).unwrap();
+            let signing = Bytes::from_bytes(&statement.read::<Vec<u8>>(5).unwrap());
+            let encryption = Bytes::from_bytes(&statement.read::<Vec<u8>>(6).unwrap());
+            let transaction = Transaction { identity, confirmation, class, data, signing, encryption };
+            return (Some(transaction), state);
+        }
+        (None, DomainState::NotFound)
+    }
+
+    /// Gets full Transaction info for any domain. Used by DNS part.
+    pub fn get_domain_transaction_and_state(&self, domain: &str) -> (Option<Transaction>, DomainState) {
+        if domain.is_empty() {
+            return (None, DomainState::NotFound);
+        }
+        let identity_hash = hash_identity(domain, None);
+        let (transaction, state) = self.get_identity_transaction_and_state(&identity_hash, self.get_height(), Utc::now().timestamp());
+        if let Some(transaction) = transaction {
+            debug!("Found transaction for domain {}: {:?}", domain, &transaction);
+            if transaction.check_identity(domain) {
+                return (Some(transaction), state);
+            }
+        }
+        (None, DomainState::NotFound)
+    }
+
+    pub fn get_domain_info(&self, domain: &str) -> Option<String> {
+        match self.get_domain_transaction_and_state(domain) {
+            (None, _) => None,
+            (Some(transaction), state) => {
+                if matches!(state, DomainState::Alive {..}) {
+                    Some(transaction.data)
+                } else {
+                    None
+                }
+            }
+        }
+    }
+
+    /// Gets all transactions of the domain with heights and times of their blocks, the newest first
+    pub fn get_domain_history(&self, domain: &str) -> Vec<(u64, i64, Transaction)> {
+        let mut result = Vec::new();
+        if domain.is_empty() {
+            return result;
+        }
+        let identity = hash_identity(domain, None);
+        let mut statement = self.db.prepare(SQL_GET_DOMAIN_HISTORY).unwrap();
+        statement.bind(1, identity.as_slice()).expect("Error in bind");
+        while let State::Row = statement.next().unwrap() {
+            let index = statement.read::<i64>(0).unwrap() as u64;
+            let timestamp = statement.read::<i64>(1).unwrap();
+            let confirmation = Bytes::from_bytes(&statement.read::<Vec<u8>>(2).unwrap());
+            let class = String::from(CLASS_DOMAIN);
+            let data = statement.read::<String>(3).unwrap();
+            let signing = Bytes::from_bytes(&statement.read::<Vec<u8>>(4).unwrap());
+            let encryption = Bytes::from_bytes(&statement.read::<Vec<u8>>(5).unwrap());
+            let transaction = Transaction { identity: identity.clone(), confirmation, class, data, signing, encryption };
+            if transaction.check_identity(domain) {
+                result.push((index, timestamp, transaction));
+            }
+        }
+        result
+    }
+
+    /// Gets last transactions of all domains that are not expired yet
+    pub fn get_alive_domains(&self, time: i64) -> Vec<Transaction> {
+        let mut result = Vec::new();
+        let mut statement = self.db.prepare(SQL_GET_LAST_DOMAINS).unwrap();
+        while let State::Row = statement.next().unwrap() {
+            let timestamp = statement.read::<i64>(0).unwrap();
+            if timestamp + self.kind.domain_lifetime() < time {
+                continue;
+            }
+            let identity = Bytes::from_bytes(&statement.read::<Vec<u8>>(1).unwrap());
+            let confirmation = Bytes::from_bytes(&statement.read::<Vec<u8>>(2).unwrap());
+            let class = String::from(CLASS_DOMAIN);
+            let data = statement.read::<String>(3).unwrap();
+            let signing = Bytes::from_bytes(&statement.read::<Vec<u8>>(4).unwrap());
+            let encryption = Bytes::from_bytes(&statement.read::<Vec<u8>>(5).unwrap());
+            result.push(Transaction { identity, confirmation, class, data, signing, encryption });
+        }
+        result
+    }
+
+    /// Saves the address of peer that we have successfully connected to
+    pub fn add_peer_success(&self, addr: &str) {
+        let mut statement = self.db.prepare(SQL_PEER_SUCCESS).unwrap();
+        statement.bind(1, addr).expect("Error in bind");
+        statement.bind(2, Utc::now().timestamp()).expect("Error in bind");
+        if let Err(e) = statement.next() {
+            warn!("Error saving peer {}: {}", addr, e);
+        }
+    }
+
+    /// Counts unsuccessful connection to known peer
+    pub fn add_peer_failure(&self, addr: &str) {
+        let mut statement = self.db.prepare(SQL_PEER_FAILURE).unwrap();
+        statement.bind(1, addr).expect("Error in bind");
+        if let Err(e) = statement.next() {
+            warn!("Error saving peer {}: {}", addr, e);
+        }
+    }
+
+    /// Gets addresses of peers that were seen during last month, freshest first.
+    /// Peers that fail to connect much more than succeed are forgotten.
+    pub fn get_known_peers(&self, limit: usize) -> Vec<String> {
+        let old = Utc::now().timestamp() - ONE_WEEK * 4;
+        if let Ok(mut statement) = self.db.prepare(SQL_DELETE_OLD_PEERS) {
+            statement.bind(1, old).expect("Error in bind");
+            let _ = statement.next();
+        }
+        let mut result = Vec::new();
+        let mut statement = self.db.prepare(SQL_GET_PEERS).unwrap();
+        statement.bind(1, old).expect("Error in bind");
+        statement.bind(2, limit as i64).expect("Error in bind");
+        while let State::Row = statement.next().unwrap() {
+            result.push(statement.read::<String>(0).unwrap());
+        }
+        result
+    }
+
+    /// Gets all peers with their history, for backups
+    pub fn get_all_peers(&self) -> Vec<KnownPeer> {
+        let mut result = Vec::new();
+        let mut statement = self.db.prepare(SQL_GET_ALL_PEERS).unwrap();
+        while let State::Row = statement.next().unwrap() {
+            result.push(KnownPeer {
+                addr: statement.read::<String>(0).unwrap(),
+                last_seen: statement.read::<i64>(1).unwrap(),
+                success: statement.read::<i64>(2).unwrap(),
+                failure: statement.read::<i64>(3).unwrap()
+            });
+        }
+        result
+    }
+
+    /// Adds peers from backup, peers that we know already keep their better history
+    pub fn restore_peers(&self, peers: &[KnownPeer]) {
+        for peer in peers {
+            let mut statement = self.db.prepare(SQL_RESTORE_PEER).unwrap();
+            statement.bind(1, peer.addr.as_str()).expect("Error in bind");
+            statement.bind(2, peer.last_seen).expect("Error in bind");
+            statement.bind(3, peer.success).expect("Error in bind");
+            statement.bind(4, peer.failure).expect("Error in bind");
+            if let Err(e) = statement.next() {
+                warn!("Error restoring peer {}: {}", &peer.addr, e);
+            }
+        }
+    }
+
+    /// Bans IP-address until some time
+    pub fn add_ban(&self, ip: &str, until: i64, reason: &str) {
+        let mut statement = self.db.prepare(SQL_ADD_BAN).unwrap();
+        statement.bind(1, ip).expect("Error in bind");
+        statement.bind(2, until).expect("Error in bind");
+        statement.bind(3, reason).expect("Error in bind");
+        if let Err(e) = statement.next() {
+            warn!("Error saving ban of {}: {}", ip, e);
+        }
+    }
+
+    pub fn remove_ban(&self, ip: &str) -> bool {
+        let mut statement = self.db.prepare(SQL_REMOVE_BAN).unwrap();
+        statement.bind(1, ip).expect("Error in bind");
+        statement.next().is_ok() && self.db.change_count() > 0
+    }
+
+    /// Gets IP-addresses that are banned at the given time
+    pub fn get_bans(&self, time: i64) -> Vec<String> {
+        let mut result = Vec::new();
+        let mut statement = self.db.prepare(SQL_GET_BANS).unwrap();
+        statement.bind(1, time).expect("Error in bind");
+        while let State::Row = statement.next().unwrap() {
+            result.push(statement.read::<String>(0).unwrap());
+        }
+        result
+    }
+
+    /// Timings of DB queries if `api.query_stats` is enabled
+    pub fn get_query_stats(&self) -> Vec<QueryStats> {
+        self.db.get_stats()
+    }
+
+    /// Size of the database in bytes, free pages included
+    pub fn get_db_size(&self) -> u64 {
+        let mut statement = self.db.prepare(SQL_GET_DB_SIZE).unwrap();
+        if let State::Row = statement.next().unwrap() {
+            return statement.read::<i64>(0).unwrap() as u64;
+        }
+        0
+    }
+
+    pub fn is_timing_queries(&self) -> bool {
+        self.db.is_timing()
+    }
+
+    pub fn get_domains_count(&self) -> i64 {
+        let mut statement = self.db.prepare(SQL_GET_DOMAINS_COUNT).unwrap();
+        if let State::Row = statement.next().unwrap() {
+            return statement.read::<i64>(0).unwrap();
+        }
+        0
+    }
+
+    pub fn get_users_count(&self) -> i64 {
+        let mut statement = self.db.prepare(SQL_GET_USERS_COUNT).unwrap();
+        if let State::Row = statement.next().unwrap() {
+            return statement.read::<i64>(0).unwrap();
+        }
+        0
+    }
+
+    pub fn get_user_block_count(&self, pub_key: &Bytes, max_height: u64) -> i64 {
+        let mut statement = self.db.prepare(SQL_GET_USER_BLOCK_COUNT).unwrap();
+        statement.bind(1, pub_key.as_slice()).expect("Error in bind");
+        statement.bind(2, max_height as i64).expect("Error in bind");
+        if let State::Row = statement.next().unwrap() {
+            return statement.read::<i64>(0).unwrap();
+        }
+        0i64
+    }
+
+    pub fn get_my_domains(&self, keystore: Option<&Keystore>) -> HashMap<Bytes, (String, i64, DomainData)> {
+        let keystore = match keystore {
+            Some(keystore) => keystore,
+            None => return HashMap::new()
+        };
+        let mut result = HashMap::new();
+        for (identity, (timestamp, data)) in self.get_owned_domains(&keystore.get_public()) {
+            let decrypted = keystore.decrypt(data.encrypted.as_slice());
+            let mut domain = String::from_utf8(decrypted.to_vec()).unwrap();
+            if domain.is_empty() {
+                domain = String::from("unknown");
+            }
+            result.insert(identity, (domain, timestamp, data));
+        }
+        result
+    }
+
+    /// Gets domains that are owned by this public key now, with the time of their last renewal.
+    /// Domain names are encrypted in data, so we need the secret encryption key to know them.
+    pub fn get_owned_domains(&self, pub_key: &Bytes) -> HashMap<Bytes, (i64, DomainData)> {
+        self.get_domains_by_owner(pub_key)
+            .into_iter()
+            .map(|domain| (domain.identity, (domain.renewed, domain.data)))
+            .collect()
+    }
+
+    /// Gets domains that are owned by this public key now, with heights of their last blocks, the oldest first
+    pub fn get_domains_by_owner(&self, pub_key: &Bytes) -> Vec<OwnedDomain> {
+        let mut result = Vec::new();
+        // Only the last blocks of domains are in the index, so all of them are ours now
+        let mut statement = self.db.prepare(SQL_GET_DOMAINS_BY_KEY).unwrap();
+        statement.bind(1, pub_key.as_slice()).expect("Error in bind");
+        while let State::Row = statement.next().unwrap() {
+            let index = statement.read::<i64>(0).unwrap() as u64;
+            let timestamp = statement.read::<i64>(1).unwrap();
+            let identity = Bytes::from_bytes(&statement.read::<Vec<u8>>(2).unwrap());
+            let data = statement.read::<String>(3).unwrap();
+            if let Ok(data) = serde_json::from_str::<DomainData>(&data) {
+                // TODO optimize
+                let renewed = self.get_domain_renewal_time(timestamp, &identity).unwrap_or(timestamp);
+                result.push(OwnedDomain { identity, index, renewed, data });
+            }
+        }
+        result
+    }
+
+    pub fn last_block(&self) -> Option<Arc<Block>> {
+        self.last_block.clone()
+    }
+
+    pub fn get_height(&self) -> u64 {
+        match self.last_block {
+            None => 0u64,
+            Some(ref block) => block.index
+        }
+    }
+
+    pub fn get_last_hash(&self) -> Bytes {
+        match &self.last_block {
+            None => Bytes::default(),
+            Some(block) => block.hash.clone()
+        }
+    }
+
+    pub fn get_soa_serial(&self) -> u32 {
+        match &self.last_full_block {
+            None => 0,
+            Some(block) => block.timestamp as u32
+        }
+    }
+
+    pub fn next_allowed_full_block(&self) -> u64 {
+        match self.last_full_block {
+            None => self.get_height() + 1,
+            Some(ref block) => {
+                if block.index < self.kind.signers_start() {
+                    self.get_height() + 1
+                } else {
+                    max(block.index + BLOCK_SIGNERS_MIN, self.get_height() + 1)
+                }
+            }
+        }
+    }
+
+    pub fn get_max_height(&self) -> u64 {
+        self.max_height
+    }
+
+    pub fn update_max_height(&mut self, height: u64) {
+        self.max_height = height;
+    }
+
+    pub fn check_new_block(&self, block: &Block) -> BlockQuality {
+        self.check_block(block, self.last_block.as_deref(), self.last_full_block.as_deref())
+    }
+
+    /// Check if this block can be added to our blockchain
+    pub fn check_block(&self, block: &Block, last_block: Option<&Block>, last_full_block: Option<&Block>) -> BlockQuality {
+        if block.version & !(BLOCK_FLAG_ARGON2 | BLOCK_SIGNAL_MASK) > CHAIN_VERSION {
+            warn!("Ignoring block from unsupported version:\n{:?}", &block);
+            return Bad;
+        }
+        if (block.version & BLOCK_FLAG_ARGON2 != 0) != self.is_rule_active(ARGON2_RULE, block.index) {
+            warn!("Ignoring block with wrong PoW algorithm:\n{:?}", &block);
+            return Bad;
+        }
+        if WRONG_HASHES.contains(&block.hash) {
+            warn!("Got block with hash from wrong hashes.");
+            return Bad;
+        }
+        let timestamp = Utc::now().timestamp();
+        if block.timestamp > timestamp + 60 {
+            warn!("Ignoring block from the future:\n{:?}", &block);
+            return Bad;
+        }
+        if let Some(last) = last_block {
+            if block.index > last.index + 1 {
+                debug!("Got future block {}", block.index);
+                return Future;
+            }
+        }
+        let difficulty = self.get_needed_difficulty(block);
+        if block.difficulty < difficulty {
+            warn!("Block difficulty is lower than needed: {} < {}", block.difficulty, difficulty);
+            return Bad;
+        }
+        if hash_difficulty(&block.hash) < block.difficulty {
+            warn!("Ignoring block with low difficulty:\n{:?}", &block);
+            return Bad;
+        }
+        if !check_block_hash(block) {
+            warn!("Ignoring block with wrong hash:\n{:?}", &block);
+            return Bad;
+        }
+        if !check_block_signature(block) {
+            warn!("Ignoring block with wrong signature:\n{:?}", &block);
+            return Bad;
+        }
+        if let Some(prev_block) = self.get_block_header(block.index - 1) {
+            // https://en.bitcoinwiki.org/wiki/Limited_Confidence_Proof-of-Activity
+            if block.prev_block_hash.ne(&prev_block.hash) {
+                if block.index < self.get_height() - LIMITED_CONFIDENCE_DEPTH {
+                    warn!("Ignoring block from shorter chain:\n{:?}", &block);
+                    return Bad;
+                } else {
+                    warn!("Rewinding chain of block with wrong previous hash:\n{:?}", &block);
+                    return Rewind;
+                }
+            }
+        }
+
+        if let Some(transaction) = &block.transaction {
+            if !check_public_key_strength(&block.pub_key, KEYSTORE_DIFFICULTY) {
+                warn!("Ignoring block with weak public key:\n{:?}", &block);
+                return Bad;
+            }
+            if let Some(handler) = get_class_handler(&transaction.class) {
+                if !handler.check(self, block, transaction) {
+                    warn!("Ignoring block with wrong transaction of class {}:\n{:?}", &transaction.class, &block);
+                    return Bad;
+                }
+            }
+            // If this domain is not available to this public key
+            if !self.is_id_available(block.index - 1, timestamp, &transaction.identity, &block.pub_key) {
+                // Only a more difficult block can take the domain during auction
+                match self.get_bid_difficulty(&transaction.identity, block.index, block.timestamp) {
+                    Some(difficulty) if block.difficulty >= difficulty => {}
+                    _ => {
+                        warn!("Block {:?} is trying to spoof an identity!", &block);
+                        return Bad;
+                    }
+                }
+            }
+            if self.can_mine_identity(&transaction.identity, block.index, block.timestamp, &block.pub_key) != Fine {
+                warn!("Block {:?} is mined too early!", &block);
+                return Bad;
+            }
+            // Check if yggdrasil only property of zone is not violated
+            if let Some(block_data) = transaction.get_domain_data() {
+                if block_data.records.len() > MAX_RECORDS {
+                    warn!("Someone mined too many records!");
+                    return Bad;
+                }
+                let signing = block.get_signing().unwrap_or(&block.pub_key);
+                if self.is_registration_limited(&transaction.identity, &block_data.zone, signing, block.index) {
+                    warn!("Block {:?} registers too many domains in zone {}!", &block, &block_data.zone);
+                    return Bad;
+                }
+                let zones = self.get_zones();
+                for z in zones {
+                    if z.name == block_data.zone && z.yggdrasil {
+                        for record in &block_data.records {
+                            if !is_yggdrasil_record(record) {
+                                warn!("Someone mined domain with clearnet records for Yggdrasil only zone!");
+                                return Bad;
+                            }
+                            if let Some(data) = record.get_data() {
+                                if data.len() > MAX_DATA_LEN {
+                                    warn!("Someone mined too long record!");
+                                    return Bad;
+                                }
+                            }
+                        }
+                    }
+                }
+            }
+        }
+        match last_block {
+            None => {
+                if !block.is_genesis() {
+                    warn!("Block is from the future, how is this possible?");
+                    return Future;
+                }
+                if !self.origin.is_zero() && block.hash.ne(&self.origin) {
+                    warn!("Mining gave us a bad block:\n{:?}", &block);
+                    return Bad;
+                }
+            }
+            Some(last_block) => {
+                if block.timestamp < last_block.timestamp && block.index > last_block.index {
+                    warn!("Ignoring block with timestamp/index collision:\n{:?}", &block);
+                    return Bad;
+                }
+                if last_block.index + 1 < block.index {
+                    warn!("Block {} arrived too early.", block.index);
+                    return Future;
+                }
+                if block.index > self.kind.signers_start() {
+                    // If this block is main, signed part of blockchain
+                    if !self.is_good_sign_block(block, last_full_block) {
+                        return Bad;
+                    }
+                }
+
+                if block.index <= last_block.index {
+                    if block.index == last_block.index && last_block.hash == block.hash {
+                        debug!("Ignoring block {}, we already have it", block.index);
+                        return Twin;
+                    }
+                    if let Some(my_block) = self.get_block(block.index) {
+                        return if my_block.hash.ne(&block.hash) {
+                            warn!("Got forked block {} with hash {:?} instead of {:?}", block.index, block.hash, last_block.hash);
+                            Fork
+                        } else {
+                            debug!("Ignoring block {}, we already have it", block.index);
+                            Twin
+                        };
+                    }
+                } else if block.prev_block_hash.ne(&last_block.hash) {
+                    warn!("Ignoring block with wrong previous hash:\n{:?}", &block);
+                    return Bad;
+                }
+            }
+        }
+
+        Good
+    }
+
+    /// Checks if this block is a good signature block
+    fn is_good_sign_block(&self, block: &Block, last_full_block: Option<&Block>) -> bool {
+        // If this is not a signing block
+        if block.transaction.is_some() {
+            return true;
+        }
+        if let Some(full_block) = last_full_block {
+            let sign_count = self.get_height() - full_block.index;
+            if sign_count < BLOCK_SIGNERS_MIN {
+                // Last full block is not locked enough
+                if block.index > full_block.index && block.transaction.is_some() {
+                    warn!("Not enough signing blocks over full {} block!", full_block.index);
+                    return false;
+                } else if !self.is_good_signer_for_block(block, full_block) {
+                    return false;
+                }
+            } else if sign_count < BLOCK_SIGNERS_ALL && block.transaction.is_none()
+                && !self.is_good_signer_for_block(block, full_block) {
+                return false;
+            }
+        }
+        true
+    }
+
+    /// Check if this block's owner is a good candidate to sign last full block
+    fn is_good_signer_for_block(&self, block: &Block, full_block: &Block) -> bool {
+        // If we got a signing block
+        let signers: HashSet<Bytes> = self.get_block_signers(full_block).into_iter().collect();
+        if !signers.contains(&block.pub_key) {
+            warn!("Ignoring block {} from '{:?}', as wrong signer!", block.index, &block.pub_key);
+            return false;
+        }
+        // If this signers' public key has already locked/signed that block we return error
+        for i in (full_block.index + 1)..block.index {
+            let signer = self.get_block_header(i).expect("Error in DB!");
+            if signer.pub_key == block.pub_key {
+                warn!("Ignoring block {} from '{:?}', already signed by this key", block.index, &block.pub_key);
+                return false;
+            }
+        }
+        true
+    }
+
+    /// Gets minimal difficulty that this block must have to be accepted
+    pub fn get_needed_difficulty(&self, block: &Block) -> u32 {
+        match &block.transaction {
+            None => {
+                if block.index == 1 {
+                    self.kind.origin_difficulty()
+                } else {
+                    self.kind.signer_difficulty()
+                }
+            }
+            Some(t) => self.get_difficulty_for_transaction(t, block.index, block.timestamp)
+        }
+    }
+
+    /// Gets difficulty of domain block at this height, before any discounts
+    pub fn get_domain_difficulty(&self, height: u64) -> u32 {
+        if !self.is_rule_active(DIFFICULTY_ADJUST_RULE, height) {
+            return self.kind.domain_difficulty();
+        }
+        let mut statement = self.db.prepare(SQL_GET_DOMAIN_TIMESTAMPS).unwrap();
+        statement.bind(1, height as i64).expect("Error in bind");
+        statement.bind(2, DIFFICULTY_ADJUST_BLOCKS as i64).expect("Error in bind");
+        let mut timestamps = Vec::new();
+        while let State::Row = statement.next().unwrap() {
+            timestamps.push(statement.read::<i64>(0).unwrap());
+        }
+        adjust_difficulty(self.kind.domain_difficulty(), &timestamps)
+    }
+
+    fn get_difficulty_for_transaction(&self, transaction: &Transaction, height: u64, time: i64) -> u32 {
+        match transaction.class.as_ref() {
+            CLASS_DOMAIN => {
+                // If this domain is already in blockchain we approve slightly smaller difficulty
+                let discount = self.get_identity_discount(&transaction.identity, false, height, time);
+                // TODO move this check somewhere appropriate
+                return match serde_json::from_str::<DomainData>(&transaction.data) {
+                    Ok(_) => self.get_domain_difficulty(height) - discount,
+                    Err(_) => {
+                        warn!("Error parsing DomainData from {:?}", transaction);
+                        u32::MAX
+                    }
+                }
+            }
+            CLASS_ORIGIN => self.kind.origin_difficulty(),
+            class => match get_class_handler(class) {
+                Some(handler) => handler.difficulty(self, transaction, height),
+                None => u32::MAX
+            }
+        }
+    }
+
+    pub fn get_identity_discount(&self, identity: &Bytes, renewal: bool, height: u64, time: i64) -> u32 {
+        match self.get_domain_update_time(identity, height, time) {
+            None => 0u32,
+            Some(timestamp) => {
+                if renewal || self.get_height() < BLOCKS_WITHOUT_DISCOUNT {
+                    return 1;
+                }
+                // Weeks since this domain was changed + 1, but not more than 7 weeks.
+                // So max discount will be 8 bits of difficulty.
+                (min((time - timestamp) / ONE_WEEK, 7i64) + 1) as u32
+            }
+        }
+    }
+
+    /// Gets public keys of a node that needs to mine "signature" block above this block
+    /// block - last full block
+    pub fn get_block_signers(&self, block: &Block) -> Vec<Bytes> {
+        let minimum_block_count = if block.index < 855 {
+            1i64
+        } else {
+            block.index as i64 / 100
+        };
+        let mut result = Vec::new();
+        if block.index < self.kind.signers_start() || self.get_height() < block.index {
+            return result;
+        }
+
+        assert!(block.transaction.is_some());
+        if self.signers.borrow().has_signers_for(block.index) {
+            return self.signers.borrow().signers.clone();
+        }
+
+        let mut set = HashSet::new();
+        let mut tail = block.signature.get_tail_u64();
+        let mut count = 1;
+        let mut mitigated = false;
+        let window = block.index - 1; // Without the last block
+        trace!("Calculating signers, tail: {}, window: {}", tail, window);
+        while set.len() < BLOCK_SIGNERS_ALL as usize {
+            let index = (tail.wrapping_mul(count) % window) + 1; // We want it to start from 1
+            if let Some(b) = self.get_block_header(index) {
+                let block_count = self.get_user_block_count(&b.pub_key, block.index);
+                if block_count < minimum_block_count {
+                    //debug!("Skipping public key {:?} from block {}, it has too little {} blocks", &b.pub_key, index, block_count);
+                    count += 1;
+                    continue;
+                }
+                if b.pub_key != block.pub_key && !set.contains(&b.pub_key) {
+                    result.push(b.pub_key.clone());
+                    set.insert(b.pub_key);
+                }
+            }
+            count += 1;
+            if !mitigated && count > 5000 {
+                tail = tail / 13;
+                mitigated = true;
+            }
+        }
+        debug!("Got signers for block {}: {:?}, loop count {}", block.index, &result, count);
+        let mut signers = self.signers.borrow_mut();
+        signers.index = block.index;
+        signers.signers = result.clone();
+        result
+    }
+
+    fn get_block_from_statement(statement: &mut Statement) -> Option<Block> {
+        let index = statement.read::<i64>(0).unwrap() as u64;
+        let timestamp = statement.read::<i64>(1).unwrap();
+        let version = statement.read::<i64>(2).unwrap() as u32;
+        let difficulty = statement.read::<i64>(3).unwrap() as u32;
+        let random = statement.read::<i64>(4).unwrap() as u32;
+        let nonce = statement.read::<i64>(5).unwrap() as u64;
+        let transaction = statement.read::<Option<String>>(6).unwrap().and_then(|json| Transaction::from_json(&json));
+        let prev_block_hash = Bytes::from_bytes(statement.read::<Vec<u8>>(7).unwrap().as_slice());
+        let hash = Bytes::from_bytes(statement.read::<Vec<u8>>(8).unwrap().as_slice());
+        let pub_key = Bytes::from_bytes(statement.read::<Vec<u8>>(9).unwrap().as_slice());
+        let signature = Bytes::from_bytes(statement.read::<Vec<u8>>(10).unwrap().as_slice());
+        Some(Block::from_all_params(index, timestamp, version, difficulty, random, nonce, prev_block_hash, hash, pub_key, signature, transaction))
+    }
+}
+
+struct SignersCache {
+    index: u64,
+    signers: Vec<Bytes>
+}
+
+impl SignersCache {
+    pub fn new() -> RefCell<SignersCache> {
+        let cache = SignersCache { index: 0, signers: Vec::new() };
+        RefCell::new(cache)
+    }
+
+    pub fn has_signers_for(&self, index: u64) -> bool {
+        self.index == index && !self.signers.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.index = 0;
+        self.signers.clear();
+    }
+}
+
+/// Calculates difficulty from timestamps of previous domain blocks, newest first.
+/// Every time the blocks come twice as often as we want, the difficulty grows by one bit, and vice versa.
+pub fn adjust_difficulty(base: u32, timestamps: &[i64]) -> u32 {
+    if timestamps.len() < DIFFICULTY_ADJUST_BLOCKS as usize {
+        return base;
+    }
+    let span = timestamps.first().unwrap() - timestamps.last().unwrap();
+    let interval = max(span / (timestamps.len() as i64 - 1), 1);
+    let mut bits = 0u32;
+    if interval < DIFFICULTY_TARGET_INTERVAL {
+        while bits < DIFFICULTY_MAX_ADJUST && interval << (bits + 1) <= DIFFICULTY_TARGET_INTERVAL {
+            bits += 1;
+        }
+        base + bits
+    } else {
+        while bits < DIFFICULTY_MAX_ADJUST && interval >= DIFFICULTY_TARGET_INTERVAL << (bits + 1) {
+            bits += 1;
+        }
+        base - bits
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use log::LevelFilter;
+    #[allow(unused_imports)]
+    use log::{debug, error, info, trace, warn};
+    use simplelog::{ColorChoice, ConfigBuilder, TermLogger, TerminalMode, LevelPadding, format_description};
+
+    use crate::blockchain::activation::{ARGON2_RULE, AUCTION_RULE, DIFFICULTY_ADJUST_RULE, RULES};
+    use crate::commons::{BLOCK_FLAG_ARGON2, REGISTRATION_LIMIT};
+use crate::{Block, Bytes, Chain, ChainKind, Keystore, Settings, Transaction, CLASS_DOMAIN};
+
+    fn init_logger() {
+        let config = ConfigBuilder::new()
+            .add_filter_ignore_str("mio::poll")
+            .set_thread_level(LevelFilter::Off)
+            .set_location_level(LevelFilter::Off)
+            .set_target_level(LevelFilter::Error)
+            .set_level_padding(LevelPadding::Right)
+            .set_time_level(LevelFilter::Error)
+            .set_time_format_custom(format_description!("[hour]:[minute]:[second].[subsecond digits:3]"))
+            .build();
+        if let Err(e) = TermLogger::init(LevelFilter::Trace, config, TerminalMode::Stdout, ColorChoice::Auto) {
+            println!("Unable to initialize logger!\n{}", e);
+        }
+    }
+
+    #[test]
+    pub fn load_and_check() {
+        init_logger();
+        let settings = Settings::default();
+        let mut chain = Chain::new(&settings, "./tests/blockchain.db");
+        chain.check_chain(u64::MAX);
+        assert_eq!(chain.get_height(), 149);
+    }
+
+    #[test]
+    pub fn add_blocks_batch() {
+        let settings = Settings::default();
+        let chain = Chain::new(&settings, "./tests/blockchain.db");
+        let blocks: Vec<Block> = (1..=40).map(|index| chain.get_block(index).unwrap()).collect();
+        let mut new_chain = Chain::new(&settings, ":memory:");
+        let rest = new_chain.add_blocks(blocks);
+        assert!(rest.is_empty());
+        assert_eq!(new_chain.get_height(), 40);
+    }
+
+    #[test]
+    pub fn prune_blocks() {
+        let settings = Settings::default();
+        let chain = Chain::new(&settings, "./tests/blockchain.db");
+        let blocks: Vec<Block> = (1..=chain.get_height()).map(|index| chain.get_block(index).unwrap()).collect();
+        let mut new_chain = Chain::new(&settings, ":memory:");
+        // Less than allowed in settings, to see it on a short chain
+        new_chain.prune_blocks = 50;
+        assert!(new_chain.add_blocks(blocks.clone()).is_empty());
+        assert_eq!(new_chain.get_height(), 149);
+        let pruned = new_chain.get_pruned_height();
+        assert!(pruned > 1 && pruned <= 50);
+        for block in &blocks {
+            let pruned = block.index > 1 && block.index <= 50 && block.transaction.is_some();
+            assert_eq!(new_chain.get_block(block.index).is_none(), pruned, "Block {}", block.index);
+            assert_eq!(new_chain.get_block_header(block.index).unwrap().hash, block.hash);
+        }
+        assert_eq!(new_chain.get_domains_count(), chain.get_domains_count());
+        new_chain.check_chain(u64::MAX);
+        assert_eq!(new_chain.get_height(), 149);
+    }
+
+    #[test]
+    pub fn check_db() {
+        let settings = Settings::default();
+        let chain = Chain::new(&settings, "./tests/blockchain.db");
+        let blocks: Vec<Block> = (1..=40).map(|index| chain.get_block(index).unwrap()).collect();
+        let mut new_chain = Chain::new(&settings, ":memory:");
+        assert!(new_chain.add_blocks(blocks).is_empty());
+        assert!(new_chain.check_db(u64::MAX, true));
+        assert_eq!(new_chain.get_height(), 40);
+        assert!(new_chain.get_db_size() > 0);
+
+        // Lost block in the middle is found, and the chain is cut before it
+        new_chain.db.execute("DELETE FROM blocks WHERE id = 35;").unwrap();
+        assert!(new_chain.check_db(10, false));
+        assert_eq!(new_chain.get_height(), 34);
+        assert_eq!(new_chain.get_last_hash(), chain.get_block(34).unwrap().hash);
+
+        // Block with changed link to previous one
+        new_chain.db.execute("UPDATE blocks SET prev_block_hash = x'00' WHERE id = 30;").unwrap();
+        assert!(new_chain.check_db(10, false));
+        assert_eq!(new_chain.get_height(), 29);
+    }
+
+    #[test]
+    pub fn registration_limit() {
+        let settings = Settings::default();
+        let chain = Chain::new(&settings, "./tests/blockchain.db");
+        let block = (1..=chain.get_height())
+            .filter_map(|index| chain.get_block(index))
+            .find(|block| block.transaction.as_ref().and_then(|t| t.get_domain_data()).is_some())
+            .unwrap();
+        let zone = block.transaction.as_ref().unwrap().get_domain_data().unwrap().zone;
+        let signing = block.get_signing().unwrap();
+        assert_eq!(chain.count_new_domains(&zone, signing, 1, block.index), 0);
+        assert_eq!(chain.count_new_domains(&zone, signing, block.index, block.index + 1), 1);
+        assert_eq!(chain.count_new_domains("no-zone", signing, block.index, block.index + 1), 0);
+        // The rule is not active in this short chain
+        assert_eq!(chain.get_registrations_left(&zone, signing, chain.get_height() + 1), None);
+        assert_eq!(ChainKind::Main.registration_limit("ygg"), 5);
+        assert_eq!(ChainKind::Main.registration_limit("mob"), REGISTRATION_LIMIT);
+
+        // Domains that a friend mined are counted for their owner, not for the friend
+        let owner = Keystore::from_random_bytes(&[1u8; 32]);
+        let friend = Keystore::from_random_bytes(&[2u8; 32]);
+        let mut chain = Chain::new(&settings, ":memory:");
+        let data = String::from(r#"{"encrypted":"","zone":"ygg","records":[]}"#);
+        for (index, name) in [(1, "one.ygg"), (2, "two.ygg")] {
+            let transaction = Transaction::from_str(String::from(name), CLASS_DOMAIN.to_owned(), data.clone(), owner.get_public(), owner.get_encryption_public());
+            let mut block = Block::new(Some(transaction), friend.get_public(), Bytes::default(), 20);
+            block.index = index;
+            chain.add_block(block);
+        }
+        assert_eq!(chain.count_new_domains("ygg", &owner.get_public(), 1, 3), 2);
+        assert_eq!(chain.count_new_domains("ygg", &friend.get_public(), 1, 3), 0);
+    }
+
+    #[test]
+    pub fn open_read_only() {
+        let settings = Settings::default();
+        let chain = Chain::open_read_only(&settings, "./tests/blockchain.db").unwrap();
+        assert_eq!(chain.get_height(), 149);
+        assert!(chain.get_block(1).is_some());
+        assert!(chain.db.execute("CREATE TABLE test (id INTEGER);").is_err());
+        assert!(Chain::open_read_only(&settings, "./tests/missing.db").is_err());
+        assert!(!std::path::Path::new("./tests/missing.db").exists());
+    }
+
+    #[test]
+    pub fn difficulty_adjust_rule() {
+        let settings = Settings::default();
+        let chain = Chain::new(&settings, "./tests/blockchain.db");
+        let blocks: Vec<Block> = (1..=chain.get_height()).map(|index| chain.get_block(index).unwrap()).collect();
+        let mut new_chain = Chain::new(&settings, ":memory:");
+        assert!(new_chain.add_blocks(blocks).is_empty());
+        new_chain.kind = ChainKind::Regtest;
+        new_chain.clear_rule_states(0);
+        let index = new_chain.get_height() + 1;
+        // Without signals the difficulty stays the same at any height
+        assert!(!new_chain.is_rule_active(DIFFICULTY_ADJUST_RULE, index));
+        assert_eq!(new_chain.get_domain_difficulty(index), ChainKind::Regtest.domain_difficulty());
+
+        let mask = RULES.iter().find(|rule| rule.name == DIFFICULTY_ADJUST_RULE).unwrap().mask();
+        new_chain.db.execute(&format!("UPDATE blocks SET version = version | {};", mask)).unwrap();
+        // Versions are changed behind the chain, so the states that it knows are wrong now
+        new_chain.clear_rule_states(0);
+        assert!(new_chain.is_rule_active(DIFFICULTY_ADJUST_RULE, index));
+        assert!(!new_chain.is_rule_active(DIFFICULTY_ADJUST_RULE, 1));
+        // Signals of removed blocks don't count anymore
+        new_chain.truncate_db_from_block(2).unwrap();
+        assert!(!new_chain.is_rule_active(DIFFICULTY_ADJUST_RULE, index));
+    }
+
+    #[test]
+    pub fn argon2_rule() {
+        let settings = Settings::default();
+        let chain = Chain::new(&settings, "./tests/blockchain.db");
+        let blocks: Vec<Block> = (1..=chain.get_height()).map(|index| chain.get_block(index).unwrap()).collect();
+        let mut new_chain = Chain::new(&settings, ":memory:");
+        assert!(new_chain.add_blocks(blocks).is_empty());
+        new_chain.kind = ChainKind::Regtest;
+        new_chain.clear_rule_states(0);
+        let index = new_chain.get_height() + 1;
+        let rule = RULES.iter().find(|rule| rule.name == ARGON2_RULE).unwrap();
+        // Miners signal the rule, but new blocks are hashed by Blakeout until it is active
+        let version = new_chain.get_block_version(index);
+        assert_eq!(version & BLOCK_FLAG_ARGON2, 0);
+        assert_eq!(version & rule.mask(), rule.mask());
+
+        new_chain.db.execute(&format!("UPDATE blocks SET version = version | {};", rule.mask())).unwrap();
+        new_chain.clear_rule_states(0);
+        let version = new_chain.get_block_version(index);
+        assert_eq!(version & BLOCK_FLAG_ARGON2, BLOCK_FLAG_ARGON2);
+        assert_eq!(version & rule.mask(), 0);
+    }
+
+    #[test]
+    pub fn auction() {
+        let settings = Settings::default();
+        let chain = Chain::new(&settings, "./tests/blockchain.db");
+        let blocks: Vec<Block> = (1..=chain.get_height()).map(|index| chain.get_block(index).unwrap()).collect();
+        let mut new_chain = Chain::new(&settings, ":memory:");
+        assert!(new_chain.add_blocks(blocks.clone()).is_empty());
+        let domains: Vec<&Block> = blocks.iter().filter(|block| block.transaction.as_ref().and_then(|t| t.get_domain_data()).is_some()).collect();
+        let (first, second) = (domains[0], domains[1]);
+        let identity = first.transaction.as_ref().unwrap().identity.clone();
+        let index = new_chain.get_height() + 1;
+        assert_eq!(new_chain.get_bid_difficulty(&identity, index, 0), None);
+
+        // Short windows of regtest, and all blocks signal the rule
+        new_chain.kind = ChainKind::Regtest;
+        let mask = RULES.iter().find(|rule| rule.name == AUCTION_RULE).unwrap().mask();
+        new_chain.db.execute(&format!("UPDATE blocks SET version = version | {};", mask)).unwrap();
+        new_chain.clear_rule_states(0);
+        assert!(new_chain.is_rule_active(AUCTION_RULE, index));
+
+        // The second block takes the domain of the first one right after it became free
+        let free = 1000 + ChainKind::Regtest.domain_lifetime() + ChainKind::Regtest.domain_renew_time();
+        new_chain.db.execute(&format!("DELETE FROM domains WHERE identity = x'{}' AND id <> {};", identity.to_string(), first.index)).unwrap();
+        new_chain.db.execute(&format!("UPDATE domains SET timestamp = 1000 WHERE id = {};", first.index)).unwrap();
+        new_chain.db.execute(&format!("UPDATE domains SET identity = x'{}', timestamp = {} WHERE id = {};", identity.to_string(), free + 10, second.index)).unwrap();
+        assert_eq!(new_chain.get_bid_difficulty(&identity, index, free + 20), Some(second.difficulty + 1));
+        assert_eq!(new_chain.get_bid_difficulty(&identity, index, free + ChainKind::Regtest.auction_time() + 1), None);
+        // Before the second block there was no auction
+        assert_eq!(new_chain.get_bid_difficulty(&identity, second.index, free + 20), None);
+    }
+
+    #[test]
+    pub fn identities() {
+        let settings = Settings::default();
+        let chain = Chain::new(&settings, "./tests/blockchain.db");
+        let blocks: Vec<Block> = (1..=chain.get_height()).map(|index| chain.get_block(index).unwrap()).collect();
+        let mut new_chain = Chain::new(&settings, ":memory:");
+        assert!(new_chain.add_blocks(blocks.clone()).is_empty());
+        assert_eq!(new_chain.get_domains_count(), chain.get_domains_count());
+
+        // After truncation the index is the same as in the chain that never had removed blocks
+        let mut short_chain = Chain::new(&settings, ":memory:");
+        assert!(short_chain.add_blocks(blocks[..100].to_vec()).is_empty());
+        new_chain.truncate_db_from_block(101).unwrap();
+        let domains = short_chain.get_alive_domains(0);
+        assert_eq!(new_chain.get_domains_count(), short_chain.get_domains_count());
+        assert_eq!(new_chain.get_alive_domains(0).len(), domains.len());
+        assert!(new_chain.get_alive_domains(0).iter().all(|transaction| domains.contains(transaction)));
+
+        // Databases of older versions get the index on start
+        new_chain.db.execute("DELETE FROM identities;").unwrap();
+        new_chain.init_identities();
+        assert_eq!(new_chain.get_domains_count(), short_chain.get_domains_count());
+        if let Some(owner) = domains.first().map(|transaction| transaction.signing.clone()) {
+            assert_eq!(new_chain.get_domains_by_owner(&owner).len(), short_chain.get_domains_by_owner(&owner).len());
+        }
+    }
+
+    #[test]
+    pub fn known_peers() {
+        let settings = Settings::default();
+        let chain = Chain::new(&settings, ":memory:");
+        chain.add_peer_success("10.0.0.1:4244");
+        chain.add_peer_success("10.0.0.2:4244");
+        chain.add_peer_success("10.0.0.1:4244");
+        for _ in 0..12 {
+            chain.add_peer_failure("10.0.0.2:4244");
+        }
+        assert_eq!(chain.get_known_peers(10), vec![String::from("10.0.0.1:4244")]);
+
+        // Peers from backup, the failing one is forgotten already
+        let peers = chain.get_all_peers();
+        assert_eq!(peers.len(), 1);
+        assert_eq!(peers[0].success, 2);
+        let restored = Chain::new(&settings, ":memory:");
+        restored.add_peer_success("10.0.0.3:4244");
+        restored.restore_peers(&peers);
+        restored.restore_peers(&peers);
+        assert_eq!(restored.get_all_peers().len(), 2);
+        assert_eq!(restored.get_known_peers(10).len(), 2);
+    }
+
+    #[test]
+    pub fn bans() {
+        let settings = Settings::default();
+        let chain = Chain::new(&settings, ":memory:");
+        chain.add_ban("10.0.0.1", 1000, "bad block");
+        chain.add_ban("10.0.0.2", 3000, "manual");
+        assert_eq!(chain.get_bans(2000), vec![String::from("10.0.0.2")]);
+        assert!(chain.remove_ban("10.0.0.2"));
+        assert!(!chain.remove_ban("10.0.0.2"));
+        assert!(chain.get_bans(2000).is_empty());
+    }
+
+    #[test]
+    pub fn difficulty_adjustment() {
+        use crate::blockchain::chain::adjust_difficulty;
+        use crate::commons::*;
+
+        let make = |interval: i64| -> Vec<i64> { (0..DIFFICULTY_ADJUST_BLOCKS as i64).rev().map(|i| 1_000_000 + i * interval).collect() };
+        // Not enough blocks
+        assert_eq!(adjust_difficulty(DOMAIN_DIFFICULTY, &[1000, 500]), DOMAIN_DIFFICULTY);
+        assert_eq!(adjust_difficulty(DOMAIN_DIFFICULTY, &make(DIFFICULTY_TARGET_INTERVAL)), DOMAIN_DIFFICULTY);
+        assert_eq!(adjust_difficulty(DOMAIN_DIFFICULTY, &make(DIFFICULTY_TARGET_INTERVAL / 2)), DOMAIN_DIFFICULTY + 1);
+        assert_eq!(adjust_difficulty(DOMAIN_DIFFICULTY, &make(DIFFICULTY_TARGET_INTERVAL * 2)), DOMAIN_DIFFICULTY - 1);
+        // Adjustment is bounded
+        assert_eq!(adjust_difficulty(DOMAIN_DIFFICULTY, &make(1)), DOMAIN_DIFFICULTY + DIFFICULTY_MAX_ADJUST);
+        assert_eq!(adjust_difficulty(DOMAIN_DIFFICULTY, &make(DIFFICULTY_TARGET_INTERVAL * 100)), DOMAIN_DIFFICULTY - DIFFICULTY_MAX_ADJUST);
+    }
+
+    #[test]
+    pub fn check_serde() {
+        let settings = Settings::default();
+        let chain = Chain::new(&settings, "./tests/blockchain.db");
+
+        // Check the first block, its transaction doesn't have identity
+        let block = chain.get_block(1).unwrap();
+        let buf = serde_cbor::to_vec(&block).unwrap();
+        let block2: Block = serde_cbor::from_slice(&buf[..]).unwrap();
+        assert_eq!(block, block2);
+
+        // Check second block, it is common "full" block with domain
+        let block = chain.get_block(2).unwrap();
+        let buf = serde_cbor::to_vec(&block).unwrap();
+        let block2: Block = serde_cbor::from_slice(&buf[..]).unwrap();
+        assert_eq!(block, block2);
+
+        // Check block 36, it is an "empty" block, used to sign full blocks
+        let block = chain.get_block(36).unwrap();
+        let buf = serde_cbor::to_vec(&block).unwrap();
+        let block2: Block = serde_cbor::from_slice(&buf[..]).unwrap();
+        assert_eq!(block, block2);
+    }
+}
\ No newline at end of file
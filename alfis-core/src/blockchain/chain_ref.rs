@@ -0,0 +1,81 @@
+//! Shared handle to the chain of running node, for DNS server, HTTP proxy, API and other threads that need only the chain.
+//! `Chain` keeps prepared statements and caches in `RefCell`s, so it is not `Sync`, and all threads use it one at a time.
+//! The chain lives in `Context` with settings and keys, the miner and the network work with all of them under one lock.
+//! The handle takes the same lock only for the time of one call, and never gives the chain out of it,
+//! so readers can't hold the lock for long or take other locks while holding it.
+
+use std::sync::{Arc, Mutex};
+
+use crate::{Block, Bytes, Chain, Context};
+
+#[derive(Clone)]
+pub struct ChainRef {
+    context: Arc<Mutex<Context>>
+}
+
+impl ChainRef {
+    pub fn new(context: Arc<Mutex<Context>>) -> Self {
+        ChainRef { context }
+    }
+
+    /// Runs `f` with the chain locked. Don't lock `Context` inside, it is locked already.
+    pub fn read<T, F: FnOnce(&Chain) -> T>(&self, f: F) -> T {
+        f(&self.context.lock().unwrap().chain)
+    }
+
+    /// Runs `f` with the chain locked for changes. Don't lock `Context` inside, it is locked already.
+    pub fn write<T, F: FnOnce(&mut Chain) -> T>(&self, f: F) -> T {
+        f(&mut self.context.lock().unwrap().chain)
+    }
+
+    pub fn get_height(&self) -> u64 {
+        self.read(|chain| chain.get_height())
+    }
+
+    pub fn get_last_hash(&self) -> Bytes {
+        self.read(|chain| chain.get_last_hash())
+    }
+
+    pub fn get_block(&self, index: u64) -> Option<Block> {
+        self.read(|chain| chain.get_block(index))
+    }
+
+    /// Data of the domain in JSON, if it is alive
+    pub fn get_domain_info(&self, domain: &str) -> Option<String> {
+        self.read(|chain| chain.get_domain_info(domain))
+    }
+
+    pub fn is_available_zone(&self, zone: &str) -> bool {
+        self.read(|chain| chain.is_available_zone(zone))
+    }
+
+    pub fn get_soa_serial(&self) -> u32 {
+        self.read(|chain| chain.get_soa_serial())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+    use std::thread;
+
+    use crate::blockchain::ChainRef;
+    use crate::{Block, Chain, Context, Settings};
+
+    #[test]
+    fn test_shared_chain() {
+        let settings = Settings::default();
+        let recorded = Chain::new(&settings, "./tests/blockchain.db");
+        let chain = Chain::new(&settings, ":memory:");
+        let context = Arc::new(Mutex::new(Context::new(String::from("test"), settings, Vec::new(), chain)));
+        let chain = ChainRef::new(Arc::clone(&context));
+
+        let blocks: Vec<Block> = (1..=10).map(|index| recorded.get_block(index).unwrap()).collect();
+        let writer = chain.clone();
+        thread::spawn(move || writer.write(|chain| chain.add_blocks(blocks))).join().unwrap();
+        assert_eq!(chain.get_height(), 10);
+        assert_eq!(context.lock().unwrap().chain.get_height(), 10);
+        assert_eq!(chain.get_last_hash(), recorded.get_block(10).unwrap().hash);
+        assert!(chain.is_available_zone("ygg"));
+    }
+}
@@ -0,0 +1,190 @@
+//! Connection to blockchain DB that keeps prepared statements for reuse,
+//! and can measure how long every query takes.
+
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::mem;
+use std::ops::{Deref, DerefMut};
+use std::time::Instant;
+
+use serde::Serialize;
+use sqlite::{Connection, OpenFlags, Statement};
+
+/// Upper bounds of histogram buckets in microseconds, the last bucket is for slower queries
+pub const HISTOGRAM_BOUNDS: [u64; 7] = [100, 300, 1_000, 3_000, 10_000, 30_000, 100_000];
+
+/// Timings of one SQL query
+#[derive(Clone, Debug, Default, PartialEq, Serialize)]
+pub struct QueryStats {
+    pub sql: &'static str,
+    pub count: u64,
+    /// Total time in microseconds
+    pub total: u64,
+    /// The slowest query in microseconds
+    pub max: u64,
+    /// Counts of queries by `HISTOGRAM_BOUNDS`
+    pub histogram: [u64; 8]
+}
+
+impl QueryStats {
+    fn add(&mut self, micros: u64) {
+        self.count += 1;
+        self.total += micros;
+        self.max = self.max.max(micros);
+        let bucket = HISTOGRAM_BOUNDS.iter().position(|bound| micros < *bound).unwrap_or(HISTOGRAM_BOUNDS.len());
+        self.histogram[bucket] += 1;
+    }
+}
+
+pub struct Db {
+    // Statements must be finalized before the connection is closed, so they go first
+    statements: RefCell<HashMap<&'static str, Statement<'static>>>,
+    stats: Option<RefCell<HashMap<&'static str, QueryStats>>>,
+    connection: Connection
+}
+
+// Statements are used only with their connection, and `Db` is not `Sync`
+unsafe impl Send for Db {}
+
+impl Db {
+    pub fn open(name: &str, timing: bool) -> sqlite::Result<Db> {
+        let connection = sqlite::open(name)?;
+        let stats = match timing {
+            true => Some(RefCell::new(HashMap::new())),
+            false => None
+        };
+        Ok(Db { statements: RefCell::new(HashMap::new()), stats, connection })
+    }
+
+    /// Opens existing database without creating it, every write to it fails
+    pub fn open_read_only(name: &str) -> sqlite::Result<Db> {
+        let connection = Connection::open_with_flags(name, OpenFlags::new().set_read_only())?;
+        Ok(Db { statements: RefCell::new(HashMap::new()), stats: None, connection })
+    }
+
+    /// Gives the statement prepared earlier, or prepares it now.
+    /// The statement is reset and returned to the cache when the result is dropped.
+    pub fn prepare(&self, sql: &'static str) -> sqlite::Result<CachedStatement> {
+        let start = self.stats.as_ref().map(|_| Instant::now());
+        let statement = match self.statements.borrow_mut().remove(sql) {
+            Some(statement) => statement,
+            None => self.connection.prepare(sql)?
+        };
+        Ok(CachedStatement { db: self, sql, statement: Some(statement), start })
+    }
+
+    pub fn execute(&self, sql: &str) -> sqlite::Result<()> {
+        self.connection.execute(sql)
+    }
+
+    pub fn change_count(&self) -> usize {
+        self.connection.change_count()
+    }
+
+    /// Timings of queries, the slowest in total go first. Empty if timing is off.
+    pub fn get_stats(&self) -> Vec<QueryStats> {
+        let mut result: Vec<QueryStats> = match &self.stats {
+            Some(stats) => stats.borrow().values().cloned().collect(),
+            None => Vec::new()
+        };
+        result.sort_by(|a, b| b.total.cmp(&a.total));
+        result
+    }
+
+    pub fn is_timing(&self) -> bool {
+        self.stats.is_some()
+    }
+
+    fn release(&self, sql: &'static str, mut statement: Statement, start: Option<Instant>) {
+        if let (Some(stats), Some(start)) = (&self.stats, start) {
+            let micros = start.elapsed().as_micros() as u64;
+            stats.borrow_mut().entry(sql).or_insert_with(|| QueryStats { sql, ..Default::default() }).add(micros);
+        }
+        // Failed statements are prepared again next time
+        if statement.reset().is_ok() {
+            // The statement lives in the cache not longer than the connection
+            let statement: Statement<'static> = unsafe { mem::transmute(statement) };
+            self.statements.borrow_mut().insert(sql, statement);
+        }
+    }
+}
+
+/// Prepared statement borrowed from the cache of `Db`
+pub struct CachedStatement<'l> {
+    db: &'l Db,
+    sql: &'static str,
+    statement: Option<Statement<'l>>,
+    start: Option<Instant>
+}
+
+impl<'l> Deref for CachedStatement<'l> {
+    type Target = Statement<'l>;
+
+    fn deref(&self) -> &Self::Target {
+        self.statement.as_ref().unwrap()
+    }
+}
+
+impl<'l> DerefMut for CachedStatement<'l> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        self.statement.as_mut().unwrap()
+    }
+}
+
+impl<'l> Drop for CachedStatement<'l> {
+    fn drop(&mut self) {
+        if let Some(statement) = self.statement.take() {
+            self.db.release(self.sql, statement, self.start);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use sqlite::State;
+
+    use super::{Db, QueryStats};
+
+    const SQL_SELECT: &str = "SELECT value FROM test WHERE id = ?;";
+
+    #[test]
+    fn test_cached_statements() {
+        let db = Db::open(":memory:", true).unwrap();
+        db.execute("CREATE TABLE test (id INTEGER, value TEXT); INSERT INTO test VALUES (1, 'one'), (2, 'two');").unwrap();
+        for (id, value) in [(1, "one"), (2, "two"), (1, "one")] {
+            let mut statement = db.prepare(SQL_SELECT).unwrap();
+            statement.bind(1, id as i64).unwrap();
+            assert_eq!(statement.next().unwrap(), State::Row);
+            assert_eq!(statement.read::<String>(0).unwrap(), value);
+        }
+        // The same query while the first one is in use
+        let mut first = db.prepare(SQL_SELECT).unwrap();
+        let mut second = db.prepare(SQL_SELECT).unwrap();
+        first.bind(1, 1i64).unwrap();
+        second.bind(1, 2i64).unwrap();
+        assert_eq!(first.next().unwrap(), State::Row);
+        assert_eq!(second.next().unwrap(), State::Row);
+        assert_eq!(second.read::<String>(0).unwrap(), "two");
+        drop(first);
+        drop(second);
+        assert_eq!(db.statements.borrow().len(), 1);
+
+        let stats = db.get_stats();
+        assert_eq!(stats.len(), 1);
+        assert_eq!(stats[0].sql, SQL_SELECT);
+        assert_eq!(stats[0].count, 5);
+        assert_eq!(stats[0].histogram.iter().sum::<u64>(), 5);
+    }
+
+    #[test]
+    fn test_histogram() {
+        let mut stats = QueryStats::default();
+        for micros in [50, 100, 2_000, 500_000] {
+            stats.add(micros);
+        }
+        assert_eq!(stats.count, 4);
+        assert_eq!(stats.total, 502_150);
+        assert_eq!(stats.max, 500_000);
+        assert_eq!(stats.histogram, [1, 1, 0, 1, 0, 0, 0, 1]);
+    }
+}
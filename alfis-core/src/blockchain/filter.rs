@@ -1,5 +1,4 @@
 use std::net::{IpAddr, SocketAddr};
-use std::sync::{Arc, Mutex};
 
 #[allow(unused_imports)]
 use log::{debug, error, info, trace, warn};
@@ -7,19 +6,19 @@ use log::{debug, error, info, trace, warn};
 use crate::blockchain::transaction::DomainData;
 use crate::dns::filter::DnsFilter;
 use crate::dns::protocol::{DnsPacket, DnsQuestion, DnsRecord, QueryType, ResultCode, TransientTtl};
-use crate::Context;
+use crate::{ChainRef, MAX_RECORD_TTL};
 use crate::dns::client::{DnsClient, DnsNetworkClient};
 
 const NAME_SERVER: &str = "ns.alfis.name";
 const SERVER_ADMIN: &str = "admin.alfis.name";
 
 pub struct BlockchainFilter {
-    context: Arc<Mutex<Context>>
+    chain: ChainRef
 }
 
 impl BlockchainFilter {
-    pub fn new(context: Arc<Mutex<Context>>) -> Self {
-        BlockchainFilter { context }
+    pub fn new(chain: ChainRef) -> Self {
+        BlockchainFilter { chain }
     }
 
     fn add_soa_record(zone: String, serial: u32, packet: &mut DnsPacket) {
@@ -37,7 +36,7 @@ impl BlockchainFilter {
     }
 
     fn get_zone_response(&self, zone: &str, serial: u32, packet: &mut DnsPacket) -> bool {
-        let have_zone = self.context.lock().unwrap().chain.is_available_zone(zone);
+        let have_zone = self.chain.is_available_zone(zone);
         if have_zone {
             BlockchainFilter::add_soa_record(zone.to_owned(), serial, packet);
         }
@@ -66,7 +65,11 @@ impl BlockchainFilter {
             let mut packet = DnsPacket::new();
             packet.header.authoritative_answer = true;
             packet.questions.push(DnsQuestion::new(String::from(qname), qtype));
-            for answer in answers {
+            for mut answer in answers {
+                // Records from old blocks may have TTL that is too long for a changing domain
+                if answer.get_ttl() > MAX_RECORD_TTL {
+                    answer.set_ttl(MAX_RECORD_TTL);
+                }
                 packet.answers.push(answer);
             }
             packet.authorities.push(DnsRecord::NS { domain: zone, host: String::from(NAME_SERVER), ttl: TransientTtl(600) });
@@ -78,7 +81,7 @@ impl BlockchainFilter {
             packet.header.authoritative_answer = true;
             packet.header.rescode = ResultCode::NOERROR;
             packet.questions.push(DnsQuestion::new(String::from(qname), qtype));
-            let serial = self.context.lock().unwrap().chain.get_soa_serial();
+            let serial = self.chain.get_soa_serial();
             BlockchainFilter::add_soa_record(zone, serial, &mut packet);
             //trace!("Returning packet: {:?}", &packet);
             Some(packet)
@@ -148,7 +151,7 @@ impl DnsFilter for BlockchainFilter {
         match parts.len() {
             1 => {
                 let mut packet = DnsPacket::new();
-                let serial = self.context.lock().unwrap().chain.get_soa_serial();
+                let serial = self.chain.get_soa_serial();
                 if self.get_zone_response(parts[0], serial, &mut packet) {
                     return Some(packet);
                 }
@@ -165,18 +168,18 @@ impl DnsFilter for BlockchainFilter {
         }
         //trace!("Searching record type '{:?}', name '{}' for domain '{}'", &qtype, &subdomain, &search);
 
-        let data = self.context.lock().unwrap().chain.get_domain_info(&top_domain);
+        let data = self.chain.get_domain_info(&top_domain);
         let zone = parts[0].to_owned();
         match data {
             None => {
-                if self.context.lock().unwrap().chain.is_available_zone(&zone) {
+                if self.chain.is_available_zone(&zone) {
                     trace!("Not found data for domain {}", &top_domain);
                     // Create DnsPacket
                     let mut packet = DnsPacket::new();
                     packet.questions.push(DnsQuestion::new(String::from(qname), qtype));
                     packet.header.rescode = ResultCode::NXDOMAIN;
                     packet.header.authoritative_answer = true;
-                    let serial = self.context.lock().unwrap().chain.get_soa_serial();
+                    let serial = self.chain.get_soa_serial();
                     BlockchainFilter::add_soa_record(zone, serial, &mut packet);
                     //trace!("Returning packet: {:?}", &packet);
                     return Some(packet);
@@ -0,0 +1,118 @@
+use crate::commons::{BLOCK_FLAG_ARGON2, CHAIN_VERSION};
+use crate::{Block, Bytes};
+
+// Hashes and difficulties of blocks are the same in the node and in alfis-verify
+pub use alfis_verify::{hash_difficulty, hash_sha256, key_hash_difficulty, same_hash};
+
+/// Checks block's hash and returns true on valid hash or false otherwise
+pub fn check_block_hash(block: &Block) -> bool {
+    // If this block's hash was already checked as good
+    if block.is_hash_good() {
+        return true;
+    }
+    let good = alfis_verify::check_block_hash(&block.into());
+    block.set_hash_good(good);
+    good
+}
+
+/// Gets version of block, it tells what PoW algorithm is used.
+/// Use [Chain::get_block_version](crate::Chain::get_block_version) to know it for new blocks.
+pub fn block_version(argon2: bool) -> u32 {
+    if argon2 {
+        CHAIN_VERSION | BLOCK_FLAG_ARGON2
+    } else {
+        CHAIN_VERSION
+    }
+}
+
+/// Hashes block data by PoW algorithm of this block version
+pub fn pow_hash(version: u32, data: &[u8]) -> Bytes {
+    Bytes::new(alfis_verify::pow_hash(version, data))
+}
+
+/// Hashes data by Blakeout
+pub fn blakeout_data(data: &[u8]) -> Bytes {
+    Bytes::new(alfis_verify::blakeout_data(data))
+}
+
+/// Hashes data by Argon2id, it needs `ARGON2_MEMORY` KiB of memory for every hash
+pub fn argon2_data(data: &[u8]) -> Bytes {
+    Bytes::new(alfis_verify::argon2_data(data))
+}
+
+/// Checks block's signature, returns true if the signature is valid, false otherwise
+pub fn check_block_signature(block: &Block) -> bool {
+    alfis_verify::check_block_signature(&block.into())
+}
+
+/// Hashes some identity (domain in case of DNS). If you give it a public key, it will hash with it as well.
+/// Giving public key is needed to create a confirmation field in [Transaction](crate::blockchain::Transaction)
+pub fn hash_identity(identity: &str, key: Option<&Bytes>) -> Bytes {
+    Bytes::new(alfis_verify::hash_identity(identity, key.map(|key| key.as_slice())))
+}
+
+/// Returns how many hashes we need to make in average to get this difficulty.
+/// Zeroes from start and end are counted together, so the chance is `(difficulty + 2) / 2^(difficulty + 1)`
+pub fn expected_hashes(difficulty: u32) -> u64 {
+    if difficulty >= 63 {
+        return u64::MAX;
+    }
+    (1u64 << (difficulty + 1)) / (difficulty as u64 + 2)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::convert::TryInto;
+
+    use crate::blockchain::hash_utils::{block_version, expected_hashes, hash_sha256, pow_hash};
+    use crate::commons::{BLOCK_FLAG_ARGON2, CHAIN_VERSION};
+
+    #[test]
+    pub fn test_pow_hash() {
+        assert_eq!(block_version(false), CHAIN_VERSION);
+        assert_eq!(block_version(true), CHAIN_VERSION | BLOCK_FLAG_ARGON2);
+        let data = b"some block data";
+        let blakeout = pow_hash(CHAIN_VERSION, data);
+        let argon2 = pow_hash(CHAIN_VERSION | BLOCK_FLAG_ARGON2, data);
+        assert_eq!(argon2.length(), 32);
+        assert_ne!(blakeout, argon2);
+        assert_eq!(argon2, pow_hash(CHAIN_VERSION | BLOCK_FLAG_ARGON2, data));
+    }
+
+    #[test]
+    pub fn test_expected_hashes() {
+        assert_eq!(expected_hashes(0), 1);
+        assert_eq!(expected_hashes(2), 2);
+        assert_eq!(expected_hashes(24), 1_290_555);
+        assert_eq!(expected_hashes(64), u64::MAX);
+    }
+
+    #[test]
+    #[ignore]
+    pub fn test_hash() {
+        let id = b"example.com";
+        let key = b"some_key";
+
+        let base = hash_sha256(id);
+
+        let identity = hash_sha256(&base);
+
+        let mut buf = Vec::new();
+        buf.append(&mut identity.clone());
+        buf.append(&mut key.to_vec());
+        let confirmation = hash_sha256(&buf);
+
+        println!("result1 = {:?}", &base);
+        println!("result2 = {:?}", &identity);
+        println!("result3 = {:?}", &confirmation);
+    }
+
+    #[test]
+    #[ignore]
+    fn test_hash_is_good() {
+        let hash = vec![0u8, 0u8, 0u8, 255, 255, 255, 255, 255];
+        let bytes: [u8; 8] = hash[..8].try_into().unwrap();
+        let int = u64::from_be_bytes(bytes);
+        println!("int = {}", int);
+    }
+}
@@ -1,10 +1,16 @@
 pub use block::Block;
 pub use chain::Chain;
+pub use chain_ref::ChainRef;
 pub use transaction::Transaction;
 
+pub mod activation;
 pub mod block;
 pub mod chain;
+pub mod chain_ref;
+pub mod db;
 pub mod filter;
 pub mod hash_utils;
+pub mod plugins;
+pub mod reverse;
 pub mod transaction;
 pub mod types;
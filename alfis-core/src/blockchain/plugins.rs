@@ -0,0 +1,105 @@
+//! Handlers of transaction classes that ALFIS doesn't know, to try new kinds of records in programs that embed ALFIS,
+//! without changes in the code that checks domains. The chain asks the handler of the class what difficulty
+//! the block needs, if the transaction is right, and gives it transactions of added blocks to keep their state.
+//! Other nodes reject blocks of classes that they don't know, so such classes are for test and private chains
+//! until they are added to ALFIS itself.
+
+use std::collections::HashMap;
+use std::sync::{Arc, RwLock};
+
+use lazy_static::lazy_static;
+
+use crate::{Block, Chain, Transaction, CLASS_DOMAIN, CLASS_ORIGIN};
+
+pub trait ClassHandler: Send + Sync {
+    /// Minimal difficulty of block with this transaction at this height
+    fn difficulty(&self, chain: &Chain, _transaction: &Transaction, height: u64) -> u32 {
+        chain.get_domain_difficulty(height)
+    }
+
+    /// Checks transaction of new block before it is added to the chain, the block itself is checked by the chain
+    fn check(&self, chain: &Chain, block: &Block, transaction: &Transaction) -> bool;
+
+    /// Applies transaction of the block that was added to the chain at height `index`
+    fn apply(&self, chain: &Chain, index: u64, timestamp: i64, transaction: &Transaction);
+
+    /// Forgets transactions of blocks from `index` and higher, they are removed from the chain
+    fn truncate(&self, _index: u64) {}
+}
+
+lazy_static! {
+    static ref HANDLERS: RwLock<HashMap<String, Arc<dyn ClassHandler>>> = RwLock::new(HashMap::new());
+}
+
+/// Registers handler for transactions of this class, classes of ALFIS can't be taken
+pub fn register_class<H: ClassHandler + 'static>(class: &str, handler: H) -> bool {
+    if class == CLASS_DOMAIN || class == CLASS_ORIGIN || class.is_empty() {
+        return false;
+    }
+    HANDLERS.write().unwrap().insert(class.to_owned(), Arc::new(handler));
+    true
+}
+
+pub fn unregister_class(class: &str) {
+    HANDLERS.write().unwrap().remove(class);
+}
+
+pub fn get_class_handler(class: &str) -> Option<Arc<dyn ClassHandler>> {
+    HANDLERS.read().unwrap().get(class).cloned()
+}
+
+/// Tells all handlers that blocks from `index` are removed
+pub(crate) fn truncate_classes(index: u64) {
+    for handler in HANDLERS.read().unwrap().values() {
+        handler.truncate(index);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicU64, Ordering};
+    use std::sync::Arc;
+
+    use crate::blockchain::plugins::{get_class_handler, register_class, truncate_classes, unregister_class, ClassHandler};
+    use crate::{Block, Bytes, Chain, Settings, Transaction, CLASS_DOMAIN};
+
+    struct Notes {
+        last: Arc<AtomicU64>
+    }
+
+    impl ClassHandler for Notes {
+        fn check(&self, _chain: &Chain, _block: &Block, transaction: &Transaction) -> bool {
+            transaction.data.len() <= 100
+        }
+
+        fn apply(&self, _chain: &Chain, index: u64, _timestamp: i64, _transaction: &Transaction) {
+            self.last.store(index, Ordering::SeqCst);
+        }
+
+        fn truncate(&self, index: u64) {
+            self.last.fetch_min(index - 1, Ordering::SeqCst);
+        }
+    }
+
+    #[test]
+    fn test_class_handler() {
+        let last = Arc::new(AtomicU64::new(0));
+        assert!(!register_class(CLASS_DOMAIN, Notes { last: Arc::clone(&last) }));
+        let json = r#"{"class":"test-note","data":"hello","signing":"0202020202020202020202020202020202020202020202020202020202020202"}"#;
+        assert!(Transaction::from_untrusted_json(json).is_err());
+
+        assert!(register_class("test-note", Notes { last: Arc::clone(&last) }));
+        let transaction = Transaction::from_untrusted_json(json).unwrap();
+        let chain = Chain::new(&Settings::default(), ":memory:");
+        let handler = get_class_handler("test-note").unwrap();
+        let block = Block::new(Some(transaction.clone()), Bytes::default(), Bytes::default(), 0);
+        assert!(handler.check(&chain, &block, &transaction));
+        assert_eq!(handler.difficulty(&chain, &transaction, 1), chain.get_domain_difficulty(1));
+        handler.apply(&chain, 10, 0, &transaction);
+        truncate_classes(5);
+        assert_eq!(last.load(Ordering::SeqCst), 4);
+
+        unregister_class("test-note");
+        assert!(get_class_handler("test-note").is_none());
+    }
+}
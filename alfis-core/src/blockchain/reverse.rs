@@ -0,0 +1,208 @@
+//! Reverse resolution (PTR) for addresses from A/AAAA records of domains in blockchain.
+//!
+//! Domain names are not stored in blockchain openly, so only owners can reveal them.
+//! The owner opts in by adding a PTR record with the name of the domain in data,
+//! and the label (or "@") in domain, for example `{"type": "PTR", "domain": "www", "data": "example.ygg"}`.
+//! Then all A/AAAA records with the same label are resolved back to `www.example.ygg`.
+
+use std::collections::HashMap;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+use std::sync::Mutex;
+
+use chrono::Utc;
+#[allow(unused_imports)]
+use log::{debug, error, info, trace, warn};
+
+use crate::blockchain::transaction::DomainData;
+use crate::dns::filter::DnsFilter;
+use crate::dns::protocol::{DnsPacket, DnsQuestion, DnsRecord, QueryType, TransientTtl};
+use crate::{ChainRef, Transaction};
+
+const IPV4_SUFFIX: &str = ".in-addr.arpa";
+const IPV6_SUFFIX: &str = ".ip6.arpa";
+const NAME_SERVER: &str = "ns.alfis.name";
+const PTR_TTL: u32 = 300;
+
+pub struct ReverseFilter {
+    chain: ChainRef,
+    // Blockchain height, for which the map was built, and the map itself
+    cache: Mutex<(u64, HashMap<IpAddr, Vec<String>>)>
+}
+
+impl ReverseFilter {
+    pub fn new(chain: ChainRef) -> Self {
+        ReverseFilter { chain, cache: Mutex::new((0, HashMap::new())) }
+    }
+
+    fn get_names(&self, addr: &IpAddr) -> Vec<String> {
+        let (height, domains) = self.chain.read(|chain| {
+            let height = chain.get_height();
+            if self.cache.lock().unwrap().0 == height {
+                (height, None)
+            } else {
+                (height, Some(chain.get_alive_domains(Utc::now().timestamp())))
+            }
+        });
+        let mut cache = self.cache.lock().unwrap();
+        if let Some(domains) = domains {
+            debug!("Rebuilding reverse zone for height {}", height);
+            let mut map: HashMap<IpAddr, Vec<String>> = HashMap::new();
+            for transaction in domains {
+                for (addr, name) in get_reverse_names(&transaction) {
+                    map.entry(addr).or_default().push(name);
+                }
+            }
+            *cache = (height, map);
+        }
+        cache.1.get(addr).cloned().unwrap_or_default()
+    }
+}
+
+impl DnsFilter for ReverseFilter {
+    fn lookup(&self, qname: &str, qtype: QueryType) -> Option<DnsPacket> {
+        if qtype != QueryType::PTR {
+            return None;
+        }
+        let addr = reverse_name_to_ip(qname)?;
+        let names = self.get_names(&addr);
+        if names.is_empty() {
+            return None;
+        }
+        let mut packet = DnsPacket::new();
+        packet.header.authoritative_answer = true;
+        packet.questions.push(DnsQuestion::new(String::from(qname), qtype));
+        for name in names {
+            packet.answers.push(DnsRecord::PTR { domain: qname.to_owned(), data: name, ttl: TransientTtl(PTR_TTL) });
+        }
+        packet.authorities.push(DnsRecord::NS { domain: qname.to_owned(), host: String::from(NAME_SERVER), ttl: TransientTtl(600) });
+        Some(packet)
+    }
+}
+
+/// Gets pairs of addresses and names that the owner of this domain allowed to resolve back
+pub fn get_reverse_names(transaction: &Transaction) -> Vec<(IpAddr, String)> {
+    let mut result = Vec::new();
+    let data: DomainData = match transaction.get_domain_data() {
+        None => return result,
+        Some(data) => data
+    };
+    for record in &data.records {
+        let (label, domain) = match record {
+            DnsRecord::PTR { domain, data, .. } => (domain, data.to_lowercase()),
+            _ => continue
+        };
+        // The owner has to reveal the real name of this domain, we check it by identity
+        if !transaction.check_identity(&domain) {
+            trace!("Wrong domain name '{}' in PTR record", &domain);
+            continue;
+        }
+        let name = match label.as_str() {
+            "@" | "" => domain.clone(),
+            l if l == domain => domain.clone(),
+            l => format!("{}.{}", l, &domain)
+        };
+        for record in &data.records {
+            let (record_label, addr) = match record {
+                DnsRecord::A { domain, addr, .. } => (domain, IpAddr::V4(*addr)),
+                DnsRecord::AAAA { domain, addr, .. } => (domain, IpAddr::V6(*addr)),
+                _ => continue
+            };
+            if record_label == label || (label == "@" && record_label == &domain) {
+                result.push((addr, name.clone()));
+            }
+        }
+    }
+    result
+}
+
+/// Parses names like `4.3.2.1.in-addr.arpa` or `b.a.9.8...ip6.arpa` to IP-address
+pub fn reverse_name_to_ip(name: &str) -> Option<IpAddr> {
+    let name = name.to_lowercase();
+    if let Some(prefix) = name.strip_suffix(IPV4_SUFFIX) {
+        let parts: Vec<&str> = prefix.split('.').collect();
+        if parts.len() != 4 {
+            return None;
+        }
+        let mut octets = [0u8; 4];
+        for (i, part) in parts.iter().rev().enumerate() {
+            octets[i] = part.parse().ok()?;
+        }
+        return Some(IpAddr::V4(Ipv4Addr::from(octets)));
+    }
+    if let Some(prefix) = name.strip_suffix(IPV6_SUFFIX) {
+        let parts: Vec<&str> = prefix.split('.').collect();
+        if parts.len() != 32 {
+            return None;
+        }
+        let mut octets = [0u8; 16];
+        for (i, part) in parts.iter().rev().enumerate() {
+            if part.len() != 1 {
+                return None;
+            }
+            let nibble = u8::from_str_radix(part, 16).ok()?;
+            octets[i / 2] |= if i % 2 == 0 { nibble << 4 } else { nibble };
+        }
+        return Some(IpAddr::V6(Ipv6Addr::from(octets)));
+    }
+    None
+}
+
+/// Makes reverse name for IP-address, like `4.3.2.1.in-addr.arpa`
+pub fn ip_to_reverse_name(addr: &IpAddr) -> String {
+    match addr {
+        IpAddr::V4(addr) => {
+            let octets = addr.octets();
+            format!("{}.{}.{}.{}{}", octets[3], octets[2], octets[1], octets[0], IPV4_SUFFIX)
+        }
+        IpAddr::V6(addr) => {
+            let mut result = String::with_capacity(72);
+            for octet in addr.octets().iter().rev() {
+                result.push_str(&format!("{:x}.{:x}.", octet & 0x0F, octet >> 4));
+            }
+            result.pop();
+            result.push_str(IPV6_SUFFIX);
+            result
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::IpAddr;
+
+    use crate::blockchain::reverse::{get_reverse_names, ip_to_reverse_name, reverse_name_to_ip};
+    use crate::blockchain::transaction::DomainData;
+    use crate::dns::protocol::{DnsRecord, TransientTtl};
+    use crate::{Bytes, Transaction, CLASS_DOMAIN};
+
+    #[test]
+    fn test_reverse_names() {
+        let addr: IpAddr = "10.1.2.3".parse().unwrap();
+        assert_eq!(ip_to_reverse_name(&addr), "3.2.1.10.in-addr.arpa");
+        assert_eq!(reverse_name_to_ip("3.2.1.10.in-addr.arpa"), Some(addr));
+
+        let addr: IpAddr = "200:abcd::1".parse().unwrap();
+        let name = ip_to_reverse_name(&addr);
+        assert_eq!(name, "1.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.d.c.b.a.0.0.2.0.ip6.arpa");
+        assert_eq!(reverse_name_to_ip(&name), Some(addr));
+
+        assert_eq!(reverse_name_to_ip("2.1.10.in-addr.arpa"), None);
+        assert_eq!(reverse_name_to_ip("example.com"), None);
+    }
+
+    #[test]
+    fn test_get_reverse_names() {
+        let records = vec![
+            DnsRecord::A { domain: String::from("@"), addr: "10.0.0.1".parse().unwrap(), ttl: TransientTtl(300) },
+            DnsRecord::A { domain: String::from("www"), addr: "10.0.0.2".parse().unwrap(), ttl: TransientTtl(300) },
+            DnsRecord::PTR { domain: String::from("www"), data: String::from("test.ygg"), ttl: TransientTtl(300) },
+            DnsRecord::PTR { domain: String::from("@"), data: String::from("wrong.ygg"), ttl: TransientTtl(300) },
+        ];
+        let data = DomainData::new(Bytes::default(), String::from("ygg"), String::new(), records, Vec::new());
+        let data = serde_json::to_string(&data).unwrap();
+        let signing = Bytes::from_bytes(&[1u8; 32]);
+        let transaction = Transaction::from_str(String::from("test.ygg"), String::from(CLASS_DOMAIN), data, signing, Bytes::default());
+        let names = get_reverse_names(&transaction);
+        assert_eq!(names, vec![("10.0.0.2".parse().unwrap(), String::from("www.test.ygg"))]);
+    }
+}
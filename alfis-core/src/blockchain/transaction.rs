@@ -4,7 +4,11 @@ use std::fmt::{Display, Formatter};
 use serde::{Deserialize, Serialize};
 
 use crate::blockchain::hash_utils::*;
+use crate::blockchain::plugins::get_class_handler;
+use crate::blockchain::types::DecodeError;
 use crate::bytes::Bytes;
+use crate::commons::MAX_TRANSACTION_SIZE;
+use crate::crypto::CryptoBox;
 use crate::dns::protocol::DnsRecord;
 use crate::{CLASS_DOMAIN, CLASS_ORIGIN};
 
@@ -42,6 +46,14 @@ impl Transaction {
         Transaction { identity: Bytes::default(), confirmation: Bytes::default(), class: String::from(CLASS_ORIGIN), data, signing, encryption }
     }
 
+    /// Makes transaction of domain, its name is encrypted by the encryption key of the owner
+    pub fn from_domain_data(class: &str, name: &str, mut data: DomainData, signing: Bytes, encryption: Bytes) -> Self {
+        let encrypted = CryptoBox::encrypt(encryption.as_slice(), name.as_bytes()).expect("Error encrypting domain name!");
+        data.encrypted = Bytes::from_bytes(&encrypted);
+        let data = serde_json::to_string(&data).unwrap();
+        Self::from_str(name.to_owned(), class.to_owned(), data, signing, encryption)
+    }
+
     pub fn from_json(json: &str) -> Option<Self> {
         match serde_json::from_str(json) {
             Ok(transaction) => Some(transaction),
@@ -49,6 +61,44 @@ impl Transaction {
         }
     }
 
+    /// Parses transaction from users or other programs, it checks sizes of all fields and never panics
+    pub fn from_untrusted_json(json: &str) -> Result<Self, DecodeError> {
+        if json.len() > MAX_TRANSACTION_SIZE {
+            return Err(DecodeError::TooBig { size: json.len() });
+        }
+        let transaction: Transaction = serde_json::from_str(json).map_err(|e| DecodeError::Malformed(e.to_string()))?;
+        transaction.check_fields()?;
+        Ok(transaction)
+    }
+
+    /// Checks that class is known and that keys and hashes have right sizes, their content is checked by the chain
+    pub(crate) fn check_fields(&self) -> Result<(), DecodeError> {
+        // Origin transaction has no domain, so it has no identity
+        let domain = match self.class.as_str() {
+            CLASS_DOMAIN => true,
+            CLASS_ORIGIN => false,
+            class if get_class_handler(class).is_some() => false,
+            _ => return Err(DecodeError::WrongField("class"))
+        };
+        let good = |bytes: &Bytes| bytes.length() == 32 || (!domain && bytes.is_empty());
+        if !good(&self.identity) {
+            return Err(DecodeError::WrongField("identity"));
+        }
+        if !good(&self.confirmation) {
+            return Err(DecodeError::WrongField("confirmation"));
+        }
+        if self.signing.length() != 32 {
+            return Err(DecodeError::WrongField("signing"));
+        }
+        if !good(&self.encryption) {
+            return Err(DecodeError::WrongField("encryption"));
+        }
+        if self.data.len() > MAX_TRANSACTION_SIZE {
+            return Err(DecodeError::TooBig { size: self.data.len() });
+        }
+        Ok(())
+    }
+
     #[allow(clippy::inherent_to_string)]
     pub fn to_string(&self) -> String {
         // Let it panic if something is not okay
@@ -88,6 +138,19 @@ impl Transaction {
     }
 }
 
+impl From<&Transaction> for alfis_verify::Transaction {
+    fn from(transaction: &Transaction) -> Self {
+        alfis_verify::Transaction {
+            class: transaction.class.clone(),
+            identity: transaction.identity.to_vec(),
+            confirmation: transaction.confirmation.to_vec(),
+            signing: transaction.signing.to_vec(),
+            encryption: transaction.encryption.to_vec(),
+            data: transaction.data.clone()
+        }
+    }
+}
+
 impl fmt::Debug for Transaction {
     fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
         fmt.debug_struct("Transaction")
@@ -116,13 +179,16 @@ pub struct DomainData {
     pub info: String,
     #[serde(default, skip_serializing_if = "Vec::is_empty")]
     pub records: Vec<DnsRecord>,
+    /// Comments to records with the same index, they are not served by DNS
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub comments: Vec<String>,
     #[serde(default, skip_serializing_if = "Vec::is_empty")]
     pub contacts: Vec<ContactsData>
 }
 
 impl DomainData {
     pub fn new(encrypted: Bytes, zone: String, info: String, records: Vec<DnsRecord>, contacts: Vec<ContactsData>) -> Self {
-        Self { encrypted, zone, info, records, contacts }
+        Self { encrypted, zone, info, records, comments: Vec::new(), contacts }
     }
 }
 
@@ -0,0 +1,96 @@
+use std::fmt::{Display, Formatter};
+
+use serde::{Deserialize, Serialize};
+
+use crate::blockchain::transaction::DomainData;
+use crate::Bytes;
+
+/// Represents a result of block check on block's arrival
+#[derive(PartialEq)]
+pub enum BlockQuality {
+    Good,
+    Twin,
+    Future,
+    Rewind,
+    Bad,
+    Fork
+}
+
+#[derive(Debug, PartialEq)]
+pub enum MineResult {
+    Fine,
+    WrongName,
+    WrongData,
+    WrongKey,
+    WrongZone,
+    NotOwned,
+    Cooldown { time: i64 },
+    /// The key has registered enough new domains in this zone recently
+    Limited
+}
+
+/// Domain that some key owns now
+#[derive(Clone, Debug)]
+pub struct OwnedDomain {
+    pub identity: Bytes,
+    /// Height of the last block with this domain
+    pub index: u64,
+    /// Time of the last renewal
+    pub renewed: i64,
+    pub data: DomainData
+}
+
+/// Peer from our database with its connection history
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct KnownPeer {
+    pub addr: String,
+    pub last_seen: i64,
+    pub success: i64,
+    pub failure: i64
+}
+
+#[derive(Debug)]
+pub struct Options {
+    pub origin: String,
+    pub version: u32
+}
+
+impl Options {
+    pub fn new(origin: String, version: u32) -> Self {
+        Options { origin, version }
+    }
+
+    pub fn empty() -> Self {
+        Options { origin: String::new(), version: 0 }
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct ZoneData {
+    pub name: String,
+    pub yggdrasil: bool
+}
+
+impl Display for ZoneData {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        f.write_str(&format!("{}, yggdrasil: {}", self.name, self.yggdrasil))
+    }
+}
+
+/// Why a block or transaction from untrusted source was not accepted
+#[derive(Debug, PartialEq)]
+pub enum DecodeError {
+    TooBig { size: usize },
+    Malformed(String),
+    WrongField(&'static str)
+}
+
+impl Display for DecodeError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        match self {
+            DecodeError::TooBig { size } => write!(f, "too big: {} bytes", size),
+            DecodeError::Malformed(e) => write!(f, "malformed: {}", e),
+            DecodeError::WrongField(name) => write!(f, "wrong field '{}'", name)
+        }
+    }
+}
@@ -137,12 +137,9 @@ impl<'de> Visitor<'de> for BytesVisitor {
     }
 
     fn visit_str<E>(self, value: &str) -> Result<Self::Value, E> where E: DeError {
-        if !value.is_empty() && value.len() % 2 == 0 {
-            Ok(Bytes::new(crate::from_hex(value).unwrap()))
-        } else if value.is_empty() {
-            Ok(Bytes::default())
-        } else {
-            Err(E::custom("Expected bytes in HEX format!"))
+        match crate::from_hex(value) {
+            Ok(data) => Ok(Bytes::new(data)),
+            Err(_) => Err(E::custom("Expected bytes in HEX format!"))
         }
     }
 
@@ -0,0 +1,112 @@
+use std::time::Duration;
+
+// Constants of blocks that are checked without the node are in alfis-verify
+pub use alfis_verify::{ARGON2_MEMORY, BLOCK_FLAG_ARGON2, BLOCK_SIGNAL_MASK, CHAIN_VERSION, CLASS_DOMAIN, DIFFICULTY_MAX_ADJUST, DOMAIN_DIFFICULTY, KEYSTORE_DIFFICULTY, SIGNER_DIFFICULTY};
+
+pub const DB_VERSION: u32 = 0;
+/// Protocol features supported by this node, sent to peers in handshake
+pub const VERSION_FLAGS: u32 = FLAG_ANNOUNCE | FLAG_BATCH;
+/// Node understands block announcements (`Message::Announce`)
+pub const FLAG_ANNOUNCE: u32 = 1;
+/// Node can send and receive blocks in batches (`Message::GetBlocks` and `Message::Blocks`)
+pub const FLAG_BATCH: u32 = 2;
+/// Node keeps only transactions of last blocks, it can't give older blocks (`Settings::prune_blocks`)
+pub const FLAG_PRUNED: u32 = 4;
+/// Pruned nodes keep at least this many last blocks
+pub const PRUNE_BLOCKS_MIN: u64 = 1000;
+/// Old blocks are pruned every this many blocks
+pub const PRUNE_INTERVAL: u64 = 100;
+
+pub const ORIGIN_DIFFICULTY: u32 = 28;
+pub const BLOCKS_WITHOUT_DISCOUNT: u64 = 4999;
+
+/// How many previous domain blocks are used to adjust difficulty
+pub const DIFFICULTY_ADJUST_BLOCKS: u64 = 24;
+/// Desired average time between domain blocks
+pub const DIFFICULTY_TARGET_INTERVAL: i64 = 3600; // One hour
+
+/// Percent of blocks in a window that must signal a new rule to lock it in
+pub const ACTIVATION_THRESHOLD: u64 = 80;
+
+/// Blocks start to be signed starting from this index
+pub const BLOCK_SIGNERS_START: u64 = 35;
+
+/// How many signers are chosen for signing
+pub const BLOCK_SIGNERS_ALL: u64 = 7;
+
+/// Minimal signatures needed
+pub const BLOCK_SIGNERS_MIN: u64 = 4;
+
+/// Limited Confidence depth
+/// https://en.bitcoinwiki.org/wiki/Limited_Confidence_Proof-of-Activity
+pub const LIMITED_CONFIDENCE_DEPTH: u64 = 4;
+
+/// We start mining signing blocks after random delay, this is the max delay
+pub const BLOCK_SIGNERS_START_RANDOM: i64 = 90;
+
+pub const NEW_DOMAINS_INTERVAL: i64 = 86400; // One day in seconds
+/// How many new domains one key can register in a zone within a window of blocks, when the limit is active
+pub const REGISTRATION_LIMIT: u64 = 10;
+/// Zones with their own limit of new domains, names in them are wanted the most
+pub const ZONE_REGISTRATION_LIMITS: &[(&str, u64)] = &[("ygg", 5), ("anon", 5)];
+pub const ONE_WEEK: i64 = 86400 * 7; // One week in seconds
+pub const DOMAIN_LIFETIME: i64 = 86400 * 365; // One year
+/// Time for the owner to remine his domain and not to loose it
+pub const DOMAIN_RENEW_TIME: i64 = 86400 * 30; // One month
+pub const MAX_RECORDS: usize = 30;
+pub const MAX_DATA_LEN: usize = 255;
+/// TTL of records is limited, so that changes of domains reach resolvers in a reasonable time
+pub const MAX_RECORD_TTL: u32 = 86400 * 7;
+/// Length limit of comments to records, they are kept in the chain, but not served by DNS
+pub const MAX_COMMENT_LEN: usize = 100;
+/// Size limit of a serialized block from network, all records of a domain fit in it
+pub const MAX_BLOCK_SIZE: usize = 32_768;
+/// Size limit of a transaction in JSON from users
+pub const MAX_TRANSACTION_SIZE: usize = 24_576;
+
+pub const DB_NAME: &str = "blockchain.db";
+pub const CLASS_ORIGIN: &str = "origin";
+pub const ALFIS_DEBUG: &str = "ALFIS_DEBUG";
+pub const ALFIS_TRACE: &str = "ALFIS_TRACE";
+
+/// Public nodes listen port
+pub const LISTEN_PORT: u16 = 4244;
+pub const UI_REFRESH_DELAY_MS: u128 = 500;
+pub const LOG_REFRESH_DELAY_SEC: u64 = 60;
+
+pub const POLL_TIMEOUT: Option<Duration> = Some(Duration::from_millis(200));
+pub const WAIT_FOR_INTERNET: Duration = Duration::from_secs(10);
+/// We start syncing blocks only when we got 4 and more connected nodes
+pub const MIN_CONNECTED_NODES_START_SYNC: usize = 4;
+pub const MAX_READ_BLOCK_TIME: u128 = 100;
+/// Connection has to finish handshake in this time
+pub const HANDSHAKE_TIMEOUT_SEC: u64 = 10;
+/// Pending message has to be sent in this time
+pub const WRITE_TIMEOUT_SEC: u64 = 30;
+pub const MAX_RECONNECTS: u32 = 5;
+pub const MAX_IDLE_SECONDS: u64 = 180;
+pub const MAX_NODES: usize = 15;
+/// When peer gets this much penalty points it is banned
+pub const BAN_SCORE: u32 = 100;
+pub const BAN_TIME_SEC: i64 = 86400;
+/// Penalty for invalid blocks
+pub const PENALTY_BAD_BLOCK: u32 = 100;
+/// Penalty for messages that we can't parse
+pub const PENALTY_MALFORMED: u32 = 50;
+/// Penalty for messages that are not correct in current state
+pub const PENALTY_PROTOCOL: u32 = 20;
+/// How many blocks we request in one batch while syncing
+pub const BLOCKS_BATCH_COUNT: u64 = 50;
+/// Size limit of all blocks in one batch, messages can't be bigger than 64K
+pub const MAX_BATCH_BYTES: usize = 50_000;
+/// How many batches of blocks we download from different peers at once
+pub const MAX_PARALLEL_CHUNKS: u64 = 8;
+/// Limit of blocks from future that we keep until we get the blocks before them
+pub const MAX_FUTURE_BLOCKS: usize = (BLOCKS_BATCH_COUNT * MAX_PARALLEL_CHUNKS * 2) as usize;
+/// If a batch is not received in this time, we request it from another peer
+pub const CHUNK_TIMEOUT_SEC: u64 = 20;
+/// How many addresses from every DNS seed we connect to immediately, others are queued
+pub const DNS_SEED_CONNECTS: usize = 3;
+/// First delay before reconnecting to bootstrap nodes, it doubles after every unsuccessful try
+pub const BOOTSTRAP_RETRY_MIN: u64 = 15;
+pub const BOOTSTRAP_RETRY_MAX: u64 = 600;
@@ -0,0 +1,357 @@
+use std::net::IpAddr;
+use std::num;
+
+pub use constants::*;
+pub use params::*;
+use rand::Rng;
+#[cfg(not(any(target_os = "macos", target_os = "dragonfly")))]
+use thread_priority::*;
+
+use crate::dns::protocol::DnsRecord;
+
+pub mod constants;
+pub mod eventbus;
+pub mod params;
+pub mod simplebus;
+
+/// Convert bytes array to HEX format
+pub fn to_hex(buf: &[u8]) -> String {
+    let mut result = String::new();
+    for x in buf.iter() {
+        result.push_str(&format!("{:01$X}", x, 2));
+    }
+    result
+}
+
+pub fn from_hex(string: &str) -> Result<Vec<u8>, num::ParseIntError> {
+    let string = string.trim();
+    // Slicing by bytes is safe only for ASCII, and every byte needs two digits
+    if !string.is_ascii() || string.len() % 2 != 0 {
+        // There is no other way to make ParseIntError
+        return Err(u8::from_str_radix("-", 16).unwrap_err());
+    }
+    (0..string.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&string[i..i + 2], 16))
+        .collect()
+}
+
+pub fn check_domain(name: &str, allow_dots: bool) -> bool {
+    if name.starts_with('.') || name.starts_with('-') || name.ends_with('.') || name.ends_with('-') {
+        return false;
+    }
+    let parts: Vec<&str> = name.rsplitn(2, '.').collect();
+    if parts.len() == 2 && parts[1].len() < 3 && is_numeric(parts[1]) {
+        return false;
+    }
+
+    let mut last_dot = false;
+    let mut last_hyphen = false;
+    for char in name.chars() {
+        if allow_dots && char == '.' {
+            if last_dot {
+                return false;
+            } else {
+                last_dot = true;
+                continue;
+            }
+        }
+        if char == '-' {
+            if last_hyphen {
+                return false;
+            } else {
+                last_hyphen = true;
+                continue;
+            }
+        }
+        last_dot = false;
+        last_hyphen = false;
+        if !char.is_ascii_alphanumeric() {
+            return false;
+        }
+    }
+    true
+}
+
+pub fn is_numeric(str: &str) -> bool {
+    for char in str.chars() {
+        if !char.is_numeric() {
+            return false;
+        }
+    }
+    true
+}
+
+pub fn get_domain_zone(domain: &str) -> String {
+    let parts: Vec<&str> = domain.rsplitn(2, '.').collect();
+    if !parts.is_empty() {
+        parts[0].to_owned()
+    } else {
+        String::new()
+    }
+}
+
+/// Generates random string of given length
+pub fn random_string(length: usize) -> String {
+    let chars: Vec<char> = "abcdefghijklmnopqrstuvwxyzABCDEFGHIJKLMNOPQRSTUVWXYZ0123456789!?".chars().collect();
+    let mut rng = rand::thread_rng();
+    let mut result = String::with_capacity(length);
+    for _ in 0..length {
+        let position: usize = rng.gen::<usize>() % chars.len();
+        let c: char = *chars.get(position).unwrap();
+        result.push(c);
+    }
+    result
+}
+
+/// Checks if this IP is from Yggdrasil network
+/// https://yggdrasil-network.github.io
+pub fn is_yggdrasil(addr: &IpAddr) -> bool {
+    if let IpAddr::V6(ipv6) = addr {
+        let first_byte = ipv6.octets()[0];
+        return first_byte == 2 || first_byte == 3;
+    }
+    false
+}
+
+/// Checks if this record has IP from Yggdrasil network
+/// https://yggdrasil-network.github.io
+pub fn is_yggdrasil_record(record: &DnsRecord) -> bool {
+    match record {
+        DnsRecord::UNKNOWN { .. } => {}
+        DnsRecord::A { .. } => return false,
+        DnsRecord::NS { .. } => {}
+        DnsRecord::CNAME { .. } => {}
+        DnsRecord::SOA { .. } => {}
+        DnsRecord::PTR { .. } => {}
+        DnsRecord::MX { .. } => {}
+        DnsRecord::TXT { .. } => {}
+        DnsRecord::AAAA { addr, .. } => return is_yggdrasil(&IpAddr::from(*addr)),
+        DnsRecord::SRV { .. } => {}
+        DnsRecord::OPT { .. } => {}
+        DnsRecord::TLSA { .. } => {}
+    }
+    true
+}
+
+/// Field of DNS record that has a wrong value, and why
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct RecordError {
+    /// One of "name", "type", "ttl", "priority", "weight", "port", "data" or "comment"
+    pub field: &'static str,
+    pub message: &'static str
+}
+
+impl RecordError {
+    fn new(field: &'static str, message: &'static str) -> Self {
+        RecordError { field, message }
+    }
+}
+
+/// Checks that DNS record can be published and will work, `yggdrasil_only` is for zones like ygg
+pub fn check_record(record: &DnsRecord, yggdrasil_only: bool) -> Result<(), RecordError> {
+    let name = match record {
+        DnsRecord::TLSA { domain, .. } => domain.clone(),
+        _ => record.get_domain().unwrap_or_default()
+    };
+    if !check_record_name(&name) {
+        return Err(RecordError::new("name", "Name must be @, * or subdomain like www"));
+    }
+    if record.get_ttl() == 0 {
+        return Err(RecordError::new("ttl", "TTL must be more than zero"));
+    }
+    if record.get_ttl() > MAX_RECORD_TTL {
+        return Err(RecordError::new("ttl", "TTL must be not more than a week"));
+    }
+    if let Some(data) = record.get_data() {
+        if data.is_empty() {
+            return Err(RecordError::new("data", "Data can't be empty"));
+        }
+        if data.len() > MAX_DATA_LEN {
+            return Err(RecordError::new("data", "Data is too long"));
+        }
+    }
+    if yggdrasil_only && !is_yggdrasil_record(record) {
+        return match record {
+            DnsRecord::A { .. } => Err(RecordError::new("type", "Zone is for Yggdrasil only, use AAAA records")),
+            _ => Err(RecordError::new("data", "Zone is for Yggdrasil only, use Yggdrasil address"))
+        };
+    }
+    match record {
+        DnsRecord::CNAME { host, .. } | DnsRecord::NS { host, .. } | DnsRecord::MX { host, .. } if !check_domain(host, true) => {
+            Err(RecordError::new("data", "Host must be a domain name like mail.example.ygg"))
+        }
+        DnsRecord::SRV { domain, port, host, .. } => {
+            if !domain.starts_with('_') {
+                return Err(RecordError::new("name", "SRV name must be like _service._tcp"));
+            }
+            if *port == 0 {
+                return Err(RecordError::new("port", "Port must be more than zero"));
+            }
+            if !check_domain(host, true) {
+                return Err(RecordError::new("data", "Host must be a domain name like xmpp.example.ygg"));
+            }
+            Ok(())
+        }
+        DnsRecord::TLSA { certificate_usage, selector, matching_type, .. } => {
+            if *certificate_usage > 3 {
+                return Err(RecordError::new("priority", "Certificate usage must be from 0 to 3"));
+            }
+            if *selector > 1 {
+                return Err(RecordError::new("weight", "Selector must be 0 or 1"));
+            }
+            if *matching_type > 2 {
+                return Err(RecordError::new("port", "Matching type must be from 0 to 2"));
+            }
+            Ok(())
+        }
+        _ => Ok(())
+    }
+}
+
+/// Checks comment of record, it is optional and only for people who edit the domain
+pub fn check_record_comment(comment: &str) -> Result<(), RecordError> {
+    if comment.len() > MAX_COMMENT_LEN {
+        return Err(RecordError::new("comment", "Comment is too long"));
+    }
+    if comment.chars().any(|c| c.is_control()) {
+        return Err(RecordError::new("comment", "Comment must be a single line of text"));
+    }
+    Ok(())
+}
+
+/// Checks name of record inside domain, like "@", "*", "www" or "_xmpp._tcp"
+fn check_record_name(name: &str) -> bool {
+    if name == "@" || name == "*" {
+        return true;
+    }
+    let name = name.strip_prefix("*.").unwrap_or(name);
+    !name.is_empty() && name.split('.').all(|label| {
+        let label = label.strip_prefix('_').unwrap_or(label);
+        !label.is_empty() && check_domain(label, false)
+    })
+}
+
+#[cfg(target_os = "windows")]
+#[allow(unused_variables)]
+pub fn setup_miner_thread(cpu: u32) {
+    let _ = set_current_thread_priority(ThreadPriority::Min);
+    //let _ = set_current_thread_ideal_processor(IdealProcessor::from(cpu));
+}
+
+#[cfg(any(target_os = "linux", target_os = "openbsd", target_os = "freebsd", target_os = "netbsd"))]
+#[allow(unused_variables)]
+pub fn setup_miner_thread(cpu: u32) {
+    let _ = set_current_thread_priority(ThreadPriority::Min);
+}
+
+#[cfg(any(target_os = "macos", target_os = "dragonfly"))]
+#[allow(unused_variables)]
+pub fn setup_miner_thread(cpu: u32) {
+    // MacOS is not supported by thread_priority crate
+}
+
+/// Checks if the computer is on AC power, computers without batteries are always on AC
+#[cfg(target_os = "windows")]
+pub fn on_ac_power() -> bool {
+    use winapi::um::winbase::{GetSystemPowerStatus, SYSTEM_POWER_STATUS};
+
+    let mut status = SYSTEM_POWER_STATUS::default();
+    // Zero means that AC line is offline, 1 - online, 255 - unknown
+    unsafe { GetSystemPowerStatus(&mut status) == 0 || status.ACLineStatus != 0 }
+}
+
+/// Checks if the computer is on AC power, computers without batteries are always on AC
+#[cfg(target_os = "linux")]
+pub fn on_ac_power() -> bool {
+    let entries = match std::fs::read_dir("/sys/class/power_supply") {
+        Ok(entries) => entries,
+        Err(_) => return true
+    };
+    let mut has_mains = false;
+    for entry in entries.flatten() {
+        let path = entry.path();
+        let kind = std::fs::read_to_string(path.join("type")).unwrap_or_default();
+        if kind.trim() != "Mains" {
+            continue;
+        }
+        has_mains = true;
+        if std::fs::read_to_string(path.join("online")).unwrap_or_default().trim() == "1" {
+            return true;
+        }
+    }
+    !has_mains
+}
+
+/// Checks if the computer is on AC power, computers without batteries are always on AC
+#[cfg(not(any(target_os = "windows", target_os = "linux")))]
+pub fn on_ac_power() -> bool {
+    true
+}
+
+#[cfg(test)]
+mod test {
+    use std::net::IpAddr;
+
+    use crate::dns::protocol::{DnsRecord, TransientTtl};
+    use crate::{check_domain, check_record, check_record_comment, is_yggdrasil, MAX_COMMENT_LEN};
+
+    #[test]
+    fn test_check_domain() {
+        assert!(check_domain("abc0", false));
+        assert!(!check_domain("ab.c", false));
+        assert!(check_domain("a.b.c", true));
+        assert!(!check_domain("ab..c", true));
+        assert!(check_domain("a-b.c", true));
+        assert!(!check_domain("a--b.c", true));
+        assert!(check_domain("a-0-b.c", true));
+        assert!(!check_domain("-ab.c", true));
+        assert!(!check_domain("ab.c-", true));
+        assert!(!check_domain(".ab.c", true));
+        assert!(!check_domain("ab.c-", true));
+        assert!(check_domain("a.com", true));
+        assert!(check_domain("ab.com", true));
+        assert!(check_domain("abc.com", true));
+        assert!(check_domain("777.com", true));
+        assert!(!check_domain("77.com", true));
+        assert!(!check_domain("7.com", true));
+    }
+
+    #[test]
+    fn test_is_yggdrasil() {
+        let addr: IpAddr = "200::1".parse().unwrap();
+        assert!(is_yggdrasil(&addr));
+        let addr: IpAddr = "226::1".parse().unwrap();
+        assert!(is_yggdrasil(&addr));
+        let addr: IpAddr = "300::1".parse().unwrap();
+        assert!(is_yggdrasil(&addr));
+        let addr: IpAddr = "326::1".parse().unwrap();
+        assert!(is_yggdrasil(&addr));
+        let addr: IpAddr = "2001::1".parse().unwrap();
+        assert!(!is_yggdrasil(&addr));
+        let addr: IpAddr = "2201::1".parse().unwrap();
+        assert!(!is_yggdrasil(&addr));
+    }
+
+    #[test]
+    fn test_check_record() {
+        let ttl = TransientTtl(3600);
+        let record = DnsRecord::A { domain: String::from("www"), addr: "10.0.0.1".parse().unwrap(), ttl };
+        assert!(check_record(&record, false).is_ok());
+        assert_eq!(check_record(&record, true).unwrap_err().field, "type");
+        let record = DnsRecord::A { domain: String::from("w w"), addr: "10.0.0.1".parse().unwrap(), ttl };
+        assert_eq!(check_record(&record, false).unwrap_err().field, "name");
+        let record = DnsRecord::MX { domain: String::from("@"), priority: 10, host: String::from("mail..example.ygg"), ttl };
+        assert_eq!(check_record(&record, false).unwrap_err().field, "data");
+        let record = DnsRecord::SRV { domain: String::from("_xmpp._tcp"), priority: 10, weight: 10, port: 0, host: String::from("xmpp.example.ygg"), ttl };
+        assert_eq!(check_record(&record, false).unwrap_err().field, "port");
+        let record = DnsRecord::SRV { domain: String::from("_xmpp._tcp"), priority: 10, weight: 10, port: 5222, host: String::from("xmpp.example.ygg"), ttl };
+        assert!(check_record(&record, true).is_ok());
+        let record = DnsRecord::A { domain: String::from("www"), addr: "10.0.0.1".parse().unwrap(), ttl: TransientTtl(86400 * 30) };
+        assert_eq!(check_record(&record, false).unwrap_err().field, "ttl");
+
+        assert!(check_record_comment("Home server").is_ok());
+        assert_eq!(check_record_comment("two\nlines").unwrap_err().field, "comment");
+        assert_eq!(check_record_comment(&"a".repeat(MAX_COMMENT_LEN + 1)).unwrap_err().field, "comment");
+    }
+}
@@ -0,0 +1,148 @@
+//! Parameters that differ between the main chain and test chains
+
+use serde::{Deserialize, Serialize};
+
+use crate::commons::constants::{BLOCK_SIGNERS_START, DOMAIN_DIFFICULTY, DOMAIN_LIFETIME, DOMAIN_RENEW_TIME, NEW_DOMAINS_INTERVAL, ORIGIN_DIFFICULTY, REGISTRATION_LIMIT, SIGNER_DIFFICULTY, ZONE_REGISTRATION_LIMITS};
+
+/// Hash of the first block of the main chain
+pub const MAIN_ORIGIN: &str = "0000001D2A77D63477172678502E51DE7F346061FF7EB188A2445ECA3FC0780E";
+/// Port of nodes in test chain
+pub const TESTNET_LISTEN_PORT: u16 = 4344;
+/// Port of DNS server in test chain, to run it together with the main node
+pub const TESTNET_DNS_PORT: u16 = 5353;
+/// Genesis of test chain is mined by its first node, put its hash to `origin` in config to stick to that chain
+pub const TESTNET_ORIGIN: &str = "";
+pub const TESTNET_PEERS: [&str; 2] = ["testnet-ip4.alfis.name:4344", "testnet-ip6.alfis.name:4344"];
+/// Test chain lives in this subdirectory of `data_dir`
+pub const TESTNET_DATA_DIR: &str = "testnet";
+/// Port of DNS server in regtest mode
+pub const REGTEST_DNS_PORT: u16 = 5354;
+/// Regtest chain lives in this subdirectory of `data_dir`
+pub const REGTEST_DATA_DIR: &str = "regtest";
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ChainKind {
+    Main,
+    /// Chain with lower difficulty to try transfers, expiration and other things without risking real domains
+    Testnet,
+    /// Local chain for tests of developers: one node without network, blocks are mined only by `generate-block` command
+    Regtest
+}
+
+impl Default for ChainKind {
+    fn default() -> Self {
+        ChainKind::Main
+    }
+}
+
+impl ChainKind {
+    pub fn name(&self) -> &'static str {
+        match self {
+            ChainKind::Main => "main",
+            ChainKind::Testnet => "testnet",
+            ChainKind::Regtest => "regtest"
+        }
+    }
+
+    pub fn domain_difficulty(&self) -> u32 {
+        match self {
+            ChainKind::Main => DOMAIN_DIFFICULTY,
+            ChainKind::Testnet => 18,
+            // Discounts for old domains are subtracted from it
+            ChainKind::Regtest => 10
+        }
+    }
+
+    pub fn signer_difficulty(&self) -> u32 {
+        match self {
+            ChainKind::Main => SIGNER_DIFFICULTY,
+            ChainKind::Testnet => 12,
+            ChainKind::Regtest => 1
+        }
+    }
+
+    pub fn origin_difficulty(&self) -> u32 {
+        match self {
+            ChainKind::Main => ORIGIN_DIFFICULTY,
+            ChainKind::Testnet => 20,
+            ChainKind::Regtest => 1
+        }
+    }
+
+    /// Domains in test chain expire fast to see what happens then
+    pub fn domain_lifetime(&self) -> i64 {
+        match self {
+            ChainKind::Main => DOMAIN_LIFETIME,
+            ChainKind::Testnet => 86400 * 3,
+            ChainKind::Regtest => 600
+        }
+    }
+
+    pub fn domain_renew_time(&self) -> i64 {
+        match self {
+            ChainKind::Main => DOMAIN_RENEW_TIME,
+            ChainKind::Testnet => 86400,
+            ChainKind::Regtest => 300
+        }
+    }
+
+    /// After domain becomes free, other keys can take it from the first one by more difficult blocks during this time
+    pub fn auction_time(&self) -> i64 {
+        match self {
+            ChainKind::Main => 86400,
+            ChainKind::Testnet => 3600,
+            ChainKind::Regtest => 120
+        }
+    }
+
+    /// Blocks are signed by other keys starting from this height, regtest has only our keys
+    pub fn signers_start(&self) -> u64 {
+        match self {
+            ChainKind::Main | ChainKind::Testnet => BLOCK_SIGNERS_START,
+            ChainKind::Regtest => u64::MAX
+        }
+    }
+
+    /// One key can mine new domains not more often than this
+    pub fn new_domains_interval(&self) -> i64 {
+        match self {
+            ChainKind::Main | ChainKind::Testnet => NEW_DOMAINS_INTERVAL,
+            ChainKind::Regtest => 0
+        }
+    }
+
+    /// How many new domains one key can register in the zone within `registration_window` blocks
+    pub fn registration_limit(&self, zone: &str) -> u64 {
+        match self {
+            ChainKind::Main | ChainKind::Testnet => ZONE_REGISTRATION_LIMITS.iter()
+                .find(|(name, _)| *name == zone)
+                .map(|(_, limit)| *limit)
+                .unwrap_or(REGISTRATION_LIMIT),
+            ChainKind::Regtest => 3
+        }
+    }
+
+    /// Rolling window of blocks where new domains of one key are counted
+    pub fn registration_window(&self) -> u64 {
+        match self {
+            ChainKind::Main => 10000,
+            ChainKind::Testnet => 1000,
+            ChainKind::Regtest => 20
+        }
+    }
+
+    /// Blocks are counted by windows of this size to activate new rules
+    pub fn activation_window(&self) -> u64 {
+        match self {
+            ChainKind::Main => 1000,
+            ChainKind::Testnet => 100,
+            ChainKind::Regtest => 10
+        }
+    }
+
+    /// True if blocks are mined only by request
+    pub fn is_on_demand(&self) -> bool {
+        *self == ChainKind::Regtest
+    }
+}
@@ -0,0 +1,131 @@
+#[allow(unused_imports)]
+use log::{debug, error, info, trace, warn};
+
+use crate::event::Event;
+use crate::eventbus::post;
+use crate::miner::MinerState;
+use crate::{Bytes, Chain, Keystore, Settings};
+
+pub struct Context {
+    pub app_version: String,
+    pub settings: Settings,
+    pub keystores: Vec<Keystore>,
+    active_key: usize,
+    pub chain: Chain,
+    pub miner_state: MinerState
+}
+
+impl Context {
+    /// Creating an essential context to work with
+    pub fn new(app_version: String, settings: Settings, keystores: Vec<Keystore>, chain: Chain) -> Context {
+        let default_key = settings.default_key.clone();
+        let mut context = Context { app_version, settings, keystores, active_key: 0, chain, miner_state: MinerState { mining: false, full: false, job: None } };
+        if !default_key.is_empty() && !context.select_key_by_name(&default_key) {
+            warn!("Default key '{}' is not loaded", &default_key);
+        }
+        context
+    }
+
+    /// Loads settings from config file again and tells everybody about it.
+    /// DNS upstreams, routes and hosts files are changed at once, other options are used when they are needed.
+    pub fn reload_settings(&mut self, config_name: &str) -> bool {
+        let mut settings = match Settings::load(config_name) {
+            Some(settings) => settings,
+            None => {
+                error!("Unable to reload settings from {}", config_name);
+                return false;
+            }
+        };
+        // Chain may be chosen by command line, it can't change until restart
+        settings.chain = self.settings.chain;
+        settings.apply_chain_defaults();
+        let restart = self.settings.restart_needed(&settings);
+        if !restart.is_empty() {
+            warn!("Changes of {} will be used after restart", restart.join(", "));
+        }
+        self.settings = settings;
+        info!("Settings are reloaded from {}", config_name);
+        post(Event::SettingsReloaded);
+        true
+    }
+
+    pub fn get_keystore(&self) -> Option<&Keystore> {
+        self.keystores.get(self.active_key)
+    }
+
+    pub fn get_keystore_mut(&mut self) -> Option<&mut Keystore> {
+        self.keystores.get_mut(self.active_key)
+    }
+
+    pub fn get_keystores(&self) -> &Vec<Keystore> {
+        &self.keystores
+    }
+
+    /// Returns loaded keystore with this public signing key, if any
+    pub fn get_keystore_by_public(&self, public: &Bytes) -> Option<&Keystore> {
+        self.keystores.iter().find(|key| key.get_public().eq(public))
+    }
+
+    pub fn has_keys(&self) -> bool {
+        !self.keystores.is_empty()
+    }
+
+    pub fn set_keystores(&mut self, keystore: Vec<Keystore>) {
+        self.keystores = keystore;
+        self.active_key = 0;
+    }
+
+    pub fn add_keystore(&mut self, keystore: Keystore) {
+        self.keystores.push(keystore);
+        self.active_key = self.keystores.len() - 1;
+    }
+
+    /// Removes keystore from loaded keys, the active key stays the same if it was not removed
+    pub fn remove_keystore(&mut self, index: usize) -> Option<Keystore> {
+        if index >= self.keystores.len() {
+            return None;
+        }
+        let keystore = self.keystores.remove(index);
+        if self.active_key > index || self.active_key >= self.keystores.len() {
+            self.active_key = self.active_key.saturating_sub(1);
+        }
+        Some(keystore)
+    }
+
+    pub fn select_key_by_index(&mut self, index: usize) -> bool {
+        if index < self.keystores.len() {
+            self.active_key = index;
+            return true;
+        }
+        false
+    }
+
+    pub fn select_key_by_public(&mut self, public: &Bytes) -> bool {
+        for (i, key) in self.keystores.iter().enumerate() {
+            if key.get_public().eq(public) {
+                self.active_key = i;
+                return true;
+            }
+        }
+        false
+    }
+
+    /// Selects key by its name or by the path of its file
+    pub fn select_key_by_name(&mut self, name: &str) -> bool {
+        for (i, key) in self.keystores.iter().enumerate() {
+            if key.get_name() == name || key.get_path() == name {
+                self.active_key = i;
+                return true;
+            }
+        }
+        false
+    }
+
+    pub fn get_active_key_index(&self) -> usize {
+        self.active_key
+    }
+
+    pub fn get_chain(&self) -> &Chain {
+        &self.chain
+    }
+}
\ No newline at end of file
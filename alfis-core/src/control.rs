@@ -0,0 +1,384 @@
+//! Local control socket for scripts and command line, so they can control running node without TCP port.
+//! Only users that can write to the socket file can connect, it gets permissions 0600 after creation,
+//! so put it to a directory that only the user of ALFIS can access.
+//! Every request and answer is one line of JSON, like `{"command":"status"}` or
+//! `{"command":"add-peer","addr":"peer.example.com:4244"}`.
+
+use std::io;
+#[cfg(unix)]
+use std::io::{BufRead, BufReader, Write};
+#[cfg(unix)]
+use std::net::IpAddr;
+use std::sync::{Arc, Mutex};
+#[cfg(unix)]
+use std::time::Duration;
+
+#[cfg(unix)]
+use chrono::Utc;
+#[allow(unused_imports)]
+use log::{debug, error, info, trace, warn};
+use serde::{Deserialize, Serialize};
+
+use crate::{Bytes, Context, Miner, Transaction};
+#[cfg(unix)]
+use crate::BAN_TIME_SEC;
+#[cfg(unix)]
+use crate::blockchain::types::MineResult;
+#[cfg(unix)]
+use crate::event::Event;
+#[cfg(unix)]
+use crate::eventbus::post;
+
+/// How many times we check for a new block after `generate-block`, every 100 ms
+#[cfg(unix)]
+const GENERATE_WAIT_STEPS: u32 = 600;
+
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+#[serde(tag = "command", rename_all = "kebab-case")]
+pub enum ControlCommand {
+    Status,
+    PauseMining,
+    ResumeMining,
+    /// Loads settings from config file again, see `Context::reload_settings`
+    ReloadConfig,
+    AddPeer { addr: String },
+    /// Bans the node for `BAN_TIME_SEC` and closes connections to it
+    Ban { ip: String },
+    /// Removes the ban, result is false if the node was not banned
+    Unban { ip: String },
+    /// Resolves the zone by these forwarders until reload, like `[[dns.routes]]` in config
+    SetRoute { zone: String, forwarders: Vec<String> },
+    /// Removes the route of the zone, its names go to usual forwarders again
+    ClearRoute { zone: String },
+    /// Mines the next block from mining queue, works only in regtest mode
+    GenerateBlock,
+    /// Adds domain transactions to mining queue, from commands that run when the node works already
+    MineTransactions { transactions: Vec<Transaction> }
+}
+
+/// Everything that `alfis status` shows, new fields are optional to talk to older nodes.
+/// Old fields are required, otherwise other answers would be parsed as empty status.
+#[derive(Serialize, Deserialize, Debug, Default, PartialEq)]
+pub struct NodeStatus {
+    pub version: String,
+    pub blocks: u64,
+    /// Hash of the last block
+    #[serde(default)]
+    pub hash: Bytes,
+    /// Height of the chain of peers while we download blocks from them
+    #[serde(default)]
+    pub syncing: Option<u64>,
+    pub domains: i64,
+    pub keys: i64,
+    pub nodes: usize,
+    /// Blocks in mining queue, with the domain block that is mined now
+    #[serde(default)]
+    pub mempool: usize,
+    pub mining: bool,
+    pub paused: bool,
+    /// Hashes per second of all mining threads
+    #[serde(default)]
+    pub hashrate: u64,
+    #[serde(default)]
+    pub dns_queries_per_sec: f64,
+    #[serde(default)]
+    pub dns_cache_hit_rate: f64,
+    #[serde(default)]
+    pub db_size: u64
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+#[serde(untagged)]
+pub enum ControlAnswer {
+    Status(NodeStatus),
+    Result { result: bool },
+    Error { error: String }
+}
+
+#[cfg(unix)]
+impl ControlAnswer {
+    fn error(text: &str) -> Self {
+        ControlAnswer::Error { error: text.to_owned() }
+    }
+}
+
+/// What we know only from events
+#[cfg(unix)]
+#[derive(Default)]
+struct Observed {
+    nodes: usize,
+    paused: bool,
+    syncing: Option<u64>,
+    hashrate: u64,
+    dns_queries_per_sec: f64,
+    dns_cache_hit_rate: f64
+}
+
+#[cfg(unix)]
+impl Observed {
+    fn update(&mut self, event: &Event) {
+        match event {
+            Event::NetworkStatus { nodes, .. } => self.nodes = *nodes,
+            Event::MinerPaused { paused } => self.paused = *paused,
+            Event::SyncProgress { height, .. } => self.syncing = Some(*height),
+            Event::SyncFinished => self.syncing = None,
+            Event::MinerProgress { speed, .. } => self.hashrate = *speed,
+            Event::MinerStopped { .. } => self.hashrate = 0,
+            Event::DnsStats { queries_per_sec, cache_hit_rate } => {
+                self.dns_queries_per_sec = *queries_per_sec;
+                self.dns_cache_hit_rate = *cache_hit_rate;
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Starts listening on control socket if it is set in settings
+#[cfg(unix)]
+pub fn start_control_socket(context: Arc<Mutex<Context>>, miner: Arc<Mutex<Miner>>, config_name: &str) {
+    use std::fs;
+    use std::os::unix::fs::PermissionsExt;
+    use std::os::unix::net::{UnixListener, UnixStream};
+    use std::path::Path;
+    use std::thread;
+
+    use crate::eventbus::register;
+
+    let path = context.lock().unwrap().settings.api.control_socket.clone();
+    if path.is_empty() {
+        return;
+    }
+    // Socket file stays after crashes, we remove it if nobody listens there
+    if Path::new(&path).exists() {
+        if UnixStream::connect(&path).is_ok() {
+            error!("Control socket {} is used by another process", &path);
+            return;
+        }
+        let _ = fs::remove_file(&path);
+    }
+    let listener = match UnixListener::bind(&path) {
+        Ok(listener) => listener,
+        Err(e) => {
+            error!("Unable to create control socket {}: {}", &path, e);
+            return;
+        }
+    };
+    if let Err(e) = fs::set_permissions(&path, fs::Permissions::from_mode(0o600)) {
+        error!("Unable to set permissions of control socket {}: {}", &path, e);
+        let _ = fs::remove_file(&path);
+        return;
+    }
+    info!("Control socket is at {}", &path);
+
+    let observed = Arc::new(Mutex::new(Observed::default()));
+    let observed_copy = Arc::clone(&observed);
+    register(move |_uuid, e| {
+        if e == Event::ActionQuit {
+            return false;
+        }
+        observed_copy.lock().unwrap().update(&e);
+        true
+    });
+
+    let config_name = config_name.to_owned();
+    let _ = thread::Builder::new().name(String::from("control")).spawn(move || {
+        for stream in listener.incoming().flatten() {
+            let writer = match stream.try_clone() {
+                Ok(writer) => writer,
+                Err(_) => continue
+            };
+            let context = Arc::clone(&context);
+            let miner = Arc::clone(&miner);
+            let observed = Arc::clone(&observed);
+            let config_name = config_name.clone();
+            let _ = thread::Builder::new().name(String::from("control-client")).spawn(move || {
+                handle_client(BufReader::new(stream), writer, &context, &miner, &observed, &config_name);
+            });
+        }
+    });
+}
+
+#[cfg(not(unix))]
+pub fn start_control_socket(context: Arc<Mutex<Context>>, _miner: Arc<Mutex<Miner>>, _config_name: &str) {
+    if !context.lock().unwrap().settings.api.control_socket.is_empty() {
+        warn!("Control socket is supported only on Unix-like systems");
+    }
+}
+
+/// Sends command to control socket of running node
+#[cfg(unix)]
+pub fn send_command(path: &str, command: &ControlCommand) -> io::Result<ControlAnswer> {
+    use std::os::unix::net::UnixStream;
+
+    let mut stream = UnixStream::connect(path)?;
+    let mut data = serde_json::to_string(command).unwrap();
+    data.push('\n');
+    stream.write_all(data.as_bytes())?;
+    let mut line = String::new();
+    BufReader::new(stream).read_line(&mut line)?;
+    serde_json::from_str(&line).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+}
+
+#[cfg(not(unix))]
+pub fn send_command(_path: &str, _command: &ControlCommand) -> io::Result<ControlAnswer> {
+    Err(io::Error::new(io::ErrorKind::Unsupported, "control socket is supported only on Unix-like systems"))
+}
+
+#[cfg(unix)]
+fn handle_client<R: BufRead, W: Write>(reader: R, mut writer: W, context: &Mutex<Context>, miner: &Mutex<Miner>, observed: &Mutex<Observed>, config_name: &str) {
+    for line in reader.lines() {
+        let line = match line {
+            Ok(line) => line,
+            Err(_) => break
+        };
+        let answer = match serde_json::from_str::<ControlCommand>(&line) {
+            Ok(command) => execute(command, context, miner, observed, config_name),
+            Err(_) => ControlAnswer::error("wrong command")
+        };
+        let mut data = serde_json::to_string(&answer).unwrap();
+        data.push('\n');
+        if writer.write_all(data.as_bytes()).is_err() {
+            break;
+        }
+    }
+}
+
+#[cfg(unix)]
+fn execute(command: ControlCommand, context: &Mutex<Context>, miner: &Mutex<Miner>, observed: &Mutex<Observed>, config_name: &str) -> ControlAnswer {
+    debug!("Control command {:?}", &command);
+    match command {
+        ControlCommand::Status => {
+            // Mining thread takes the context while it holds the queue, so we don't take them together
+            let queued = miner.lock().unwrap().get_jobs_count();
+            let observed = observed.lock().unwrap();
+            let context = context.lock().unwrap();
+            ControlAnswer::Status(NodeStatus {
+                version: context.app_version.clone(),
+                blocks: context.chain.get_height(),
+                hash: context.chain.get_last_hash(),
+                syncing: observed.syncing,
+                domains: context.chain.get_domains_count(),
+                keys: context.chain.get_users_count(),
+                nodes: observed.nodes,
+                mempool: queued + context.miner_state.job.is_some() as usize,
+                mining: context.miner_state.mining,
+                paused: observed.paused,
+                hashrate: observed.hashrate,
+                dns_queries_per_sec: observed.dns_queries_per_sec,
+                dns_cache_hit_rate: observed.dns_cache_hit_rate,
+                db_size: context.chain.get_db_size()
+            })
+        }
+        ControlCommand::PauseMining => {
+            post(Event::ActionPauseMining);
+            ControlAnswer::Result { result: true }
+        }
+        ControlCommand::ResumeMining => {
+            post(Event::ActionResumeMining);
+            ControlAnswer::Result { result: true }
+        }
+        ControlCommand::ReloadConfig => match context.lock().unwrap().reload_settings(config_name) {
+            true => ControlAnswer::Result { result: true },
+            false => ControlAnswer::error("unable to load config")
+        },
+        ControlCommand::AddPeer { addr } => match addr.rsplit_once(':') {
+            Some((host, port)) if !host.is_empty() && port.parse::<u16>().is_ok() => {
+                post(Event::ActionAddPeer { addr });
+                ControlAnswer::Result { result: true }
+            }
+            _ => ControlAnswer::error("address must be like 'host:port'")
+        },
+        ControlCommand::Ban { ip } => {
+            if ip.parse::<IpAddr>().is_err() {
+                return ControlAnswer::Error { error: format!("wrong IP-address '{}'", ip) };
+            }
+            context.lock().unwrap().chain.add_ban(&ip, Utc::now().timestamp() + BAN_TIME_SEC, "manual");
+            post(Event::BansChanged);
+            ControlAnswer::Result { result: true }
+        }
+        ControlCommand::Unban { ip } => {
+            let result = context.lock().unwrap().chain.remove_ban(&ip);
+            post(Event::BansChanged);
+            ControlAnswer::Result { result }
+        }
+        ControlCommand::SetRoute { zone, forwarders } => {
+            if zone.trim_matches('.').is_empty() || forwarders.is_empty() {
+                return ControlAnswer::error("zone and at least one forwarder are needed");
+            }
+            post(Event::ActionSetRoute { zone, forwarders: Some(forwarders) });
+            ControlAnswer::Result { result: true }
+        }
+        ControlCommand::ClearRoute { zone } => {
+            post(Event::ActionSetRoute { zone, forwarders: None });
+            ControlAnswer::Result { result: true }
+        }
+        ControlCommand::GenerateBlock => {
+            let height = {
+                let context = context.lock().unwrap();
+                if !context.chain.get_kind().is_on_demand() {
+                    return ControlAnswer::error("blocks are generated by command only in regtest mode");
+                }
+                context.chain.get_height()
+            };
+            post(Event::ActionGenerateBlock);
+            // Difficulty in regtest is trivial, so we wait not long
+            for _ in 0..GENERATE_WAIT_STEPS {
+                std::thread::sleep(Duration::from_millis(100));
+                if context.lock().unwrap().chain.get_height() > height {
+                    return ControlAnswer::Result { result: true };
+                }
+            }
+            ControlAnswer::error("no block was mined yet, it will be mined when something is added to mining queue")
+        }
+        ControlCommand::MineTransactions { transactions } => {
+            if let Some(e) = transactions.iter().find_map(|transaction| transaction.check_fields().err()) {
+                return ControlAnswer::Error { error: format!("wrong transaction: {:?}", e) };
+            }
+            let results = miner.lock().unwrap().add_transactions(transactions);
+            match results.iter().filter(|result| **result == MineResult::Fine).count() {
+                0 => ControlAnswer::Error { error: format!("no domain can be mined: {:?}", results) },
+                _ => ControlAnswer::Result { result: true }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::control::{ControlAnswer, ControlCommand, NodeStatus};
+
+    #[test]
+    fn test_protocol() {
+        let command: ControlCommand = serde_json::from_str(r#"{"command":"add-peer","addr":"127.0.0.1:4244"}"#).unwrap();
+        assert_eq!(command, ControlCommand::AddPeer { addr: String::from("127.0.0.1:4244") });
+        assert_eq!(serde_json::to_string(&ControlCommand::PauseMining).unwrap(), r#"{"command":"pause-mining"}"#);
+        assert_eq!(serde_json::to_string(&ControlCommand::GenerateBlock).unwrap(), r#"{"command":"generate-block"}"#);
+        let command: ControlCommand = serde_json::from_str(r#"{"command":"ban","ip":"10.0.0.1"}"#).unwrap();
+        assert_eq!(command, ControlCommand::Ban { ip: String::from("10.0.0.1") });
+        assert_eq!(serde_json::to_string(&ControlCommand::Unban { ip: String::from("10.0.0.1") }).unwrap(), r#"{"command":"unban","ip":"10.0.0.1"}"#);
+        let command: ControlCommand = serde_json::from_str(r#"{"command":"set-route","zone":"corp.example","forwarders":["10.0.0.53:53"]}"#).unwrap();
+        assert_eq!(command, ControlCommand::SetRoute { zone: String::from("corp.example"), forwarders: vec![String::from("10.0.0.53:53")] });
+        assert_eq!(serde_json::to_string(&ControlCommand::ClearRoute { zone: String::from("corp.example") }).unwrap(), r#"{"command":"clear-route","zone":"corp.example"}"#);
+        let command = ControlCommand::MineTransactions { transactions: Vec::new() };
+        assert_eq!(serde_json::to_string(&command).unwrap(), r#"{"command":"mine-transactions","transactions":[]}"#);
+
+        let answers = [
+            ControlAnswer::Status(NodeStatus { blocks: 10, syncing: Some(20), ..Default::default() }),
+            ControlAnswer::Result { result: true },
+            ControlAnswer::Result { result: false },
+            ControlAnswer::Error { error: String::from("Unknown command") }
+        ];
+        for answer in answers {
+            let text = serde_json::to_string(&answer).unwrap();
+            assert_eq!(serde_json::from_str::<ControlAnswer>(&text).unwrap(), answer);
+        }
+        assert_eq!(serde_json::from_str::<ControlAnswer>(r#"{"result":true}"#).unwrap(), ControlAnswer::Result { result: true });
+        assert_eq!(serde_json::from_str::<ControlAnswer>(r#"{"error":"Busy"}"#).unwrap(), ControlAnswer::Error { error: String::from("Busy") });
+        // Status from older node without new fields
+        let text = r#"{"version":"0.8.0","blocks":5,"domains":1,"keys":1,"nodes":3,"mining":false,"paused":false}"#;
+        match serde_json::from_str::<ControlAnswer>(text).unwrap() {
+            ControlAnswer::Status(status) => assert_eq!((status.blocks, status.nodes, status.syncing), (5, 3, None)),
+            answer => panic!("Wrong answer {:?}", answer)
+        }
+    }
+}
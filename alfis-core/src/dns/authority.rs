@@ -109,7 +109,7 @@ impl<'a> Zones {
                 zone.add_record(&rr);
             }
 
-            println!("Loaded zone {} with {} records", zone.domain, record_count);
+            info!("Loaded zone {} with {} records", zone.domain, record_count);
 
             self.zones.insert(zone.domain.clone(), zone);
         }
@@ -124,7 +124,7 @@ impl<'a> Zones {
             let mut zone_file = match File::create(&filename) {
                 Ok(x) => x,
                 Err(_) => {
-                    println!("Failed to save file {:?}", filename);
+                    error!("Failed to save file {:?}", filename);
                     continue;
                 }
             };
@@ -4,6 +4,7 @@ extern crate serde;
 use std::clone::Clone;
 use std::collections::{BTreeMap, HashMap, HashSet};
 use std::hash::{Hash, Hasher};
+use std::sync::atomic::{AtomicUsize, Ordering};
 use std::sync::{Arc, RwLock};
 
 use chrono::*;
@@ -234,12 +235,23 @@ impl Cache {
 
 #[derive(Default)]
 pub struct SynchronizedCache {
-    pub cache: RwLock<Cache>
+    pub cache: RwLock<Cache>,
+    hits: AtomicUsize,
+    misses: AtomicUsize
 }
 
 impl SynchronizedCache {
     pub fn new() -> SynchronizedCache {
-        SynchronizedCache { cache: RwLock::new(Cache::new()) }
+        SynchronizedCache { cache: RwLock::new(Cache::new()), hits: AtomicUsize::new(0), misses: AtomicUsize::new(0) }
+    }
+
+    /// Lookups that were answered from cache
+    pub fn get_hit_count(&self) -> usize {
+        self.hits.load(Ordering::Acquire)
+    }
+
+    pub fn get_miss_count(&self) -> usize {
+        self.misses.load(Ordering::Acquire)
     }
 
     pub fn list(&self) -> Result<Vec<Arc<DomainEntry>>> {
@@ -260,7 +272,12 @@ impl SynchronizedCache {
             Err(_) => return None
         };
 
-        cache.lookup(qname, qtype)
+        let result = cache.lookup(qname, qtype);
+        match result {
+            Some(_) => self.hits.fetch_add(1, Ordering::Release),
+            None => self.misses.fetch_add(1, Ordering::Release)
+        };
+        result
     }
 
     pub fn store(&self, records: &[DnsRecord]) -> Result<()> {
@@ -379,4 +396,15 @@ mod tests {
         assert_eq!(1, cache.domain_entries.get(&"www.microsoft.com".to_string()).unwrap().updates);
         assert_eq!(1, cache.domain_entries.get(&"www.microsoft.com".to_string()).unwrap().hits);
     }
+
+    #[test]
+    fn test_hit_count() {
+        let cache = SynchronizedCache::new();
+        assert!(cache.lookup("www.google.com", QueryType::A).is_none());
+        cache.store_nxdomain("www.google.com", QueryType::A, 3600).unwrap();
+        assert!(cache.lookup("www.google.com", QueryType::A).is_some());
+        assert!(cache.lookup("www.google.com", QueryType::A).is_some());
+        assert_eq!(cache.get_hit_count(), 2);
+        assert_eq!(cache.get_miss_count(), 1);
+    }
 }
@@ -241,7 +241,7 @@ impl DnsClient for DnsNetworkClient {
                         let packet = match DnsPacket::from_buffer(&mut res_buffer) {
                             Ok(packet) => packet,
                             Err(err) => {
-                                println!("DnsNetworkClient failed to parse packet with error: {:?}", err);
+                                warn!("DnsNetworkClient failed to parse packet with error: {:?}", err);
                                 continue;
                             }
                         };
@@ -265,7 +265,7 @@ impl DnsClient for DnsNetworkClient {
                             if let Some(idx) = matched_query {
                                 pending_queries.remove(idx);
                             } else {
-                                println!("Discarding response for: {:?}", packet.questions[0]);
+                                debug!("Discarding response for: {:?}", packet.questions[0]);
                             }
                         }
                     }
@@ -300,7 +300,7 @@ impl DnsClient for DnsNetworkClient {
                         let packet = match DnsPacket::from_buffer(&mut res_buffer) {
                             Ok(packet) => packet,
                             Err(err) => {
-                                println!("DnsNetworkClient failed to parse packet with error: {:?}", err);
+                                warn!("DnsNetworkClient failed to parse packet with error: {:?}", err);
                                 continue;
                             }
                         };
@@ -324,7 +324,7 @@ impl DnsClient for DnsNetworkClient {
                             if let Some(idx) = matched_query {
                                 pending_queries.remove(idx);
                             } else {
-                                println!("Discarding response for: {:?}", packet.questions[0]);
+                                debug!("Discarding response for: {:?}", packet.questions[0]);
                             }
                         }
                     }
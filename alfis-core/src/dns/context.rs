@@ -0,0 +1,230 @@
+//! The `ServerContext in this thread holds the common state across the server
+
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, RwLock};
+
+use derive_more::{Display, Error, From};
+
+use crate::dns::authority::Authority;
+use crate::dns::cache::SynchronizedCache;
+use crate::dns::client::{DnsClient, DnsNetworkClient};
+#[cfg(feature = "doh")]
+use crate::dns::client::HttpsDnsClient;
+use crate::dns::filter::DnsFilter;
+use crate::dns::resolve::{DnsResolver, ForwardingDnsResolver, RecursiveDnsResolver};
+
+#[derive(Debug, Display, From, Error)]
+pub enum ContextError {
+    Authority(crate::dns::authority::AuthorityError),
+    Client(crate::dns::client::ClientError),
+    Io(std::io::Error)
+}
+
+type Result<T> = std::result::Result<T, ContextError>;
+
+pub struct ServerStatistics {
+    pub tcp_query_count: AtomicUsize,
+    pub udp_query_count: AtomicUsize,
+    /// Queries that were dropped because all workers were busy and their queue was full
+    pub dropped_query_count: AtomicUsize
+}
+
+impl ServerStatistics {
+    pub fn get_tcp_query_count(&self) -> usize {
+        self.tcp_query_count.load(Ordering::Acquire)
+    }
+
+    pub fn get_udp_query_count(&self) -> usize {
+        self.udp_query_count.load(Ordering::Acquire)
+    }
+
+    pub fn get_query_count(&self) -> usize {
+        self.get_tcp_query_count() + self.get_udp_query_count()
+    }
+
+    pub fn get_dropped_query_count(&self) -> usize {
+        self.dropped_query_count.load(Ordering::Acquire)
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum ResolveStrategy {
+    Recursive,
+    Forward { upstreams: Vec<String> }
+}
+
+impl ResolveStrategy {
+    /// Makes strategy from the list of upstreams, empty list means recursive resolution
+    pub fn from_upstreams(upstreams: &[String]) -> Self {
+        match upstreams.is_empty() {
+            true => ResolveStrategy::Recursive,
+            false => ResolveStrategy::Forward { upstreams: upstreams.to_vec() }
+        }
+    }
+}
+
+pub struct ServerContext {
+    pub authority: Authority,
+    pub cache: SynchronizedCache,
+    /// Hosts files and blockchain, can be changed at runtime
+    pub filters: RwLock<Vec<Box<dyn DnsFilter + Sync + Send>>>,
+    pub old_client: Box<dyn DnsClient + Sync + Send>,
+    pub doh_client: Option<Box<dyn DnsClient + Sync + Send>>,
+    pub dns_listen: String,
+    pub api_port: u16,
+    /// Can be changed at runtime when settings are reloaded
+    pub resolve_strategy: RwLock<ResolveStrategy>,
+    /// Zones that are resolved with their own strategy, can be changed at runtime
+    pub routes: RwLock<Vec<(String, ResolveStrategy)>>,
+    pub allow_recursive: bool,
+    pub enable_udp: bool,
+    pub enable_tcp: bool,
+    pub enable_api: bool,
+    pub statistics: ServerStatistics,
+    pub zones_dir: &'static str,
+    /// Set when the node is shutting down, servers stop to take new queries
+    pub stopped: AtomicBool
+}
+
+impl Default for ServerContext {
+    fn default() -> Self {
+        ServerContext::new(String::from("0.0.0.0:53"), Vec::new())
+    }
+}
+
+impl ServerContext {
+    #[allow(unused_variables)]
+    pub fn new(dns_listen: String, bootstraps: Vec<String>) -> ServerContext {
+        #[cfg(not(feature = "doh"))]
+        let doh_client = None;
+        #[cfg(feature = "doh")]
+        let doh_client: Option<Box<dyn DnsClient + Sync + Send>> = Some(Box::new(HttpsDnsClient::new(bootstraps)));
+
+        ServerContext {
+            authority: Authority::new(),
+            cache: SynchronizedCache::new(),
+            filters: RwLock::new(Vec::new()),
+            old_client: Box::new(DnsNetworkClient::new(10000 + (rand::random::<u16>() % 50000))),
+            doh_client,
+            dns_listen,
+            api_port: 5380,
+            resolve_strategy: RwLock::new(ResolveStrategy::Recursive),
+            routes: RwLock::new(Vec::new()),
+            allow_recursive: true,
+            enable_udp: true,
+            enable_tcp: true,
+            enable_api: false,
+            statistics: ServerStatistics { tcp_query_count: AtomicUsize::new(0), udp_query_count: AtomicUsize::new(0), dropped_query_count: AtomicUsize::new(0) },
+            zones_dir: "zones",
+            stopped: AtomicBool::new(false)
+        }
+    }
+
+    pub fn initialize(&mut self) -> Result<()> {
+        // Start UDP client thread
+        self.old_client.run()?;
+        // Start DoH client
+        if let Some(client) = &self.doh_client {
+            client.run()?;
+        }
+
+        // Load authority data
+        self.authority.load()?;
+
+        Ok(())
+    }
+
+    pub fn create_resolver(&self, ptr: Arc<ServerContext>) -> Box<dyn DnsResolver> {
+        Self::resolver_for_strategy(&self.resolve_strategy.read().unwrap(), ptr)
+    }
+
+    /// Creates resolver for this particular name, taking zone routes into account
+    pub fn create_resolver_for(&self, ptr: Arc<ServerContext>, qname: &str) -> Box<dyn DnsResolver> {
+        let routes = self.routes.read().unwrap();
+        match find_route(&routes, qname) {
+            None => self.create_resolver(ptr),
+            Some(strategy) => Self::resolver_for_strategy(strategy, ptr)
+        }
+    }
+
+    fn resolver_for_strategy(strategy: &ResolveStrategy, ptr: Arc<ServerContext>) -> Box<dyn DnsResolver> {
+        match strategy {
+            ResolveStrategy::Recursive => Box::new(RecursiveDnsResolver::new(ptr)),
+            ResolveStrategy::Forward { upstreams } => Box::new(ForwardingDnsResolver::new(ptr, upstreams.clone()))
+        }
+    }
+
+    /// Sets resolve strategy for some zone, or removes the route if strategy is None
+    pub fn set_route(&self, zone: &str, strategy: Option<ResolveStrategy>) {
+        let zone = zone.trim_matches('.').to_lowercase();
+        let mut routes = self.routes.write().unwrap();
+        routes.retain(|(z, _)| z != &zone);
+        if let Some(strategy) = strategy {
+            routes.push((zone, strategy));
+        }
+    }
+
+    /// Removes all routes, before setting them again from reloaded settings
+    pub fn clear_routes(&self) {
+        self.routes.write().unwrap().clear();
+    }
+
+    pub fn get_routes(&self) -> Vec<(String, ResolveStrategy)> {
+        self.routes.read().unwrap().clone()
+    }
+}
+
+/// Finds the route with the longest zone that this name belongs to
+pub fn find_route<'a>(routes: &'a [(String, ResolveStrategy)], qname: &str) -> Option<&'a ResolveStrategy> {
+    let qname = qname.trim_end_matches('.').to_lowercase();
+    routes
+        .iter()
+        .filter(|(zone, _)| zone.is_empty() || qname == *zone || qname.ends_with(&format!(".{}", zone)))
+        .max_by_key(|(zone, _)| zone.len())
+        .map(|(_, strategy)| strategy)
+}
+
+#[cfg(test)]
+pub mod tests {
+
+    use std::sync::atomic::{AtomicBool, AtomicUsize};
+    use std::sync::Arc;
+
+    use super::*;
+    use crate::dns::authority::Authority;
+    use crate::dns::cache::SynchronizedCache;
+    use crate::dns::client::tests::{DnsStubClient, StubCallback};
+
+    pub fn create_test_context(callback: Box<StubCallback>) -> Arc<ServerContext> {
+        Arc::new(ServerContext {
+            authority: Authority::new(),
+            cache: SynchronizedCache::new(),
+            filters: RwLock::new(Vec::new()),
+            old_client: Box::new(DnsStubClient::new(callback)),
+            doh_client: Some(Box::new(HttpsDnsClient::new(Vec::new()))),
+            dns_listen: String::from("0.0.0.0:53"),
+            api_port: 5380,
+            resolve_strategy: RwLock::new(ResolveStrategy::Recursive),
+            routes: RwLock::new(Vec::new()),
+            allow_recursive: true,
+            enable_udp: true,
+            enable_tcp: true,
+            enable_api: false,
+            statistics: ServerStatistics { tcp_query_count: AtomicUsize::new(0), udp_query_count: AtomicUsize::new(0), dropped_query_count: AtomicUsize::new(0) },
+            zones_dir: "zones",
+            stopped: AtomicBool::new(false)
+        })
+    }
+
+    #[test]
+    fn test_find_route() {
+        let corp = ResolveStrategy::Forward { upstreams: vec![String::from("10.0.0.53:53")] };
+        let lab = ResolveStrategy::Recursive;
+        let routes = vec![(String::from("corp.example"), corp.clone()), (String::from("lab.corp.example"), lab.clone())];
+        assert_eq!(find_route(&routes, "www.corp.example"), Some(&corp));
+        assert_eq!(find_route(&routes, "corp.example."), Some(&corp));
+        assert_eq!(find_route(&routes, "host.lab.corp.example"), Some(&lab));
+        assert_eq!(find_route(&routes, "notcorp.example"), None);
+        assert_eq!(find_route(&routes, "example.com"), None);
+    }
+}
@@ -22,6 +22,8 @@ pub mod context;
 pub mod filter;
 pub mod hosts;
 pub mod protocol;
+#[cfg(feature = "doq")]
+pub mod quic;
 pub mod resolve;
 pub mod server;
 
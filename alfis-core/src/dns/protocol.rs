@@ -5,6 +5,7 @@ use std::hash::{Hash, Hasher};
 use std::net::{Ipv4Addr, Ipv6Addr};
 
 use derive_more::{Display, Error, From};
+use log::debug;
 use rand::random;
 use serde::{Deserialize, Serialize};
 
@@ -464,7 +465,7 @@ impl DnsRecord {
                 }
             }
             DnsRecord::UNKNOWN { .. } => {
-                println!("Skipping record: {:?}", self);
+                debug!("Skipping record: {:?}", self);
             }
         }
 
@@ -546,6 +547,23 @@ impl DnsRecord {
             DnsRecord::OPT { .. } => 0
         }
     }
+
+    pub fn set_ttl(&mut self, value: u32) {
+        match self {
+            DnsRecord::A { ttl, .. }
+            | DnsRecord::AAAA { ttl, .. }
+            | DnsRecord::NS { ttl, .. }
+            | DnsRecord::CNAME { ttl, .. }
+            | DnsRecord::SRV { ttl, .. }
+            | DnsRecord::PTR { ttl, .. }
+            | DnsRecord::MX { ttl, .. }
+            | DnsRecord::UNKNOWN { ttl, .. }
+            | DnsRecord::SOA { ttl, .. }
+            | DnsRecord::TXT { ttl, .. }
+            | DnsRecord::TLSA { ttl, .. } => *ttl = TransientTtl(value),
+            DnsRecord::OPT { .. } => {}
+        }
+    }
 }
 
 /// The result code for a DNS query, as described in the specification
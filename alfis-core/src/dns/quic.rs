@@ -0,0 +1,320 @@
+//! Experimental DNS-over-QUIC server implementation (RFC 9250)
+//!
+//! Every query comes in its own bidirectional stream, prefixed with two bytes of length,
+//! the answer is sent back to the same stream, after which the stream is finished.
+
+use std::collections::HashMap;
+use std::net::{SocketAddr, UdpSocket};
+use std::sync::atomic::Ordering;
+use std::sync::mpsc::{sync_channel, Receiver, SyncSender, TrySendError};
+use std::sync::Arc;
+use std::thread::Builder;
+use std::time::Duration;
+
+use log::{debug, error, trace, warn};
+use rand::RngCore;
+
+use crate::blockchain::hash_utils::hash_sha256;
+use crate::dns::buffer::{PacketBuffer, VectorPacketBuffer};
+use crate::dns::context::ServerContext;
+use crate::dns::protocol::DnsPacket;
+use crate::dns::server::{execute_query, DnsServer, ServerError, QUEUE_PER_THREAD};
+
+/// ALPN token for DoQ
+const DOQ_ALPN: &[u8] = b"doq";
+/// Error code for streams that we can't answer, as defined in RFC
+const DOQ_INTERNAL_ERROR: u64 = 0x1;
+const MAX_DATAGRAM_SIZE: usize = 1350;
+const MAX_MESSAGE_SIZE: usize = 0xFFFF + 2;
+/// How often we check timers and answers from workers
+const LOOP_TIMEOUT: Duration = Duration::from_millis(10);
+
+type Result<T> = std::result::Result<T, ServerError>;
+
+/// Query, sent to worker threads: connection id, stream id and the query itself
+type QuicQuery = (Vec<u8>, u64, DnsPacket);
+/// Answer from worker threads: connection id, stream id and serialized answer with length prefix
+type QuicAnswer = (Vec<u8>, u64, Vec<u8>);
+
+struct QuicClient {
+    conn: quiche::Connection,
+    /// Partially received data of streams
+    streams: HashMap<u64, Vec<u8>>
+}
+
+/// The DoQ server
+///
+/// All QUIC work is done on one thread, and the queries are resolved by a pool of threads,
+/// the same way as in the UDP server.
+pub struct DnsQuicServer {
+    context: Arc<ServerContext>,
+    listen: String,
+    cert_file: String,
+    key_file: String,
+    thread_count: usize
+}
+
+impl DnsQuicServer {
+    pub fn new(context: Arc<ServerContext>, listen: String, cert_file: String, key_file: String, thread_count: usize) -> DnsQuicServer {
+        DnsQuicServer { context, listen, cert_file, key_file, thread_count }
+    }
+
+    fn create_config(&self) -> std::result::Result<quiche::Config, quiche::Error> {
+        let mut config = quiche::Config::new(quiche::PROTOCOL_VERSION)?;
+        config.load_cert_chain_from_pem_file(&self.cert_file)?;
+        config.load_priv_key_from_pem_file(&self.key_file)?;
+        config.set_application_protos(&[DOQ_ALPN])?;
+        config.set_max_idle_timeout(30_000);
+        config.set_max_recv_udp_payload_size(MAX_DATAGRAM_SIZE);
+        config.set_max_send_udp_payload_size(MAX_DATAGRAM_SIZE);
+        config.set_initial_max_data(1_000_000);
+        config.set_initial_max_stream_data_bidi_remote(MAX_MESSAGE_SIZE as u64);
+        config.set_initial_max_streams_bidi(100);
+        config.set_disable_active_migration(true);
+        Ok(config)
+    }
+}
+
+impl DnsServer for DnsQuicServer {
+    fn run_server(self) -> Result<()> {
+        let mut config = match self.create_config() {
+            Ok(config) => config,
+            Err(e) => {
+                error!("Error configuring DoQ server: {:?}", e);
+                return Err(ServerError::Io(std::io::Error::new(std::io::ErrorKind::InvalidInput, "Wrong DoQ configuration")));
+            }
+        };
+        let socket = UdpSocket::bind(self.listen.as_str())?;
+        socket.set_read_timeout(Some(LOOP_TIMEOUT))?;
+        let local_addr = socket.local_addr()?;
+
+        // Spawn threads for resolving queries
+        // Both queues are bounded, queries are dropped when workers can't keep up with them
+        let (query_sender, query_receiver) = sync_channel::<QuicQuery>(self.thread_count * QUEUE_PER_THREAD);
+        let (answer_sender, answer_receiver) = sync_channel::<QuicAnswer>(self.thread_count * QUEUE_PER_THREAD);
+        let query_receiver = Arc::new(std::sync::Mutex::new(query_receiver));
+        for thread_id in 0..self.thread_count {
+            let context = Arc::clone(&self.context);
+            let query_receiver = Arc::clone(&query_receiver);
+            let answer_sender = answer_sender.clone();
+            let name = "DnsQuicServer-request-".to_string() + &thread_id.to_string();
+            let _ = Builder::new().name(name).spawn(move || loop {
+                let (conn_id, stream_id, request) = match query_receiver.lock().unwrap().recv() {
+                    Ok(query) => query,
+                    Err(_) => break
+                };
+                let mut packet = execute_query(Arc::clone(&context), &request);
+                // Message ID must be zero in DoQ
+                packet.header.id = 0;
+                let mut res_buffer = VectorPacketBuffer::new();
+                if packet.write(&mut res_buffer, 0xFFFF).is_err() {
+                    debug!("Failed to write DoQ answer");
+                    continue;
+                }
+                let len = res_buffer.pos();
+                let mut data = Vec::with_capacity(len + 2);
+                data.extend_from_slice(&(len as u16).to_be_bytes());
+                data.extend_from_slice(&res_buffer.buffer[..len]);
+                if answer_sender.send((conn_id, stream_id, data)).is_err() {
+                    break;
+                }
+            })?;
+        }
+
+        let mut secret = [0u8; 32];
+        rand::thread_rng().fill_bytes(&mut secret);
+        let context = Arc::clone(&self.context);
+        let _ = Builder::new().name("DnsQuicServer-incoming".into()).spawn(move || {
+            let mut clients: HashMap<Vec<u8>, QuicClient> = HashMap::new();
+            let mut buf = [0u8; 65535];
+            let mut out = [0u8; MAX_DATAGRAM_SIZE];
+            loop {
+                match socket.recv_from(&mut buf) {
+                    Ok((len, from)) => {
+                        handle_datagram(&socket, &context, &mut config, &mut clients, &mut buf[..len], &mut out, from, local_addr, &secret, &query_sender);
+                    }
+                    Err(e) if e.kind() == std::io::ErrorKind::WouldBlock || e.kind() == std::io::ErrorKind::TimedOut => {}
+                    Err(e) => {
+                        debug!("Failed to read from DoQ socket: {:?}", e);
+                    }
+                }
+
+                process_answers(&mut clients, &answer_receiver);
+
+                // Send all pending packets and process timers
+                for client in clients.values_mut() {
+                    if client.conn.timeout() == Some(Duration::ZERO) {
+                        client.conn.on_timeout();
+                    }
+                    flush_client(&socket, client, &mut out);
+                }
+                clients.retain(|_, client| {
+                    if client.conn.is_closed() {
+                        trace!("DoQ connection closed");
+                    }
+                    !client.conn.is_closed()
+                });
+            }
+        })?;
+
+        Ok(())
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn handle_datagram(socket: &UdpSocket, context: &ServerContext, config: &mut quiche::Config, clients: &mut HashMap<Vec<u8>, QuicClient>, buf: &mut [u8],
+                   out: &mut [u8], from: SocketAddr, local: SocketAddr, secret: &[u8], queries: &SyncSender<QuicQuery>) {
+    let header = match quiche::Header::from_slice(buf, quiche::MAX_CONN_ID_LEN) {
+        Ok(header) => header,
+        Err(e) => {
+            debug!("Error parsing QUIC header: {:?}", e);
+            return;
+        }
+    };
+    // Client uses its random connection id until it gets ours, so we derive our id from it
+    let mut conn_id = header.dcid.to_vec();
+    if !clients.contains_key(&conn_id) {
+        conn_id = derive_conn_id(secret, &header.dcid);
+    }
+    if !clients.contains_key(&conn_id) {
+        if header.ty != quiche::Type::Initial {
+            trace!("Got QUIC packet for unknown connection");
+            return;
+        }
+        if !quiche::version_is_supported(header.version) {
+            if let Ok(len) = quiche::negotiate_version(&header.scid, &header.dcid, out) {
+                let _ = socket.send_to(&out[..len], from);
+            }
+            return;
+        }
+        let scid = quiche::ConnectionId::from_vec(conn_id.clone());
+        let conn = match quiche::accept(&scid, None, local, from, config) {
+            Ok(conn) => conn,
+            Err(e) => {
+                warn!("Error accepting DoQ connection: {:?}", e);
+                return;
+            }
+        };
+        debug!("New DoQ connection from {}", from);
+        clients.insert(conn_id.clone(), QuicClient { conn, streams: HashMap::new() });
+    }
+
+    let client = clients.get_mut(&conn_id).unwrap();
+    if let Err(e) = client.conn.recv(buf, quiche::RecvInfo { from, to: local }) {
+        debug!("DoQ receive failed: {:?}", e);
+        return;
+    }
+
+    if client.conn.is_established() {
+        let mut stream_buf = [0u8; 4096];
+        for stream_id in client.conn.readable() {
+            while let Ok((len, fin)) = client.conn.stream_recv(stream_id, &mut stream_buf) {
+                let data = client.streams.entry(stream_id).or_default();
+                data.extend_from_slice(&stream_buf[..len]);
+                if data.len() > MAX_MESSAGE_SIZE {
+                    warn!("Too big DoQ message from {}", from);
+                    let _ = client.conn.close(false, 0x2, b"");
+                    return;
+                }
+                if fin {
+                    let data = client.streams.remove(&stream_id).unwrap_or_default();
+                    match parse_query(&data) {
+                        Some(packet) => match queries.try_send((conn_id.clone(), stream_id, packet)) {
+                            Ok(_) => {}
+                            Err(TrySendError::Full(_)) => {
+                                let _ = context.statistics.dropped_query_count.fetch_add(1, Ordering::Release);
+                                debug!("DoQ request queue is full, dropping query from {}", from);
+                                let _ = client.conn.stream_shutdown(stream_id, quiche::Shutdown::Write, DOQ_INTERNAL_ERROR);
+                            }
+                            Err(TrySendError::Disconnected(_)) => warn!("DoQ workers have stopped")
+                        },
+                        None => {
+                            // Protocol error, as defined in RFC
+                            let _ = client.conn.close(true, 0x2, b"");
+                            return;
+                        }
+                    }
+                    break;
+                }
+            }
+        }
+    }
+}
+
+/// Makes our connection id from the one that client has chosen for us
+fn derive_conn_id(secret: &[u8], dcid: &[u8]) -> Vec<u8> {
+    let mut data = secret.to_vec();
+    data.extend_from_slice(dcid);
+    let mut hash = hash_sha256(&data);
+    hash.truncate(quiche::MAX_CONN_ID_LEN);
+    hash
+}
+
+/// Parses DNS message from stream data with two bytes length prefix
+fn parse_query(data: &[u8]) -> Option<DnsPacket> {
+    if data.len() < 2 + 12 {
+        return None;
+    }
+    let len = u16::from_be_bytes([data[0], data[1]]) as usize;
+    if len != data.len() - 2 {
+        return None;
+    }
+    let mut buffer = VectorPacketBuffer::new();
+    buffer.buffer.extend_from_slice(&data[2..]);
+    match DnsPacket::from_buffer(&mut buffer) {
+        Ok(packet) if packet.header.id == 0 => Some(packet),
+        _ => None
+    }
+}
+
+fn process_answers(clients: &mut HashMap<Vec<u8>, QuicClient>, answers: &Receiver<QuicAnswer>) {
+    while let Ok((conn_id, stream_id, data)) = answers.try_recv() {
+        if let Some(client) = clients.get_mut(&conn_id) {
+            if let Err(e) = client.conn.stream_send(stream_id, &data, true) {
+                debug!("Error sending DoQ answer: {:?}", e);
+            }
+        }
+    }
+}
+
+fn flush_client(socket: &UdpSocket, client: &mut QuicClient, out: &mut [u8]) {
+    loop {
+        let (len, send_info) = match client.conn.send(out) {
+            Ok(result) => result,
+            Err(quiche::Error::Done) => break,
+            Err(e) => {
+                debug!("DoQ send failed: {:?}", e);
+                client.conn.close(false, 0x1, b"").ok();
+                break;
+            }
+        };
+        if let Err(e) = socket.send_to(&out[..len], send_info.to) {
+            debug!("Error sending DoQ packet: {:?}", e);
+            break;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::dns::buffer::{PacketBuffer, VectorPacketBuffer};
+    use crate::dns::protocol::{DnsPacket, DnsQuestion, QueryType};
+    use crate::dns::quic::parse_query;
+
+    #[test]
+    fn test_parse_query() {
+        let mut packet = DnsPacket::new();
+        packet.questions.push(DnsQuestion::new(String::from("alfis.ygg"), QueryType::A));
+        let mut buffer = VectorPacketBuffer::new();
+        packet.write(&mut buffer, 512).unwrap();
+        let len = buffer.pos();
+        let mut data = (len as u16).to_be_bytes().to_vec();
+        data.extend_from_slice(&buffer.buffer[..len]);
+        let parsed = parse_query(&data).unwrap();
+        assert_eq!(parsed.questions[0].name, "alfis.ygg");
+
+        // Wrong length prefix
+        data[1] = data[1].wrapping_add(1);
+        assert!(parse_query(&data).is_none());
+    }
+}
@@ -5,6 +5,7 @@ use std::sync::Arc;
 use std::vec::Vec;
 
 use derive_more::{Display, Error, From};
+use log::debug;
 use rand::seq::IteratorRandom;
 
 use crate::dns::context::ServerContext;
@@ -52,7 +53,7 @@ pub trait DnsResolver {
             }
         }
 
-        for filter in context.filters.iter() {
+        for filter in context.filters.read().unwrap().iter() {
             if let Some(packet) = filter.lookup(qname, qtype) {
                 context.cache.store(&packet.answers)?;
                 return Ok(packet);
@@ -157,7 +158,7 @@ impl DnsResolver for RecursiveDnsResolver {
 
         // Start querying name servers
         loop {
-            println!("attempting lookup of {:?} {} with ns {}", qtype, qname, ns);
+            debug!("Attempting lookup of {:?} {} with ns {}", qtype, qname, ns);
 
             let ns_copy = ns.clone();
 
@@ -244,7 +245,7 @@ mod tests {
 
         match Arc::get_mut(&mut context) {
             Some(mut ctx) => {
-                ctx.resolve_strategy = ResolveStrategy::Forward { upstreams: vec![String::from("127.0.0.1:53")] };
+                *ctx.resolve_strategy.get_mut().unwrap() = ResolveStrategy::Forward { upstreams: vec![String::from("127.0.0.1:53")] };
             }
             None => panic!()
         }
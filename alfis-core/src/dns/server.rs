@@ -1,16 +1,15 @@
 //! UDP and TCP server implementations for DNS
 
-use std::collections::VecDeque;
 use std::io::Write;
 use std::net::{Shutdown, SocketAddr, TcpListener, TcpStream, UdpSocket};
 use std::sync::atomic::Ordering;
-use std::sync::mpsc::{channel, Sender};
-use std::sync::{Arc, Condvar, Mutex};
+use std::sync::mpsc::{sync_channel, Receiver, SyncSender, TrySendError};
+use std::sync::{Arc, Mutex};
 use std::thread::Builder;
+use std::time::Duration;
 
 use derive_more::{Display, Error, From};
 use log::{debug, error, warn};
-use rand::random;
 
 use crate::dns::buffer::{BytePacketBuffer, PacketBuffer, StreamPacketBuffer, VectorPacketBuffer};
 use crate::dns::context::ServerContext;
@@ -27,6 +26,11 @@ pub enum ServerError {
 
 type Result<T> = std::result::Result<T, ServerError>;
 
+/// How many queries can wait for every worker thread, the queries above that are dropped
+pub(crate) const QUEUE_PER_THREAD: usize = 16;
+/// Slow TCP clients must not hold worker threads for long
+const TCP_TIMEOUT: Duration = Duration::from_secs(5);
+
 macro_rules! return_or_report {
     ( $x:expr, $message:expr ) => {
         match $x {
@@ -109,7 +113,7 @@ pub fn execute_query(context: Arc<ServerContext>, request: &DnsPacket) -> DnsPac
         packet.questions.push(question.clone());
         log::trace!("Resolving: {}, type {:?}", &question.name, &question.qtype);
 
-        let mut resolver = context.create_resolver(Arc::clone(&context));
+        let mut resolver = context.create_resolver_for(Arc::clone(&context), &question.name);
         let res_code = match resolver.resolve(&question.name, question.qtype, request.header.recursion_desired) {
             Ok(result) => {
                 let res_code = result.header.rescode;
@@ -157,18 +161,16 @@ pub fn execute_query(context: Arc<ServerContext>, request: &DnsPacket) -> DnsPac
 /// The UDP server
 ///
 /// Accepts DNS queries through UDP, and uses the `ServerContext` to determine
-/// how to service the request. Packets are read on a single thread, after which
-/// a new thread is spawned to service the request asynchronously.
+/// how to service the request. Packets are read on a single thread, and then they go
+/// through bounded queue to worker threads. When the queue is full new packets are dropped.
 pub struct DnsUdpServer {
     context: Arc<ServerContext>,
-    request_queue: Arc<Mutex<VecDeque<(SocketAddr, DnsPacket)>>>,
-    request_cond: Arc<Condvar>,
     thread_count: usize
 }
 
 impl DnsUdpServer {
     pub fn new(context: Arc<ServerContext>, thread_count: usize) -> DnsUdpServer {
-        DnsUdpServer { context, request_queue: Arc::new(Mutex::new(VecDeque::new())), request_cond: Arc::new(Condvar::new()), thread_count }
+        DnsUdpServer { context, thread_count: thread_count.max(1) }
     }
 }
 
@@ -179,6 +181,8 @@ impl DnsServer for DnsUdpServer {
     fn run_server(self) -> Result<()> {
         // Bind the socket
         let socket = UdpSocket::bind(self.context.dns_listen.as_str())?;
+        let (sender, receiver) = sync_channel::<(SocketAddr, DnsPacket)>(self.thread_count * QUEUE_PER_THREAD);
+        let receiver = Arc::new(Mutex::new(receiver));
 
         // Spawn threads for handling requests
         for thread_id in 0..self.thread_count {
@@ -191,46 +195,20 @@ impl DnsServer for DnsUdpServer {
             };
 
             let context = Arc::clone(&self.context);
-            let request_cond = self.request_cond.clone();
-            let request_queue = self.request_queue.clone();
+            let receiver = Arc::clone(&receiver);
 
             let name = "DnsUdpServer-request-".to_string() + &thread_id.to_string();
             let _ = Builder::new().name(name).spawn(move || {
                 loop {
-                    // Acquire lock, and wait on the condition until data is
-                    // available. Then proceed with popping an entry of the queue.
-                    let (src, request) = match request_queue
-                        .lock()
-                        .ok()
-                        .and_then(|x| request_cond.wait(x).ok())
-                        .and_then(|mut x| x.pop_front())
-                    {
+                    let (src, request) = match next_request(&receiver) {
                         Some(x) => x,
                         None => {
-                            debug!("Not expected to happen!");
-                            continue;
+                            debug!("UDP request queue is closed");
+                            break;
                         }
                     };
 
-                    let mut size_limit = 512 - 32; // Minus 32 bytes for the packet header
-
-                    // Check for EDNS
-                    if request.resources.len() == 1 {
-                        if let DnsRecord::OPT { packet_len, .. } = request.resources[0] {
-                            size_limit = packet_len as usize;
-                        }
-                    }
-
-                    // Create a response buffer, and ask the context for an appropriate resolver
-                    let mut res_buffer = VectorPacketBuffer::new();
-
-                    let mut packet = execute_query(Arc::clone(&context), &request);
-                    let _ = packet.write(&mut res_buffer, size_limit);
-
-                    // Fire off the response
-                    let len = res_buffer.pos();
-                    let data = return_or_report!(res_buffer.get_range(0, len), "Failed to get buffer data");
-                    ignore_or_report!(socket_clone.send_to(data, src), "Failed to send response packet");
+                    handle_udp_request(&context, &socket_clone, src, request);
                 }
             })?;
         }
@@ -259,6 +237,11 @@ impl DnsServer for DnsUdpServer {
                         }
                     };
 
+                    if self.context.stopped.load(Ordering::Acquire) {
+                        debug!("UDP service loop has finished");
+                        break;
+                    }
+
                     // Parse it
                     let request = match DnsPacket::from_buffer(&mut req_buffer) {
                         Ok(x) => x,
@@ -277,14 +260,16 @@ impl DnsServer for DnsUdpServer {
                     }
                     working_ids.put(key, cur_time);
 
-                    // Acquire lock, add request to queue, and notify waiting threads using the condition.
-                    match self.request_queue.lock() {
-                        Ok(mut queue) => {
-                            queue.push_back((src, request));
-                            self.request_cond.notify_one();
+                    // The client will repeat the query if we are too busy now
+                    match sender.try_send((src, request)) {
+                        Ok(_) => {}
+                        Err(TrySendError::Full(_)) => {
+                            let _ = self.context.statistics.dropped_query_count.fetch_add(1, Ordering::Release);
+                            debug!("UDP request queue is full, dropping query from {}", src);
                         }
-                        Err(e) => {
-                            debug!("Failed to send UDP request for processing: {}", e);
+                        Err(TrySendError::Disconnected(_)) => {
+                            warn!("UDP workers have stopped");
+                            break;
                         }
                     }
                 }
@@ -294,66 +279,41 @@ impl DnsServer for DnsUdpServer {
     }
 }
 
-/// TCP DNS server
+/// TCP DNS server, connections go to worker threads through bounded queue like in UDP server
 pub struct DnsTcpServer {
     context: Arc<ServerContext>,
-    senders: Vec<Sender<TcpStream>>,
     thread_count: usize
 }
 
 impl DnsTcpServer {
     pub fn new(context: Arc<ServerContext>, thread_count: usize) -> DnsTcpServer {
-        DnsTcpServer { context, senders: Vec::new(), thread_count }
+        DnsTcpServer { context, thread_count: thread_count.max(1) }
     }
 }
 
 impl DnsServer for DnsTcpServer {
-    fn run_server(mut self) -> Result<()> {
+    fn run_server(self) -> Result<()> {
         let socket = TcpListener::bind(self.context.dns_listen.as_str())?;
+        let (sender, receiver) = sync_channel::<TcpStream>(self.thread_count * QUEUE_PER_THREAD);
+        let receiver = Arc::new(Mutex::new(receiver));
 
-        // Spawn threads for handling requests, and create the channels
+        // Spawn threads for handling requests
         for thread_id in 0..self.thread_count {
-            let (tx, rx) = channel();
-            self.senders.push(tx);
-
             let context = Arc::clone(&self.context);
+            let receiver = Arc::clone(&receiver);
 
             let name = "DnsTcpServer-request-".to_string() + &thread_id.to_string();
             let _ = Builder::new().name(name).spawn(move || {
                 loop {
-                    let mut stream = match rx.recv() {
-                        Ok(x) => x,
-                        Err(_) => continue
-                    };
-
-                    let _ = context.statistics.tcp_query_count.fetch_add(1, Ordering::Release);
-
-                    // When DNS packets are sent over TCP, they're prefixed with a two byte
-                    // length. We don't really need to know the length in advance, so we
-                    // just move past it and continue reading as usual
-                    ignore_or_report!(read_packet_length(&mut stream), "Failed to read query packet length");
-
-                    let request = {
-                        let mut stream_buffer = StreamPacketBuffer::new(&mut stream);
-                        return_or_report!(DnsPacket::from_buffer(&mut stream_buffer), "Failed to read query packet")
+                    let stream = match next_request(&receiver) {
+                        Some(x) => x,
+                        None => {
+                            debug!("TCP request queue is closed");
+                            break;
+                        }
                     };
 
-                    let mut res_buffer = VectorPacketBuffer::new();
-
-                    let mut packet = execute_query(Arc::clone(&context), &request);
-                    ignore_or_report!(packet.write(&mut res_buffer, 0xFFFF), "Failed to write packet to buffer");
-
-                    // As is the case for incoming queries, we need to send a 2 byte length
-                    // value before handing of the actual packet.
-                    let len = res_buffer.pos();
-                    ignore_or_report!(write_packet_length(&mut stream, len), "Failed to write packet size");
-
-                    // Now we can go ahead and write the actual packet
-                    let data = return_or_report!(res_buffer.get_range(0, len), "Failed to get packet data");
-
-                    ignore_or_report!(stream.write(data), "Failed to write response packet");
-
-                    ignore_or_report!(stream.shutdown(Shutdown::Both), "Failed to shutdown socket");
+                    handle_tcp_request(&context, stream);
                 }
             })?;
         }
@@ -376,12 +336,22 @@ impl DnsServer for DnsTcpServer {
                         }
                     };
 
-                    // Hand it off to a worker thread
-                    let thread_no = random::<usize>() % self.thread_count;
-                    match self.senders[thread_no].send(stream) {
+                    if self.context.stopped.load(Ordering::Acquire) {
+                        debug!("TCP service loop has finished");
+                        break;
+                    }
+
+                    // Hand it off to a worker thread, or close it if all of them are busy
+                    match sender.try_send(stream) {
                         Ok(_) => {}
-                        Err(e) => {
-                            warn!("Failed to send TCP request for processing on thread {}: {}", thread_no, e);
+                        Err(TrySendError::Full(stream)) => {
+                            let _ = self.context.statistics.dropped_query_count.fetch_add(1, Ordering::Release);
+                            debug!("TCP request queue is full, closing connection");
+                            let _ = stream.shutdown(Shutdown::Both);
+                        }
+                        Err(TrySendError::Disconnected(_)) => {
+                            warn!("TCP workers have stopped");
+                            break;
                         }
                     }
                 }
@@ -391,6 +361,68 @@ impl DnsServer for DnsTcpServer {
     }
 }
 
+/// Answers one UDP query, errors end only this query and not the worker thread
+fn handle_udp_request(context: &Arc<ServerContext>, socket: &UdpSocket, src: SocketAddr, request: DnsPacket) {
+    let mut size_limit = 512 - 32; // Minus 32 bytes for the packet header
+
+    // Check for EDNS
+    if request.resources.len() == 1 {
+        if let DnsRecord::OPT { packet_len, .. } = request.resources[0] {
+            size_limit = packet_len as usize;
+        }
+    }
+
+    // Create a response buffer, and ask the context for an appropriate resolver
+    let mut res_buffer = VectorPacketBuffer::new();
+
+    let mut packet = execute_query(Arc::clone(context), &request);
+    let _ = packet.write(&mut res_buffer, size_limit);
+
+    // Fire off the response
+    let len = res_buffer.pos();
+    let data = return_or_report!(res_buffer.get_range(0, len), "Failed to get buffer data");
+    ignore_or_report!(socket.send_to(data, src), "Failed to send response packet");
+}
+
+/// Answers one query on TCP connection, errors end only this connection and not the worker thread
+fn handle_tcp_request(context: &Arc<ServerContext>, mut stream: TcpStream) {
+    let _ = context.statistics.tcp_query_count.fetch_add(1, Ordering::Release);
+    let _ = stream.set_read_timeout(Some(TCP_TIMEOUT));
+    let _ = stream.set_write_timeout(Some(TCP_TIMEOUT));
+
+    // When DNS packets are sent over TCP, they're prefixed with a two byte
+    // length. We don't really need to know the length in advance, so we
+    // just move past it and continue reading as usual
+    ignore_or_report!(read_packet_length(&mut stream), "Failed to read query packet length");
+
+    let request = {
+        let mut stream_buffer = StreamPacketBuffer::new(&mut stream);
+        return_or_report!(DnsPacket::from_buffer(&mut stream_buffer), "Failed to read query packet")
+    };
+
+    let mut res_buffer = VectorPacketBuffer::new();
+
+    let mut packet = execute_query(Arc::clone(context), &request);
+    ignore_or_report!(packet.write(&mut res_buffer, 0xFFFF), "Failed to write packet to buffer");
+
+    // As is the case for incoming queries, we need to send a 2 byte length
+    // value before handing of the actual packet.
+    let len = res_buffer.pos();
+    ignore_or_report!(write_packet_length(&mut stream, len), "Failed to write packet size");
+
+    // Now we can go ahead and write the actual packet
+    let data = return_or_report!(res_buffer.get_range(0, len), "Failed to get packet data");
+
+    ignore_or_report!(stream.write(data), "Failed to write response packet");
+
+    ignore_or_report!(stream.shutdown(Shutdown::Both), "Failed to shutdown socket");
+}
+
+/// Takes next request from the queue shared by worker threads, returns None when the queue is closed
+fn next_request<T>(receiver: &Mutex<Receiver<T>>) -> Option<T> {
+    receiver.lock().ok()?.recv().ok()
+}
+
 #[cfg(test)]
 mod tests {
 
@@ -455,7 +487,7 @@ mod tests {
 
         match Arc::get_mut(&mut context) {
             Some(mut ctx) => {
-                ctx.resolve_strategy = ResolveStrategy::Forward { upstreams: vec![String::from("127.0.0.1:53")] };
+                *ctx.resolve_strategy.get_mut().unwrap() = ResolveStrategy::Forward { upstreams: vec![String::from("127.0.0.1:53")] };
             }
             None => panic!()
         }
@@ -545,7 +577,7 @@ mod tests {
 
         match Arc::get_mut(&mut context2) {
             Some(mut ctx) => {
-                ctx.resolve_strategy = ResolveStrategy::Forward { upstreams: vec![String::from("127.0.0.1:53")] };
+                *ctx.resolve_strategy.get_mut().unwrap() = ResolveStrategy::Forward { upstreams: vec![String::from("127.0.0.1:53")] };
             }
             None => panic!()
         }
@@ -0,0 +1,163 @@
+use std::sync::atomic::Ordering;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use std::{env, thread};
+
+#[allow(unused_imports)]
+use log::{debug, error, info, trace, warn, LevelFilter};
+
+use crate::blockchain::filter::BlockchainFilter;
+use crate::blockchain::reverse::ReverseFilter;
+use crate::dns::context::{ResolveStrategy, ServerContext};
+use crate::dns::filter::DnsFilter;
+use crate::dns::hosts::HostsFilter;
+#[cfg(feature = "doq")]
+use crate::dns::quic::DnsQuicServer;
+use crate::dns::server::{DnsServer, DnsTcpServer, DnsUdpServer};
+use crate::event::Event;
+use crate::eventbus::{post, register};
+use crate::settings::Dns;
+use crate::{ChainRef, Context, Settings};
+
+/// How often we post statistics of DNS server
+const DNS_STATS_PERIOD: Duration = Duration::from_secs(10);
+
+/// Starts UDP and TCP DNS-servers
+pub fn start_dns_server(context: &Arc<Mutex<Context>>, settings: &Settings) -> bool {
+    let server_context = create_server_context(Arc::clone(context), settings);
+
+    let mut result = true;
+    if server_context.enable_udp {
+        let udp_server = DnsUdpServer::new(Arc::clone(&server_context), settings.dns.threads);
+        if let Err(e) = udp_server.run_server() {
+            error!("Failed to bind UDP listener on {}: {:?}", &server_context.dns_listen, e);
+            result = false;
+        }
+    }
+
+    if server_context.enable_tcp {
+        let tcp_server = DnsTcpServer::new(Arc::clone(&server_context), settings.dns.threads);
+        if let Err(e) = tcp_server.run_server() {
+            error!("Failed to bind TCP listener on {}: {:?}", &server_context.dns_listen, e);
+            result = false;
+        }
+    }
+
+    if !settings.dns.doq_listen.is_empty() && !start_doq_server(&server_context, settings) {
+        result = false;
+    }
+    start_dns_stats(server_context);
+    result
+}
+
+/// Posts rate of queries and cache hits periodically, for `alfis status`
+fn start_dns_stats(server_context: Arc<ServerContext>) {
+    let _ = thread::Builder::new().name(String::from("dns-stats")).spawn(move || {
+        let mut last = (Instant::now(), 0, 0, 0);
+        while !server_context.stopped.load(Ordering::Acquire) {
+            thread::sleep(DNS_STATS_PERIOD);
+            let queries = server_context.statistics.get_query_count();
+            let hits = server_context.cache.get_hit_count();
+            let misses = server_context.cache.get_miss_count();
+            let (time, last_queries, last_hits, last_misses) = last;
+            let lookups = (hits - last_hits) + (misses - last_misses);
+            let cache_hit_rate = if lookups > 0 { (hits - last_hits) as f64 / lookups as f64 } else { 0.0 };
+            let queries_per_sec = (queries - last_queries) as f64 / time.elapsed().as_secs_f64();
+            post(Event::DnsStats { queries_per_sec, cache_hit_rate });
+            last = (Instant::now(), queries, hits, misses);
+        }
+    });
+}
+
+/// Starts experimental DNS-over-QUIC server
+#[cfg(feature = "doq")]
+fn start_doq_server(server_context: &Arc<ServerContext>, settings: &Settings) -> bool {
+    let dns = &settings.dns;
+    let doq_server = DnsQuicServer::new(Arc::clone(server_context), dns.doq_listen.clone(), dns.tls_cert.clone(), dns.tls_key.clone(), dns.threads);
+    if let Err(e) = doq_server.run_server() {
+        error!("Failed to start DoQ listener on {}: {:?}", &dns.doq_listen, e);
+        return false;
+    }
+    info!("Started DoQ listener on {}", &dns.doq_listen);
+    true
+}
+
+#[cfg(not(feature = "doq"))]
+fn start_doq_server(_server_context: &Arc<ServerContext>, _settings: &Settings) -> bool {
+    warn!("This build doesn't support DNS-over-QUIC, rebuild with 'doq' feature");
+    true
+}
+
+/// Creates DNS-context with all needed settings
+fn create_server_context(context: Arc<Mutex<Context>>, settings: &Settings) -> Arc<ServerContext> {
+    let mut server_context = ServerContext::new(settings.dns.listen.clone(), settings.dns.bootstraps.clone());
+    server_context.allow_recursive = true;
+    apply_dns_settings(&server_context, &context, &settings.dns);
+    match server_context.initialize() {
+        Ok(_) => {}
+        Err(e) => { panic!("DNS server failed to initialize: {:?}", e); }
+    }
+
+    let server_context = Arc::new(server_context);
+    let server_context_copy = Arc::clone(&server_context);
+    register(move |_uuid, e| {
+        match e {
+            Event::SettingsReloaded => {
+                // We can't lock the context in listener, it may be locked by the code that posted this event
+                let server_context = Arc::clone(&server_context_copy);
+                let context = Arc::clone(&context);
+                thread::spawn(move || {
+                    let dns = context.lock().unwrap().settings.dns.clone();
+                    apply_dns_settings(&server_context, &context, &dns);
+                    info!("DNS settings are reloaded");
+                });
+            }
+            Event::ActionSetRoute { zone, forwarders } => {
+                info!("Resolving zone '{}' with {:?} until reload", zone, forwarders);
+                server_context_copy.set_route(&zone, forwarders.as_deref().map(ResolveStrategy::from_upstreams));
+            }
+            Event::ActionQuit => {
+                server_context_copy.stopped.store(true, Ordering::Release);
+                return false;
+            }
+            _ => {}
+        }
+        true
+    });
+    server_context
+}
+
+/// Sets upstreams, routes and filters, that can be changed without restart
+fn apply_dns_settings(server_context: &ServerContext, context: &Arc<Mutex<Context>>, dns: &Dns) {
+    *server_context.resolve_strategy.write().unwrap() = ResolveStrategy::from_upstreams(&dns.forwarders);
+    server_context.clear_routes();
+    for route in &dns.routes {
+        debug!("Resolving zone '{}' with {:?}", &route.zone, &route.forwarders);
+        server_context.set_route(&route.zone, Some(ResolveStrategy::from_upstreams(&route.forwarders)));
+    }
+    // Add host filters
+    let mut filters: Vec<Box<dyn DnsFilter + Sync + Send>> = Vec::new();
+    for host in &dns.hosts {
+        if host == "system" {
+            if cfg!(target_os = "windows") {
+                if let Ok(root) = env::var("SYSTEMROOT") {
+                    let filename = format!("{}{}", &root, "\\System32\\drivers\\etc\\hosts");
+                    debug!("Loading hosts from '{}'", &filename);
+                    filters.push(Box::new(HostsFilter::new(&filename)));
+                }
+            } else {
+                let filename = "/etc/hosts";
+                debug!("Loading hosts from '{}'", filename);
+                filters.push(Box::new(HostsFilter::new(filename)));
+            }
+        } else {
+            debug!("Loading hosts from '{}'", &host);
+            filters.push(Box::new(HostsFilter::new(host)));
+        }
+    }
+    if dns.reverse {
+        filters.push(Box::new(ReverseFilter::new(ChainRef::new(Arc::clone(context)))));
+    }
+    filters.push(Box::new(BlockchainFilter::new(ChainRef::new(Arc::clone(context)))));
+    *server_context.filters.write().unwrap() = filters;
+}
@@ -0,0 +1,47 @@
+#[derive(Clone, PartialEq, Debug)]
+pub enum Event {
+    MinerStarted,
+    MinerStopped { success: bool, full: bool },
+    MinerStats { thread: u32, speed: u64, max_diff: u32, target_diff: u32 },
+    /// Total speed of all mining threads and estimated time to find a block in seconds
+    MinerProgress { speed: u64, target_diff: u32, eta: u64 },
+    KeyGeneratorStarted,
+    KeyGeneratorStopped,
+    KeyCreated { path: String, public: String, hash: String },
+    KeyLoaded { path: String, public: String, hash: String },
+    KeySaved { path: String, public: String, hash: String },
+    /// New block was added to our chain, mined by us or received from network
+    NewBlock { index: u64, hash: String },
+    /// Domain transaction got enough signing blocks after it
+    DomainConfirmed { index: u64, identity: String },
+    /// Domain of our key or of a key from `watch_keys` expires soon
+    DomainExpiring { identity: String, owner: String, expire: i64 },
+    /// Domain of our key or of a key from `watch_keys` was changed in this block by other key
+    DomainConflict { index: u64, identity: String, owner: String, key: String },
+    BlockchainChanged { index: u64 },
+    ActionStopMining,
+    ActionPauseMining,
+    ActionResumeMining,
+    /// Mine the next block from the queue, miner waits for it in regtest mode
+    ActionGenerateBlock,
+    /// Connect to this node, it is given by user as "host:port"
+    ActionAddPeer { addr: String },
+    /// Resolve the zone by these forwarders, or remove its route if they are None
+    ActionSetRoute { zone: String, forwarders: Option<Vec<String>> },
+    /// Bans in DB were changed by user, network has to load them again
+    BansChanged,
+    /// Settings in context were loaded from config file again
+    SettingsReloaded,
+    MinerPaused { paused: bool },
+    ActionQuit,
+    NetworkStatus { blocks: u64, domains: i64, keys: i64, nodes: usize },
+    PeerConnected { addr: String, inbound: bool },
+    PeerDisconnected { addr: String },
+    SyncProgress { have: u64, height: u64 },
+    SyncFinished,
+    /// Queries per second that DNS server got in the last period, and the share of cache lookups that were hits
+    DnsStats { queries_per_sec: f64, cache_hit_rate: f64 },
+    /// Our clock differs from clocks of peers by this many seconds, positive if it is ahead
+    ClockSkew { offset: i64 },
+    Error { text: String }
+}
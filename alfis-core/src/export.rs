@@ -0,0 +1,229 @@
+//! Exports names resolvable by ALFIS to hosts file, dnsmasq or unbound config formats.
+//! Useful when ALFIS can't be used as the system resolver.
+//! Blockchain has only hashes of names, so we export only names that we know: of our keys and from `export.domains`.
+
+use std::collections::BTreeSet;
+use std::fs;
+use std::net::IpAddr;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+#[allow(unused_imports)]
+use log::{debug, error, info, trace, warn};
+
+use crate::blockchain::transaction::DomainData;
+use crate::dns::protocol::DnsRecord;
+use crate::event::Event;
+use crate::eventbus::register;
+use crate::{get_domain_zone, Chain, Context};
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ExportFormat {
+    Hosts,
+    Dnsmasq,
+    Unbound
+}
+
+impl FromStr for ExportFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "hosts" => Ok(ExportFormat::Hosts),
+            "dnsmasq" => Ok(ExportFormat::Dnsmasq),
+            "unbound" => Ok(ExportFormat::Unbound),
+            _ => Err(format!("Unknown export format '{}', use one of: hosts, dnsmasq, unbound", s))
+        }
+    }
+}
+
+/// One exported name with its address and TTL
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub struct ExportRecord {
+    pub name: String,
+    pub addr: IpAddr,
+    pub ttl: u32
+}
+
+/// Collects names of domains owned by loaded keys and names from `export.domains` setting
+pub fn collect_names(context: &Context) -> Vec<String> {
+    let mut names = BTreeSet::new();
+    for keystore in context.get_keystores() {
+        for (_identity, (domain, _timestamp, _data)) in context.chain.get_my_domains(Some(keystore)) {
+            names.insert(domain);
+        }
+    }
+    for domain in &context.settings.export.domains {
+        names.insert(domain.to_lowercase());
+    }
+    names.into_iter().collect()
+}
+
+/// Gets A and AAAA records of given domains from blockchain, filtering them by zone if it is not empty
+pub fn collect_records(chain: &Chain, names: &[String], zone: &str) -> Vec<ExportRecord> {
+    let mut result = Vec::new();
+    for name in names {
+        if !zone.is_empty() && get_domain_zone(name) != zone {
+            continue;
+        }
+        let data = match chain.get_domain_info(name) {
+            None => continue,
+            Some(data) => data
+        };
+        let data: DomainData = match serde_json::from_str(&data) {
+            Ok(data) => data,
+            Err(_) => continue
+        };
+        result.append(&mut records_from_data(name, &data));
+    }
+    result.sort();
+    result.dedup();
+    result
+}
+
+/// Converts A and AAAA records of one domain to a list of fully qualified names with addresses
+pub fn records_from_data(name: &str, data: &DomainData) -> Vec<ExportRecord> {
+    let mut result = Vec::new();
+    for record in &data.records {
+        let (domain, addr) = match record {
+            DnsRecord::A { domain, addr, .. } => (domain, IpAddr::from(*addr)),
+            DnsRecord::AAAA { domain, addr, .. } => (domain, IpAddr::from(*addr)),
+            _ => continue
+        };
+        // Wildcards can't be expressed in hosts files, so we skip them everywhere
+        let full_name = match domain.as_str() {
+            "*" => continue,
+            "@" | "" => name.to_owned(),
+            d if d == name => name.to_owned(),
+            d => format!("{}.{}", d, name)
+        };
+        result.push(ExportRecord { name: full_name, addr, ttl: record.get_ttl() });
+    }
+    result
+}
+
+/// Formats records in given format
+pub fn format_records(records: &[ExportRecord], format: ExportFormat) -> String {
+    let mut result = String::from("# Generated by ALFIS, do not edit manually\n");
+    match format {
+        ExportFormat::Hosts => {
+            for record in records {
+                result.push_str(&format!("{}\t{}\n", record.addr, record.name));
+            }
+        }
+        ExportFormat::Dnsmasq => {
+            for record in records {
+                result.push_str(&format!("host-record={},{},{}\n", record.name, record.addr, record.ttl));
+            }
+        }
+        ExportFormat::Unbound => {
+            let zones: BTreeSet<String> = records.iter().map(|r| get_domain_zone(&r.name)).collect();
+            for zone in zones {
+                result.push_str(&format!("local-zone: \"{}.\" transparent\n", zone));
+            }
+            for record in records {
+                let class = match record.addr {
+                    IpAddr::V4(_) => "A",
+                    IpAddr::V6(_) => "AAAA"
+                };
+                result.push_str(&format!("local-data: \"{}. {} IN {} {}\"\n", record.name, record.ttl, class, record.addr));
+            }
+        }
+    }
+    result
+}
+
+/// Makes full export from the context in given format
+pub fn export(context: &Context, format: ExportFormat, zone: &str) -> String {
+    let names = collect_names(context);
+    let records = collect_records(&context.chain, &names, zone);
+    format_records(&records, format)
+}
+
+/// Writes export to the file from settings
+pub fn export_to_file(context: &Context) -> bool {
+    let settings = &context.settings.export;
+    let format = match ExportFormat::from_str(&settings.format) {
+        Ok(format) => format,
+        Err(e) => {
+            error!("{}", e);
+            return false;
+        }
+    };
+    let text = export(context, format, &settings.zone);
+    match fs::write(&settings.file, text) {
+        Ok(_) => {
+            debug!("Exported names to {}", &settings.file);
+            true
+        }
+        Err(e) => {
+            error!("Error writing export to {}: {}", &settings.file, e);
+            false
+        }
+    }
+}
+
+/// Writes export file and regenerates it on every blockchain change
+pub fn start_exporter(context: Arc<Mutex<Context>>) {
+    if context.lock().unwrap().settings.export.file.is_empty() {
+        return;
+    }
+    export_to_file(&context.lock().unwrap());
+    let busy = Arc::new(AtomicBool::new(false));
+    register(move |_uuid, e| {
+        if matches!(e, Event::BlockchainChanged { .. } | Event::SyncFinished | Event::KeyLoaded { .. }) {
+            // Events are posted while the context can be locked, so we do the work in another thread
+            if !busy.swap(true, Ordering::SeqCst) {
+                let context = Arc::clone(&context);
+                let busy = Arc::clone(&busy);
+                thread::spawn(move || {
+                    if let Ok(context) = context.lock() {
+                        export_to_file(&context);
+                    }
+                    busy.store(false, Ordering::SeqCst);
+                });
+            }
+        }
+        !matches!(e, Event::ActionQuit)
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::blockchain::transaction::DomainData;
+    use crate::dns::protocol::{DnsRecord, TransientTtl};
+    use crate::export::{format_records, records_from_data, ExportFormat};
+    use crate::Bytes;
+
+    fn test_data() -> DomainData {
+        let records = vec![
+            DnsRecord::A { domain: String::from("@"), addr: "10.0.0.1".parse().unwrap(), ttl: TransientTtl(300) },
+            DnsRecord::AAAA { domain: String::from("www"), addr: "200::1".parse().unwrap(), ttl: TransientTtl(600) },
+            DnsRecord::A { domain: String::from("*"), addr: "10.0.0.2".parse().unwrap(), ttl: TransientTtl(300) },
+            DnsRecord::TXT { domain: String::from("@"), data: String::from("text"), ttl: TransientTtl(300) },
+        ];
+        DomainData::new(Bytes::default(), String::from("ygg"), String::new(), records, Vec::new())
+    }
+
+    #[test]
+    fn test_records_from_data() {
+        let records = records_from_data("test.ygg", &test_data());
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[0].name, "test.ygg");
+        assert_eq!(records[1].name, "www.test.ygg");
+    }
+
+    #[test]
+    fn test_formats() {
+        let records = records_from_data("test.ygg", &test_data());
+        let hosts = format_records(&records, ExportFormat::Hosts);
+        assert!(hosts.contains("10.0.0.1\ttest.ygg\n"));
+        let dnsmasq = format_records(&records, ExportFormat::Dnsmasq);
+        assert!(dnsmasq.contains("host-record=www.test.ygg,200::1,600\n"));
+        let unbound = format_records(&records, ExportFormat::Unbound);
+        assert!(unbound.contains("local-zone: \"ygg.\" transparent\n"));
+        assert!(unbound.contains("local-data: \"test.ygg. 300 IN A 10.0.0.1\"\n"));
+    }
+}
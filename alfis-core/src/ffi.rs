@@ -0,0 +1,204 @@
+//! C API to resolve ALFIS names, for NSS modules, proxies and programs in other languages.
+//! The library is built from `alfis-ffi` crate by `cargo build -p alfis-ffi --release`, functions are declared in `include/alfis.h`.
+//! Names are resolved by DNS server of a local node, or directly from the database of a node after `alfis_open_db()`.
+//! Panics must not unwind into C code, so every function catches them and returns `ALFIS_ERROR_INTERNAL`.
+
+use std::ffi::CStr;
+use std::net::{SocketAddr, UdpSocket};
+use std::os::raw::{c_char, c_int};
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::Duration;
+
+use lazy_static::lazy_static;
+
+use crate::blockchain::filter::BlockchainFilter;
+use crate::dns::buffer::BytePacketBuffer;
+use crate::dns::filter::DnsFilter;
+use crate::dns::protocol::{DnsPacket, DnsQuestion, QueryType};
+use crate::{Chain, ChainRef, Context, Settings};
+
+/// Wrong arguments, like NULL pointers or bad address
+pub const ALFIS_ERROR_ARGS: c_int = -1;
+/// Records don't fit in the buffer
+pub const ALFIS_ERROR_BUFFER: c_int = -2;
+/// Node didn't answer or database can't be opened
+pub const ALFIS_ERROR_RESOLVE: c_int = -3;
+/// Unexpected error inside the library, like a damaged database
+pub const ALFIS_ERROR_INTERNAL: c_int = -4;
+
+const DEFAULT_NODE: &str = "127.0.0.1:53";
+const NODE_TIMEOUT: Duration = Duration::from_secs(3);
+
+/// Where we take names from, it is cloned to not hold the lock while resolving
+#[derive(Clone)]
+enum Source {
+    Node(SocketAddr),
+    Database(Arc<BlockchainFilter>)
+}
+
+lazy_static! {
+    static ref SOURCE: Mutex<Source> = Mutex::new(Source::Node(DEFAULT_NODE.parse().unwrap()));
+}
+
+/// Resolves names by DNS server of the node at this address, like "127.0.0.1:53", it is the default.
+///
+/// # Safety
+/// `addr` must be a valid NUL-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn alfis_use_node(addr: *const c_char) -> c_int {
+    guard(|| match read_str(addr).and_then(|addr| addr.parse().ok()) {
+        Some(addr) => {
+            *lock_source() = Source::Node(addr);
+            0
+        }
+        None => ALFIS_ERROR_ARGS
+    })
+}
+
+/// Resolves names from the database of a node, like "/var/lib/alfis/blockchain.db".
+/// The database is opened read-only, the node can work with it at the same time.
+///
+/// # Safety
+/// `path` must be a valid NUL-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn alfis_open_db(path: *const c_char) -> c_int {
+    guard(|| {
+        let path = match read_str(path) {
+            Some(path) => path,
+            None => return ALFIS_ERROR_ARGS
+        };
+        let settings = Settings { origin: String::new(), ..Settings::default() };
+        let chain = match Chain::open_read_only(&settings, &path) {
+            Ok(chain) => chain,
+            Err(_) => return ALFIS_ERROR_RESOLVE
+        };
+        let context = Context::new(env!("CARGO_PKG_VERSION").to_owned(), settings, Vec::new(), chain);
+        *lock_source() = Source::Database(Arc::new(BlockchainFilter::new(ChainRef::new(Arc::new(Mutex::new(context))))));
+        0
+    })
+}
+
+/// Resolves the name and writes data of found records of this type (1 for A, 28 for AAAA and so on) to `out_buf`,
+/// as text separated by '\n' and ended by NUL, like "10.0.0.1\n10.0.0.2".
+/// Returns count of records, 0 if nothing is found, or negative `ALFIS_ERROR_*` code.
+///
+/// # Safety
+/// `name` must be a valid NUL-terminated string, `out_buf` must point to `out_len` writable bytes.
+#[no_mangle]
+pub unsafe extern "C" fn alfis_resolve(name: *const c_char, qtype: u16, out_buf: *mut c_char, out_len: usize) -> c_int {
+    guard(|| {
+        let name = match read_str(name) {
+            Some(name) if !name.is_empty() => name.trim_end_matches('.').to_lowercase(),
+            _ => return ALFIS_ERROR_ARGS
+        };
+        if out_buf.is_null() || out_len == 0 {
+            return ALFIS_ERROR_ARGS;
+        }
+        let qtype = QueryType::from_num(qtype);
+        let source = lock_source().clone();
+        let packet = match source {
+            Source::Node(addr) => query_node(&addr, &name, qtype),
+            Source::Database(filter) => Some(filter.lookup(&name, qtype).unwrap_or_default())
+        };
+        let packet = match packet {
+            Some(packet) => packet,
+            None => return ALFIS_ERROR_RESOLVE
+        };
+        let records: Vec<String> = packet.answers.iter().filter(|record| record.get_querytype() == qtype).filter_map(|record| record.get_data()).collect();
+        let out = std::slice::from_raw_parts_mut(out_buf as *mut u8, out_len);
+        write_records(&records, out)
+    })
+}
+
+/// Runs the body of exported function and turns its panic to error code
+fn guard<F: FnOnce() -> c_int>(body: F) -> c_int {
+    panic::catch_unwind(AssertUnwindSafe(body)).unwrap_or(ALFIS_ERROR_INTERNAL)
+}
+
+/// Source stays usable even if some call panicked while holding the lock
+fn lock_source() -> MutexGuard<'static, Source> {
+    SOURCE.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+unsafe fn read_str(ptr: *const c_char) -> Option<String> {
+    if ptr.is_null() {
+        return None;
+    }
+    CStr::from_ptr(ptr).to_str().ok().map(|s| s.to_owned())
+}
+
+fn query_node(addr: &SocketAddr, name: &str, qtype: QueryType) -> Option<DnsPacket> {
+    let local = if addr.is_ipv4() { "0.0.0.0:0" } else { "[::]:0" };
+    let socket = UdpSocket::bind(local).ok()?;
+    socket.set_read_timeout(Some(NODE_TIMEOUT)).ok()?;
+
+    let mut packet = DnsPacket::new();
+    packet.header.id = rand::random();
+    packet.header.questions = 1;
+    packet.header.recursion_desired = true;
+    packet.questions.push(DnsQuestion::new(name.to_owned(), qtype));
+    let mut buffer = BytePacketBuffer::new();
+    packet.write(&mut buffer, 512).ok()?;
+    socket.send_to(&buffer.buf[0..buffer.pos], addr).ok()?;
+
+    let mut buffer = BytePacketBuffer::new();
+    socket.recv_from(&mut buffer.buf).ok()?;
+    let response = DnsPacket::from_buffer(&mut buffer).ok()?;
+    match response.header.id == packet.header.id {
+        true => Some(response),
+        false => None
+    }
+}
+
+/// Writes records as lines ended by NUL, returns count of records or `ALFIS_ERROR_BUFFER`
+fn write_records(records: &[String], out: &mut [u8]) -> c_int {
+    let text = records.join("\n");
+    if text.len() + 1 > out.len() {
+        return ALFIS_ERROR_BUFFER;
+    }
+    out[..text.len()].copy_from_slice(text.as_bytes());
+    out[text.len()] = 0;
+    records.len() as c_int
+}
+
+#[cfg(test)]
+mod tests {
+    use std::ffi::CString;
+    use std::ptr;
+
+    use crate::ffi::{alfis_open_db, alfis_resolve, alfis_use_node, guard, write_records, ALFIS_ERROR_ARGS, ALFIS_ERROR_BUFFER, ALFIS_ERROR_INTERNAL, ALFIS_ERROR_RESOLVE};
+
+    #[test]
+    fn test_write_records() {
+        let records = vec![String::from("10.0.0.1"), String::from("10.0.0.2")];
+        let mut out = [0xFFu8; 20];
+        assert_eq!(write_records(&records, &mut out), 2);
+        assert_eq!(&out[..18], b"10.0.0.1\n10.0.0.2\0");
+        assert_eq!(write_records(&records, &mut out[..17]), ALFIS_ERROR_BUFFER);
+        assert_eq!(write_records(&[], &mut out), 0);
+        assert_eq!(out[0], 0);
+    }
+
+    #[test]
+    fn test_arguments() {
+        let mut out = [0u8; 16];
+        let name = CString::new("example.ygg").unwrap();
+        let wrong = CString::new("not an address").unwrap();
+        unsafe {
+            assert_eq!(alfis_resolve(ptr::null(), 1, out.as_mut_ptr() as *mut _, out.len()), ALFIS_ERROR_ARGS);
+            assert_eq!(alfis_resolve(name.as_ptr(), 1, ptr::null_mut(), 0), ALFIS_ERROR_ARGS);
+            assert_eq!(alfis_use_node(wrong.as_ptr()), ALFIS_ERROR_ARGS);
+        }
+    }
+
+    #[test]
+    fn test_open_db() {
+        let missing = CString::new("./tests/missing.db").unwrap();
+        unsafe {
+            assert_eq!(alfis_open_db(missing.as_ptr()), ALFIS_ERROR_RESOLVE);
+        }
+        assert!(!std::path::Path::new("./tests/missing.db").exists());
+        assert_eq!(guard(|| panic!("test")), ALFIS_ERROR_INTERNAL);
+    }
+}
@@ -0,0 +1,187 @@
+//! Small local protocol for external miners.
+//! Every request and answer is one line of JSON. Miner asks for work with `{"method":"getwork"}`,
+//! gets block template with a position of nonce in it, and sends found nonce back with
+//! `{"method":"submit","id":1,"nonce":12345}`.
+
+use std::collections::HashMap;
+use std::io::{BufRead, BufReader, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+use chrono::Utc;
+#[allow(unused_imports)]
+use log::{debug, error, info, trace, warn};
+use serde::{Deserialize, Serialize};
+
+use crate::blockchain::hash_utils::{hash_difficulty, pow_hash};
+use crate::blockchain::types::BlockQuality;
+use crate::commons::BLOCK_FLAG_ARGON2;
+use crate::event::Event;
+use crate::eventbus::post;
+use crate::{to_hex, Block, Bytes, Context};
+
+/// Position of nonce (u64, little endian) in the block data
+pub const NONCE_OFFSET: usize = 28;
+/// How many given templates we remember
+const MAX_TEMPLATES: usize = 16;
+
+#[derive(Deserialize)]
+#[serde(tag = "method", rename_all = "lowercase")]
+enum Request {
+    GetWork,
+    Submit { id: u64, nonce: u64 }
+}
+
+#[derive(Serialize)]
+#[serde(untagged)]
+enum Answer {
+    Work { id: u64, data: String, nonce_offset: usize, target: u32, algorithm: &'static str },
+    Result { result: bool },
+    Error { error: String }
+}
+
+impl Answer {
+    fn error(text: &str) -> Self {
+        Answer::Error { error: text.to_owned() }
+    }
+}
+
+struct Templates {
+    next_id: u64,
+    blocks: HashMap<u64, Block>
+}
+
+/// Starts listening for external miners if it is enabled in settings
+pub fn start_getwork_server(context: Arc<Mutex<Context>>) {
+    let listen = context.lock().unwrap().settings.mining.getwork_listen.clone();
+    if listen.is_empty() {
+        return;
+    }
+    let listener = match TcpListener::bind(&listen) {
+        Ok(listener) => listener,
+        Err(e) => {
+            error!("Unable to start getwork server on {}: {}", &listen, e);
+            return;
+        }
+    };
+    info!("Getwork server listens on {}", &listen);
+    let templates = Arc::new(Mutex::new(Templates { next_id: 1, blocks: HashMap::new() }));
+    let _ = thread::Builder::new().name(String::from("getwork")).spawn(move || {
+        for stream in listener.incoming().flatten() {
+            let context = Arc::clone(&context);
+            let templates = Arc::clone(&templates);
+            let _ = thread::Builder::new().name(String::from("getwork-client")).spawn(move || {
+                handle_client(stream, &context, &templates);
+            });
+        }
+    });
+}
+
+fn handle_client(stream: TcpStream, context: &Arc<Mutex<Context>>, templates: &Mutex<Templates>) {
+    let mut writer = match stream.try_clone() {
+        Ok(writer) => writer,
+        Err(_) => return
+    };
+    for line in BufReader::new(stream).lines() {
+        let line = match line {
+            Ok(line) => line,
+            Err(_) => break
+        };
+        let answer = match serde_json::from_str::<Request>(&line) {
+            Ok(Request::GetWork) => get_work(context, templates),
+            Ok(Request::Submit { id, nonce }) => submit(context, templates, id, nonce),
+            Err(_) => Answer::error("wrong request")
+        };
+        let mut data = serde_json::to_string(&answer).unwrap();
+        data.push('\n');
+        if writer.write_all(data.as_bytes()).is_err() {
+            break;
+        }
+    }
+}
+
+/// Makes new template from the domain block that we mine now
+fn get_work(context: &Arc<Mutex<Context>>, templates: &Mutex<Templates>) -> Answer {
+    let context = context.lock().unwrap();
+    let mut block = match &context.miner_state.job {
+        Some(job) => job.block.clone(),
+        None => return Answer::error("no work")
+    };
+    if context.chain.is_waiting_signers() {
+        return Answer::error("no work");
+    }
+    block.index = context.chain.get_height() + 1;
+    block.prev_block_hash = context.chain.get_last_hash();
+    block.timestamp = Utc::now().timestamp();
+    block.version = context.chain.get_block_version(block.index);
+    block.random = rand::random();
+    block.nonce = 0;
+    block.hash = Bytes::default();
+    block.signature = Bytes::default();
+    block.difficulty = block.difficulty.max(context.chain.get_needed_difficulty(&block));
+    drop(context);
+
+    let data = to_hex(&block.as_bytes_compact());
+    let target = block.difficulty;
+    let algorithm = if block.version & BLOCK_FLAG_ARGON2 != 0 { "argon2id" } else { "blakeout" };
+    let mut templates = templates.lock().unwrap();
+    let id = templates.next_id;
+    templates.next_id += 1;
+    templates.blocks.insert(id, block);
+    if templates.blocks.len() > MAX_TEMPLATES {
+        let oldest = *templates.blocks.keys().min().unwrap();
+        templates.blocks.remove(&oldest);
+    }
+    Answer::Work { id, data, nonce_offset: NONCE_OFFSET, target, algorithm }
+}
+
+/// Checks found nonce and adds the block to our chain
+fn submit(context: &Arc<Mutex<Context>>, templates: &Mutex<Templates>, id: u64, nonce: u64) -> Answer {
+    let mut block = match templates.lock().unwrap().blocks.remove(&id) {
+        Some(block) => block,
+        None => return Answer::error("unknown work")
+    };
+    block.nonce = nonce;
+    let hash = pow_hash(block.version, &block.as_bytes_compact());
+    if hash_difficulty(hash.as_slice()) < block.difficulty {
+        return Answer::error("low difficulty");
+    }
+    block.hash = hash;
+
+    let mut context = context.lock().unwrap();
+    let signer = match &context.miner_state.job {
+        Some(job) if job.block.transaction == block.transaction => Arc::clone(&job.signer),
+        _ => return Answer::error("stale work")
+    };
+    block.signature = match signer.sign(&block.as_bytes_compact()) {
+        Some(signature) => signature,
+        None => return Answer::error("signer refused")
+    };
+    if context.chain.check_new_block(&block) != BlockQuality::Good {
+        warn!("Block from external miner is not good");
+        return Answer::error("stale work");
+    }
+    info!("External miner found good block!");
+    context.chain.add_block(block);
+    context.miner_state.job = None;
+    drop(context);
+    // Our own mining threads are not needed anymore
+    post(Event::ActionStopMining);
+    post(Event::MinerStopped { success: true, full: true });
+    Answer::Result { result: true }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::getwork::NONCE_OFFSET;
+    use crate::Block;
+
+    #[test]
+    fn test_nonce_offset() {
+        let mut block = Block::new(None, Default::default(), Default::default(), 24);
+        block.nonce = 0x0102030405060708;
+        let data = block.as_bytes_compact();
+        assert_eq!(&data[NONCE_OFFSET..NONCE_OFFSET + 8], &0x0102030405060708u64.to_le_bytes());
+    }
+}
@@ -0,0 +1,326 @@
+//! Import of names from other naming systems, to move them to ALFIS. It reads names exported by `namecoin-cli name_list`,
+//! or plain zone files, and converts their data to DNS records of ALFIS domains in the given zone.
+//! Only names of Namecoin wallet are taken, other names can't be claimed.
+//! Hosts of CNAME, NS and MX records in the old zone are moved to the new zone together with the names,
+//! and records that can't be published in ALFIS are skipped with the reason.
+
+use std::collections::BTreeMap;
+use std::str::FromStr;
+
+#[allow(unused_imports)]
+use log::{debug, error, info, trace, warn};
+use serde_json::Value;
+
+use crate::blockchain::transaction::ContactsData;
+use crate::commons::{check_domain, check_record, RecordError};
+use crate::dns::protocol::{DnsRecord, TransientTtl};
+
+/// TTL of imported records when there is no TTL in the source
+const DEFAULT_TTL: u32 = 3600;
+/// Zone of Namecoin domains
+const NAMECOIN_ZONE: &str = "bit";
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ImportFormat {
+    Namecoin,
+    Zone
+}
+
+impl FromStr for ImportFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "namecoin" => Ok(ImportFormat::Namecoin),
+            "zone" => Ok(ImportFormat::Zone),
+            _ => Err(format!("Unknown import format '{}', use one of: namecoin, zone", s))
+        }
+    }
+}
+
+/// Domain of ALFIS with records converted from other system
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ImportedName {
+    pub name: String,
+    pub records: Vec<DnsRecord>,
+    pub contacts: Vec<ContactsData>,
+    /// Records that can't be published in ALFIS, with the reason
+    pub skipped: Vec<(DnsRecord, RecordError)>
+}
+
+/// Reads names from text in given format and moves them to `zone`, names that are not valid in ALFIS are skipped.
+/// Records are checked like records from GUI, `yggdrasil_only` is for zones like ygg.
+pub fn import_names(text: &str, format: ImportFormat, zone: &str, yggdrasil_only: bool) -> Result<Vec<ImportedName>, String> {
+    let names = match format {
+        ImportFormat::Namecoin => parse_namecoin(text, zone)?,
+        ImportFormat::Zone => parse_zone(text, zone)
+    };
+    Ok(names.into_iter()
+        .filter(|name| {
+            let good = check_domain(&name.name, true);
+            if !good {
+                warn!("Name {} can't be used in ALFIS, skipping it", &name.name);
+            }
+            good
+        })
+        .map(|mut name| {
+            for record in std::mem::take(&mut name.records) {
+                match check_record(&record, yggdrasil_only) {
+                    Ok(_) => name.records.push(record),
+                    Err(e) => name.skipped.push((record, e))
+                }
+            }
+            name
+        })
+        .collect())
+}
+
+/// Parses JSON array from `name_list` of Namecoin, only domains (`d/` names) of our wallet are taken
+pub fn parse_namecoin(text: &str, zone: &str) -> Result<Vec<ImportedName>, String> {
+    let list: Vec<Value> = serde_json::from_str(text).map_err(|e| format!("Wrong Namecoin export: {}", e))?;
+    let mut result = Vec::new();
+    for item in list {
+        let label = match item["name"].as_str().and_then(|name| name.strip_prefix("d/")) {
+            Some(label) => label.to_lowercase(),
+            None => continue
+        };
+        if item["ismine"] == Value::Bool(false) || item["expired"] == Value::Bool(true) {
+            debug!("Name d/{} is not ours or expired, skipping it", &label);
+            continue;
+        }
+        // Value of Namecoin name is JSON in a string
+        let value: Value = match item["value"].as_str().map(|value| serde_json::from_str::<Value>(value)) {
+            Some(Ok(value)) => value,
+            _ => {
+                warn!("Name d/{} has wrong value, skipping it", &label);
+                continue;
+            }
+        };
+        let mut name = ImportedName { name: format!("{}.{}", label, zone), ..ImportedName::default() };
+        namecoin_records(&value, "@", &mut name.records);
+        if let Value::Object(map) = &value["map"] {
+            for (sub, value) in map {
+                let sub = match sub.as_str() {
+                    "" => "@",
+                    sub => sub
+                };
+                match value {
+                    // Old names have just IP in map
+                    Value::String(ip) => push_record(&mut name.records, "A", sub, ip),
+                    value => namecoin_records(value, sub, &mut name.records)
+                }
+            }
+        }
+        for record in name.records.iter_mut() {
+            map_host(record, NAMECOIN_ZONE, zone);
+        }
+        if let Some(email) = value["email"].as_str() {
+            name.contacts.push(ContactsData { name: String::from("email"), value: email.to_owned() });
+        }
+        result.push(name);
+    }
+    Ok(result)
+}
+
+/// Converts fields of Namecoin domain object to records for `domain` (label or "@")
+fn namecoin_records(value: &Value, domain: &str, records: &mut Vec<DnsRecord>) {
+    for (field, record_type) in [("ip", "A"), ("ip6", "AAAA"), ("alias", "CNAME"), ("ns", "NS"), ("txt", "TXT")] {
+        match &value[field] {
+            Value::String(data) => push_record(records, record_type, domain, data),
+            Value::Array(list) => {
+                for data in list.iter().filter_map(|data| data.as_str()) {
+                    push_record(records, record_type, domain, data);
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Parses zone file with records of one or more domains, the last label of names is replaced by `zone`.
+/// Supported records are A, AAAA, CNAME, NS, MX and TXT, others are skipped.
+pub fn parse_zone(text: &str, zone: &str) -> Vec<ImportedName> {
+    let mut names: BTreeMap<String, Vec<DnsRecord>> = BTreeMap::new();
+    let mut origin = String::new();
+    let mut ttl = DEFAULT_TTL;
+    let mut owner = String::new();
+    for line in text.lines() {
+        let line = line.split(';').next().unwrap_or_default();
+        if line.trim().is_empty() {
+            continue;
+        }
+        let mut parts: Vec<&str> = line.split_whitespace().collect();
+        match parts[0] {
+            "$ORIGIN" if parts.len() > 1 => {
+                origin = parts[1].trim_end_matches('.').to_lowercase();
+                continue;
+            }
+            "$TTL" if parts.len() > 1 => {
+                ttl = parts[1].parse().unwrap_or(DEFAULT_TTL);
+                continue;
+            }
+            _ => {}
+        }
+        // Lines that start with space have the same owner as previous record
+        if !line.starts_with(char::is_whitespace) {
+            owner = absolute_name(&parts.remove(0).to_lowercase(), &origin);
+        }
+        let mut record_ttl = ttl;
+        while !parts.is_empty() {
+            if let Ok(value) = parts[0].parse() {
+                record_ttl = value;
+            } else if !parts[0].eq_ignore_ascii_case("IN") {
+                break;
+            }
+            parts.remove(0);
+        }
+        if parts.len() < 2 {
+            continue;
+        }
+        let (domain, sub) = match split_owner(&owner, zone) {
+            Some(result) => result,
+            None => continue
+        };
+        let record_type = parts[0].to_uppercase();
+        let data = match record_type.as_str() {
+            "CNAME" | "NS" => absolute_name(parts[1], &origin),
+            "MX" if parts.len() > 2 => format!("{} {}", parts[1], absolute_name(parts[2], &origin)),
+            "TXT" => parts[1..].join(" ").trim_matches('"').to_owned(),
+            _ => parts[1].to_owned()
+        };
+        let records = names.entry(domain).or_default();
+        let count = records.len();
+        push_record(records, &record_type, &sub, &data);
+        if let Some(record) = records.get_mut(count) {
+            set_ttl(record, record_ttl);
+            // Owner is in the old zone, hosts in it are moved to the new zone with it
+            map_host(record, owner.rsplit('.').next().unwrap_or_default(), zone);
+        }
+    }
+    names.into_iter()
+        .filter(|(_name, records)| !records.is_empty())
+        .map(|(name, records)| ImportedName { name, records, ..ImportedName::default() })
+        .collect()
+}
+
+/// Makes name from zone file absolute, without the last dot
+fn absolute_name(name: &str, origin: &str) -> String {
+    if name == "@" {
+        origin.to_owned()
+    } else if let Some(name) = name.strip_suffix('.') {
+        name.to_owned()
+    } else if origin.is_empty() {
+        name.to_owned()
+    } else {
+        format!("{}.{}", name, origin)
+    }
+}
+
+/// Splits "www.example.bit" to ALFIS domain "example.<zone>" and its label "www"
+fn split_owner(owner: &str, zone: &str) -> Option<(String, String)> {
+    let labels: Vec<&str> = owner.split('.').collect();
+    if labels.len() < 2 {
+        return None;
+    }
+    let domain = format!("{}.{}", labels[labels.len() - 2], zone);
+    let sub = match labels.len() {
+        2 => String::from("@"),
+        n => labels[..n - 2].join(".")
+    };
+    Some((domain, sub))
+}
+
+/// Adds record of this type if its data is good
+fn push_record(records: &mut Vec<DnsRecord>, record_type: &str, domain: &str, data: &str) {
+    let domain = domain.to_owned();
+    let ttl = TransientTtl(DEFAULT_TTL);
+    let record = match record_type {
+        "A" => data.parse().ok().map(|addr| DnsRecord::A { domain, addr, ttl }),
+        "AAAA" => data.parse().ok().map(|addr| DnsRecord::AAAA { domain, addr, ttl }),
+        "CNAME" => Some(DnsRecord::CNAME { domain, host: data.trim_end_matches('.').to_owned(), ttl }),
+        "NS" => Some(DnsRecord::NS { domain, host: data.trim_end_matches('.').to_owned(), ttl }),
+        "TXT" => Some(DnsRecord::TXT { domain, data: data.to_owned(), ttl }),
+        "MX" => data.split_once(' ').and_then(|(priority, host)| {
+            let host = host.trim_end_matches('.').to_owned();
+            priority.parse().ok().map(|priority| DnsRecord::MX { domain, priority, host, ttl })
+        }),
+        _ => None
+    };
+    match record {
+        Some(record) => records.push(record),
+        None => debug!("Skipping {} record with data '{}'", record_type, data)
+    }
+}
+
+/// Moves host of CNAME, NS or MX record from `source` zone to `zone`, like "mail.example.bit" to "mail.example.ygg".
+/// Hosts in other zones, like "example.com", stay as they are.
+fn map_host(record: &mut DnsRecord, source: &str, zone: &str) {
+    if let DnsRecord::CNAME { host, .. } | DnsRecord::NS { host, .. } | DnsRecord::MX { host, .. } = record {
+        if let Some(name) = host.strip_suffix(source).and_then(|name| name.strip_suffix('.')) {
+            *host = format!("{}.{}", name, zone);
+        }
+    }
+}
+
+fn set_ttl(record: &mut DnsRecord, value: u32) {
+    match record {
+        DnsRecord::A { ttl, .. } | DnsRecord::AAAA { ttl, .. } | DnsRecord::CNAME { ttl, .. } | DnsRecord::NS { ttl, .. } |
+        DnsRecord::TXT { ttl, .. } | DnsRecord::MX { ttl, .. } => *ttl = TransientTtl(value),
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::dns::protocol::{DnsRecord, TransientTtl};
+    use crate::import::{import_names, ImportFormat};
+
+    #[test]
+    fn test_namecoin() {
+        let text = r#"[
+            {"name": "d/example", "value": "{\"ip\":\"10.0.0.1\",\"map\":{\"www\":{\"alias\":\"example.bit.\"}},\"email\":\"me@example.com\"}", "ismine": true},
+            {"name": "d/other", "value": "{\"ip\":\"10.0.0.2\"}", "ismine": false},
+            {"name": "id/someone", "value": "{}"}
+        ]"#;
+        let names = import_names(text, ImportFormat::Namecoin, "btn", false).unwrap();
+        assert_eq!(names.len(), 1);
+        assert_eq!(names[0].name, "example.btn");
+        assert_eq!(names[0].records, vec![
+            DnsRecord::A { domain: String::from("@"), addr: "10.0.0.1".parse().unwrap(), ttl: TransientTtl(3600) },
+            DnsRecord::CNAME { domain: String::from("www"), host: String::from("example.btn"), ttl: TransientTtl(3600) }
+        ]);
+        assert_eq!(names[0].contacts[0].value, "me@example.com");
+        assert!(import_names("not json", ImportFormat::Namecoin, "btn", false).is_err());
+
+        // A records are not allowed in Yggdrasil zones
+        let names = import_names(text, ImportFormat::Namecoin, "anon", true).unwrap();
+        assert_eq!(names[0].records.len(), 1);
+        assert_eq!(names[0].skipped.len(), 1);
+        assert_eq!(names[0].skipped[0].1.field, "type");
+    }
+
+    #[test]
+    fn test_zone() {
+        let text = "$ORIGIN example.bit.\n$TTL 600\n\
+            @       IN  A     10.0.0.1 ; main\n\
+            \t\tIN  MX    10 mail\n\
+            www 300 IN  CNAME @\n\
+            web     IN  CNAME example.com.\n\
+            old 999999999 IN TXT \"too long TTL\"\n\
+            mail.other.bit. A 10.0.0.3\n\
+            @       IN  SOA   ns1 admin 1 2 3 4 5\n";
+        let names = import_names(text, ImportFormat::Zone, "btn", false).unwrap();
+        assert_eq!(names.len(), 2);
+        assert_eq!(names[0].name, "example.btn");
+        assert_eq!(names[0].records, vec![
+            DnsRecord::A { domain: String::from("@"), addr: "10.0.0.1".parse().unwrap(), ttl: TransientTtl(600) },
+            DnsRecord::MX { domain: String::from("@"), priority: 10, host: String::from("mail.example.btn"), ttl: TransientTtl(600) },
+            DnsRecord::CNAME { domain: String::from("www"), host: String::from("example.btn"), ttl: TransientTtl(300) },
+            DnsRecord::CNAME { domain: String::from("web"), host: String::from("example.com"), ttl: TransientTtl(600) }
+        ]);
+        assert_eq!(names[0].skipped.len(), 1);
+        assert_eq!(names[0].skipped[0].1.field, "ttl");
+        assert_eq!(names[1].name, "other.btn");
+        assert_eq!(names[1].records.len(), 1);
+    }
+}
@@ -0,0 +1,53 @@
+//! Storage of passwords for encrypted key files in the keychain of OS
+//! (Secret Service on Linux and BSDs, Keychain on macOS, Credential Manager on Windows).
+
+#[allow(unused_imports)]
+use log::{debug, error, info, trace, warn};
+
+/// Service name for our entries in keychain
+#[cfg(feature = "keychain")]
+const SERVICE: &str = "alfis";
+
+/// Gets saved password for key file, if there is any
+#[cfg(feature = "keychain")]
+pub fn get_password(key_file: &str) -> Option<String> {
+    match keyring::Entry::new(SERVICE, &entry_name(key_file)).get_password() {
+        Ok(password) => Some(password),
+        Err(e) => {
+            debug!("No password for {} in keychain: {}", key_file, e);
+            None
+        }
+    }
+}
+
+/// Saves password for key file, returns false if keychain is not available
+#[cfg(feature = "keychain")]
+pub fn save_password(key_file: &str, password: &str) -> bool {
+    match keyring::Entry::new(SERVICE, &entry_name(key_file)).set_password(password) {
+        Ok(_) => true,
+        Err(e) => {
+            warn!("Unable to save password for {} to keychain: {}", key_file, e);
+            false
+        }
+    }
+}
+
+/// We use full path of key file, so the same file from different working directories has the same entry
+#[cfg(feature = "keychain")]
+fn entry_name(key_file: &str) -> String {
+    match std::fs::canonicalize(key_file) {
+        Ok(path) => path.to_string_lossy().to_string(),
+        Err(_) => key_file.to_owned()
+    }
+}
+
+#[cfg(not(feature = "keychain"))]
+pub fn get_password(_key_file: &str) -> Option<String> {
+    None
+}
+
+#[cfg(not(feature = "keychain"))]
+pub fn save_password(_key_file: &str, _password: &str) -> bool {
+    warn!("This build doesn't support keychain, build with 'keychain' feature to enable it");
+    false
+}
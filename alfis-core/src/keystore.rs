@@ -0,0 +1,803 @@
+extern crate ed25519_dalek;
+extern crate rand;
+extern crate serde;
+extern crate serde_json;
+
+use std::fs::File;
+use std::io::Write;
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize};
+use std::sync::{atomic, Arc, Mutex};
+use std::time::Instant;
+use std::{fs, thread};
+
+use argon2::{Algorithm, Argon2, Params, Version};
+use bip39::{Language, Mnemonic};
+use blakeout::Blakeout;
+use ed25519_dalek::Keypair;
+#[allow(unused_imports)]
+use log::{debug, error, info, trace, warn};
+use rand_old::{CryptoRng, RngCore};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use self::ed25519_dalek::ed25519::signature::Signature;
+use self::ed25519_dalek::{PublicKey, SecretKey, Signer, Verifier};
+use crate::blockchain::hash_utils::*;
+use crate::bytes::Bytes;
+use crate::commons::KEYSTORE_DIFFICULTY;
+use crate::crypto::{Chacha, CryptoBox};
+use crate::event::Event;
+use crate::eventbus::{post, register};
+use crate::signer::Signer;
+use crate::{from_hex, setup_miner_thread, to_hex, Context};
+
+/// Memory in KiB for Argon2id that derives the key to encrypt key files
+const PASSWORD_ARGON2_MEMORY: u32 = 19456;
+const PASSWORD_ARGON2_ITERATIONS: u32 = 2;
+const PASSWORD_SALT_LEN: usize = 16;
+const PASSWORD_NONCE_LEN: usize = 12;
+/// Domain separation for derivation of encryption key from signing key
+const ENCRYPTION_KEY_CONTEXT: &[u8] = b"ALFIS-ENCRYPTION-KEY";
+/// Domain separation for fingerprints of public keys
+const FINGERPRINT_CONTEXT: &[u8] = b"ALFIS-FINGERPRINT";
+/// How many words are in fingerprint, every word is 11 bits
+const FINGERPRINT_WORDS: usize = 5;
+/// Domain separation for derivation of domain keys from master key
+const DOMAIN_KEY_CONTEXT: &[u8] = b"ALFIS-DOMAIN-KEY";
+
+#[derive(Debug)]
+pub struct Keystore {
+    keypair: Keypair,
+    hash: Mutex<Bytes>,
+    path: String,
+    name: String,
+    crypto_box: CryptoBox,
+    old: bool
+}
+
+impl Keystore {
+    pub fn new() -> Self {
+        let mut csprng = rand_old::thread_rng();
+        let keypair = ed25519_dalek::Keypair::generate(&mut csprng);
+        let crypto_box = CryptoBox::generate(&mut csprng);
+        Keystore { keypair, hash: Mutex::new(Bytes::default()), path: String::new(), name: String::new(), crypto_box, old: false }
+    }
+
+    pub fn from_random<R>(csprng: &mut R) -> Self where R: CryptoRng + RngCore {
+        let keypair = ed25519_dalek::Keypair::generate(csprng);
+        let crypto_box = CryptoBox::generate(csprng);
+        Keystore { keypair, hash: Mutex::new(Bytes::default()), path: String::new(), name: String::new(), crypto_box, old: false }
+    }
+
+    pub fn from_bytes(seed: &[u8]) -> Self {
+        let keypair = Keypair::from_bytes(seed).expect("Error creating keypair from bytes!");
+        let mut csprng = rand_old::thread_rng();
+        let crypto_box = CryptoBox::generate(&mut csprng);
+        Keystore { keypair, hash: Mutex::new(Bytes::default()), path: String::new(), name: String::new(), crypto_box, old: false }
+    }
+
+    /// Makes keys from secret signing key, encryption key is derived from it,
+    /// so that both can be restored from mnemonic words
+    pub fn from_random_bytes(key: &[u8]) -> Self {
+        let secret = SecretKey::from_bytes(key).unwrap();
+        let public = PublicKey::from(&secret);
+        let keypair = Keypair { secret, public };
+        let crypto_box = CryptoBox::new(&derive_encryption_seed(key));
+        Keystore { keypair, hash: Mutex::new(Bytes::default()), path: String::new(), name: String::new(), crypto_box, old: false }
+    }
+
+    /// Loads keys from file, if the file is encrypted the `password` is needed to decrypt them
+    pub fn from_file(filename: &str, password: &str) -> Option<Self> {
+        let path = Path::new(filename);
+        match fs::read(&path) {
+            Ok(key) => {
+                match toml::from_slice::<Keys>(key.as_slice()) {
+                    Ok(keys) => {
+                        let (signing_secret, encryption_secret) = match keys.get_secrets(password) {
+                            Some(secrets) => secrets,
+                            None => {
+                                if password.is_empty() {
+                                    error!("Key file {} is encrypted, you need a password to load it", filename);
+                                } else {
+                                    error!("Wrong password for key file {}", filename);
+                                }
+                                return None;
+                            }
+                        };
+                        let secret = SecretKey::from_bytes(&signing_secret).unwrap();
+                        let public = PublicKey::from_bytes(&from_hex(&keys.signing.public).unwrap()).unwrap();
+                        let keypair = Keypair { secret, public };
+                        let crypto_box = CryptoBox::from_strings(&to_hex(&encryption_secret), &keys.encryption.public);
+                        let keystore = Keystore { keypair, hash: Mutex::new(Bytes::default()), path: String::from(filename), name: keys.name.clone(), crypto_box, old: false };
+                        let bytes = Bytes::from_bytes(&keystore.keypair.public.to_bytes());
+                        if check_public_key_strength(&bytes, KEYSTORE_DIFFICULTY) {
+                            Some(keystore)
+                        } else {
+                            None
+                        }
+                    }
+                    Err(e) => {
+                        error!("Error loading keystore from {}: {}", filename, e);
+                        None
+                    }
+                }
+            }
+            Err(_) => {
+                None
+            }
+        }
+    }
+
+    /// Restores keys from 24 mnemonic words, returns None if the words are wrong or the key is not strong enough
+    pub fn from_mnemonic(words: &str) -> Option<Self> {
+        let secret = secret_from_mnemonic(words)?;
+        let keystore = Keystore::from_random_bytes(&secret);
+        match check_public_key_strength(&keystore.get_public(), KEYSTORE_DIFFICULTY) {
+            true => Some(keystore),
+            false => None
+        }
+    }
+
+    /// Returns 24 words to backup secret signing key
+    pub fn get_mnemonic(&self) -> String {
+        Mnemonic::from_entropy(&self.keypair.secret.to_bytes()).expect("Error making mnemonic").to_string()
+    }
+
+    /// Checks if encryption key can be restored from mnemonic words, old keys have random encryption keys
+    pub fn is_mnemonic_complete(&self) -> bool {
+        self.crypto_box.secret.to_bytes() == derive_encryption_seed(&self.keypair.secret.to_bytes())
+    }
+
+    /// Exports keys to portable JSON container, see `KeyContainer`
+    pub fn export_json(&self, password: &str) -> String {
+        let mut secrets = self.keypair.secret.to_bytes().to_vec();
+        secrets.extend_from_slice(&self.crypto_box.secret.to_bytes());
+        let crypto = ContainerCrypto::encrypt(password, &secrets);
+        let container = KeyContainer {
+            version: CONTAINER_VERSION,
+            name: self.name.clone(),
+            public: to_hex(&self.keypair.public.to_bytes()),
+            encryption_public: to_hex(&self.crypto_box.public.to_bytes()),
+            crypto
+        };
+        serde_json::to_string_pretty(&container).unwrap()
+    }
+
+    /// Imports keys from JSON container, returns None if the container is wrong or the password doesn't fit
+    pub fn import_json(json: &str, password: &str) -> Option<Self> {
+        let container: KeyContainer = serde_json::from_str(json).ok()?;
+        if container.version != CONTAINER_VERSION || !container.crypto.is_supported() {
+            warn!("Unsupported key container version {}", container.version);
+            return None;
+        }
+        let secrets = container.crypto.decrypt(password)?;
+        if secrets.len() != 64 {
+            return None;
+        }
+        let secret = SecretKey::from_bytes(&secrets[..32]).ok()?;
+        let public = PublicKey::from(&secret);
+        if to_hex(public.as_bytes()) != container.public.to_uppercase() {
+            return None;
+        }
+        let crypto_box = CryptoBox::new(&secrets[32..]);
+        if to_hex(crypto_box.public.as_bytes()) != container.encryption_public.to_uppercase() {
+            return None;
+        }
+        let keypair = Keypair { secret, public };
+        Some(Keystore { keypair, hash: Mutex::new(Bytes::default()), path: String::new(), name: container.name, crypto_box, old: false })
+    }
+
+    /// Reads public signing key from key file, it is not encrypted even in encrypted files
+    pub fn read_public(filename: &str) -> Option<Bytes> {
+        let keys = toml::from_slice::<Keys>(&fs::read(Path::new(filename)).ok()?).ok()?;
+        Some(Bytes::new(from_hex(&keys.signing.public).ok()?))
+    }
+
+    /// Checks if key file needs a password to be loaded
+    pub fn is_encrypted_file(filename: &str) -> bool {
+        match fs::read(Path::new(filename)) {
+            Ok(key) => matches!(toml::from_slice::<Keys>(key.as_slice()), Ok(keys) if keys.encrypted),
+            Err(_) => false
+        }
+    }
+
+    /// Saves keys to file, secret keys are encrypted if `password` is not empty
+    pub fn save(&mut self, filename: &str, password: &str) {
+        match File::create(Path::new(filename)) {
+            Ok(mut f) => {
+                let keys = match password.is_empty() {
+                    true => self.get_keys(),
+                    false => self.get_encrypted_keys(password)
+                };
+                let data = toml::to_string(&keys).unwrap();
+                f.write_all(data.trim().as_bytes()).expect("Error saving keystore");
+                self.path = filename.to_owned();
+            }
+            Err(_) => { error!("Error saving key file!"); }
+        }
+    }
+
+    pub fn get_public(&self) -> Bytes {
+        Bytes::from_bytes(&self.keypair.public.to_bytes())
+    }
+
+    pub fn get_private(&self) -> Bytes {
+        Bytes::from_bytes(&self.keypair.secret.to_bytes())
+    }
+
+    pub fn get_encryption_public(&self) -> Bytes {
+        Bytes::from_bytes(self.crypto_box.public.as_bytes())
+    }
+
+    pub fn get_keys(&self) -> Keys {
+        let signing = KeyPack::new(to_hex(&self.keypair.public.to_bytes()), to_hex(&self.keypair.secret.to_bytes()));
+        let encryption = KeyPack::new(to_hex(&self.crypto_box.public.to_bytes()), to_hex(&self.crypto_box.secret.to_bytes()));
+        let mut keys = Keys::new(false, signing, encryption);
+        keys.name = self.name.clone();
+        keys
+    }
+
+    pub fn get_encrypted_keys(&self, password: &str) -> Keys {
+        let salt: [u8; PASSWORD_SALT_LEN] = rand::random();
+        let key = password_key(password, &salt);
+        let signing = KeyPack::new(to_hex(&self.keypair.public.to_bytes()), encrypt_secret(&key, &self.keypair.secret.to_bytes()));
+        let encryption = KeyPack::new(to_hex(&self.crypto_box.public.to_bytes()), encrypt_secret(&key, &self.crypto_box.secret.to_bytes()));
+        let mut keys = Keys::new(true, signing, encryption);
+        keys.name = self.name.clone();
+        keys.salt = to_hex(&salt);
+        keys
+    }
+
+    pub fn get_path(&self) -> &str {
+        &self.path
+    }
+
+    /// Returns the name of this key, or the name of its file if the name was not set
+    pub fn get_name(&self) -> String {
+        if !self.name.is_empty() {
+            return self.name.clone();
+        }
+        match Path::new(&self.path).file_stem() {
+            Some(stem) => stem.to_string_lossy().to_string(),
+            None => String::new()
+        }
+    }
+
+    pub fn set_name(&mut self, name: &str) {
+        self.name = name.to_owned();
+    }
+
+    pub fn get_fingerprint(&self) -> String {
+        key_fingerprint(self.keypair.public.as_bytes())
+    }
+
+    pub fn get_hash(&self) -> Bytes {
+        let mut hash = self.hash.lock().unwrap();
+        if hash.is_empty() {
+            *hash = blakeout_data(&self.get_public());
+        }
+        hash.clone()
+    }
+
+    pub fn sign(&self, message: &[u8]) -> [u8; 64] {
+        self.keypair.sign(message).to_bytes()
+    }
+
+    pub fn check(message: &[u8], public_key: &[u8], signature: &[u8]) -> bool {
+        let key = PublicKey::from_bytes(public_key).expect("Wrong public key!");
+        let signature = Signature::from_bytes(signature).unwrap();
+        key.verify(message, &signature).is_ok()
+    }
+
+    pub fn encrypt(&self, message: &[u8]) -> Bytes {
+        let encrypted = self.crypto_box.hide(message).unwrap();
+        Bytes::from_bytes(&encrypted)
+    }
+
+    pub fn decrypt(&self, message: &[u8]) -> Bytes {
+        match self.crypto_box.reveal(message) {
+            Ok(decrypted) => Bytes::from_bytes(&decrypted),
+            Err(_) => {
+                warn!("Decryption failed");
+                Bytes::default()
+            }
+        }
+    }
+}
+
+impl Default for Keystore {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Clone for Keystore {
+    fn clone(&self) -> Self {
+        let keypair = Keypair::from_bytes(&self.keypair.to_bytes()).unwrap();
+        Self { keypair, hash: Mutex::new(Bytes::default()), path: self.path.clone(), name: self.name.clone(), crypto_box: self.crypto_box.clone(), old: self.old }
+    }
+}
+
+impl Signer for Keystore {
+    fn public_key(&self) -> Bytes {
+        self.get_public()
+    }
+
+    fn sign(&self, message: &[u8]) -> Option<Bytes> {
+        Some(Bytes::from_bytes(&Keystore::sign(self, message)))
+    }
+
+    fn key_file(&self) -> &str {
+        self.get_path()
+    }
+}
+
+impl PartialEq for Keystore {
+    fn eq(&self, other: &Self) -> bool {
+        self.keypair.to_bytes().eq(&other.keypair.to_bytes())
+    }
+}
+
+/// Makes short fingerprint of public key from words, like `oven-apple-swing-ivory-cart`,
+/// it is much easier to compare by eye than 64 hex symbols
+pub fn key_fingerprint(public: &[u8]) -> String {
+    let mut digest = Sha256::new();
+    digest.update(FINGERPRINT_CONTEXT);
+    digest.update(public);
+    let hash = digest.finalize();
+    let words = Language::English.word_list();
+    let mut bits = u64::from_be_bytes(hash[..8].try_into().unwrap());
+    let mut result = Vec::with_capacity(FINGERPRINT_WORDS);
+    for _ in 0..FINGERPRINT_WORDS {
+        result.push(words[(bits >> 53) as usize]);
+        bits <<= 11;
+    }
+    result.join("-")
+}
+
+/// Checks if some public key is "strong" enough to mine domains
+/// TODO Optimize by caching Blakeout somewhere
+pub fn check_public_key_strength(key: &Bytes, strength: u32) -> bool {
+    let bytes = blakeout_data(key);
+    key_hash_difficulty(&bytes) >= strength
+}
+
+pub fn create_key(context: Arc<Mutex<Context>>) {
+    let mining = Arc::new(AtomicBool::new(true));
+    let miners_count = Arc::new(AtomicUsize::new(0));
+    post(Event::KeyGeneratorStarted);
+    let lower = context.lock().unwrap().settings.mining.lower;
+    let threads = context.lock().unwrap().settings.mining.threads;
+    let threads = match threads {
+        0 => num_cpus::get(),
+        _ => threads
+    };
+    for cpu in 0..threads {
+        let context = Arc::clone(&context);
+        let mining = mining.clone();
+        let miners_count = miners_count.clone();
+        thread::spawn(move || {
+            miners_count.fetch_add(1, atomic::Ordering::SeqCst);
+            if lower {
+                setup_miner_thread(cpu as u32);
+            }
+            match generate_key(KEYSTORE_DIFFICULTY, mining.clone()) {
+                None => {
+                    debug!("Keystore mining finished");
+                }
+                Some(keystore) => {
+                    let mut context = context.lock().unwrap();
+                    let hash = keystore.get_hash().to_string();
+                    let path = keystore.get_path().to_owned();
+                    let public = keystore.get_public().to_string();
+                    info!("Key mined successfully! Public key: {}, hash: {}", &public, &hash);
+                    context.add_keystore(keystore);
+                    post(Event::KeyCreated { path, public, hash });
+                }
+            }
+            let miners = miners_count.fetch_sub(1, atomic::Ordering::SeqCst) - 1;
+            if miners == 0 {
+                post(Event::KeyGeneratorStopped);
+            }
+        });
+    }
+    register(move |_uuid, e| {
+        if e == Event::ActionStopMining {
+            info!("Stopping keystore miner");
+            mining.store(false, atomic::Ordering::SeqCst);
+            false
+        } else {
+            true
+        }
+    });
+}
+
+/// Mines new strong key in `threads` threads without context, for the time when the node is not started yet
+pub fn mine_key(threads: usize, mining: Arc<AtomicBool>) -> Option<Keystore> {
+    let handles: Vec<_> = (0..threads.max(1))
+        .map(|_| {
+            let mining = Arc::clone(&mining);
+            thread::spawn(move || generate_key(KEYSTORE_DIFFICULTY, mining))
+        })
+        .collect();
+    // The first found key stops other threads
+    handles.into_iter().filter_map(|handle| handle.join().ok().flatten()).next()
+}
+
+/// Starts derivation of the key for `domain` from current key in background threads
+pub fn create_domain_key(context: Arc<Mutex<Context>>, domain: &str) {
+    let (master, threads) = {
+        let context = context.lock().unwrap();
+        match context.get_keystore() {
+            Some(keystore) => (keystore.clone(), context.settings.mining.threads),
+            None => return
+        }
+    };
+    let threads = match threads {
+        0 => num_cpus::get(),
+        _ => threads
+    };
+    let mining = Arc::new(AtomicBool::new(true));
+    let domain = domain.to_lowercase();
+    post(Event::KeyGeneratorStarted);
+    let mining_copy = Arc::clone(&mining);
+    thread::spawn(move || {
+        match derive_domain_key(&master, &domain, threads, Arc::clone(&mining_copy)) {
+            None => debug!("Domain key derivation stopped"),
+            Some(keystore) => {
+                let hash = keystore.get_hash().to_string();
+                let public = keystore.get_public().to_string();
+                info!("Key for domain {} derived successfully! Public key: {}, hash: {}", &domain, &public, &hash);
+                context.lock().unwrap().add_keystore(keystore);
+                post(Event::KeyCreated { path: String::new(), public, hash });
+            }
+        }
+        mining_copy.store(false, atomic::Ordering::SeqCst);
+        post(Event::KeyGeneratorStopped);
+    });
+    register(move |_uuid, e| {
+        if e == Event::ActionStopMining {
+            mining.store(false, atomic::Ordering::SeqCst);
+            false
+        } else {
+            mining.load(atomic::Ordering::SeqCst)
+        }
+    });
+}
+
+/// Derives the key for domain from master key. The key has to be strong, so we search for it like in `generate_key`,
+/// but candidates are made from master key, domain name and counter. The candidate with smallest counter is taken,
+/// so the same key is found every time with any number of threads, and master key alone can restore all domain keys.
+pub fn derive_domain_key(master: &Keystore, domain: &str, threads: usize, mining: Arc<AtomicBool>) -> Option<Keystore> {
+    let threads = threads.max(1) as u64;
+    let master_secret = master.keypair.secret.to_bytes();
+    let found = Arc::new(AtomicU64::new(u64::MAX));
+    let mut handles = Vec::new();
+    for start in 0..threads {
+        let found = Arc::clone(&found);
+        let mining = Arc::clone(&mining);
+        let domain = domain.to_owned();
+        handles.push(thread::spawn(move || {
+            let mut digest = Blakeout::default();
+            let mut counter = start;
+            // Every thread checks all its counters below the smallest found one
+            while counter < found.load(atomic::Ordering::SeqCst) {
+                if !mining.load(atomic::Ordering::SeqCst) {
+                    return;
+                }
+                let secret = domain_key_secret(&master_secret, &domain, counter);
+                let public = PublicKey::from(&SecretKey::from_bytes(&secret).unwrap());
+                digest.reset();
+                digest.update(public.as_bytes());
+                if key_hash_difficulty(digest.result()) >= KEYSTORE_DIFFICULTY {
+                    found.fetch_min(counter, atomic::Ordering::SeqCst);
+                    return;
+                }
+                counter += threads;
+            }
+        }));
+    }
+    for handle in handles {
+        let _ = handle.join();
+    }
+    let counter = found.load(atomic::Ordering::SeqCst);
+    if counter == u64::MAX || !mining.load(atomic::Ordering::SeqCst) {
+        return None;
+    }
+    let mut keystore = Keystore::from_random_bytes(&domain_key_secret(&master_secret, domain, counter));
+    keystore.set_name(domain);
+    Some(keystore)
+}
+
+/// Makes candidate for secret key of domain
+fn domain_key_secret(master: &[u8], domain: &str, counter: u64) -> [u8; 32] {
+    let mut digest = Sha256::new();
+    digest.update(DOMAIN_KEY_CONTEXT);
+    digest.update(master);
+    digest.update(domain.as_bytes());
+    digest.update(counter.to_le_bytes());
+    digest.finalize().into()
+}
+
+fn generate_key(difficulty: u32, mining: Arc<AtomicBool>) -> Option<Keystore> {
+    use self::rand::RngCore;
+    let mut rng = rand::thread_rng();
+    let mut time = Instant::now();
+    let mut count = 0u128;
+    let mut digest = Blakeout::default();
+    let mut buf = [0u8; 32];
+    loop {
+        rng.fill_bytes(&mut buf);
+        let secret = SecretKey::from_bytes(&buf).unwrap();
+        let public = PublicKey::from(&secret);
+        digest.reset();
+        digest.update(public.as_bytes());
+        if key_hash_difficulty(digest.result()) >= difficulty {
+            mining.store(false, atomic::Ordering::SeqCst);
+            let keystore = Keystore::from_random_bytes(&buf);
+            return Some(keystore);
+        }
+        if !mining.load(atomic::Ordering::SeqCst) {
+            return None;
+        }
+        let elapsed = time.elapsed().as_millis();
+        if elapsed >= 60000 {
+            debug!("Mining speed {} H/s", count / 60);
+            time = Instant::now();
+            count = 0;
+        }
+        count += 1;
+    }
+}
+
+/// Derives the seed of encryption key from secret signing key
+fn derive_encryption_seed(secret: &[u8]) -> [u8; 32] {
+    let mut digest = Sha256::new();
+    digest.update(ENCRYPTION_KEY_CONTEXT);
+    digest.update(secret);
+    digest.finalize().into()
+}
+
+fn secret_from_mnemonic(words: &str) -> Option<Vec<u8>> {
+    let mnemonic = Mnemonic::parse_normalized(words.trim()).ok()?;
+    let secret = mnemonic.to_entropy();
+    match secret.len() {
+        32 => Some(secret),
+        _ => None
+    }
+}
+
+/// Derives the key for encryption of secret keys from password
+fn password_key(password: &str, salt: &[u8]) -> [u8; 32] {
+    argon2_key(password, salt, PASSWORD_ARGON2_MEMORY, PASSWORD_ARGON2_ITERATIONS, 1).expect("Wrong Argon2 params")
+}
+
+fn argon2_key(password: &str, salt: &[u8], memory: u32, iterations: u32, parallelism: u32) -> Option<[u8; 32]> {
+    let params = Params::new(memory, iterations, parallelism, Some(32)).ok()?;
+    let argon = Argon2::new(Algorithm::Argon2id, Version::V0x13, params);
+    let mut key = [0u8; 32];
+    argon.hash_password_into(password.as_bytes(), salt, &mut key).ok()?;
+    Some(key)
+}
+
+/// Encrypts secret key, the result is hex of random nonce followed by encrypted key
+fn encrypt_secret(key: &[u8], secret: &[u8]) -> String {
+    let nonce: [u8; PASSWORD_NONCE_LEN] = rand::random();
+    let chacha = Chacha::new(key, &nonce);
+    let mut result = nonce.to_vec();
+    result.extend_from_slice(&chacha.encrypt(secret).expect("Error encrypting key"));
+    to_hex(&result)
+}
+
+/// Decrypts secret key, returns None if the password was wrong
+fn decrypt_secret(key: &[u8], data: &str) -> Option<Vec<u8>> {
+    let data = from_hex(data).ok()?;
+    if data.len() <= PASSWORD_NONCE_LEN {
+        return None;
+    }
+    let (nonce, encrypted) = data.split_at(PASSWORD_NONCE_LEN);
+    Chacha::new(key, nonce).decrypt(encrypted).ok()
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct KeyPack {
+    public: String,
+    secret: String
+}
+
+impl KeyPack {
+    pub fn new(public: String, secret: String) -> Self {
+        Self { public, secret }
+    }
+}
+
+const CONTAINER_VERSION: u32 = 1;
+const CONTAINER_CIPHER: &str = "chacha20poly1305";
+const CONTAINER_KDF: &str = "argon2id";
+
+/// Portable encrypted container of keys, version 1:
+/// ```json
+/// {
+///   "version": 1,
+///   "name": "my key",
+///   "public": "hex of ed25519 public signing key",
+///   "encryption_public": "hex of x25519 public encryption key",
+///   "crypto": {
+///     "cipher": "chacha20poly1305",
+///     "ciphertext": "hex of encrypted 32 bytes of secret signing key followed by 32 bytes of secret encryption key",
+///     "nonce": "hex of 12 bytes nonce",
+///     "kdf": "argon2id",
+///     "kdfparams": { "memory": 19456, "iterations": 2, "parallelism": 1, "salt": "hex of salt" }
+///   }
+/// }
+/// ```
+/// The key for cipher is 32 bytes of Argon2id (version 0x13) of password with these parameters, memory is in KiB.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct KeyContainer {
+    pub version: u32,
+    #[serde(default)]
+    pub name: String,
+    pub public: String,
+    pub encryption_public: String,
+    pub crypto: ContainerCrypto
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct ContainerCrypto {
+    pub cipher: String,
+    pub ciphertext: String,
+    pub nonce: String,
+    pub kdf: String,
+    pub kdfparams: KdfParams
+}
+
+impl ContainerCrypto {
+    /// Encrypts data with the key from password, salt and nonce are random
+    pub fn encrypt(password: &str, data: &[u8]) -> Self {
+        let salt: [u8; PASSWORD_SALT_LEN] = rand::random();
+        let nonce: [u8; PASSWORD_NONCE_LEN] = rand::random();
+        let key = password_key(password, &salt);
+        let ciphertext = Chacha::new(&key, &nonce).encrypt(data).expect("Error encrypting data");
+        let kdfparams = KdfParams { memory: PASSWORD_ARGON2_MEMORY, iterations: PASSWORD_ARGON2_ITERATIONS, parallelism: 1, salt: to_hex(&salt) };
+        ContainerCrypto { cipher: CONTAINER_CIPHER.to_owned(), ciphertext: to_hex(&ciphertext), nonce: to_hex(&nonce), kdf: CONTAINER_KDF.to_owned(), kdfparams }
+    }
+
+    pub fn is_supported(&self) -> bool {
+        self.cipher == CONTAINER_CIPHER && self.kdf == CONTAINER_KDF
+    }
+
+    /// Returns None if the password doesn't fit or the data is damaged
+    pub fn decrypt(&self, password: &str) -> Option<Vec<u8>> {
+        if !self.is_supported() {
+            return None;
+        }
+        let params = &self.kdfparams;
+        let key = argon2_key(password, &from_hex(&params.salt).ok()?, params.memory, params.iterations, params.parallelism)?;
+        let nonce = from_hex(&self.nonce).ok()?;
+        if nonce.len() != PASSWORD_NONCE_LEN {
+            return None;
+        }
+        Chacha::new(&key, &nonce).decrypt(&from_hex(&self.ciphertext).ok()?).ok()
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct KdfParams {
+    pub memory: u32,
+    pub iterations: u32,
+    pub parallelism: u32,
+    pub salt: String
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct Keys {
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    name: String,
+    encrypted: bool,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    salt: String,
+    signing: KeyPack,
+    encryption: KeyPack
+}
+
+impl Keys {
+    pub fn new(encrypted: bool, signing: KeyPack, encryption: KeyPack) -> Self {
+        Self { name: String::new(), encrypted, salt: String::new(), signing, encryption }
+    }
+
+    /// Returns secret keys for signing and encryption, decrypting them if needed
+    fn get_secrets(&self, password: &str) -> Option<(Vec<u8>, Vec<u8>)> {
+        if !self.encrypted {
+            return Some((from_hex(&self.signing.secret).ok()?, from_hex(&self.encryption.secret).ok()?));
+        }
+        if password.is_empty() {
+            return None;
+        }
+        let key = password_key(password, &from_hex(&self.salt).ok()?);
+        Some((decrypt_secret(&key, &self.signing.secret)?, decrypt_secret(&key, &self.encryption.secret)?))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::keystore::{domain_key_secret, key_fingerprint, secret_from_mnemonic, Keys};
+    use crate::Keystore;
+
+    #[test]
+    pub fn test_signature() {
+        let keystore: Keystore = Keystore::new();
+        let data = b"{ identity: 178135D209C697625E3EC71DA5C760382E54936F824EE5083908DA66B14ECE18,\
+    confirmation: A4A0AFECD1A511825226F0D3437C6C6BDAE83554040AA7AEB49DEFEAB0AE9EA4 }";
+        let signature = keystore.sign(data);
+        assert!(Keystore::check(data, &keystore.get_public(), &signature), "Wrong signature!")
+    }
+
+    #[test]
+    pub fn test_mnemonic() {
+        let keystore = Keystore::from_random_bytes(&[7u8; 32]);
+        let words = keystore.get_mnemonic();
+        assert_eq!(words.split_whitespace().count(), 24);
+        assert!(keystore.is_mnemonic_complete());
+        assert_eq!(secret_from_mnemonic(&words).unwrap(), keystore.get_private().as_slice());
+        let restored = Keystore::from_random_bytes(&secret_from_mnemonic(&words).unwrap());
+        assert_eq!(restored.get_encryption_public(), keystore.get_encryption_public());
+        assert!(secret_from_mnemonic("abandon abandon ability").is_none());
+        assert!(!Keystore::new().is_mnemonic_complete());
+    }
+
+    #[test]
+    pub fn test_domain_key_secret() {
+        let master = [1u8; 32];
+        assert_eq!(domain_key_secret(&master, "test.ygg", 5), domain_key_secret(&master, "test.ygg", 5));
+        assert_ne!(domain_key_secret(&master, "test.ygg", 5), domain_key_secret(&master, "test.ygg", 6));
+        assert_ne!(domain_key_secret(&master, "test.ygg", 5), domain_key_secret(&master, "best.ygg", 5));
+        assert_ne!(domain_key_secret(&master, "test.ygg", 5), domain_key_secret(&[2u8; 32], "test.ygg", 5));
+    }
+
+    #[test]
+    pub fn test_json_container() {
+        let mut keystore = Keystore::new();
+        keystore.set_name("test");
+        let json = keystore.export_json("password");
+        let imported = Keystore::import_json(&json, "password").unwrap();
+        assert!(imported == keystore);
+        assert_eq!(imported.get_encryption_public(), keystore.get_encryption_public());
+        assert_eq!(imported.get_name(), "test");
+        assert!(Keystore::import_json(&json, "wrong").is_none());
+        assert!(Keystore::import_json(&json.replace("\"version\": 1", "\"version\": 2"), "password").is_none());
+    }
+
+    #[test]
+    pub fn test_fingerprint() {
+        let keystore = Keystore::new();
+        let fingerprint = keystore.get_fingerprint();
+        assert_eq!(fingerprint.split('-').count(), 5);
+        assert_eq!(fingerprint, key_fingerprint(&keystore.get_public()));
+        assert_ne!(fingerprint, Keystore::new().get_fingerprint());
+    }
+
+    #[test]
+    pub fn test_key_name() {
+        let mut keystore: Keystore = Keystore::new();
+        assert_eq!(keystore.get_name(), "");
+        keystore.path = String::from("keys/work.toml");
+        assert_eq!(keystore.get_name(), "work");
+        keystore.set_name("personal");
+        assert_eq!(keystore.get_name(), "personal");
+    }
+
+    #[test]
+    pub fn test_encrypted_keys() {
+        let keystore: Keystore = Keystore::new();
+        let keys = keystore.get_encrypted_keys("password");
+        let string = toml::to_string(&keys).unwrap();
+        assert!(!string.contains(&keystore.get_private().to_string()));
+
+        let keys: Keys = toml::from_str(&string).unwrap();
+        let (signing, _) = keys.get_secrets("password").unwrap();
+        assert_eq!(signing, keystore.get_private().as_slice());
+        assert!(keys.get_secrets("wrong").is_none());
+        assert!(keys.get_secrets("").is_none());
+    }
+}
\ No newline at end of file
@@ -0,0 +1,73 @@
+//! Core of ALFIS: blockchain, domain transactions, keys, validation of blocks and resolution of names.
+//! The node with GUI and command line is in `alfis` crate, this one is for programs that want to embed ALFIS,
+//! like browsers, resolvers and explorers.
+//!
+//! The main parts are:
+//! * [`Chain`] keeps blocks in SQLite database, checks new blocks and finds domains;
+//! * [`Block`] and [`Transaction`] are the data of blockchain, [`Keystore`] signs them;
+//! * [`blockchain::filter::BlockchainFilter`] answers DNS queries for domains from blockchain;
+//! * [`Network`] synchronizes the chain with other nodes, [`Miner`] mines new blocks;
+//! * [`Context`] holds settings, keys and chain for all of them, and [`event`] is how they tell about changes;
+//! * [`ChainRef`] gives the chain from `Context` to threads that don't need anything else;
+//! * [`blockchain::plugins`] lets embedding programs add their own classes of transactions.
+//!
+//! To look up a domain in the database of a node:
+//! ```no_run
+//! use alfis_core::api::get_domain_info;
+//! use alfis_core::{Chain, Settings};
+//!
+//! let chain = Chain::new(&Settings::default(), "blockchain.db");
+//! if let Some(info) = get_domain_info(&chain, "example.ygg") {
+//!     println!("{} is owned by {}", info.name, info.owner.to_string());
+//! }
+//! ```
+
+pub use blockchain::block::Block;
+pub use blockchain::transaction::Transaction;
+pub use commons::simplebus::*;
+
+pub use crate::blockchain::{Chain, ChainRef};
+pub use crate::bytes::Bytes;
+pub use crate::commons::*;
+pub use crate::context::Context;
+pub use crate::keystore::Keystore;
+pub use crate::miner::Miner;
+pub use crate::p2p::Network;
+pub use crate::settings::Settings;
+pub use crate::signer::Signer;
+
+pub mod api;
+pub mod backup;
+pub mod blockchain;
+pub mod bytes;
+pub mod commons;
+pub mod context;
+pub mod control;
+pub mod crypto;
+pub mod dns;
+pub mod dns_utils;
+pub mod event;
+pub mod export;
+#[cfg(feature = "ffi")]
+pub mod ffi;
+pub mod getwork;
+pub mod import;
+pub mod keychain;
+pub mod keystore;
+pub mod light;
+pub mod lock;
+pub mod logger;
+pub mod miner;
+pub mod p2p;
+pub mod proxy;
+pub mod qr;
+pub mod service;
+pub mod settings;
+pub mod signer;
+pub mod snapshot;
+#[cfg(test)]
+pub mod sim;
+pub mod vanity;
+pub mod vectors;
+pub mod watch;
+pub mod webhook;
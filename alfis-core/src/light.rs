@@ -0,0 +1,211 @@
+//! Light client: it keeps only headers of blocks and asks full nodes for proofs of domains by their REST API.
+//! Hash of a block covers its transaction, so blocks are downloaded once to check their hashes and signatures,
+//! but only small headers are stored. A proof of domain is the block with its last transaction and the blocks
+//! after it, they are checked by `alfis-verify` and must be the same as our headers.
+//!
+//! Ownership of domains and signers of blocks need the whole chain, so they are not checked,
+//! and the client can't tell if a full node hides a newer transaction of the domain. Ask several nodes.
+
+use std::fmt::{Display, Formatter};
+use std::fs;
+use std::net::SocketAddr;
+use std::path::Path;
+
+use chrono::Utc;
+#[allow(unused_imports)]
+use log::{debug, error, info, trace, warn};
+use serde::{Deserialize, Serialize};
+
+use crate::api::http::send_request;
+use crate::blockchain::hash_utils::hash_identity;
+use crate::commons::{DOMAIN_LIFETIME, LIMITED_CONFIDENCE_DEPTH};
+use crate::{Block, Bytes, Transaction};
+
+/// File with headers in data directory
+pub const HEADERS_FILE: &str = "light_headers.cbor";
+/// How many blocks after the block of domain are given in its proof
+pub const PROOF_BLOCKS: u64 = 7;
+
+/// What we keep of every block
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Header {
+    pub index: u64,
+    pub timestamp: i64,
+    pub hash: Bytes
+}
+
+#[derive(Debug, PartialEq)]
+pub enum LightError {
+    /// Full node is not available or gave something strange
+    Network(String),
+    /// Block is not valid or doesn't follow our headers
+    WrongBlock(u64),
+    /// Proof of domain is not valid
+    Proof(alfis_verify::ProofError),
+    /// Block of proof is not in our chain of headers, maybe we need to sync
+    NotInChain(u64),
+    /// Domain is not found or expired
+    NotFound
+}
+
+impl Display for LightError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        match self {
+            LightError::Network(e) => write!(f, "network error: {}", e),
+            LightError::WrongBlock(index) => write!(f, "wrong block {}", index),
+            LightError::Proof(e) => write!(f, "wrong proof: {:?}", e),
+            LightError::NotInChain(index) => write!(f, "block {} is not in our chain", index),
+            LightError::NotFound => write!(f, "domain not found")
+        }
+    }
+}
+
+pub struct LightClient {
+    /// Hash of the first block, zero to take any chain
+    origin: Bytes,
+    /// API of a full node
+    api: SocketAddr,
+    headers: Vec<Header>
+}
+
+impl LightClient {
+    pub fn new(origin: Bytes, api: SocketAddr) -> Self {
+        LightClient { origin, api, headers: Vec::new() }
+    }
+
+    /// Loads headers saved earlier, if they are from the same chain
+    pub fn load(origin: Bytes, api: SocketAddr, path: &Path) -> Self {
+        let mut client = Self::new(origin, api);
+        if let Some(headers) = fs::read(path).ok().and_then(|data| serde_cbor::from_slice::<Vec<Header>>(&data).ok()) {
+            if headers.first().map(|header| client.origin.is_zero() || header.hash == client.origin).unwrap_or(false) {
+                client.headers = headers;
+            }
+        }
+        client
+    }
+
+    pub fn save(&self, path: &Path) -> std::io::Result<()> {
+        let data = serde_cbor::to_vec(&self.headers).map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, e))?;
+        fs::write(path, data)
+    }
+
+    pub fn get_height(&self) -> u64 {
+        self.headers.len() as u64
+    }
+
+    pub fn get_header(&self, index: u64) -> Option<&Header> {
+        index.checked_sub(1).and_then(|i| self.headers.get(i as usize))
+    }
+
+    /// Checks blocks that must follow our headers one by one, and keeps their headers
+    pub fn add_blocks(&mut self, blocks: &[Block]) -> Result<(), LightError> {
+        for block in blocks {
+            if block.index != self.get_height() + 1 {
+                return Err(LightError::WrongBlock(block.index));
+            }
+            match self.headers.last() {
+                None => {
+                    if !block.prev_block_hash.is_zero() || (!self.origin.is_zero() && block.hash != self.origin) {
+                        return Err(LightError::WrongBlock(block.index));
+                    }
+                }
+                Some(last) => {
+                    if block.prev_block_hash != last.hash || block.timestamp < last.timestamp {
+                        return Err(LightError::WrongBlock(block.index));
+                    }
+                }
+            }
+            if alfis_verify::verify_block(&block.into()).is_err() {
+                return Err(LightError::WrongBlock(block.index));
+            }
+            self.headers.push(Header { index: block.index, timestamp: block.timestamp, hash: block.hash.clone() });
+        }
+        Ok(())
+    }
+
+    /// Downloads new blocks from the full node, returns our new height.
+    /// If the node has other last blocks, we drop ours that can be changed and try again.
+    pub fn sync(&mut self) -> Result<u64, LightError> {
+        let mut rewound = false;
+        loop {
+            // The node gives blocks before this height, from the last one
+            let before = self.get_height() + 1 + crate::api::MAX_BLOCKS;
+            let mut blocks: Vec<Block> = self.request(&format!("/blocks?before={}", before))?;
+            // If the node has no new blocks it gives its last ones
+            blocks.retain(|block| block.index > self.get_height());
+            blocks.reverse();
+            if blocks.is_empty() {
+                return Ok(self.get_height());
+            }
+            match self.add_blocks(&blocks) {
+                Ok(_) => rewound = false,
+                Err(LightError::WrongBlock(index)) if !rewound && index == blocks[0].index && self.get_height() > 0 => {
+                    let height = self.get_height().saturating_sub(LIMITED_CONFIDENCE_DEPTH);
+                    warn!("Block {} doesn't follow our headers, dropping headers after {}", index, height);
+                    self.headers.truncate(height as usize);
+                    rewound = true;
+                }
+                Err(e) => return Err(e)
+            }
+        }
+    }
+
+    /// Gets the last transaction of domain with its proof, the records are in its data
+    pub fn lookup(&self, name: &str) -> Result<Transaction, LightError> {
+        let name = name.to_lowercase();
+        let blocks: Vec<Block> = self.request(&format!("/domains/{}/proof", &name))?;
+        let proof: Vec<alfis_verify::Block> = blocks.iter().map(|block| block.into()).collect();
+        alfis_verify::verify_domain_proof(&name, &proof).map_err(LightError::Proof)?;
+        for block in &blocks {
+            match self.get_header(block.index) {
+                Some(header) if header.hash == block.hash => {}
+                _ => return Err(LightError::NotInChain(block.index))
+            }
+        }
+        let block = &blocks[0];
+        let transaction = block.transaction.clone().ok_or(LightError::NotFound)?;
+        if transaction.identity != hash_identity(&name, None) || block.timestamp + DOMAIN_LIFETIME < Utc::now().timestamp() {
+            return Err(LightError::NotFound);
+        }
+        Ok(transaction)
+    }
+
+    fn request<T: for<'de> Deserialize<'de>>(&self, path: &str) -> Result<T, LightError> {
+        match send_request(&self.api, "GET", path, "") {
+            Ok((200, body)) => serde_json::from_str(&body).map_err(|e| LightError::Network(e.to_string())),
+            Ok((404, _)) => Err(LightError::NotFound),
+            Ok((status, _)) => Err(LightError::Network(format!("status {}", status))),
+            Err(e) => Err(LightError::Network(e.to_string()))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::SocketAddr;
+
+    use crate::light::{LightClient, LightError};
+    use crate::{Block, Bytes, Chain, Settings};
+
+    #[test]
+    fn test_add_blocks() {
+        let chain = Chain::new(&Settings::default(), "./tests/blockchain.db");
+        let blocks: Vec<Block> = (1..=20).map(|index| chain.get_block(index).unwrap()).collect();
+        let api: SocketAddr = "127.0.0.1:4246".parse().unwrap();
+
+        let mut client = LightClient::new(Bytes::default(), api);
+        assert_eq!(client.add_blocks(&blocks[..10]), Ok(()));
+        // Blocks must follow one by one
+        assert_eq!(client.add_blocks(&blocks[11..]), Err(LightError::WrongBlock(12)));
+        let mut broken = blocks[10].clone();
+        broken.nonce += 1;
+        assert_eq!(client.add_blocks(&[broken]), Err(LightError::WrongBlock(11)));
+        assert_eq!(client.add_blocks(&blocks[10..]), Ok(()));
+        assert_eq!(client.get_height(), 20);
+        assert_eq!(client.get_header(20).unwrap().hash, blocks[19].hash);
+
+        // Chain with other origin is not taken
+        let mut client = LightClient::new(Bytes::from_bytes(&[1u8; 32]), api);
+        assert_eq!(client.add_blocks(&blocks), Err(LightError::WrongBlock(1)));
+    }
+}
@@ -0,0 +1,89 @@
+//! Lock of data directory, so that two nodes don't work with the same database and don't break it.
+//! The lock is an exclusive transaction in a small SQLite file near the database, so it works the same way
+//! on all systems, and the system releases it when the process exits, even after a crash.
+//! Pid file for init systems is kept by the lock too, so it is removed when the node stops.
+
+use std::path::{Path, PathBuf};
+use std::{fmt, fs, io};
+
+use sqlite::Connection;
+
+/// File in data directory that is locked while the node works
+pub const LOCK_FILE: &str = "alfis.lock";
+/// Code of SQLite error when the file is locked by another connection
+const SQLITE_BUSY: isize = 5;
+const SQL_LOCK: &str = "PRAGMA locking_mode = EXCLUSIVE; BEGIN EXCLUSIVE; CREATE TABLE IF NOT EXISTS lock ('pid' INTEGER); \
+                        DELETE FROM lock; INSERT INTO lock (pid) VALUES ({});";
+
+#[derive(Debug, PartialEq)]
+pub enum LockError {
+    /// Another process works with this data directory
+    Busy,
+    Failed(String)
+}
+
+impl fmt::Display for LockError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LockError::Busy => write!(f, "it is used by another running ALFIS"),
+            LockError::Failed(e) => write!(f, "{}", e)
+        }
+    }
+}
+
+/// Holds the lock until it is dropped
+pub struct DataLock {
+    _connection: Connection,
+    pid_file: Option<PathBuf>
+}
+
+impl DataLock {
+    /// Locks data directory, fails at once if another process holds it
+    pub fn acquire(data_dir: &str) -> Result<Self, LockError> {
+        let path = Path::new(data_dir).join(LOCK_FILE);
+        let connection = sqlite::open(&path).map_err(|e| LockError::Failed(e.to_string()))?;
+        match connection.execute(SQL_LOCK.replace("{}", &std::process::id().to_string())) {
+            Ok(_) => Ok(DataLock { _connection: connection, pid_file: None }),
+            Err(e) if e.code == Some(SQLITE_BUSY) => Err(LockError::Busy),
+            Err(e) => Err(LockError::Failed(e.to_string()))
+        }
+    }
+
+    /// Writes process id to the file, it is removed when the lock is dropped
+    pub fn write_pid_file(&mut self, path: &str) -> io::Result<()> {
+        fs::write(path, std::process::id().to_string())?;
+        self.pid_file = Some(PathBuf::from(path));
+        Ok(())
+    }
+}
+
+impl Drop for DataLock {
+    fn drop(&mut self) {
+        if let Some(path) = &self.pid_file {
+            let _ = fs::remove_file(path);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use crate::lock::{DataLock, LockError};
+
+    #[test]
+    fn test_data_lock() {
+        let dir = std::env::temp_dir().join(format!("alfis-lock-test-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let dir_name = dir.to_string_lossy().to_string();
+        let mut lock = DataLock::acquire(&dir_name).unwrap();
+        assert_eq!(DataLock::acquire(&dir_name).err(), Some(LockError::Busy));
+        let pid_file = dir.join("alfis.pid");
+        lock.write_pid_file(&pid_file.to_string_lossy()).unwrap();
+        assert_eq!(fs::read_to_string(&pid_file).unwrap(), std::process::id().to_string());
+        drop(lock);
+        assert!(!pid_file.exists());
+        assert!(DataLock::acquire(&dir_name).is_ok());
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,245 @@
+//! Logging of the node: levels for separate modules, text or JSON lines, console and rotating files.
+//! Console and text files are written by `simplelog`, we only filter records for them.
+
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::str::FromStr;
+use std::sync::Mutex;
+
+use chrono::{Local, SecondsFormat};
+use log::{LevelFilter, Log, Metadata, Record, SetLoggerError};
+use simplelog::{Config, SharedLogger};
+
+/// Noisy modules of dependencies that we don't show, unless they are set in config
+const QUIET_MODULES: [&str; 3] = ["mio::poll", "rustls::client", "ureq::"];
+
+/// Levels of messages for modules, by prefix of their target
+#[derive(Debug, PartialEq)]
+pub struct LogFilter {
+    level: LevelFilter,
+    modules: Vec<(String, LevelFilter)>
+}
+
+impl LogFilter {
+    /// Makes filter from level like "info" and list of modules like "alfis_core::p2p=debug"
+    pub fn parse(level: &str, modules: &[String]) -> Result<Self, String> {
+        let level = LevelFilter::from_str(level).map_err(|_| format!("Wrong log level '{}'", level))?;
+        let mut filter = LogFilter { level, modules: Vec::new() };
+        for module in modules {
+            match module.split_once('=') {
+                Some((name, level)) => match LevelFilter::from_str(level.trim()) {
+                    Ok(level) => filter.modules.push((name.trim().to_owned(), level)),
+                    Err(_) => return Err(format!("Wrong log level in '{}'", module))
+                },
+                None => return Err(format!("Log level of module must be like 'alfis_core::p2p=debug', not '{}'", module))
+            }
+        }
+        for module in QUIET_MODULES {
+            if !filter.modules.iter().any(|(name, _)| name.starts_with(module) || module.starts_with(name.as_str())) {
+                filter.modules.push((module.to_owned(), LevelFilter::Off));
+            }
+        }
+        // The most specific module is checked first
+        filter.modules.sort_by(|a, b| b.0.len().cmp(&a.0.len()));
+        Ok(filter)
+    }
+
+    pub fn set_level(&mut self, level: LevelFilter) {
+        self.level = level;
+    }
+
+    pub fn level_for(&self, target: &str) -> LevelFilter {
+        for (name, level) in &self.modules {
+            if target.starts_with(name.as_str()) {
+                return *level;
+            }
+        }
+        self.level
+    }
+
+    /// The most verbose level of all, `log` macros skip everything above it
+    pub fn max_level(&self) -> LevelFilter {
+        self.modules.iter().map(|(_, level)| *level).fold(self.level, std::cmp::max)
+    }
+}
+
+/// Gives records that pass the filter to all outputs
+pub struct Logger {
+    filter: LogFilter,
+    outputs: Vec<Box<dyn SharedLogger>>
+}
+
+impl Logger {
+    /// Outputs must be created with `LevelFilter::Trace`, levels are checked here
+    pub fn init(filter: LogFilter, outputs: Vec<Box<dyn SharedLogger>>) -> Result<(), SetLoggerError> {
+        log::set_max_level(filter.max_level());
+        log::set_boxed_logger(Box::new(Logger { filter, outputs }))
+    }
+}
+
+impl Log for Logger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.level() <= self.filter.level_for(metadata.target())
+    }
+
+    fn log(&self, record: &Record) {
+        if self.enabled(record.metadata()) {
+            for output in &self.outputs {
+                output.log(record);
+            }
+        }
+    }
+
+    fn flush(&self) {
+        for output in &self.outputs {
+            output.flush();
+        }
+    }
+}
+
+/// Writes every record as JSON object on its own line
+pub struct JsonLogger<W: Write + Send + 'static> {
+    writer: Mutex<W>,
+    config: Config
+}
+
+impl<W: Write + Send + 'static> JsonLogger<W> {
+    pub fn new(writer: W) -> Box<Self> {
+        Box::new(JsonLogger { writer: Mutex::new(writer), config: Config::default() })
+    }
+}
+
+impl<W: Write + Send + 'static> Log for JsonLogger<W> {
+    fn enabled(&self, _metadata: &Metadata) -> bool {
+        true
+    }
+
+    fn log(&self, record: &Record) {
+        let mut writer = self.writer.lock().unwrap();
+        let _ = writeln!(writer, "{}", json_line(record));
+    }
+
+    fn flush(&self) {
+        let _ = self.writer.lock().unwrap().flush();
+    }
+}
+
+impl<W: Write + Send + 'static> SharedLogger for JsonLogger<W> {
+    fn level(&self) -> LevelFilter {
+        LevelFilter::Trace
+    }
+
+    fn config(&self) -> Option<&Config> {
+        Some(&self.config)
+    }
+
+    fn as_log(self: Box<Self>) -> Box<dyn Log> {
+        Box::new(*self)
+    }
+}
+
+fn json_line(record: &Record) -> String {
+    let value = serde_json::json!({
+        "time": Local::now().to_rfc3339_opts(SecondsFormat::Millis, false),
+        "level": record.level().as_str(),
+        "target": record.target(),
+        "thread": std::thread::current().name().unwrap_or_default(),
+        "message": record.args().to_string()
+    });
+    value.to_string()
+}
+
+/// Log file that is moved to `name.1` when it gets bigger than `max_size`, older files are moved to `name.2` and so on.
+/// Files are switched only after the end of line, as loggers write one record by several writes.
+pub struct RotatingFile {
+    path: String,
+    file: File,
+    size: u64,
+    max_size: u64,
+    keep: usize,
+    line_ended: bool
+}
+
+impl RotatingFile {
+    /// Opens the file to append, `max_size` is in bytes, 0 means no rotation
+    pub fn open(path: &str, max_size: u64, keep: usize) -> io::Result<Self> {
+        let file = OpenOptions::new().append(true).create(true).open(path)?;
+        let size = file.metadata()?.len();
+        Ok(RotatingFile { path: path.to_owned(), file, size, max_size, keep, line_ended: true })
+    }
+
+    fn rotate(&mut self) -> io::Result<()> {
+        if self.keep == 0 {
+            let _ = fs::remove_file(&self.path);
+        } else {
+            for i in (1..self.keep).rev() {
+                let _ = fs::rename(format!("{}.{}", &self.path, i), format!("{}.{}", &self.path, i + 1));
+            }
+            fs::rename(&self.path, format!("{}.1", &self.path))?;
+        }
+        self.file = OpenOptions::new().write(true).create(true).truncate(true).open(&self.path)?;
+        self.size = 0;
+        Ok(())
+    }
+}
+
+impl Write for RotatingFile {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if self.max_size > 0 && self.size >= self.max_size && self.line_ended {
+            self.rotate()?;
+        }
+        let written = self.file.write(buf)?;
+        self.size += written as u64;
+        self.line_ended = buf[..written].ends_with(b"\n");
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.file.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::io::Write;
+
+    use log::LevelFilter;
+
+    use crate::logger::{LogFilter, RotatingFile};
+
+    #[test]
+    fn test_filter() {
+        let modules = vec![String::from("alfis_core::p2p=debug"), String::from("alfis_core::p2p::peers=warn"), String::from("ureq=info")];
+        let filter = LogFilter::parse("info", &modules).unwrap();
+        assert_eq!(filter.level_for("alfis_core::blockchain"), LevelFilter::Info);
+        assert_eq!(filter.level_for("alfis_core::p2p::network"), LevelFilter::Debug);
+        assert_eq!(filter.level_for("alfis_core::p2p::peers"), LevelFilter::Warn);
+        assert_eq!(filter.level_for("mio::poll"), LevelFilter::Off);
+        assert_eq!(filter.level_for("ureq::unit"), LevelFilter::Info);
+        assert_eq!(filter.max_level(), LevelFilter::Debug);
+
+        assert!(LogFilter::parse("loud", &[]).is_err());
+        assert!(LogFilter::parse("info", &[String::from("alfis_core::p2p")]).is_err());
+    }
+
+    #[test]
+    fn test_rotation() {
+        let dir = std::env::temp_dir().join("alfis-log-test");
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("alfis.log").to_string_lossy().to_string();
+
+        let mut file = RotatingFile::open(&path, 10, 2).unwrap();
+        for i in 0..4 {
+            // A record written by parts stays in one file
+            write!(file, "line {}", i).unwrap();
+            writeln!(file, " of log").unwrap();
+        }
+        assert_eq!(fs::read_to_string(&path).unwrap(), "line 3 of log\n");
+        assert_eq!(fs::read_to_string(format!("{}.1", &path)).unwrap(), "line 2 of log\n");
+        assert_eq!(fs::read_to_string(format!("{}.2", &path)).unwrap(), "line 1 of log\n");
+        assert!(fs::metadata(format!("{}.3", &path)).is_err());
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
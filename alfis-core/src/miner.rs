@@ -0,0 +1,791 @@
+use std::cmp::max;
+use std::collections::HashMap;
+use std::{fs, io};
+use std::sync::atomic::{AtomicBool, AtomicU32, AtomicU64, Ordering};
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread;
+use std::thread::sleep;
+use std::time::{Duration, Instant};
+
+use blakeout::Blakeout;
+use chrono::{Local, Timelike, Utc};
+#[allow(unused_imports)]
+use log::{debug, error, info, trace, warn};
+use num_cpus;
+use serde::{Deserialize, Serialize};
+
+use crate::blockchain::hash_utils::*;
+use crate::blockchain::types::{BlockQuality, MineResult};
+use crate::commons::*;
+use crate::event::Event;
+use crate::eventbus::{post, register};
+use crate::keystore::check_public_key_strength;
+use crate::settings::Mining;
+use crate::signer::WatchKey;
+use crate::{setup_miner_thread, Block, Bytes, Chain, Context, Keystore, Signer, Transaction};
+
+/// Pending domain jobs are saved here to resume mining after restart
+const MINING_JOBS_FILE: &str = "mining_jobs.json";
+/// Mined blocks of offline keys are saved to files with this prefix and index of block
+pub const UNSIGNED_BLOCK_PREFIX: &str = "unsigned-block-";
+/// How long mining threads work without a rest if `cpu_limit` is set
+const CPU_LIMIT_PERIOD: Duration = Duration::from_millis(100);
+
+#[derive(Clone, Debug)]
+pub struct MineJob {
+    pub start: i64,
+    pub block: Block,
+    pub signer: Arc<dyn Signer>
+}
+
+impl MineJob {
+    fn is_full(&self) -> bool {
+        self.block.transaction.is_some()
+    }
+
+    fn is_signing(&self) -> bool {
+        self.block.transaction.is_none()
+    }
+
+    fn is_due(&self) -> bool {
+        self.start == 0 || self.start < Utc::now().timestamp()
+    }
+}
+
+/// Domain block to mine and the key to sign it, saved to file to resume mining after restart
+#[derive(Serialize, Deserialize)]
+struct SavedJob {
+    block: Block,
+    key_file: String,
+    /// Time to start mining, for new domains that wait for their day
+    #[serde(default)]
+    start: i64
+}
+
+#[derive(Clone, Debug)]
+pub struct MinerState {
+    pub mining: bool,
+    pub full: bool,
+    /// Domain block that we mine now, it is given to external miners too
+    pub job: Option<MineJob>
+}
+
+pub struct Miner {
+    context: Arc<Mutex<Context>>,
+    jobs: Arc<Mutex<Vec<MineJob>>>,
+    running: Arc<AtomicBool>,
+    mining: Arc<AtomicBool>,
+    paused: Arc<AtomicBool>,
+    /// Count of blocks added to chain, miners rebuild their blocks when it changes
+    tip: Arc<AtomicU64>,
+    /// Count of blocks requested by `Event::ActionGenerateBlock`, used only in regtest mode
+    generate: Arc<AtomicU64>,
+    cond_var: Arc<Condvar>
+}
+
+impl Miner {
+    pub fn new(context: Arc<Mutex<Context>>) -> Self {
+        Miner {
+            context,
+            jobs: Arc::new(Mutex::new(Vec::new())),
+            running: Arc::new(AtomicBool::new(false)),
+            mining: Arc::new(AtomicBool::new(false)),
+            paused: Arc::new(AtomicBool::new(false)),
+            tip: Arc::new(AtomicU64::new(0u64)),
+            generate: Arc::new(AtomicU64::new(0u64)),
+            cond_var: Arc::new(Condvar::new())
+        }
+    }
+
+    /// Count of blocks waiting in mining queue, without the one that we mine now
+    pub fn get_jobs_count(&self) -> usize {
+        self.jobs.lock().unwrap().len()
+    }
+
+    pub fn add_block(&mut self, block: Block, keystore: Keystore) {
+        self.add_block_with_signer(block, Arc::new(keystore));
+    }
+
+    /// Adds a job to mine the block that will be signed by some external signer
+    pub fn add_block_with_signer(&mut self, block: Block, signer: Arc<dyn Signer>) {
+        {
+            let mut jobs = self.jobs.lock().unwrap();
+            if block.transaction.is_none() {
+                jobs.retain(|job| job.block.transaction.is_some());
+            }
+            jobs.push(MineJob { start: 0, block, signer });
+        }
+        self.cond_var.notify_one();
+    }
+
+    /// Adds a job to mine domain transaction that was prepared by somebody else.
+    /// The block is signed by our active key, but the domain belongs to the owner of transaction.
+    pub fn add_transaction(&mut self, transaction: Transaction) -> MineResult {
+        let (block, keystore) = {
+            let context = self.context.lock().unwrap();
+            let keystore = match context.get_keystore() {
+                Some(keystore) => keystore.clone(),
+                None => return MineResult::WrongKey
+            };
+            let result = context.chain.can_mine_transaction(&transaction, &keystore.get_public());
+            if result != MineResult::Fine {
+                return result;
+            }
+            let height = context.chain.get_height();
+            let difficulty = context.chain.get_domain_difficulty(height + 1);
+            let discount = context.chain.get_identity_discount(&transaction.identity, false, height, Utc::now().timestamp());
+            (Block::new(Some(transaction), keystore.get_public(), Bytes::default(), difficulty - discount), keystore)
+        };
+        self.add_block(block, keystore);
+        MineResult::Fine
+    }
+
+    /// Adds a batch of domain transactions, like names imported from other naming system.
+    /// Renewals go first, and new domains are scheduled one per day, as one key can't mine new domains more often.
+    /// Returns result for every transaction, only those with `Fine` are added.
+    pub fn add_transactions(&mut self, transactions: Vec<Transaction>) -> Vec<MineResult> {
+        let (results, mut jobs) = {
+            let context = self.context.lock().unwrap();
+            let keystore = match context.get_keystore() {
+                Some(keystore) => keystore.clone(),
+                None => return transactions.iter().map(|_| MineResult::WrongKey).collect()
+            };
+            let height = context.chain.get_height();
+            let now = Utc::now().timestamp();
+            let difficulty = context.chain.get_domain_difficulty(height + 1);
+            let mut results = Vec::new();
+            let mut renewals = Vec::new();
+            let mut new_domains = Vec::new();
+            // New domains that the key can register yet in every zone
+            let mut left: HashMap<(String, Bytes), Option<u64>> = HashMap::new();
+            for transaction in transactions {
+                let delay = match context.chain.can_mine_transaction(&transaction, &keystore.get_public()) {
+                    MineResult::Fine => 0,
+                    MineResult::Cooldown { time } => time,
+                    result => {
+                        results.push(result);
+                        continue;
+                    }
+                };
+                let is_new = !context.chain.is_domain_in_blockchain(height, &transaction.identity);
+                if is_new {
+                    let zone = transaction.get_domain_data().map(|data| data.zone).unwrap_or_default();
+                    // Domains are counted for their owners, friends give us transactions signed by their keys
+                    let left = left.entry((zone, transaction.signing.clone()))
+                        .or_insert_with_key(|(zone, signing)| context.chain.get_registrations_left(zone, signing, height + 1));
+                    match left {
+                        Some(0) => {
+                            results.push(MineResult::Limited);
+                            continue;
+                        }
+                        Some(count) => *count -= 1,
+                        None => {}
+                    }
+                }
+                results.push(MineResult::Fine);
+                let discount = context.chain.get_identity_discount(&transaction.identity, false, height, now);
+                let block = Block::new(Some(transaction), keystore.get_public(), Bytes::default(), difficulty - discount);
+                if is_new {
+                    let start = match delay + new_domains.len() as i64 * context.chain.get_kind().new_domains_interval() {
+                        0 => 0,
+                        delay => now + delay
+                    };
+                    new_domains.push(MineJob { start, block, signer: Arc::new(keystore.clone()) });
+                } else {
+                    renewals.push(MineJob { start: 0, block, signer: Arc::new(keystore.clone()) });
+                }
+            }
+            renewals.append(&mut new_domains);
+            (results, renewals)
+        };
+        info!("Adding {} domain jobs to mining queue", jobs.len());
+        self.jobs.lock().unwrap().append(&mut jobs);
+        self.cond_var.notify_one();
+        results
+    }
+
+    /// Adds a job to move the domain to `new_key`, the block is signed by the key that owns the domain now.
+    /// Domain data stays the same, only the name is encrypted again by the new encryption key.
+    pub fn rotate_key(&mut self, domain: &str, new_key: &Keystore) -> MineResult {
+        let block = {
+            let context = self.context.lock().unwrap();
+            match make_renewal_block(&context, domain, Some((new_key.get_public(), new_key.get_encryption_public()))) {
+                Ok(block) => block,
+                Err(result) => return result
+            }
+        };
+        let keystore = match self.context.lock().unwrap().get_keystore_by_public(&block.pub_key) {
+            Some(keystore) => keystore.clone(),
+            None => return MineResult::NotOwned
+        };
+        self.add_block(block, keystore);
+        MineResult::Fine
+    }
+
+    /// Adds a job to renew the domain that is owned by a key from `watch_keys`.
+    /// When the block is mined it is saved to file, to be signed on the computer with the secret key.
+    pub fn renew_offline(&mut self, domain: &str) -> MineResult {
+        let block = {
+            let context = self.context.lock().unwrap();
+            let block = match make_renewal_block(&context, domain, None) {
+                Ok(block) => block,
+                Err(result) => return result
+            };
+            if !context.settings.get_watch_keys().contains(&block.pub_key) {
+                return MineResult::NotOwned;
+            }
+            block
+        };
+        let signer = Arc::new(WatchKey::new(block.pub_key.clone()));
+        self.add_block_with_signer(block, signer);
+        MineResult::Fine
+    }
+
+    /// Adds domain jobs that were not finished when we were closed last time.
+    /// Searched nonces are not saved, every search starts with new random and timestamp anyway.
+    pub fn resume_jobs(&mut self) {
+        let data = match fs::read_to_string(MINING_JOBS_FILE) {
+            Ok(data) => data,
+            Err(_) => return
+        };
+        let saved: Vec<SavedJob> = match serde_json::from_str(&data) {
+            Ok(saved) => saved,
+            Err(e) => {
+                warn!("Error loading saved mining jobs: {}", e);
+                return;
+            }
+        };
+        for job in saved {
+            // Encrypted keys can't be loaded without password, so we look for them in already loaded keys first
+            let loaded = self.context.lock().unwrap().get_keystores().iter().find(|k| k.get_path() == job.key_file).cloned();
+            match loaded.or_else(|| Keystore::from_file(&job.key_file, "")) {
+                Some(keystore) => {
+                    info!("Resuming mining of domain block with key {}", &job.key_file);
+                    self.jobs.lock().unwrap().push(MineJob { start: job.start, block: job.block, signer: Arc::new(keystore) });
+                }
+                None => warn!("Unable to load key {} for saved mining job", &job.key_file)
+            }
+        }
+    }
+
+    pub fn stop(&mut self) {
+        self.mining.store(false, Ordering::SeqCst);
+        self.running.store(false, Ordering::SeqCst);
+        self.cond_var.notify_all();
+    }
+
+    pub fn start_mining_thread(&mut self) {
+        let context = Arc::clone(&self.context);
+        let jobs = self.jobs.clone();
+        let running = self.running.clone();
+        let mining = self.mining.clone();
+        let paused = self.paused.clone();
+        let tip = self.tip.clone();
+        let generate = self.generate.clone();
+        let cond_var = self.cond_var.clone();
+        thread::spawn(move || {
+            Miner::run_main_loop(&context, jobs, running, mining, paused, tip, generate, cond_var);
+        });
+
+        // Add events listener to a [Bus]
+        let running = self.running.clone();
+        let mining = self.mining.clone();
+        let paused = self.paused.clone();
+        let tip = self.tip.clone();
+        let generate = self.generate.clone();
+        let cond_var = self.cond_var.clone();
+        register(move |_uuid, e| {
+            match e {
+                Event::ActionQuit => { running.store(false, Ordering::Relaxed); }
+                Event::NewBlock { .. } => { tip.fetch_add(1, Ordering::SeqCst); }
+                Event::BlockchainChanged { .. } => {}
+                Event::ActionStopMining => {
+                    mining.store(false, Ordering::SeqCst);
+                }
+                Event::ActionPauseMining => { paused.store(true, Ordering::SeqCst); }
+                Event::ActionResumeMining => { paused.store(false, Ordering::SeqCst); }
+                Event::ActionGenerateBlock => {
+                    generate.fetch_add(1, Ordering::SeqCst);
+                    cond_var.notify_one();
+                }
+                _ => {}
+            }
+            true
+        });
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn run_main_loop(context: &Arc<Mutex<Context>>, jobs: Arc<Mutex<Vec<MineJob>>>, running: Arc<AtomicBool>, mining: Arc<AtomicBool>, paused: Arc<AtomicBool>, tip: Arc<AtomicU64>, generate: Arc<AtomicU64>, cond_var: Arc<Condvar>) {
+        running.store(true, Ordering::SeqCst);
+        let on_demand = context.lock().unwrap().chain.get_kind().is_on_demand();
+        let delay = Duration::from_secs(30);
+        let mut current_job: Option<MineJob> = None;
+        let mut saved_jobs = String::new();
+        while running.load(Ordering::SeqCst) {
+            saved_jobs = save_jobs(&jobs.lock().unwrap(), &current_job, saved_jobs);
+            if let Some(ref cur_job) = current_job {
+                // If we are mining signing block
+                if mining.load(Ordering::Relaxed) && cur_job.is_signing() {
+                    sleep(delay);
+                    continue;
+                }
+
+                // If we are mining something ours
+                if mining.load(Ordering::Relaxed) && cur_job.is_full() {
+                    let mut signing_waits = false;
+                    let mut jobs = jobs.lock().unwrap();
+                    if jobs.len() > 0 {
+                        debug!("Got new job to mine");
+                        let job = jobs.remove(0);
+                        // If we have some signing job
+                        if job.is_signing() && job.is_due() {
+                            info!("Replacing current mining job with signing job!");
+                            // We cancel current job, waiting for threads to finish
+                            mining.store(false, Ordering::SeqCst);
+                            sleep(Duration::from_millis(100));
+                            // Return current job to queue
+                            jobs.insert(0, current_job.take().unwrap());
+
+                            mining.store(true, Ordering::SeqCst);
+                            current_job = Some(job.clone());
+                            Miner::mine_internal(Arc::clone(context), job, mining.clone(), paused.clone(), tip.clone());
+                            continue;
+                        } else {
+                            debug!("This job will wait for now");
+                            signing_waits = job.is_signing();
+                            jobs.insert(0, job);
+                        }
+                    }
+
+                    if !signing_waits {
+                        if let Ok(context) = context.lock() {
+                            let keystores = context.get_keystores();
+                            // Ask the blockchain if we have to sign something
+                            if let Some((block, keystore)) = context.chain.get_sign_block(keystores) {
+                                info!("Got signing job, adding to queue");
+                                // We start mining sign block after some time, not everyone in the same time
+                                let start = Utc::now().timestamp() + (rand::random::<i64>() % BLOCK_SIGNERS_START_RANDOM);
+                                jobs.push(MineJob { start, block, signer: Arc::new(keystore) });
+                            }
+                        }
+                    }
+                    let _lock = cond_var.wait_timeout(jobs, delay).expect("Error in wait lock!");
+                }
+            } else {
+                let mut jobs = jobs.lock().unwrap();
+                if jobs.len() > 0 {
+                    debug!("Got new job to mine");
+                    let job = jobs.remove(0);
+                    if job.is_due() && may_start(on_demand, &generate) {
+                        mining.store(true, Ordering::SeqCst);
+                        current_job = Some(job.clone());
+                        Miner::mine_internal(Arc::clone(context), job, mining.clone(), paused.clone(), tip.clone());
+                    } else {
+                        debug!("This job will wait for now");
+                        jobs.insert(0, job);
+                    }
+                } else {
+                    // If our queue is empty
+                    if let Ok(context) = context.lock() {
+                        let keystores = context.get_keystores();
+                        // Ask the blockchain if we have to sign something
+                        if let Some((block, keystore)) = context.chain.get_sign_block(keystores) {
+                            info!("Got signing job, adding to queue");
+                            // We start mining sign block after some time, not everyone in the same time
+                            let start = Utc::now().timestamp() + (rand::random::<i64>() % BLOCK_SIGNERS_START_RANDOM);
+                            jobs.push(MineJob { start, block, signer: Arc::new(keystore) });
+                        }
+                    }
+                }
+                let _lock = cond_var.wait_timeout(jobs, delay).expect("Error in wait lock!");
+            }
+
+            if !mining.load(Ordering::Relaxed) {
+                current_job = None;
+            }
+        }
+        info!("Stopped mining queue thread");
+    }
+
+    pub fn is_mining(&self) -> bool {
+        self.running.load(Ordering::Relaxed)
+    }
+
+    fn mine_internal(context: Arc<Mutex<Context>>, mut job: MineJob, mining: Arc<AtomicBool>, paused: Arc<AtomicBool>, tip: Arc<AtomicU64>) {
+        // Clear signature and hash just in case
+        job.block.signature = Bytes::default();
+        job.block.hash = Bytes::default();
+        job.block.version = CHAIN_VERSION;
+        // If this block needs to be a signer
+        if job.block.index > 0 && !job.block.prev_block_hash.is_empty() {
+            info!("Mining signing block");
+            job.block.pub_key = job.signer.public_key();
+            if !check_public_key_strength(&job.block.pub_key, KEYSTORE_DIFFICULTY) {
+                warn!("Can not mine block with weak public key!");
+                post(Event::MinerStopped { success: false, full: false });
+                mining.store(false, Ordering::SeqCst);
+                return;
+            }
+            let new_block = context.lock().unwrap().chain.update_sign_block_for_mining(job.block);
+            match new_block {
+                None => {
+                    warn!("We missed block to lock");
+                    post(Event::MinerStopped { success: false, full: false });
+                    mining.store(false, Ordering::SeqCst);
+                    return;
+                }
+                Some(block) => {
+                    job.block = block;
+                }
+            }
+        } else {
+            job.block.index = context.lock().unwrap().chain.get_height() + 1;
+            job.block.prev_block_hash = match context.lock().unwrap().chain.last_block() {
+                None => Bytes::default(),
+                Some(block) => block.hash.clone()
+            };
+            // Other blocks of this key could come while the job waited in queue
+            if let Some(transaction) = &job.block.transaction {
+                let context = context.lock().unwrap();
+                let zone = transaction.get_domain_data().map(|data| data.zone).unwrap_or_default();
+                if context.chain.is_registration_limited(&transaction.identity, &zone, &transaction.signing, job.block.index) {
+                    warn!("Dropping mining of domain in zone {}, the key has registered enough domains there recently", &zone);
+                    post(Event::MinerStopped { success: false, full: true });
+                    mining.store(false, Ordering::SeqCst);
+                    return;
+                }
+                // Freed domain that somebody else has taken during auction needs more difficult block
+                let (height, now) = (job.block.index - 1, Utc::now().timestamp());
+                if !context.chain.is_id_available(height, now, &transaction.identity, &job.block.pub_key) {
+                    if let Some(difficulty) = context.chain.get_bid_difficulty(&transaction.identity, job.block.index, now) {
+                        info!("Bidding for domain in auction with difficulty {}", difficulty);
+                        job.block.difficulty = max(job.block.difficulty, difficulty);
+                    }
+                }
+            }
+        }
+
+        let (lower, threads, schedule) = {
+            post(Event::MinerStarted);
+            let mut context = context.lock().unwrap();
+            context.miner_state.mining = true;
+            context.miner_state.full = job.block.transaction.is_some();
+            context.miner_state.job = if job.is_full() { Some(job.clone()) } else { None };
+            (context.settings.mining.lower, context.settings.mining.threads, context.settings.mining.clone())
+        };
+        let cpus = num_cpus::get();
+        let threads = match threads {
+            0 => cpus,
+            _ => threads
+        } as u32;
+        debug!("Starting {} threads for mining", threads);
+        debug!("Mining block {}", serde_json::to_string(&job.block).unwrap());
+        let thread_spawn_interval = Duration::from_millis(100);
+        let live_threads = Arc::new(AtomicU32::new(0u32));
+        // Hashes made by all threads, to measure total speed
+        let hashes = Arc::new(AtomicU64::new(0u64));
+        for cpu in 0..threads {
+            let context = Arc::clone(&context);
+            let job = job.clone();
+            let mining = Arc::clone(&mining);
+            let paused = Arc::clone(&paused);
+            let tip = Arc::clone(&tip);
+            let schedule = schedule.clone();
+            let live_threads = Arc::clone(&live_threads);
+            let hashes = Arc::clone(&hashes);
+            thread::spawn(move || {
+                live_threads.fetch_add(1, Ordering::SeqCst);
+                if lower {
+                    setup_miner_thread(cpu);
+                }
+                let full = job.block.transaction.is_some();
+                match find_hash(Arc::clone(&context), job.block, Arc::clone(&mining), &paused, &schedule, &hashes, &tip, cpu) {
+                    None => {
+                        if live_threads.load(Ordering::Relaxed) >= threads {
+                            debug!("Mining was cancelled");
+                        }
+                        let count = live_threads.fetch_sub(1, Ordering::SeqCst);
+                        // If this is the last thread, but mining was not stopped by another thread
+                        if count == 1 {
+                            if let Ok(mut context) = context.lock() {
+                                context.miner_state.mining = false;
+                                context.miner_state.job = None;
+                            }
+                            post(Event::MinerStopped { success: false, full });
+                        }
+                    }
+                    Some(block) if job.signer.is_offline() => {
+                        save_unsigned_block(&block);
+                        if let Ok(mut context) = context.lock() {
+                            context.miner_state.mining = false;
+                            context.miner_state.job = None;
+                        }
+                        post(Event::MinerStopped { success: false, full });
+                        mining.store(false, Ordering::SeqCst);
+                    }
+                    Some(mut block) => {
+                        let index = block.index;
+                        let mut context = context.lock().unwrap();
+                        let mut success = false;
+                        match job.signer.sign(&block.as_bytes_compact()) {
+                            Some(signature) => block.signature = signature,
+                            None => warn!("Signer refused to sign mined block!")
+                        }
+                        if context.chain.check_new_block(&block) != BlockQuality::Good {
+                            warn!("Error adding mined block!");
+                            if index == 0 {
+                                error!("To mine genesis block you need to make 'origin' an empty string in config.");
+                            }
+                        } else {
+                            info!("Mined good block!");
+                            if block.index == 1 {
+                                context.settings.origin = block.hash.to_string();
+                            }
+                            context.chain.add_block(block);
+                            success = true;
+                        }
+                        context.miner_state.mining = false;
+                        context.miner_state.job = None;
+                        post(Event::MinerStopped { success, full });
+                        mining.store(false, Ordering::SeqCst);
+                    }
+                }
+            });
+            thread::sleep(thread_spawn_interval);
+        }
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn find_hash(context: Arc<Mutex<Context>>, mut block: Block, running: Arc<AtomicBool>, paused: &AtomicBool, schedule: &Mining, hashes: &AtomicU64, tip: &AtomicU64, thread: u32) -> Option<Block> {
+    let mut target_diff = block.difficulty;
+    let full = block.transaction.is_some();
+    let mut digest = Blakeout::new();
+    let mut max_diff = 0;
+    let mut tip_seen;
+    loop {
+        if !wait_while_paused(&running, paused, schedule, thread) {
+            return None;
+        }
+        block.random = rand::random();
+        block.timestamp = Utc::now().timestamp();
+        let waiting_signers = {
+            let context = context.lock().unwrap();
+            tip_seen = tip.load(Ordering::Relaxed);
+            if let Some(b) = context.chain.last_block() {
+                block.prev_block_hash = b.hash.clone();
+                block.index = b.index + 1;
+            }
+            block.version = context.chain.get_block_version(block.index);
+            // Difficulty of domains depends on the height, it could grow while we were waiting
+            if full {
+                target_diff = max(target_diff, context.chain.get_needed_difficulty(&block));
+                block.difficulty = target_diff;
+            }
+            context.chain.is_waiting_signers()
+        };
+        if !running.load(Ordering::Relaxed) {
+            return None;
+        }
+        if full && waiting_signers {
+            //trace!("Mining full block is not allowed until previous is not signed");
+            // We can't mine now, as we need to wait for block to be signed
+            thread::sleep(Duration::from_millis(5000));
+            continue;
+        }
+
+        let argon2 = block.version & BLOCK_FLAG_ARGON2 != 0;
+        let mut argon2_hash = Bytes::default();
+        let mut time = Instant::now();
+        let mut work_time = Instant::now();
+        let mut prev_nonce = 0;
+        for nonce in 0..u64::MAX {
+            if !running.load(Ordering::Relaxed) {
+                return None;
+            }
+            block.nonce = nonce;
+
+            let diff = if argon2 {
+                argon2_hash = argon2_data(&block.as_bytes_compact());
+                hash_difficulty(argon2_hash.as_slice())
+            } else {
+                digest.reset();
+                digest.update(&block.as_bytes_compact());
+                hash_difficulty(digest.result())
+            };
+            if diff >= target_diff {
+                block.hash = if argon2 { argon2_hash } else { Bytes::from_bytes(digest.result()) };
+                return Some(block);
+            }
+            if diff > max_diff {
+                max_diff = diff;
+            }
+            // If we are limited by CPU usage we rest proportionally to the work done
+            if schedule.cpu_limit < 100 {
+                let work = work_time.elapsed();
+                if work >= CPU_LIMIT_PERIOD {
+                    let limit = max(schedule.cpu_limit, 1) as u32;
+                    thread::sleep(work * (100 - limit) / limit);
+                    work_time = Instant::now();
+                }
+            }
+
+            let elapsed = time.elapsed().as_millis();
+            if elapsed >= 1000 {
+                if !wait_while_paused(&running, paused, schedule, thread) {
+                    return None;
+                }
+                block.timestamp = Utc::now().timestamp();
+                if elapsed > 10000 {
+                    let seconds = elapsed as u64 / 1000;
+                    let speed = (nonce - prev_nonce) / seconds;
+                    //debug!("Mining speed {} H/s, max difficulty {}", speed, max_diff);
+                    post(Event::MinerStats { thread, speed, max_diff, target_diff });
+                    hashes.fetch_add(nonce - prev_nonce, Ordering::Relaxed);
+                    // The first thread reports the speed of all threads
+                    if thread == 0 {
+                        let speed = hashes.swap(0, Ordering::Relaxed) / seconds;
+                        let eta = expected_hashes(target_diff) / max(speed, 1);
+                        post(Event::MinerProgress { speed, target_diff, eta });
+                    }
+                    time = Instant::now();
+                    prev_nonce = nonce;
+                }
+            }
+
+            // Some block was added to our chain, we need to rebuild our block on top of it
+            if block.index > 1 && tip.load(Ordering::Relaxed) != tip_seen {
+                if !full && context.lock().unwrap().chain.get_height() >= block.index {
+                    //trace!("Blockchain changed while mining signing block, dropping work");
+                    running.store(false, Ordering::SeqCst);
+                    return None;
+                }
+                break;
+            }
+        }
+    }
+}
+
+/// Measures mining speed of PoW for this block version in given count of threads, returns hashes per second
+pub fn benchmark(version: u32, threads: usize, duration: Duration) -> u64 {
+    let hashes = Arc::new(AtomicU64::new(0u64));
+    let handles: Vec<_> = (0..threads).map(|_| {
+        let hashes = Arc::clone(&hashes);
+        thread::spawn(move || {
+            let mut block = Block::new(None, Bytes::default(), Bytes::default(), 0);
+            block.version = version;
+            block.random = rand::random();
+            let mut digest = Blakeout::new();
+            let start = Instant::now();
+            let mut count = 0u64;
+            while start.elapsed() < duration {
+                block.nonce = count;
+                let data = block.as_bytes_compact();
+                if version & BLOCK_FLAG_ARGON2 != 0 {
+                    argon2_data(&data);
+                } else {
+                    digest.reset();
+                    digest.update(&data);
+                }
+                count += 1;
+            }
+            hashes.fetch_add(count, Ordering::Relaxed);
+        })
+    }).collect();
+    for handle in handles {
+        let _ = handle.join();
+    }
+    hashes.load(Ordering::Relaxed) / max(duration.as_secs(), 1)
+}
+
+/// Makes transaction that renews the domain with the same data, or moves it to new (signing, encryption) keys.
+/// Returns it with the public key of current owner, that has to sign the block with it.
+pub fn make_renewal_transaction(chain: &Chain, domain: &str, new_keys: Option<(Bytes, Bytes)>) -> Result<(Transaction, Bytes), MineResult> {
+    let transaction = match chain.get_domain_transaction_and_state(domain).0 {
+        Some(transaction) => transaction,
+        None => return Err(MineResult::WrongName)
+    };
+    let data = match transaction.get_domain_data() {
+        Some(data) => data,
+        None => return Err(MineResult::WrongData)
+    };
+    let owner = transaction.signing.clone();
+    let (signing, encryption) = new_keys.unwrap_or((transaction.signing, transaction.encryption));
+    Ok((Transaction::from_domain_data(CLASS_DOMAIN, domain, data, signing, encryption), owner))
+}
+
+/// Makes block that renews the domain owned by some of our keys, or moves it to new (signing, encryption) keys.
+/// The block has to be mined and signed by the current owner.
+fn make_renewal_block(context: &Context, domain: &str, new_keys: Option<(Bytes, Bytes)>) -> Result<Block, MineResult> {
+    let (transaction, owner) = make_renewal_transaction(&context.chain, domain, new_keys)?;
+    let height = context.chain.get_height();
+    let difficulty = context.chain.get_domain_difficulty(height + 1);
+    let discount = context.chain.get_identity_discount(&transaction.identity, true, height, Utc::now().timestamp());
+    Ok(Block::new(Some(transaction), owner, Bytes::default(), difficulty - discount))
+}
+
+/// Saves mined block that needs to be signed by offline key
+fn save_unsigned_block(block: &Block) {
+    let file_name = format!("{}{}.json", UNSIGNED_BLOCK_PREFIX, block.index);
+    match fs::write(&file_name, serde_json::to_string_pretty(block).unwrap()) {
+        Ok(_) => warn!("Block {} is mined, sign it by 'alfis --sign-block {}' where your key is, and import it back by 'alfis --import-block' quickly, before other blocks are added", block.index, &file_name),
+        Err(e) => error!("Error saving unsigned block to {}: {}", &file_name, e)
+    }
+}
+
+/// Saves current and queued domain jobs to file if they have changed since the last time.
+/// Jobs with keys that are not saved to files are not saved, we can't sign them after restart.
+/// Returns saved data to compare with it next time.
+fn save_jobs(jobs: &[MineJob], current: &Option<MineJob>, saved: String) -> String {
+    let list: Vec<SavedJob> = current.iter()
+        .chain(jobs.iter())
+        .filter(|job| job.is_full() && !job.signer.key_file().is_empty())
+        .map(|job| SavedJob { block: job.block.clone(), key_file: job.signer.key_file().to_owned(), start: job.start })
+        .collect();
+    let data = serde_json::to_string(&list).unwrap();
+    if data == saved {
+        return saved;
+    }
+    let result = if list.is_empty() {
+        match fs::remove_file(MINING_JOBS_FILE) {
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(()),
+            result => result
+        }
+    } else {
+        fs::write(MINING_JOBS_FILE, &data)
+    };
+    if let Err(e) = result {
+        warn!("Error saving mining jobs: {}", e);
+    }
+    data
+}
+
+/// In regtest mode jobs are started only when blocks are requested
+fn may_start(on_demand: bool, generate: &AtomicU64) -> bool {
+    !on_demand || generate.fetch_update(Ordering::SeqCst, Ordering::SeqCst, |count| count.checked_sub(1)).is_ok()
+}
+
+/// Waits while mining is paused by user or by schedule.
+/// Returns false if mining was stopped while we were waiting.
+fn wait_while_paused(running: &AtomicBool, paused: &AtomicBool, schedule: &Mining, thread: u32) -> bool {
+    let mut was_paused = false;
+    loop {
+        if !running.load(Ordering::Relaxed) {
+            return false;
+        }
+        let hour = Local::now().hour() as u8;
+        if !paused.load(Ordering::Relaxed) && schedule.is_allowed(hour, on_ac_power()) {
+            if was_paused && thread == 0 {
+                info!("Mining resumed");
+                post(Event::MinerPaused { paused: false });
+            }
+            return true;
+        }
+        if !was_paused && thread == 0 {
+            info!("Mining paused");
+            post(Event::MinerPaused { paused: true });
+        }
+        was_paused = true;
+        thread::sleep(Duration::from_secs(1));
+    }
+}
@@ -0,0 +1,61 @@
+//! Sanity check of our clock by timestamps that peers send in handshake.
+//! Blocks from the future are rejected and domains expire by time, so a node with wrong clock
+//! rejects good blocks, and others reject blocks that it mines.
+
+/// Offset of our clock from the clocks of peers that we warn about, in seconds
+pub const MAX_CLOCK_SKEW_SEC: i64 = 30;
+/// We need timestamps from at least this many peers to judge our clock
+pub const MIN_CLOCK_PEERS: usize = 3;
+
+/// Median offset of peer clocks from ours, positive if our clock is behind
+pub fn median_offset(offsets: &[i64]) -> Option<i64> {
+    if offsets.len() < MIN_CLOCK_PEERS {
+        return None;
+    }
+    let mut offsets = offsets.to_vec();
+    offsets.sort_unstable();
+    Some(offsets[offsets.len() / 2])
+}
+
+/// Remembers if our clock is skewed, to tell about it only when it changes
+#[derive(Default)]
+pub struct ClockCheck {
+    skewed: bool
+}
+
+impl ClockCheck {
+    /// Returns the median offset if our clock became skewed or became fine again
+    pub fn update(&mut self, offsets: &[i64]) -> Option<i64> {
+        let offset = median_offset(offsets)?;
+        let skewed = offset.abs() > MAX_CLOCK_SKEW_SEC;
+        if skewed == self.skewed {
+            return None;
+        }
+        self.skewed = skewed;
+        Some(offset)
+    }
+
+    pub fn is_skewed(&self) -> bool {
+        self.skewed
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::p2p::clock::{median_offset, ClockCheck};
+
+    #[test]
+    fn test_clock_check() {
+        assert_eq!(median_offset(&[100, 200]), None);
+        // One peer with broken clock doesn't matter
+        assert_eq!(median_offset(&[1, -2, 5000]), Some(1));
+
+        let mut check = ClockCheck::default();
+        assert_eq!(check.update(&[0, 1, 2]), None);
+        assert_eq!(check.update(&[-90, -100, 10, -95]), Some(-90));
+        assert!(check.is_skewed());
+        assert_eq!(check.update(&[-90, -100, -95]), None);
+        assert_eq!(check.update(&[0, 2, -1]), Some(0));
+        assert!(!check.is_skewed());
+    }
+}
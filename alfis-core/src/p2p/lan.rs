@@ -0,0 +1,111 @@
+//! Discovery of other nodes in local network by UDP broadcasts.
+//! Every node sends its listen port and random id, and the nodes that hear it connect to it as usual.
+
+use std::io;
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+use std::time::Instant;
+
+#[allow(unused_imports)]
+use log::{debug, error, info, trace, warn};
+use mio::net::UdpSocket;
+use mio::{Interest, Registry, Token};
+
+use crate::Bytes;
+
+const MAGIC: &[u8] = b"ALFIS";
+/// How often we tell about ourselves
+const ANNOUNCE_PERIOD_SEC: u64 = 30;
+
+pub struct LanDiscovery {
+    socket: UdpSocket,
+    port: u16,
+    packet: Vec<u8>,
+    origin: Bytes,
+    my_id: String,
+    timer: Option<Instant>
+}
+
+impl LanDiscovery {
+    /// Creates discovery socket on `port`, `listen_port` is the port that other nodes will connect to
+    pub fn new(port: u16, listen_port: u16, origin: Bytes, my_id: &str) -> io::Result<Self> {
+        let socket = UdpSocket::bind(SocketAddr::new(IpAddr::V4(Ipv4Addr::UNSPECIFIED), port))?;
+        socket.set_broadcast(true)?;
+        let packet = make_packet(&origin, my_id, listen_port);
+        Ok(LanDiscovery { socket, port, packet, origin, my_id: my_id.to_owned(), timer: None })
+    }
+
+    pub fn register(&mut self, registry: &Registry, token: Token) -> io::Result<()> {
+        registry.register(&mut self.socket, token, Interest::READABLE)
+    }
+
+    /// Sends our announce to all hosts in local network, if it is time to do it
+    pub fn announce(&mut self) {
+        if let Some(timer) = self.timer {
+            if timer.elapsed().as_secs() < ANNOUNCE_PERIOD_SEC {
+                return;
+            }
+        }
+        let addr = SocketAddr::new(IpAddr::V4(Ipv4Addr::BROADCAST), self.port);
+        if let Err(e) = self.socket.send_to(&self.packet, addr) {
+            debug!("Error sending LAN announce: {}", e);
+        }
+        self.timer = Some(Instant::now());
+    }
+
+    /// Reads all received announces and returns addresses of nodes from them
+    pub fn receive(&mut self) -> Vec<SocketAddr> {
+        let mut result = Vec::new();
+        let mut buf = [0u8; 256];
+        while let Ok((len, from)) = self.socket.recv_from(&mut buf) {
+            if let Some(port) = parse_packet(&buf[..len], &self.origin, &self.my_id) {
+                trace!("Found node {}:{} in local network", from.ip(), port);
+                result.push(SocketAddr::new(from.ip(), port));
+            }
+        }
+        result
+    }
+}
+
+/// Makes packet: magic, origin of blockchain, listen port and length-prefixed random id
+fn make_packet(origin: &Bytes, my_id: &str, listen_port: u16) -> Vec<u8> {
+    let mut packet = Vec::with_capacity(MAGIC.len() + origin.length() + 3 + my_id.len());
+    packet.extend_from_slice(MAGIC);
+    packet.extend_from_slice(origin.as_slice());
+    packet.extend_from_slice(&listen_port.to_be_bytes());
+    packet.push(my_id.len() as u8);
+    packet.extend_from_slice(my_id.as_bytes());
+    packet
+}
+
+/// Gets listen port from packet if it came from other node of the same blockchain
+fn parse_packet(packet: &[u8], origin: &Bytes, my_id: &str) -> Option<u16> {
+    let packet = packet.strip_prefix(MAGIC)?;
+    let packet = packet.strip_prefix(origin.as_slice())?;
+    if packet.len() < 3 {
+        return None;
+    }
+    let port = u16::from_be_bytes([packet[0], packet[1]]);
+    let id = packet.get(3..3 + packet[2] as usize)?;
+    if id == my_id.as_bytes() || port == 0 {
+        return None;
+    }
+    Some(port)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::p2p::lan::{make_packet, parse_packet};
+    use crate::Bytes;
+
+    #[test]
+    fn test_packet() {
+        let origin = Bytes::from_bytes(&[1u8; 32]);
+        let packet = make_packet(&origin, "abcdef", 4244);
+        assert_eq!(parse_packet(&packet, &origin, "123456"), Some(4244));
+        // Our own packet
+        assert_eq!(parse_packet(&packet, &origin, "abcdef"), None);
+        // Other blockchain
+        assert_eq!(parse_packet(&packet, &Bytes::from_bytes(&[2u8; 32]), "123456"), None);
+        assert_eq!(parse_packet(&packet[..40], &origin, "123456"), None);
+    }
+}
@@ -0,0 +1,167 @@
+extern crate serde;
+extern crate serde_json;
+
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+use serde_cbor::Error;
+
+use crate::commons::BLOCKS_BATCH_COUNT;
+use crate::Bytes;
+
+// Limits of fields in messages, they are much bigger than real values
+const MAX_VERSION_LEN: usize = 32;
+const MAX_ORIGIN_LEN: usize = 64;
+const MAX_ID_LEN: usize = 32;
+const MAX_HASH_LEN: usize = 64;
+const MAX_PEERS: usize = 50;
+const MAX_ADDR_LEN: usize = 64;
+
+#[derive(Debug, Serialize, Deserialize)]
+pub enum Message {
+    Error,
+    Hand {
+        app_version: String,
+        origin: String,
+        version: u32,
+        public: bool,
+        rand_id: String,
+        #[serde(default)]
+        flags: u32,
+        #[serde(default)]
+        height: u64,
+        /// Our clock, peers compare it with theirs. Old nodes don't send it.
+        #[serde(default)]
+        time: i64
+    },
+    Shake {
+        app_version: String,
+        origin: String,
+        version: u32,
+        public: bool,
+        rand_id: String,
+        height: u64,
+        #[serde(default)]
+        flags: u32,
+        #[serde(default)]
+        time: i64
+    },
+    Ping { height: u64, hash: Bytes },
+    Pong { height: u64, hash: Bytes },
+    Twin,
+    Loop,
+    GetPeers,
+    Peers { peers: Vec<String> },
+    GetBlock { index: u64 },
+    Block { index: u64, block: Vec<u8> },
+    /// Announcement of new block, full block is requested only by those who don't have it
+    Announce { index: u64, hash: Bytes },
+    GetBlocks { from: u64, count: u64 },
+    Blocks { from: u64, blocks: Vec<Vec<u8>> }
+}
+
+impl Message {
+    pub fn from_bytes(bytes: Vec<u8>) -> Result<Message, Error> {
+        serde_cbor::from_slice(bytes.as_slice())
+    }
+
+    /// Checks that the message doesn't exceed sane limits, so it can't be used to waste our resources
+    pub fn is_valid(&self) -> bool {
+        match self {
+            Message::Hand { app_version, origin, rand_id, .. } | Message::Shake { app_version, origin, rand_id, .. } => {
+                app_version.len() <= MAX_VERSION_LEN && origin.len() <= MAX_ORIGIN_LEN && rand_id.len() <= MAX_ID_LEN
+            }
+            Message::Ping { hash, .. } | Message::Pong { hash, .. } | Message::Announce { hash, .. } => hash.length() <= MAX_HASH_LEN,
+            Message::Peers { peers } => peers.len() <= MAX_PEERS && peers.iter().all(|peer| peer.len() <= MAX_ADDR_LEN),
+            Message::GetBlocks { from, count } => is_valid_index(*from) && *count <= BLOCKS_BATCH_COUNT,
+            Message::Blocks { from, blocks } => is_valid_index(*from) && blocks.len() as u64 <= BLOCKS_BATCH_COUNT,
+            _ => true
+        }
+    }
+
+    pub fn hand(app_version: &str, origin: &str, version: u32, public: bool, rand_id: &str, height: u64, flags: u32) -> Self {
+        Message::Hand {
+            app_version: app_version.to_owned(),
+            origin: origin.to_owned(),
+            version,
+            public,
+            rand_id: rand_id.to_owned(),
+            flags,
+            height,
+            time: Utc::now().timestamp()
+        }
+    }
+
+    pub fn shake(app_version: &str, origin: &str, version: u32, public: bool, rand_id: &str, height: u64, flags: u32) -> Self {
+        Message::Shake {
+            app_version: app_version.to_owned(),
+            origin: origin.to_owned(),
+            version,
+            public,
+            rand_id: rand_id.to_owned(),
+            height,
+            flags,
+            time: Utc::now().timestamp()
+        }
+    }
+
+    pub fn ping(height: u64, hash: Bytes) -> Self {
+        Message::Ping { height, hash }
+    }
+
+    pub fn pong(height: u64, hash: Bytes) -> Self {
+        Message::Pong { height, hash }
+    }
+
+    pub fn block(height: u64, block: Vec<u8>) -> Self {
+        Message::Block { index: height, block }
+    }
+}
+
+/// Blocks start from 1, and indexes are stored as i64 in DB
+fn is_valid_index(index: u64) -> bool {
+    index > 0 && index <= i64::MAX as u64
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::p2p::Message;
+
+    /// Old nodes don't send flags, height and time in handshake, we must understand them
+    #[test]
+    fn test_old_hand() {
+        #[derive(serde::Serialize)]
+        enum OldMessage {
+            Hand { app_version: String, origin: String, version: u32, public: bool, rand_id: String }
+        }
+        let old = OldMessage::Hand {
+            app_version: String::from("0.8.2"),
+            origin: String::from("origin"),
+            version: 1,
+            public: true,
+            rand_id: String::from("abc")
+        };
+        let data = serde_cbor::to_vec(&old).unwrap();
+        match Message::from_bytes(data).unwrap() {
+            Message::Hand { flags, height, rand_id, time, .. } => {
+                assert_eq!(flags, 0);
+                assert_eq!(height, 0);
+                assert_eq!(time, 0);
+                assert_eq!(rand_id, "abc");
+            }
+            _ => panic!("Wrong message")
+        }
+    }
+
+    #[test]
+    fn test_limits() {
+        let peers = (0..100).map(|i| format!("10.0.0.{}:4244", i)).collect();
+        assert!(!Message::Peers { peers }.is_valid());
+        assert!(Message::Peers { peers: vec![String::from("10.0.0.1:4244")] }.is_valid());
+        assert!(!Message::GetBlocks { from: 1, count: 1000 }.is_valid());
+        assert!(Message::GetBlocks { from: 1, count: 50 }.is_valid());
+        assert!(!Message::GetBlocks { from: 0, count: 50 }.is_valid());
+        assert!(!Message::GetBlocks { from: u64::MAX, count: 50 }.is_valid());
+        assert!(!Message::Blocks { from: u64::MAX, blocks: vec![Vec::new()] }.is_valid());
+        assert!(!Message::hand(&"0".repeat(100), "origin", 1, true, "id", 1, 0).is_valid());
+    }
+}
@@ -1,8 +1,13 @@
+pub mod clock;
+pub mod lan;
 pub mod message;
 pub mod network;
 pub mod peer;
 pub mod peers;
+pub mod socks;
 pub mod state;
+pub mod throttle;
+pub mod upnp;
 
 pub use message::Message;
 pub use network::Network;
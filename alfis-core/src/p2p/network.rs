@@ -1,16 +1,17 @@
 extern crate serde;
 extern crate serde_json;
 
-use std::cmp::max;
+use std::cmp::{max, min};
 use std::collections::{HashMap, HashSet};
 use std::io::{Error, ErrorKind, Read, Write};
-use std::net::{IpAddr, Shutdown, SocketAddr, SocketAddrV4, ToSocketAddrs};
+use std::net::{IpAddr, Ipv4Addr, Shutdown, SocketAddr, ToSocketAddrs};
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::{Arc, Mutex};
 use std::time::{Duration, Instant};
 use std::{io, thread};
 
 use byteorder::{BigEndian, ReadBytesExt, WriteBytesExt};
+use chrono::Utc;
 #[allow(unused_imports)]
 use log::{debug, error, info, trace, warn};
 use mio::event::Event;
@@ -24,10 +25,17 @@ use crate::blockchain::types::BlockQuality;
 use crate::commons::*;
 use crate::crypto::Chacha;
 use crate::eventbus::{post, register};
-use crate::p2p::{Message, Peer, Peers, State};
-use crate::{Block, Bytes, Context};
+use crate::p2p::clock::ClockCheck;
+use crate::p2p::lan::LanDiscovery;
+use crate::p2p::peers::canonical_addr;
+use crate::p2p::throttle::Throttle;
+use crate::p2p::{upnp, Message, Peer, Peers, State};
+use crate::{Block, Bytes, Chain, Context};
 
 const SERVER: Token = Token(0);
+/// Additional IPv4 listener for systems where IPv6 socket doesn't accept IPv4 connections
+const SERVER_V4: Token = Token(1);
+const LAN: Token = Token(2);
 
 pub struct Network {
     context: Arc<Mutex<Context>>,
@@ -37,7 +45,10 @@ pub struct Network {
     // States of peer connections, and some data to send when sockets become writable
     peers: Peers,
     // Orphan blocks from future
-    future_blocks: HashMap<u64, Block>
+    future_blocks: HashMap<u64, Block>,
+    upload: Throttle,
+    download: Throttle,
+    clock: ClockCheck
 }
 
 impl Network {
@@ -47,34 +58,106 @@ impl Network {
         let secret_key = StaticSecret::new(&mut thread_rng);
         let public_key = PublicKey::from(&secret_key);
         let peers = Peers::new();
-        Network { context, secret_key, public_key, token: Token(1), peers, future_blocks: HashMap::new() }
+        let (upload, download) = (Throttle::new(0), Throttle::new(0));
+        Network { context, secret_key, public_key, token: Token(3), peers, future_blocks: HashMap::new(), upload, download, clock: ClockCheck::default() }
     }
 
     pub fn start(&mut self) {
-        let (listen_addr, peers_addrs, yggdrasil_only) = {
+        let (listen_addr, peers_addrs, dns_seeds, yggdrasil_only, proxy, upnp, lan_discovery, origin) = {
             let c = self.context.lock().unwrap();
-            (c.settings.net.listen.clone(), c.settings.net.peers.clone(), c.settings.net.yggdrasil_only)
+            let net = &c.settings.net;
+            self.peers.set_limits(net.max_inbound, net.max_outbound, net.max_per_ip);
+            self.upload = Throttle::new(net.upload_limit * 1024);
+            self.download = Throttle::new(net.download_limit * 1024);
+            let origin = c.settings.get_origin();
+            (
+                net.listen.clone(),
+                net.peers.clone(),
+                net.dns_seeds.clone(),
+                net.yggdrasil_only,
+                net.proxy.clone(),
+                net.upnp,
+                net.lan_discovery,
+                origin
+            )
         };
+        let proxy: Option<SocketAddr> = match proxy.is_empty() {
+            true => None,
+            false => match proxy.parse() {
+                Ok(proxy) => Some(proxy),
+                Err(e) => {
+                    // We must not connect directly if user wants to hide behind the proxy
+                    error!("Wrong proxy address '{}' in config: {}, network is disabled", &proxy, e);
+                    return;
+                }
+            }
+        };
+        if let Some(proxy) = &proxy {
+            info!("All outgoing connections will go through proxy {}", proxy);
+            info!("Incoming connections, UPnP and discovery of nodes in local network are disabled while using proxy");
+            // Nobody can connect to us, so we don't ask others to share our address
+            self.context.lock().unwrap().settings.net.public = false;
+        }
+        let lan_discovery = lan_discovery && proxy.is_none();
+        self.peers.set_proxy(proxy);
 
         let running = Arc::new(AtomicBool::new(true));
-        subscribe_to_bus(Arc::clone(&running));
-
-        // Starting server socket
-        let addr = listen_addr.parse().expect("Error parsing listen address");
-        let mut server = TcpListener::bind(addr).expect("Can't bind to address");
-        debug!("Started node listener on {}", server.local_addr().unwrap());
+        let added_peers = Arc::new(Mutex::new(Vec::new()));
+        let bans_changed = Arc::new(AtomicBool::new(false));
+        subscribe_to_bus(Arc::clone(&running), Arc::clone(&added_peers), Arc::clone(&bans_changed));
 
         let mut events = Events::with_capacity(64);
         let mut poll = Poll::new().expect("Unable to create poll");
-        poll.registry().register(&mut server, SERVER, Interest::READABLE).expect("Error registering poll");
+        // Starting server socket
+        let addr: SocketAddr = listen_addr.parse().expect("Error parsing listen address");
+        let mut servers = Vec::new();
+        if proxy.is_none() {
+            let mut server = TcpListener::bind(addr).expect("Can't bind to address");
+            debug!("Started node listener on {}", server.local_addr().unwrap());
+            if upnp && !upnp::start_port_mapping(server.local_addr().unwrap().port()) {
+                // Nobody can connect to us, so we don't ask others to share our address
+                info!("Port mapping failed, working with outgoing connections only");
+                self.context.lock().unwrap().settings.net.public = false;
+            }
+            poll.registry().register(&mut server, SERVER, Interest::READABLE).expect("Error registering poll");
+            servers.push((SERVER, server));
+            if let Some(mut server) = bind_ipv4_listener(&addr) {
+                debug!("Started additional node listener on {}", server.local_addr().unwrap());
+                poll.registry().register(&mut server, SERVER_V4, Interest::READABLE).expect("Error registering poll");
+                servers.push((SERVER_V4, server));
+            }
+        }
+        let mut lan = match lan_discovery {
+            false => None,
+            true => match LanDiscovery::new(LISTEN_PORT, addr.port(), origin, self.peers.get_my_id()) {
+                Ok(mut lan) => {
+                    lan.register(poll.registry(), LAN).expect("Error registering poll");
+                    info!("Started discovery of nodes in local network");
+                    Some(lan)
+                }
+                Err(e) => {
+                    warn!("Unable to start discovery of nodes in local network: {}", e);
+                    None
+                }
+            }
+        };
 
         // Starting peer connections to bootstrap nodes
-        wait_for_internet(WAIT_FOR_INTERNET);
+        if proxy.is_none() {
+            wait_for_internet(WAIT_FOR_INTERNET);
+        }
         self.peers.connect_peers(&peers_addrs, poll.registry(), &mut self.token, yggdrasil_only);
+        self.peers.connect_seeds(&dns_seeds, poll.registry(), &mut self.token, yggdrasil_only);
+        // And the peers that we remember from previous runs
+        let known_peers = self.context.lock().unwrap().chain.get_known_peers(MAX_NODES * 2);
+        self.peers.add_peers_from_exchange(known_peers);
 
+        self.load_bans();
         let mut ui_timer = Instant::now();
         let mut log_timer = Instant::now();
+        let mut bans_timer = Instant::now();
         let mut bootstrap_timer = Instant::now();
+        let mut bootstrap_delay = BOOTSTRAP_RETRY_MIN;
         let mut connect_timer = Instant::now();
         let mut last_events_time = Instant::now();
         let mut old_blocks = 0u64;
@@ -83,14 +166,23 @@ impl Network {
         let mut old_nodes = 0usize;
         let mut old_banned = 0usize;
         let mut seen_blocks = HashSet::new();
+        let mut announced_hash = self.context.lock().unwrap().chain.get_last_hash();
         loop {
-            if self.peers.get_peers_count() == 0 && bootstrap_timer.elapsed().as_secs() > 60 {
+            if self.peers.get_peers_count() == 0 && bootstrap_timer.elapsed().as_secs() > bootstrap_delay {
                 warn!("Restarting swarm connections...");
-                wait_for_internet(WAIT_FOR_INTERNET);
+                if proxy.is_none() {
+                    wait_for_internet(WAIT_FOR_INTERNET);
+                }
                 // Starting peer connections to bootstrap nodes
                 self.peers.connect_peers(&peers_addrs, poll.registry(), &mut self.token, yggdrasil_only);
+                self.peers.connect_seeds(&dns_seeds, poll.registry(), &mut self.token, yggdrasil_only);
                 bootstrap_timer = Instant::now();
                 last_events_time = Instant::now();
+                // If it doesn't help we will try again later, and so on
+                bootstrap_delay = min(bootstrap_delay * 2, BOOTSTRAP_RETRY_MAX);
+                debug!("Next reconnect to bootstrap nodes in {} seconds", bootstrap_delay);
+            } else if self.peers.get_peers_active_count() > 0 {
+                bootstrap_delay = BOOTSTRAP_RETRY_MIN;
             }
             // Poll Mio for events, blocking until we get an event.
             poll.poll(&mut events, POLL_TIMEOUT)
@@ -98,55 +190,39 @@ impl Network {
             if !running.load(Ordering::SeqCst) {
                 break;
             }
+            // Connections and addresses that we got through the proxy
+            self.peers.update_proxied(poll.registry());
+            // Peers that were added by user while we are running
+            let added: Vec<String> = std::mem::take(&mut *added_peers.lock().unwrap());
+            if !added.is_empty() {
+                self.peers.connect_peers(&added, poll.registry(), &mut self.token, yggdrasil_only);
+            }
 
             // Process each event.
             for event in events.iter() {
                 //trace!("Event for socket {} is {:?}", event.token().0, &event);
                 // We can use the token we previously provided to `register` to determine for which socket the event is.
                 match event.token() {
-                    SERVER => {
-                        //debug!("Event for server socket {} is {:?}", event.token().0, &event);
-                        // If this is an event for the server, it means a connection is ready to be accepted.
-                        while let Ok((mut stream, mut address)) = server.accept() {
-                            // Checking if it is an ipv4-mapped ipv6 if yes convert to ipv4
-                            if address.is_ipv6() {
-                                if let IpAddr::V6(ipv6) = address.ip() {
-                                    if let Some(ipv4) = ipv6.to_ipv4() {
-                                        address = SocketAddr::V4(SocketAddrV4::new(ipv4, address.port()))
-                                    }
-                                }
-                            }
-
-                            if self.peers.is_ignored(&address.ip()) {
-                                debug!("Ignoring connection from banned {:?}", &address.ip());
-                                stream.shutdown(Shutdown::Both).unwrap_or_else(|e| {
-                                    warn!("Error in shutdown, {}", e);
-                                });
-                                let _ = poll.registry().reregister(&mut server, SERVER, Interest::READABLE);
-                                continue;
-                            }
-
-                            if yggdrasil_only && !is_yggdrasil(&address.ip()) {
-                                debug!("Dropping connection from Internet");
-                                stream.shutdown(Shutdown::Both).unwrap_or_else(|e| {
-                                    warn!("Error in shutdown, {}", e);
-                                });
-                                let _ = poll.registry().reregister(&mut server, SERVER, Interest::READABLE);
-                                continue;
-                            }
-
-                            //debug!("Accepted connection from: {} to local IP: {}", address, local_ip);
-                            let token = self.next_token();
-                            poll.registry().register(&mut stream, token, Interest::READABLE).expect("Error registering poll");
-                            let peer = Peer::new(address, stream, State::Connected, true);
-                            self.peers.add_peer(token, peer);
+                    SERVER | SERVER_V4 => {
+                        if let Some((token, server)) = servers.iter_mut().find(|(token, _)| *token == event.token()) {
+                            self.accept_connections(poll.registry(), server, *token, yggdrasil_only);
                         }
-                        if let Err(e) = poll.registry().reregister(&mut server, SERVER, Interest::READABLE) {
-                            panic!("Error reregistering server token!\n{}", e);
+                    }
+                    LAN => {
+                        if let Some(lan) = lan.as_mut() {
+                            for addr in lan.receive() {
+                                self.peers.add_lan_peer(addr);
+                            }
                         }
                     }
                     token => {
                         if !self.handle_connection_event(poll.registry(), event, &mut seen_blocks) {
+                            if let Some(peer) = self.peers.get_peer(&token) {
+                                // Outgoing connection that didn't even shake hands
+                                if !peer.is_inbound() && matches!(peer.get_state(), State::Connecting | State::ServerHandshake) {
+                                    self.context.lock().unwrap().chain.add_peer_failure(&peer.get_addr().to_string());
+                                }
+                            }
                             let _ = self.peers.close_peer(poll.registry(), &token);
                             let blocks = self.context.lock().unwrap().chain.get_height();
                             let keys = self.context.lock().unwrap().chain.get_users_count();
@@ -201,17 +277,36 @@ impl Network {
                         }
                         log_timer = Instant::now();
                         seen_blocks.clear();
+                        self.check_clock();
                     }
-                    if nodes < MAX_NODES && connect_timer.elapsed().as_secs() >= 5 {
+                    if self.peers.need_more_outbound() && connect_timer.elapsed().as_secs() >= 5 {
                         self.peers.connect_new_peers(poll.registry(), &mut self.token, yggdrasil_only);
                         connect_timer = Instant::now();
                     }
                     (blocks, max_height, context.chain.get_last_hash())
                 };
 
+                // Announce new blocks, mined by us or received from others, but not while syncing
+                if hash != announced_hash && height >= max_height {
+                    self.peers.announce_block(poll.registry(), height, &hash);
+                    announced_hash = hash.clone();
+                }
                 let have_blocks: HashSet<u64> = self.future_blocks.values().map(|block| block.index).collect();
-                self.peers.update(poll.registry(), hash, height, max_height, have_blocks);
+                let can_download = self.download.is_available();
+                self.peers.update(poll.registry(), hash, height, max_height, have_blocks, can_download);
                 ui_timer = Instant::now();
+                if let Some(lan) = lan.as_mut() {
+                    lan.announce();
+                }
+            }
+
+            if bans_changed.swap(false, Ordering::SeqCst) {
+                self.load_bans();
+                self.peers.close_banned(poll.registry());
+                bans_timer = Instant::now();
+            } else if bans_timer.elapsed().as_secs() > LOG_REFRESH_DELAY_SEC {
+                self.load_bans();
+                bans_timer = Instant::now();
             }
         }
         if !running.load(Ordering::SeqCst) {
@@ -221,6 +316,61 @@ impl Network {
         }
     }
 
+    /// Warns if our clock differs much from clocks of peers
+    fn check_clock(&mut self) {
+        if let Some(offset) = self.clock.update(&self.peers.get_clock_offsets()) {
+            if self.clock.is_skewed() {
+                warn!("Clock of this computer differs from clocks of other nodes by {} seconds, check its time synchronization", offset.abs());
+                post(crate::event::Event::ClockSkew { offset: -offset });
+            } else {
+                info!("Clock of this computer is in sync with other nodes again");
+            }
+        }
+    }
+
+    fn accept_connections(&mut self, registry: &Registry, server: &mut TcpListener, server_token: Token, yggdrasil_only: bool) {
+        // If this is an event for the server, it means a connection is ready to be accepted.
+        while let Ok((mut stream, address)) = server.accept() {
+            // Checking if it is an ipv4-mapped ipv6 if yes convert to ipv4
+            let address = canonical_addr(address);
+
+            if self.peers.is_ignored(&address.ip()) {
+                debug!("Ignoring connection from banned {:?}", &address.ip());
+                stream.shutdown(Shutdown::Both).unwrap_or_else(|e| {
+                    warn!("Error in shutdown, {}", e);
+                });
+                let _ = registry.reregister(server, server_token, Interest::READABLE);
+                continue;
+            }
+
+            if yggdrasil_only && !is_yggdrasil(&address.ip()) {
+                debug!("Dropping connection from Internet");
+                stream.shutdown(Shutdown::Both).unwrap_or_else(|e| {
+                    warn!("Error in shutdown, {}", e);
+                });
+                let _ = registry.reregister(server, server_token, Interest::READABLE);
+                continue;
+            }
+
+            if !self.peers.make_inbound_slot(registry, &address.ip()) {
+                stream.shutdown(Shutdown::Both).unwrap_or_else(|e| {
+                    warn!("Error in shutdown, {}", e);
+                });
+                let _ = registry.reregister(server, server_token, Interest::READABLE);
+                continue;
+            }
+
+            //debug!("Accepted connection from: {} to local IP: {}", address, local_ip);
+            let token = self.next_token();
+            registry.register(&mut stream, token, Interest::READABLE).expect("Error registering poll");
+            let peer = Peer::new(address, stream, State::Connected, true);
+            self.peers.add_peer(token, peer);
+        }
+        if let Err(e) = registry.reregister(server, server_token, Interest::READABLE) {
+            panic!("Error reregistering server token!\n{}", e);
+        }
+    }
+
     fn handle_connection_event(&mut self, registry: &Registry, event: &Event, seen_blocks: &mut HashSet<Bytes>) -> bool {
         if event.is_error() || (event.is_read_closed() && event.is_write_closed()) {
             return false;
@@ -316,6 +466,7 @@ impl Network {
         };
 
         if let Ok(data) = data {
+            self.download.consume(data.len());
             let data = {
                 match self.peers.get_peer(&event.token()) {
                     Some(peer) => {
@@ -332,6 +483,12 @@ impl Network {
                 }
             };
             match Message::from_bytes(data) {
+                Ok(message) if !message.is_valid() => {
+                    let peer = self.peers.get_peer(&event.token()).unwrap();
+                    warn!("Message from {} exceeds limits", &peer.get_addr());
+                    let _ = self.misbehave(&event.token(), PENALTY_MALFORMED, "message exceeding limits");
+                    return false;
+                }
                 Ok(message) => {
                     //let m = format!("{:?}", &message);
                     let new_state = self.handle_message(message, &event.token(), seen_blocks);
@@ -376,6 +533,7 @@ impl Network {
                 Err(e) => {
                     let peer = self.peers.get_peer(&event.token()).unwrap();
                     warn!("Error deserializing message from {}: {}", &peer.get_addr(), e.to_string());
+                    let _ = self.misbehave(&event.token(), PENALTY_MALFORMED, "malformed message");
                     return false;
                 }
             }
@@ -412,7 +570,8 @@ impl Network {
                     //debug!("Connected to peer {}, sending hello...", &peer.get_addr());
                     let data: Vec<u8> = {
                         let c = self.context.lock().unwrap();
-                        let message = Message::hand(&c.app_version, &c.settings.origin, CHAIN_VERSION, c.settings.net.public, &my_id);
+                        let height = c.chain.get_height();
+                        let message = Message::hand(&c.app_version, &c.settings.origin, CHAIN_VERSION, c.settings.net.public, &my_id, height, my_flags(&c));
                         //info!("Sending: {:?}", &message);
                         encode_message(&message, peer.get_cipher()).unwrap()
                     };
@@ -422,9 +581,15 @@ impl Network {
                 }
                 State::Connected => {}
                 State::Message { data } => {
+                    // If we have sent too much, the message will wait for the next update of peers
+                    if !self.upload.is_available() {
+                        registry.reregister(peer.get_stream(), event.token(), Interest::READABLE).unwrap();
+                        return true;
+                    }
                     //debug!("Sending data to {}: {}", &peer.get_addr(), &String::from_utf8(data.clone()).unwrap());
                     if let Ok(data) = encode_bytes(&data, peer.get_cipher()) {
                         send_message(peer.get_stream(), &data).unwrap_or_else(|e| warn!("Error sending message {}", e));
+                        self.upload.consume(data.len());
                     }
                     peer.set_state(State::idle());
                 }
@@ -460,14 +625,14 @@ impl Network {
     }
 
     fn handle_message(&mut self, message: Message, token: &Token, seen_blocks: &mut HashSet<Bytes>) -> State {
-        let (my_height, my_hash, my_origin, my_version, me_public) = {
+        let (my_height, my_hash, my_origin, my_version, me_public, my_flags) = {
             let context = self.context.lock().unwrap();
             // TODO cache it somewhere
-            (context.chain.get_height(), context.chain.get_last_hash(), &context.settings.origin.clone(), CHAIN_VERSION, context.settings.net.public)
+            (context.chain.get_height(), context.chain.get_last_hash(), &context.settings.origin.clone(), CHAIN_VERSION, context.settings.net.public, my_flags(&context))
         };
         let my_id = self.peers.get_my_id().to_owned();
         let answer = match message {
-            Message::Hand { app_version, origin, version, public, rand_id } => {
+            Message::Hand { app_version, origin, version, public, rand_id, flags, height, time } => {
                 if !version_compatible(&app_version) {
                     info!("Banning peer with version {}", &app_version);
                     return State::Banned;
@@ -482,16 +647,24 @@ impl Network {
                     if version == my_version {
                         peer.set_public(public);
                         peer.set_active(true);
+                        peer.set_flags(flags);
+                        peer.set_height(height);
+                        peer.set_clock(time);
+                        post(crate::event::Event::PeerConnected { addr: peer.get_addr().to_string(), inbound: true });
+                        if peer.is_higher(my_height) {
+                            self.context.lock().unwrap().chain.update_max_height(height);
+                            post(crate::event::Event::SyncProgress { have: my_height, height });
+                        }
                     } else {
                         warn!("Handshake from unsupported version: {} (local version: {})", version, my_version);
                     }
-                    State::message(Message::shake(&app_version, &origin, my_version, me_public, &my_id, my_height))
+                    State::message(Message::shake(&app_version, &origin, my_version, me_public, &my_id, my_height, my_flags))
                 } else {
                     warn!("Handshake from unsupported chain: {}", &origin);
                     State::Banned
                 }
             }
-            Message::Shake { app_version, origin, version, public, rand_id, height } => {
+            Message::Shake { app_version, origin, version, public, rand_id, height, flags, time } => {
                 if origin.ne(my_origin) {
                     return State::Banned;
                 } else if version > my_version {
@@ -512,13 +685,17 @@ impl Network {
                 // TODO check rand_id whether we have this peers connection already
                 debug!("Outgoing v{} on {}", &app_version, peer.get_addr().ip());
                 peer.set_height(height);
+                peer.set_flags(flags);
+                peer.set_clock(time);
                 peer.set_active(true);
                 peer.set_public(public);
                 peer.reset_reconnects();
+                post(crate::event::Event::PeerConnected { addr: peer.get_addr().to_string(), inbound: false });
                 let mut context = self.context.lock().unwrap();
+                context.chain.add_peer_success(&peer.get_addr().to_string());
                 if peer.is_higher(my_height) {
                     context.chain.update_max_height(height);
-                    let event = crate::event::Event::Syncing { have: my_height, height: max(height, my_height) };
+                    let event = crate::event::Event::SyncProgress { have: my_height, height: max(height, my_height) };
                     post(event);
                 }
                 if nodes < MAX_NODES && random::<bool>() {
@@ -536,11 +713,11 @@ impl Network {
                 if seen_blocks.contains(&hash) {
                     return State::message(Message::pong(my_height, my_hash));
                 }
-                if peer.is_higher(my_height) {
+                if peer.is_higher(my_height) && peer.can_give_block(my_height + 1) {
                     let mut context = self.context.lock().unwrap();
                     context.chain.update_max_height(height);
                     info!("Peer is higher, requesting block {} from {}", height, peer.get_addr().ip());
-                    State::message(Message::GetBlock { index: my_height + 1 })
+                    State::message(peer.request_blocks(my_height + 1))
                 } else if my_height == height && hash.ne(&my_hash) {
                     info!("Hashes are different, requesting block {} from {}", my_height, peer.get_addr().ip());
                     info!("My hash: {:?}, their hash: {:?}", &my_hash, &hash);
@@ -557,11 +734,11 @@ impl Network {
                 if seen_blocks.contains(&hash) {
                     return State::idle();
                 }
-                if peer.is_higher(my_height) {
+                if peer.is_higher(my_height) && peer.can_give_block(my_height + 1) {
                     let mut context = self.context.lock().unwrap();
                     context.chain.update_max_height(height);
                     info!("Peer is higher, requesting block {} from {}", height, peer.get_addr().ip());
-                    State::message(Message::GetBlock { index: my_height + 1 })
+                    State::message(peer.request_blocks(my_height + 1))
                 } else if my_height == height && hash.ne(&my_hash) {
                     info!("Hashes are different, requesting block {} from {}", my_height, peer.get_addr().ip());
                     info!("My hash: {:?}, their hash: {:?}", &my_hash, &hash);
@@ -599,15 +776,15 @@ impl Network {
             Message::Block { index, block } => {
                 let peer = self.peers.get_mut_peer(token).unwrap();
                 peer.set_active(true);
-                let block: Block = match Block::from_bytes(block.as_slice()) {
+                let block: Block = match Block::from_untrusted_bytes(block.as_slice()) {
                     Ok(block) => block,
                     Err(e) => {
                         warn!("Error deserializing block! {}", e);
-                        return State::Banned;
+                        return self.misbehave(token, PENALTY_MALFORMED, "malformed block");
                     }
                 };
                 if index != block.index {
-                    return State::Banned;
+                    return self.misbehave(token, PENALTY_PROTOCOL, "wrong block index");
                 }
                 debug!("Received block {} with hash {:?}", block.index, &block.hash);
                 if !seen_blocks.contains(&block.hash) {
@@ -616,6 +793,68 @@ impl Network {
                     State::idle()
                 }
             }
+            Message::Announce { index, hash } => {
+                let peer = self.peers.get_mut_peer(token).unwrap();
+                peer.set_active(true);
+                if index > peer.get_height() {
+                    peer.set_height(index);
+                }
+                if seen_blocks.contains(&hash) || (index == my_height && hash == my_hash) {
+                    State::idle()
+                } else if index > my_height && peer.can_give_block(my_height + 1) {
+                    self.context.lock().unwrap().chain.update_max_height(index);
+                    debug!("Got announce of block {} from {}, requesting block {}", index, peer.get_addr().ip(), my_height + 1);
+                    State::message(peer.request_blocks(my_height + 1))
+                } else if index == my_height {
+                    debug!("Got announce of different block {} from {}, requesting it", index, peer.get_addr().ip());
+                    State::message(Message::GetBlock { index })
+                } else {
+                    State::idle()
+                }
+            }
+            Message::GetBlocks { from, count } => {
+                let peer = self.peers.get_mut_peer(token).unwrap();
+                peer.set_active(true);
+                let context = self.context.lock().unwrap();
+                let mut blocks = Vec::new();
+                let mut size = 0;
+                for index in from..from.saturating_add(min(count, BLOCKS_BATCH_COUNT)) {
+                    let block = match context.chain.get_block(index) {
+                        None => break,
+                        Some(block) => block.as_bytes()
+                    };
+                    if !blocks.is_empty() && size + block.len() > MAX_BATCH_BYTES {
+                        break;
+                    }
+                    size += block.len();
+                    blocks.push(block);
+                }
+                match blocks.is_empty() {
+                    true => State::Error,
+                    false => State::message(Message::Blocks { from, blocks })
+                }
+            }
+            Message::Blocks { from, blocks } => {
+                let peer = self.peers.get_mut_peer(token).unwrap();
+                peer.set_active(true);
+                // Batches that we didn't ask for would fill our memory with blocks from future
+                if !peer.take_requested_batch(from) {
+                    warn!("Got blocks from {} that we didn't request from {}", from, peer.get_addr().ip());
+                    return self.misbehave(token, PENALTY_PROTOCOL, "unrequested blocks");
+                }
+                let mut parsed = Vec::with_capacity(blocks.len());
+                for (i, block) in blocks.iter().enumerate() {
+                    match Block::from_untrusted_bytes(block.as_slice()) {
+                        Ok(block) if Some(block.index) == from.checked_add(i as u64) => parsed.push(block),
+                        _ => {
+                            warn!("Wrong block in batch from {}", peer.get_addr().ip());
+                            return self.misbehave(token, PENALTY_MALFORMED, "wrong block in batch");
+                        }
+                    }
+                }
+                debug!("Received {} blocks starting from {}", parsed.len(), from);
+                self.handle_blocks(token, from, parsed, seen_blocks)
+            }
             Message::Twin => State::Twin,
             Message::Loop => State::Loop
         };
@@ -628,24 +867,12 @@ impl Network {
         let peer = self.peers.get_mut_peer(token).unwrap();
         peer.set_received_block(block.index);
         trace!("New block from {}", &peer.get_addr());
+        let (index, addr) = (block.index, peer.get_addr());
 
         let mut context = self.context.lock().unwrap();
         let max_height = context.chain.get_max_height();
-        match context.chain.check_new_block(&block) {
-            BlockQuality::Good => {
-                let mut next_index = block.index + 1;
-                context.chain.add_block(block);
-                // If we have some consequent blocks in a bucket of 'future blocks', we add them
-                while let Some(block) = self.future_blocks.remove(&next_index) {
-                    if context.chain.check_new_block(&block) == BlockQuality::Good {
-                        debug!("Added block {} from future blocks", next_index);
-                        context.chain.add_block(block);
-                    } else {
-                        warn!("Block {} in future blocks is bad!", block.index);
-                        break;
-                    }
-                    next_index += 1;
-                }
+        match process_block(&mut context.chain, &mut self.future_blocks, seen_blocks, block) {
+            BlockOutcome::Added => {
                 let my_height = context.chain.get_height();
                 post(crate::event::Event::BlockchainChanged { index: my_height });
                 // If it was the last block to sync
@@ -653,50 +880,113 @@ impl Network {
                     post(crate::event::Event::SyncFinished);
                     self.future_blocks.clear();
                 } else {
-                    let event = crate::event::Event::Syncing { have: my_height, height: max(max_height, my_height) };
+                    let event = crate::event::Event::SyncProgress { have: my_height, height: max(max_height, my_height) };
                     post(event);
                 }
                 let domains = context.chain.get_domains_count();
                 let keys = context.chain.get_users_count();
                 post(crate::event::Event::NetworkStatus { blocks: my_height, domains, keys, nodes: peers_count });
             }
-            BlockQuality::Twin => { debug!("Ignoring duplicate block {}", block.index); }
-            BlockQuality::Future => {
-                debug!("Got future block {}", block.index);
-                self.future_blocks.insert(block.index, block);
+            BlockOutcome::Replaced => {
+                let index = context.chain.get_height();
+                post(crate::event::Event::BlockchainChanged { index });
             }
-            BlockQuality::Bad => {
+            BlockOutcome::Ignored => {}
+            BlockOutcome::Bad => {
                 // TODO save bad public keys to banned table
-                debug!("Ignoring bad block from {}:\n{:?}", peer.get_addr(), &block);
+                debug!("Ignoring bad block {} from {}", index, addr);
                 let height = context.chain.get_height();
-                if height + 1 == block.index {
+                if height + 1 == index {
                     context.chain.update_max_height(height);
                     post(crate::event::Event::SyncFinished);
                 }
-                return State::Banned;
-            }
-            BlockQuality::Rewind => {
-                debug!("Got some orphan block, requesting its parent");
-                return State::message(Message::GetBlock { index: block.index - 1 });
-            }
-            BlockQuality::Fork => {
-                debug!("Got forked block {} with hash {:?}", block.index, block.hash);
-                // If we are very much behind of blockchain
-                let lagged = block.index == context.chain.get_height() && block.index + LIMITED_CONFIDENCE_DEPTH <= max_height;
-                let our_block = context.chain.get_block(block.index).unwrap();
-                if block.is_better_than(&our_block) || lagged {
-                    context.chain.replace_block(block).expect("Error replacing block with fork");
-                    let index = context.chain.get_height();
-                    post(crate::event::Event::BlockchainChanged { index });
-                } else {
-                    debug!("Fork in not better than our block, dropping.");
-                    return State::message(Message::block(our_block.index, our_block.as_bytes()));
-                }
+                drop(context);
+                return self.misbehave(token, PENALTY_BAD_BLOCK, "bad block");
+            }
+            BlockOutcome::Rewind(index) => return State::message(Message::GetBlock { index }),
+            BlockOutcome::OurBlock(block) => return State::message(Message::block(block.index, block.as_bytes()))
+        }
+        State::idle()
+    }
+
+    /// Adds a batch of blocks at once. Batches come from different peers in any order,
+    /// so the batches from future are kept until we get all blocks before them.
+    fn handle_blocks(&mut self, token: &Token, from: u64, mut blocks: Vec<Block>, seen_blocks: &mut HashSet<Bytes>) -> State {
+        self.peers.batch_received(from);
+        let height = self.context.lock().unwrap().chain.get_height();
+        if from > height + 1 {
+            if self.future_blocks.len() + blocks.len() > MAX_FUTURE_BLOCKS {
+                debug!("Too many blocks from future, dropping {} blocks starting from {}", blocks.len(), from);
+                return State::idle();
+            }
+            debug!("Keeping {} blocks starting from {} for later", blocks.len(), from);
+            for block in blocks {
+                self.future_blocks.insert(block.index, block);
+            }
+            return State::idle();
+        }
+        blocks.retain(|block| block.index > height);
+        // Appending blocks that we have got earlier in other batches
+        let mut next = height + blocks.len() as u64 + 1;
+        while let Some(block) = self.future_blocks.remove(&next) {
+            blocks.push(block);
+            next += 1;
+        }
+
+        let hashes: Vec<Bytes> = blocks.iter().map(|block| block.hash.clone()).collect();
+        let rest = self.context.lock().unwrap().chain.add_blocks(blocks);
+        let added = hashes.len() - rest.len();
+        seen_blocks.extend(hashes.into_iter().take(added));
+        if added > 0 {
+            let peers_count = self.peers.get_peers_active_count();
+            let context = self.context.lock().unwrap();
+            let my_height = context.chain.get_height();
+            let max_height = context.chain.get_max_height();
+            debug!("Added {} blocks from batch, height is {}", added, my_height);
+            post(crate::event::Event::BlockchainChanged { index: my_height });
+            if my_height >= max_height {
+                post(crate::event::Event::SyncFinished);
+                self.future_blocks.clear();
+            } else {
+                post(crate::event::Event::SyncProgress { have: my_height, height: max_height });
+            }
+            let domains = context.chain.get_domains_count();
+            let keys = context.chain.get_users_count();
+            post(crate::event::Event::NetworkStatus { blocks: my_height, domains, keys, nodes: peers_count });
+        }
+
+        // The first block that we couldn't add is handled as usual, it can be a fork for example
+        if let Some(block) = rest.into_iter().next() {
+            if !seen_blocks.contains(&block.hash) {
+                return self.handle_block(token, block, seen_blocks);
             }
         }
         State::idle()
     }
 
+    /// Penalizes misbehaving peer, and bans it for a long time if it has too many penalty points
+    fn misbehave(&mut self, token: &Token, score: u32, reason: &str) -> State {
+        let ip = match self.peers.get_peer(token) {
+            None => return State::Error,
+            Some(peer) => peer.get_addr().ip()
+        };
+        if self.peers.penalize(&ip, score) {
+            warn!("Banning peer {} for {}", &ip, reason);
+            let until = Utc::now().timestamp() + BAN_TIME_SEC;
+            self.context.lock().unwrap().chain.add_ban(&ip.to_string(), until, reason);
+            State::Banned
+        } else {
+            State::idle()
+        }
+    }
+
+    /// Loads banned addresses from DB, they can be changed from command line
+    fn load_bans(&mut self) {
+        let bans = self.context.lock().unwrap().chain.get_bans(Utc::now().timestamp());
+        let bans = bans.iter().filter_map(|ip| ip.parse().ok()).collect();
+        self.peers.set_banned(bans);
+    }
+
     /// Gets new token from old token, mutating the last
     pub fn next_token(&mut self) -> Token {
         let current = self.token.0;
@@ -705,17 +995,105 @@ impl Network {
     }
 }
 
-fn subscribe_to_bus(running: Arc<AtomicBool>) {
+/// What happened to a block from peer in `process_block`
+#[derive(Debug, PartialEq)]
+pub enum BlockOutcome {
+    /// Block is added, with the next blocks that were kept for later
+    Added,
+    /// Block of a better fork replaced ours
+    Replaced,
+    /// Block is a duplicate, or it is kept for later
+    Ignored,
+    /// Block is invalid, the peer misbehaves
+    Bad,
+    /// We need the block with this index first
+    Rewind(u64),
+    /// Our block is better than the fork, the peer needs it
+    OurBlock(Block)
+}
+
+/// Checks a block from peer and adds it to the chain, with the next blocks from `future_blocks`.
+/// Blocks from future are kept there, broken copies of blocks are removed from `seen_blocks`.
+/// The network and the simulator of nodes handle blocks by it, peers and events are up to them.
+pub fn process_block(chain: &mut Chain, future_blocks: &mut HashMap<u64, Block>, seen_blocks: &mut HashSet<Bytes>, block: Block) -> BlockOutcome {
+    match chain.check_new_block(&block) {
+        BlockQuality::Good => {
+            let mut next_index = block.index + 1;
+            chain.add_block(block);
+            // If we have some consequent blocks in a bucket of 'future blocks', we add them
+            while let Some(block) = future_blocks.remove(&next_index) {
+                if chain.check_new_block(&block) == BlockQuality::Good {
+                    debug!("Added block {} from future blocks", next_index);
+                    chain.add_block(block);
+                } else {
+                    warn!("Block {} in future blocks is bad!", block.index);
+                    seen_blocks.remove(&block.hash);
+                    break;
+                }
+                next_index += 1;
+            }
+            BlockOutcome::Added
+        }
+        BlockQuality::Twin => {
+            debug!("Ignoring duplicate block {}", block.index);
+            BlockOutcome::Ignored
+        }
+        BlockQuality::Future => {
+            debug!("Got future block {}", block.index);
+            if future_blocks.len() < MAX_FUTURE_BLOCKS {
+                future_blocks.insert(block.index, block);
+            }
+            BlockOutcome::Ignored
+        }
+        BlockQuality::Bad => {
+            // Broken copy of a good block has the same hash, we must not ignore the good one
+            seen_blocks.remove(&block.hash);
+            BlockOutcome::Bad
+        }
+        BlockQuality::Rewind => {
+            debug!("Got some orphan block, requesting its parent");
+            BlockOutcome::Rewind(block.index - 1)
+        }
+        BlockQuality::Fork => {
+            debug!("Got forked block {} with hash {:?}", block.index, block.hash);
+            // If we are very much behind of blockchain
+            let lagged = block.index == chain.get_height() && block.index + LIMITED_CONFIDENCE_DEPTH <= chain.get_max_height();
+            let our_block = chain.get_block(block.index).unwrap();
+            if block.is_better_than(&our_block) || lagged {
+                chain.replace_block(block).expect("Error replacing block with fork");
+                BlockOutcome::Replaced
+            } else {
+                debug!("Fork in not better than our block, dropping.");
+                BlockOutcome::OurBlock(our_block)
+            }
+        }
+    }
+}
+
+fn subscribe_to_bus(running: Arc<AtomicBool>, added_peers: Arc<Mutex<Vec<String>>>, bans_changed: Arc<AtomicBool>) {
     use crate::event::Event;
     register(move |_uuid, e| {
-        if let Event::ActionQuit = e {
-            running.store(false, Ordering::SeqCst);
-            return false;
+        match e {
+            Event::ActionQuit => {
+                running.store(false, Ordering::SeqCst);
+                return false;
+            }
+            Event::ActionAddPeer { addr } => added_peers.lock().unwrap().push(addr),
+            Event::BansChanged => bans_changed.store(true, Ordering::SeqCst),
+            _ => {}
         }
         true
     });
 }
 
+/// Our protocol features for handshake
+fn my_flags(context: &Context) -> u32 {
+    match context.chain.is_pruned() {
+        true => VERSION_FLAGS | FLAG_PRUNED,
+        false => VERSION_FLAGS
+    }
+}
+
 fn encode_bytes(data: &[u8], cipher: &Option<Chacha>) -> Result<Vec<u8>, chacha20poly1305::aead::Error> {
     match cipher {
         None => Ok(data.to_owned()),
@@ -897,6 +1275,24 @@ fn send_message(connection: &mut TcpStream, data: &[u8]) -> io::Result<()> {
     connection.flush()
 }
 
+/// If we listen on all IPv6 addresses we also try to listen on all IPv4 addresses.
+/// On systems with dual-stack sockets it fails, as IPv6 socket accepts IPv4 connections already.
+fn bind_ipv4_listener(addr: &SocketAddr) -> Option<TcpListener> {
+    match addr {
+        SocketAddr::V6(v6) if v6.ip().is_unspecified() => {
+            let addr = SocketAddr::new(IpAddr::V4(Ipv4Addr::UNSPECIFIED), addr.port());
+            match TcpListener::bind(addr) {
+                Ok(server) => Some(server),
+                Err(e) => {
+                    trace!("Not listening on {} separately: {}", addr, e);
+                    None
+                }
+            }
+        }
+        _ => None
+    }
+}
+
 fn wait_for_internet(timeout: Duration) {
     let addr = "alfis.name:443";
     let start = Instant::now();
@@ -1,25 +1,35 @@
+use std::cmp::min;
+use std::collections::HashSet;
 use std::net::SocketAddr;
 use std::time::Instant;
 
+use chrono::Utc;
 use mio::net::TcpStream;
 
+use crate::commons::{BLOCKS_BATCH_COUNT, FLAG_BATCH, FLAG_PRUNED, HANDSHAKE_TIMEOUT_SEC, MAX_PARALLEL_CHUNKS, PRUNE_BLOCKS_MIN, WRITE_TIMEOUT_SEC};
 use crate::crypto::Chacha;
-use crate::p2p::State;
+use crate::p2p::{Message, State};
 
 #[derive(Debug)]
 pub struct Peer {
     addr: SocketAddr,
     stream: TcpStream,
     state: State,
+    state_time: Instant,
     id: String,
     height: u64,
+    flags: u32,
     inbound: bool,
     public: bool,
     active: bool,
     last_active: Instant,
     reconnects: u32,
     received_block: u64,
+    /// First indexes of batches that we have requested from this peer, other batches are not accepted
+    requested_batches: HashSet<u64>,
     sent_height: u64,
+    /// How many seconds the clock of peer is ahead of ours
+    clock_offset: Option<i64>,
     cipher: Option<Chacha>
 }
 
@@ -29,15 +39,19 @@ impl Peer {
             addr,
             stream,
             state,
+            state_time: Instant::now(),
             id: String::new(),
             height: 0,
+            flags: 0,
             inbound,
             public: false,
             active: false,
             last_active: Instant::now(),
             reconnects: 0,
             received_block: 0,
+            requested_batches: HashSet::new(),
             sent_height: 0,
+            clock_offset: None,
             cipher: None
         }
     }
@@ -75,6 +89,17 @@ impl Peer {
 
     pub fn set_state(&mut self, state: State) {
         self.state = state;
+        self.state_time = Instant::now();
+    }
+
+    /// Checks if the peer is stuck in handshake or with unsent message for too long
+    pub fn is_timed_out(&self) -> bool {
+        let elapsed = self.state_time.elapsed().as_secs();
+        match self.state {
+            State::Connecting | State::Connected | State::ServerHandshake | State::HandshakeFinished => elapsed >= HANDSHAKE_TIMEOUT_SEC,
+            State::Message { .. } => elapsed >= WRITE_TIMEOUT_SEC,
+            _ => false
+        }
     }
 
     pub fn get_id(&self) -> &str {
@@ -89,6 +114,26 @@ impl Peer {
         self.height
     }
 
+    pub fn set_flags(&mut self, flags: u32) {
+        self.flags = flags;
+    }
+
+    /// Checks if the peer has announced support of some protocol feature
+    pub fn has_flag(&self, flag: u32) -> bool {
+        self.flags & flag == flag
+    }
+
+    /// Remembers the difference of clocks by time from handshake, 0 means that the peer didn't send it
+    pub fn set_clock(&mut self, time: i64) {
+        if time != 0 {
+            self.clock_offset = Some(time - Utc::now().timestamp());
+        }
+    }
+
+    pub fn get_clock_offset(&self) -> Option<i64> {
+        self.clock_offset
+    }
+
     pub fn set_sent_height(&mut self, height: u64) {
         self.sent_height = height;
     }
@@ -109,8 +154,35 @@ impl Peer {
         self.received_block = index;
     }
 
+    /// Makes request for blocks starting from `from`, in batches if the peer supports it.
+    /// Requested blocks are marked as received to not request them again.
+    pub fn request_blocks(&mut self, from: u64) -> Message {
+        if self.has_flag(FLAG_BATCH) {
+            let count = min(self.height.saturating_sub(from) + 1, BLOCKS_BATCH_COUNT);
+            self.received_block = from + count - 1;
+            // Peer that doesn't answer for a long time can't make us keep all its requests
+            if self.requested_batches.len() >= MAX_PARALLEL_CHUNKS as usize {
+                self.requested_batches.clear();
+            }
+            self.requested_batches.insert(from);
+            Message::GetBlocks { from, count }
+        } else {
+            Message::GetBlock { index: from }
+        }
+    }
+
+    /// Returns true if we have requested the batch starting from this index, and forgets the request
+    pub fn take_requested_batch(&mut self, from: u64) -> bool {
+        self.requested_batches.remove(&from)
+    }
+
+    /// Pruned peers have only last blocks, we don't ask them for older ones
+    pub fn can_give_block(&self, index: u64) -> bool {
+        !self.has_flag(FLAG_PRUNED) || index + PRUNE_BLOCKS_MIN > self.height
+    }
+
     pub fn has_more_blocks(&self, height: u64) -> bool {
-        if self.height <= height {
+        if self.height <= height || !self.can_give_block(height + 1) {
             return false;
         }
         if self.received_block > height {
@@ -2,6 +2,7 @@ use std::cmp::min;
 use std::collections::{HashMap, HashSet};
 use std::{io, thread};
 use std::net::{IpAddr, Shutdown, SocketAddr, ToSocketAddrs};
+use std::sync::mpsc::{channel, Receiver, Sender};
 use std::time::{Duration, Instant};
 
 use chrono::Utc;
@@ -14,27 +15,61 @@ use rand::random;
 use rand::seq::IteratorRandom;
 
 use crate::commons::*;
-use crate::p2p::{Message, Peer, State};
+use crate::event::Event;
+use crate::eventbus::post;
+use crate::p2p::{socks, Message, Peer, State};
 use crate::{commons, Bytes};
 
 const PING_PERIOD: u64 = 30;
 
+/// Results of the work that helper threads do with the proxy
+enum ProxyResult {
+    Connected(Token, io::Result<std::net::TcpStream>),
+    Resolved(Vec<SocketAddr>)
+}
+
 pub struct Peers {
     peers: HashMap<Token, Peer>,
     new_peers: Vec<SocketAddr>,
     ignored: HashSet<IpAddr>,
     ignore_timer: Instant,
+    // Addresses banned for misbehavior, they are stored in DB
+    banned: HashSet<IpAddr>,
+    // Penalty points of misbehaving peers
+    scores: HashMap<IpAddr, u32>,
+    // Batches of blocks that we have requested: first block index -> peer and time of request
+    requested: HashMap<u64, (Token, Instant)>,
+    // SOCKS5 proxy for all outbound connections
+    proxy: Option<SocketAddr>,
+    // Connections that are being made through the proxy in helper threads
+    proxied: HashMap<Token, SocketAddr>,
+    proxy_sender: Sender<ProxyResult>,
+    proxy_receiver: Receiver<ProxyResult>,
+    max_inbound: usize,
+    max_outbound: usize,
+    max_per_ip: usize,
     my_id: String,
     behind_ping_sent_time: i64
 }
 
 impl Peers {
     pub fn new() -> Self {
+        let (proxy_sender, proxy_receiver) = channel();
         Peers {
             peers: HashMap::new(),
             new_peers: Vec::new(),
             ignored: HashSet::new(),
             ignore_timer: Instant::now(),
+            banned: HashSet::new(),
+            scores: HashMap::new(),
+            requested: HashMap::new(),
+            proxy: None,
+            proxied: HashMap::new(),
+            proxy_sender,
+            proxy_receiver,
+            max_inbound: 64,
+            max_outbound: MAX_NODES,
+            max_per_ip: 4,
             my_id: commons::random_string(6),
             behind_ping_sent_time: 0
         }
@@ -59,6 +94,9 @@ impl Peers {
             let _ = stream.shutdown(Shutdown::Both);
             let _ = registry.deregister(stream);
             let addr = peer.get_addr();
+            if peer.active() {
+                post(Event::PeerDisconnected { addr: addr.to_string() });
+            }
             match peer.get_state() {
                 State::Connecting => {
                     debug!("Peer connection {} to {:?} has timed out", &token.0, &addr);
@@ -121,7 +159,7 @@ impl Peers {
                     warn!("Error parsing peer {}", peer);
                     continue;
                 }
-                Ok(addr) => addr
+                Ok(addr) => canonical_addr(addr)
             };
 
             if self.peers
@@ -138,7 +176,7 @@ impl Peers {
                 continue;
             }
 
-            if self.ignored.contains(&addr.ip()) {
+            if self.is_ignored(&addr.ip()) {
                 debug!("Skipping ignored address from exchange: {}", &addr);
                 continue;
             }
@@ -155,6 +193,68 @@ impl Peers {
         }
     }
 
+    /// Sets proxy to make all outbound connections and name resolutions through it
+    pub fn set_proxy(&mut self, proxy: Option<SocketAddr>) {
+        self.proxy = proxy;
+    }
+
+    pub fn set_limits(&mut self, max_inbound: usize, max_outbound: usize, max_per_ip: usize) {
+        self.max_inbound = max_inbound;
+        self.max_outbound = max_outbound;
+        self.max_per_ip = max_per_ip;
+    }
+
+    fn get_connections_count(&self, inbound: bool) -> usize {
+        let count = self.peers.values().filter(|peer| peer.is_inbound() == inbound).count();
+        match inbound {
+            true => count,
+            // New connections that are still going through the proxy
+            false => count + self.proxied.keys().filter(|token| !self.peers.contains_key(token)).count()
+        }
+    }
+
+    pub fn need_more_outbound(&self) -> bool {
+        self.get_connections_count(false) < self.max_outbound
+    }
+
+    /// Checks if we can accept new connection from this IP-address.
+    /// If all inbound slots are taken, closes the least useful inbound connection.
+    /// Outbound connections are never closed here, so nobody can push them out by connecting to us.
+    pub fn make_inbound_slot(&mut self, registry: &Registry, ip: &IpAddr) -> bool {
+        if !ip.is_loopback() && self.peers.values().filter(|peer| peer.get_addr().ip() == *ip).count() >= self.max_per_ip {
+            debug!("Too many connections from {}", ip);
+            return false;
+        }
+        if self.get_connections_count(true) < self.max_inbound {
+            return true;
+        }
+        let victim = self.peers
+            .iter()
+            .filter(|(_, peer)| peer.is_inbound())
+            .min_by_key(|(_, peer)| (peer.active(), peer.get_height()))
+            .map(|(token, _)| *token);
+        match victim {
+            None => false,
+            Some(token) => {
+                debug!("All inbound slots are taken, closing connection {}", token.0);
+                self.close_peer(registry, &token);
+                true
+            }
+        }
+    }
+
+    /// Adds address of the node found in local network, these addresses are private, but we want them
+    pub fn add_lan_peer(&mut self, addr: SocketAddr) {
+        if self.is_ignored(&addr.ip()) || self.new_peers.contains(&addr) {
+            return;
+        }
+        if self.peers.values().any(|peer| peer.equals(&addr)) {
+            return;
+        }
+        debug!("Got new peer {} from local network", &addr);
+        self.new_peers.push(addr);
+    }
+
     pub fn get_my_id(&self) -> &str {
         &self.my_id
     }
@@ -164,7 +264,38 @@ impl Peers {
     }
 
     pub fn is_ignored(&self, addr: &IpAddr) -> bool {
-        self.ignored.contains(addr)
+        self.ignored.contains(addr) || self.banned.contains(addr)
+    }
+
+    /// Adds penalty points to the peer, returns true if it has to be banned
+    pub fn penalize(&mut self, addr: &IpAddr, score: u32) -> bool {
+        let total = self.scores.entry(*addr).or_insert(0);
+        *total += score;
+        debug!("Peer {} got {} penalty points, total {}", addr, score, total);
+        if *total >= BAN_SCORE {
+            self.scores.remove(addr);
+            self.banned.insert(*addr);
+            return true;
+        }
+        false
+    }
+
+    /// Replaces the set of banned addresses, with the one loaded from DB
+    pub fn set_banned(&mut self, banned: HashSet<IpAddr>) {
+        self.banned = banned;
+    }
+
+    /// Closes connections to banned addresses, after user has banned some of them
+    pub fn close_banned(&mut self, registry: &Registry) {
+        let tokens: Vec<Token> = self.peers
+            .iter()
+            .filter(|(_, peer)| self.banned.contains(&peer.get_addr().ip()))
+            .map(|(token, _)| *token)
+            .collect();
+        for token in tokens {
+            debug!("Closing connection {} to banned node", token.0);
+            self.close_peer(registry, &token);
+        }
     }
 
     pub fn get_peers_for_exchange(&self, peer_address: &SocketAddr) -> Vec<String> {
@@ -204,6 +335,11 @@ impl Peers {
         count
     }
 
+    /// Offsets of clocks of active peers from ours
+    pub fn get_clock_offsets(&self) -> Vec<i64> {
+        self.peers.values().filter(|peer| peer.active()).filter_map(|peer| peer.get_clock_offset()).collect()
+    }
+
     pub fn is_tween_connect(&self, id: &str) -> bool {
         for (_, peer) in self.peers.iter() {
             if peer.active() && peer.get_id() == id {
@@ -214,7 +350,7 @@ impl Peers {
     }
 
     pub fn get_peers_banned_count(&self) -> usize {
-        self.ignored.len()
+        self.ignored.union(&self.banned).count()
     }
 
     pub fn ignore_peer(&mut self, registry: &Registry, token: &Token) {
@@ -250,12 +386,17 @@ impl Peers {
         false
     }
 
-    pub fn update(&mut self, registry: &Registry, hash: Bytes, height: u64, max_height: u64, have_blocks: HashSet<u64>) {
+    pub fn update(&mut self, registry: &Registry, hash: Bytes, height: u64, max_height: u64, have_blocks: HashSet<u64>, can_download: bool) {
         let nodes = self.get_peers_active_count();
 
         let random_time = random::<u64>() % PING_PERIOD;
         let mut stale_tokens = Vec::new();
         for (token, peer) in self.peers.iter_mut() {
+            if peer.is_timed_out() {
+                debug!("Connection {} to {} has timed out in state {:?}", token.0, peer.get_addr(), peer.get_state());
+                stale_tokens.push((*token, peer.get_addr()));
+                continue;
+            }
             if let State::Idle { from } = peer.get_state() {
                 if from.elapsed().as_secs() >= PING_PERIOD + random_time {
                     // Sometimes we check for new peers instead of pinging
@@ -289,11 +430,14 @@ impl Peers {
         // TODO make it individual for every IP
         if self.ignore_timer.elapsed().as_secs() >= 600 {
             self.ignored.clear();
+            self.scores.clear();
             self.ignore_timer = Instant::now();
         }
 
         // If someone has more blocks we sync
-        if nodes >= MIN_CONNECTED_NODES_START_SYNC && height < max_height {
+        // If we have downloaded too much, new requests will wait
+        let need_sync = nodes >= MIN_CONNECTED_NODES_START_SYNC && height < max_height && can_download;
+        if need_sync && !self.ask_batches_from_peers(registry, height, max_height, &have_blocks) {
             let count = min(max_height - height, nodes as u64);
             self.ask_blocks_from_peers(registry, height, height + count, have_blocks);
         }
@@ -330,9 +474,13 @@ impl Peers {
         }
 
         for (token, peer) in self.peers.iter_mut() {
-            if peer.get_state().need_reconnect() {
+            if peer.get_state().need_reconnect() && !self.proxied.contains_key(token) {
                 let addr = peer.get_addr();
-                if let Ok(mut stream) = TcpStream::connect(addr) {
+                if let Some(proxy) = self.proxy {
+                    debug!("Trying to reconnect to peer {} through proxy, count {}", &addr, peer.reconnects());
+                    self.proxied.insert(*token, addr);
+                    connect_through_proxy(self.proxy_sender.clone(), proxy, *token, addr);
+                } else if let Ok(mut stream) = TcpStream::connect(addr) {
                     debug!("Trying to reconnect to peer {}, count {}", &addr, peer.reconnects());
                     registry.register(&mut stream, *token, Interest::WRITABLE).unwrap();
                     peer.set_state(State::Connecting);
@@ -345,6 +493,75 @@ impl Peers {
         }
     }
 
+    /// Takes connections and addresses that helper threads got through the proxy
+    pub fn update_proxied(&mut self, registry: &Registry) {
+        while let Ok(result) = self.proxy_receiver.try_recv() {
+            match result {
+                ProxyResult::Resolved(addresses) => {
+                    for addr in addresses {
+                        if !self.skip_peer_connection(&addr) && !self.new_peers.contains(&addr) {
+                            self.new_peers.push(addr);
+                        }
+                    }
+                }
+                ProxyResult::Connected(token, result) => {
+                    let addr = match self.proxied.remove(&token) {
+                        Some(addr) => addr,
+                        None => continue
+                    };
+                    let stream = result.and_then(|stream| {
+                        stream.set_read_timeout(None)?;
+                        stream.set_write_timeout(None)?;
+                        stream.set_nonblocking(true)?;
+                        Ok(TcpStream::from_std(stream))
+                    });
+                    match (stream, self.peers.get_mut(&token)) {
+                        (Ok(mut stream), Some(peer)) => {
+                            registry.register(&mut stream, token, Interest::WRITABLE).unwrap();
+                            peer.set_state(State::Connecting);
+                            peer.inc_reconnects();
+                            peer.set_stream(stream);
+                        }
+                        (Ok(mut stream), None) => {
+                            trace!("Created connection {}, to peer {} through proxy", &token.0, &addr);
+                            registry.register(&mut stream, token, Interest::WRITABLE).unwrap();
+                            let mut peer = Peer::new(addr, stream, State::Connecting, false);
+                            peer.set_public(true);
+                            self.peers.insert(token, peer);
+                        }
+                        (Err(e), Some(peer)) => {
+                            debug!("Could not reconnect to {} through proxy: {}", &addr, e);
+                            peer.inc_reconnects();
+                        }
+                        (Err(e), None) => {
+                            debug!("Could not connect to {} through proxy: {}", &addr, e);
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    /// Sends announcement of new block to idle peers that are lower and understand announcements
+    pub fn announce_block(&mut self, registry: &Registry, index: u64, hash: &Bytes) {
+        let mut count = 0;
+        for (token, peer) in self.peers.iter_mut() {
+            if !peer.active() || !peer.get_state().is_idle() || !peer.has_flag(FLAG_ANNOUNCE) {
+                continue;
+            }
+            if peer.get_height() >= index || peer.get_sent_height() >= index {
+                continue;
+            }
+            peer.set_state(State::message(Message::Announce { index, hash: hash.clone() }));
+            peer.set_sent_height(index);
+            registry.reregister(peer.get_stream(), *token, Interest::WRITABLE).unwrap();
+            count += 1;
+        }
+        if count > 0 {
+            debug!("Announced block {} to {} peer(s)", index, count);
+        }
+    }
+
     #[allow(dead_code)]
     fn ask_block_from_peer(&mut self, registry: &Registry, height: u64) {
         let mut rng = rand::thread_rng();
@@ -361,6 +578,59 @@ impl Peers {
         }
     }
 
+    /// Splits missing blocks to batches and requests them from different peers that support batches.
+    /// Batches that were not received in time are requested again from other peers.
+    /// Returns false if there are no such peers.
+    fn ask_batches_from_peers(&mut self, registry: &Registry, height: u64, max_height: u64, have_blocks: &HashSet<u64>) -> bool {
+        if !self.peers.values().any(|peer| peer.active() && peer.has_flag(FLAG_BATCH)) {
+            return false;
+        }
+        let mut stalled = HashSet::new();
+        self.requested.retain(|from, (token, time)| {
+            if *from <= height {
+                return false;
+            }
+            if time.elapsed().as_secs() >= CHUNK_TIMEOUT_SEC {
+                debug!("Batch of blocks from {} has timed out, requesting it from another peer", from);
+                stalled.insert(*token);
+                return false;
+            }
+            true
+        });
+
+        let last = min(max_height, height + BLOCKS_BATCH_COUNT * MAX_PARALLEL_CHUNKS);
+        let mut from = height + 1;
+        while from <= last {
+            if self.requested.contains_key(&from) || have_blocks.contains(&from) {
+                from += BLOCKS_BATCH_COUNT;
+                continue;
+            }
+            let busy: HashSet<Token> = self.requested.values().map(|(token, _)| *token).collect();
+            let best = self.peers
+                .iter_mut()
+                .filter(|(token, _)| !busy.contains(*token) && !stalled.contains(*token))
+                .filter(|(_, peer)| peer.active() && peer.get_state().is_idle() && peer.has_flag(FLAG_BATCH) && peer.get_height() >= from && peer.can_give_block(from))
+                .max_by_key(|(_, peer)| peer.get_height());
+            match best {
+                None => break,
+                Some((token, peer)) => {
+                    debug!("Requesting blocks from {} from peer {}", from, &peer.get_addr().ip());
+                    let message = peer.request_blocks(from);
+                    registry.reregister(peer.get_stream(), *token, Interest::WRITABLE).unwrap();
+                    peer.set_state(State::message(message));
+                    self.requested.insert(from, (*token, Instant::now()));
+                }
+            }
+            from += BLOCKS_BATCH_COUNT;
+        }
+        true
+    }
+
+    /// Marks requested batch as received
+    pub fn batch_received(&mut self, from: u64) {
+        self.requested.remove(&from);
+    }
+
     fn ask_blocks_from_peers(&mut self, registry: &Registry, height: u64, max_height: u64, have_blocks: HashSet<u64>) {
         let mut rng = rand::thread_rng();
         let mut peers = self.peers
@@ -391,7 +661,14 @@ impl Peers {
         }
         self.new_peers.sort();
         self.new_peers.dedup();
-        let addr = self.new_peers.remove(0);
+        // We try to keep a mix of IPv4 and IPv6 outgoing connections
+        let (v4, v6) = self.peers
+            .values()
+            .filter(|peer| !peer.is_inbound())
+            .fold((0, 0), |(v4, v6), peer| if peer.get_addr().is_ipv4() { (v4 + 1, v6) } else { (v4, v6 + 1) });
+        let prefer_v6 = v6 < v4;
+        let index = self.new_peers.iter().position(|addr| addr.is_ipv6() == prefer_v6).unwrap_or(0);
+        let addr = self.new_peers.remove(index);
         match self.connect_peer(&addr, registry, unique_token, yggdrasil_only) {
             Ok(_) => {}
             Err(_) => {
@@ -409,7 +686,7 @@ impl Peers {
                 break;
             }
 
-            let mut addresses = Self::resolve(peer);
+            let mut addresses = self.resolve_peer(peer);
             while !addresses.is_empty() {
                 let addr = addresses.remove(0);
                 if !set.contains(&addr) {
@@ -431,6 +708,46 @@ impl Peers {
         }
     }
 
+    /// Resolving DNS seeds to addresses of nodes, connecting to some of them and queueing others
+    pub fn connect_seeds(&mut self, seeds: &[String], registry: &Registry, unique_token: &mut Token, yggdrasil_only: bool) {
+        let mut rng = rand::thread_rng();
+        for seed in seeds.iter() {
+            let host = match seed.contains(':') {
+                true => seed.to_owned(),
+                false => format!("{}:{}", seed, LISTEN_PORT)
+            };
+            let mut addresses = self.resolve_peer(&host);
+            addresses.shuffle(&mut rng);
+            let mut connected = 0;
+            for addr in addresses {
+                if self.skip_peer_connection(&addr) || self.new_peers.contains(&addr) {
+                    continue;
+                }
+                if connected < DNS_SEED_CONNECTS && self.connect_peer(&addr, registry, unique_token, yggdrasil_only).is_ok() {
+                    connected += 1;
+                    continue;
+                }
+                self.new_peers.push(addr);
+            }
+            debug!("Connected to {} node(s) from seed {}", connected, seed);
+        }
+    }
+
+    /// Resolves address directly, or through the proxy if it is set.
+    /// Names are resolved through the proxy in helper thread, their addresses are queued to `new_peers` later.
+    fn resolve_peer(&self, peer: &str) -> Vec<SocketAddr> {
+        match &self.proxy {
+            None => Self::resolve(peer),
+            Some(proxy) => match peer.parse::<SocketAddr>() {
+                Ok(addr) => vec![addr],
+                Err(_) => {
+                    resolve_through_proxy(self.proxy_sender.clone(), *proxy, peer.to_owned());
+                    vec![]
+                }
+            }
+        }
+    }
+
     /// Tries to resolve some address several times with timeout
     fn resolve(peer: &str) -> Vec<SocketAddr> {
         info!("Resolving address {}", peer);
@@ -455,7 +772,7 @@ impl Peers {
     }
 
     fn connect_peer(&mut self, addr: &SocketAddr, registry: &Registry, unique_token: &mut Token, yggdrasil_only: bool) -> io::Result<()> {
-        if self.ignored.contains(&addr.ip()) {
+        if self.is_ignored(&addr.ip()) {
             return Err(io::Error::from(io::ErrorKind::ConnectionAborted));
         }
         if yggdrasil_only && !is_yggdrasil(&addr.ip()) {
@@ -463,6 +780,15 @@ impl Peers {
             return Err(io::Error::from(io::ErrorKind::InvalidInput));
         }
         trace!("Connecting to peer {}", &addr);
+        if let Some(proxy) = self.proxy {
+            if self.proxied.values().any(|a| a == addr) {
+                return Err(io::Error::from(io::ErrorKind::AlreadyExists));
+            }
+            let token = next(unique_token);
+            self.proxied.insert(token, *addr);
+            connect_through_proxy(self.proxy_sender.clone(), proxy, token, *addr);
+            return Ok(());
+        }
         match TcpStream::connect(*addr) {
             Ok(mut stream) => {
                 //stream.set_nodelay(true)?;
@@ -500,6 +826,35 @@ pub fn next(current: &mut Token) -> Token {
     Token(next)
 }
 
+/// Makes the proxy handshake in helper thread, so that it doesn't block network loop
+fn connect_through_proxy(sender: Sender<ProxyResult>, proxy: SocketAddr, token: Token, addr: SocketAddr) {
+    thread::spawn(move || {
+        let _ = sender.send(ProxyResult::Connected(token, socks::connect(&proxy, &addr)));
+    });
+}
+
+/// Resolves name through the proxy in helper thread
+fn resolve_through_proxy(sender: Sender<ProxyResult>, proxy: SocketAddr, peer: String) {
+    thread::spawn(move || {
+        match socks::resolve(&proxy, &peer, LISTEN_PORT) {
+            Ok(addresses) => {
+                let _ = sender.send(ProxyResult::Resolved(addresses));
+            }
+            Err(e) => debug!("Can't resolve address {} through proxy: {}", peer, e)
+        }
+    });
+}
+
+/// Converts IPv4-mapped IPv6 addresses to IPv4 ones
+pub fn canonical_addr(addr: SocketAddr) -> SocketAddr {
+    if let IpAddr::V6(ipv6) = addr.ip() {
+        if let Some(ipv4) = ipv6.to_ipv4_mapped() {
+            return SocketAddr::new(IpAddr::V4(ipv4), addr.port());
+        }
+    }
+    addr
+}
+
 fn skip_private_addr(addr: &SocketAddr) -> bool {
     if addr.ip().is_loopback() {
         return true;
@@ -0,0 +1,140 @@
+//! Minimal SOCKS5 client (RFC 1928) to make outbound connections through Tor or other proxy.
+//! Names are resolved by the proxy too, using RESOLVE command of Tor.
+
+use std::io::{self, Error, ErrorKind, Read, Write};
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, TcpStream};
+use std::time::Duration;
+
+#[allow(unused_imports)]
+use log::{debug, error, info, trace, warn};
+
+const SOCKS_VERSION: u8 = 5;
+const NO_AUTH: u8 = 0;
+const CMD_CONNECT: u8 = 1;
+/// Tor extension to resolve names through the proxy
+const CMD_RESOLVE: u8 = 0xF0;
+const ATYP_IPV4: u8 = 1;
+const ATYP_DOMAIN: u8 = 3;
+const ATYP_IPV6: u8 = 4;
+const PROXY_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Connects to target address through the proxy, returns blocking stream
+pub fn connect(proxy: &SocketAddr, target: &SocketAddr) -> io::Result<TcpStream> {
+    let mut stream = open(proxy)?;
+    stream.write_all(&make_request(CMD_CONNECT, &Target::Addr(*target)))?;
+    read_reply(&mut stream)?;
+    trace!("Connected to {} through proxy {}", target, proxy);
+    Ok(stream)
+}
+
+/// Resolves `host:port` or `host` with given default port through the proxy
+pub fn resolve(proxy: &SocketAddr, peer: &str, default_port: u16) -> io::Result<Vec<SocketAddr>> {
+    if let Ok(addr) = peer.parse::<SocketAddr>() {
+        return Ok(vec![addr]);
+    }
+    let (host, port) = match peer.rsplit_once(':') {
+        Some((host, port)) => (host, port.parse().map_err(|_| Error::from(ErrorKind::InvalidInput))?),
+        None => (peer, default_port)
+    };
+    let mut stream = open(proxy)?;
+    stream.write_all(&make_request(CMD_RESOLVE, &Target::Domain(host.to_owned(), port)))?;
+    let addr = read_reply(&mut stream)?;
+    Ok(vec![SocketAddr::new(addr.ip(), port)])
+}
+
+enum Target {
+    Addr(SocketAddr),
+    Domain(String, u16)
+}
+
+fn open(proxy: &SocketAddr) -> io::Result<TcpStream> {
+    let mut stream = TcpStream::connect_timeout(proxy, PROXY_TIMEOUT)?;
+    stream.set_read_timeout(Some(PROXY_TIMEOUT))?;
+    stream.set_write_timeout(Some(PROXY_TIMEOUT))?;
+    stream.write_all(&[SOCKS_VERSION, 1, NO_AUTH])?;
+    let mut buf = [0u8; 2];
+    stream.read_exact(&mut buf)?;
+    if buf[0] != SOCKS_VERSION || buf[1] != NO_AUTH {
+        warn!("Proxy {} wants authentication, it is not supported", proxy);
+        return Err(Error::from(ErrorKind::PermissionDenied));
+    }
+    Ok(stream)
+}
+
+fn make_request(command: u8, target: &Target) -> Vec<u8> {
+    let mut buf = vec![SOCKS_VERSION, command, 0];
+    let port = match target {
+        Target::Addr(addr) => {
+            match addr.ip() {
+                IpAddr::V4(ip) => {
+                    buf.push(ATYP_IPV4);
+                    buf.extend_from_slice(&ip.octets());
+                }
+                IpAddr::V6(ip) => {
+                    buf.push(ATYP_IPV6);
+                    buf.extend_from_slice(&ip.octets());
+                }
+            }
+            addr.port()
+        }
+        Target::Domain(host, port) => {
+            buf.push(ATYP_DOMAIN);
+            buf.push(host.len() as u8);
+            buf.extend_from_slice(host.as_bytes());
+            *port
+        }
+    };
+    buf.extend_from_slice(&port.to_be_bytes());
+    buf
+}
+
+/// Reads reply to our request, returns bound address from it
+fn read_reply(stream: &mut TcpStream) -> io::Result<SocketAddr> {
+    let mut buf = [0u8; 4];
+    stream.read_exact(&mut buf)?;
+    if buf[0] != SOCKS_VERSION {
+        return Err(Error::from(ErrorKind::InvalidData));
+    }
+    if buf[1] != 0 {
+        debug!("Proxy returned error {}", buf[1]);
+        return Err(Error::from(ErrorKind::ConnectionRefused));
+    }
+    let ip = match buf[3] {
+        ATYP_IPV4 => {
+            let mut octets = [0u8; 4];
+            stream.read_exact(&mut octets)?;
+            IpAddr::V4(Ipv4Addr::from(octets))
+        }
+        ATYP_IPV6 => {
+            let mut octets = [0u8; 16];
+            stream.read_exact(&mut octets)?;
+            IpAddr::V6(Ipv6Addr::from(octets))
+        }
+        ATYP_DOMAIN => {
+            let mut len = [0u8; 1];
+            stream.read_exact(&mut len)?;
+            let mut name = vec![0u8; len[0] as usize];
+            stream.read_exact(&mut name)?;
+            IpAddr::V4(Ipv4Addr::UNSPECIFIED)
+        }
+        _ => return Err(Error::from(ErrorKind::InvalidData))
+    };
+    let mut port = [0u8; 2];
+    stream.read_exact(&mut port)?;
+    Ok(SocketAddr::new(ip, u16::from_be_bytes(port)))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::p2p::socks::{make_request, Target, CMD_CONNECT, CMD_RESOLVE};
+
+    #[test]
+    fn test_requests() {
+        let request = make_request(CMD_CONNECT, &Target::Addr("10.1.2.3:4244".parse().unwrap()));
+        assert_eq!(request, vec![5, 1, 0, 1, 10, 1, 2, 3, 0x10, 0x94]);
+        let request = make_request(CMD_RESOLVE, &Target::Domain(String::from("alfis.name"), 4244));
+        assert_eq!(&request[..5], &[5, 0xF0, 0, 3, 10]);
+        assert_eq!(&request[5..15], b"alfis.name");
+        assert_eq!(&request[15..], &[0x10, 0x94]);
+    }
+}
@@ -0,0 +1,62 @@
+use std::time::Instant;
+
+/// Simple token bucket to limit bandwidth.
+/// Messages are never split, so the bucket can go to debt, and then we wait until it is paid.
+#[derive(Debug)]
+pub struct Throttle {
+    /// Bytes per second, zero means unlimited
+    rate: u64,
+    available: i64,
+    last: Instant
+}
+
+impl Throttle {
+    pub fn new(rate: u64) -> Self {
+        Throttle { rate, available: rate as i64, last: Instant::now() }
+    }
+
+    /// Counts sent or received bytes
+    pub fn consume(&mut self, bytes: usize) {
+        if self.rate == 0 {
+            return;
+        }
+        self.refill();
+        self.available -= bytes as i64;
+    }
+
+    /// Checks if we can send or receive more data now
+    pub fn is_available(&mut self) -> bool {
+        if self.rate == 0 {
+            return true;
+        }
+        self.refill();
+        self.available > 0
+    }
+
+    fn refill(&mut self) {
+        let elapsed = self.last.elapsed().as_millis() as u64;
+        let amount = self.rate * elapsed / 1000;
+        if amount > 0 {
+            // We don't let it accumulate more than one second of traffic
+            self.available = (self.available + amount as i64).min(self.rate as i64);
+            self.last = Instant::now();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::p2p::throttle::Throttle;
+
+    #[test]
+    fn test_throttle() {
+        let mut unlimited = Throttle::new(0);
+        unlimited.consume(1_000_000);
+        assert!(unlimited.is_available());
+
+        let mut throttle = Throttle::new(1_000_000);
+        assert!(throttle.is_available());
+        throttle.consume(1_500_000);
+        assert!(!throttle.is_available());
+    }
+}
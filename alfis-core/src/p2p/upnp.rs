@@ -0,0 +1,58 @@
+//! Port forwarding on home routers with UPnP IGD, so the nodes behind NAT can accept inbound connections.
+
+#[allow(unused_imports)]
+use log::{debug, error, info, trace, warn};
+
+/// Lease time of port mapping in seconds, we renew it twice as often
+#[cfg(feature = "upnp")]
+const LEASE_DURATION: u32 = 3600;
+
+/// Maps the listen port on the router and keeps this mapping alive.
+/// Returns false if there is no router with UPnP or it refused mapping.
+#[cfg(feature = "upnp")]
+pub fn start_port_mapping(port: u16) -> bool {
+    use std::net::{SocketAddr, SocketAddrV4, UdpSocket};
+    use std::thread;
+    use std::time::Duration;
+
+    use igd::{PortMappingProtocol, SearchOptions};
+
+    let gateway = match igd::search_gateway(SearchOptions::default()) {
+        Ok(gateway) => gateway,
+        Err(e) => {
+            info!("No UPnP router found: {}", e);
+            return false;
+        }
+    };
+    // To know our local address in the network of router we "connect" UDP socket to it
+    let local_ip = match UdpSocket::bind("0.0.0.0:0").and_then(|s| s.connect(gateway.addr).and_then(|_| s.local_addr())) {
+        Ok(SocketAddr::V4(addr)) => *addr.ip(),
+        _ => {
+            warn!("Unable to get local address for port mapping");
+            return false;
+        }
+    };
+    let local_addr = SocketAddrV4::new(local_ip, port);
+    if let Err(e) = gateway.add_port(PortMappingProtocol::TCP, port, local_addr, LEASE_DURATION, "ALFIS") {
+        warn!("Router {} refused to map port {}: {}", gateway.addr, port, e);
+        return false;
+    }
+    match gateway.get_external_ip() {
+        Ok(ip) => info!("Mapped port {} on router, external address is {}:{}", port, ip, port),
+        Err(_) => info!("Mapped port {} on router {}", port, gateway.addr)
+    }
+
+    let _ = thread::Builder::new().name(String::from("upnp")).spawn(move || loop {
+        thread::sleep(Duration::from_secs(LEASE_DURATION as u64 / 2));
+        if let Err(e) = gateway.add_port(PortMappingProtocol::TCP, port, local_addr, LEASE_DURATION, "ALFIS") {
+            warn!("Error renewing port mapping: {}", e);
+        }
+    });
+    true
+}
+
+#[cfg(not(feature = "upnp"))]
+pub fn start_port_mapping(_port: u16) -> bool {
+    warn!("This build doesn't support UPnP, build with 'upnp' feature to enable it");
+    false
+}
@@ -0,0 +1,228 @@
+//! Local HTTP proxy for browsers that don't use the system resolver, or can't use ALFIS as it.
+//! It resolves names of ALFIS zones from blockchain by itself, and serves proxy auto-config file at `/proxy.pac`,
+//! that sends only ALFIS names to this proxy. Both `CONNECT` (for HTTPS) and plain HTTP requests are supported.
+//! Other names are not proxied, so the proxy can't be used to hide somebody's traffic.
+
+use std::io::{self, BufRead, BufReader, Error, ErrorKind, Write};
+use std::net::{IpAddr, Shutdown, SocketAddr, TcpListener, TcpStream, ToSocketAddrs};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+#[allow(unused_imports)]
+use log::{debug, error, info, trace, warn};
+
+use crate::blockchain::filter::BlockchainFilter;
+use crate::dns::filter::DnsFilter;
+use crate::dns::protocol::{DnsRecord, QueryType};
+use crate::{get_domain_zone, ChainRef, Context};
+
+/// Timeout of connecting to sites
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(15);
+/// Maximal count of lines in request head
+const MAX_HEAD_LINES: usize = 100;
+/// How many CNAME records we follow
+const MAX_ALIASES: usize = 4;
+
+/// Starts HTTP proxy if it is enabled in settings
+pub fn start_http_proxy(context: Arc<Mutex<Context>>) {
+    let listen = context.lock().unwrap().settings.http_proxy.listen.clone();
+    if listen.is_empty() {
+        return;
+    }
+    let listener = match TcpListener::bind(&listen) {
+        Ok(listener) => listener,
+        Err(e) => {
+            error!("Unable to start HTTP proxy on {}: {}", &listen, e);
+            return;
+        }
+    };
+    info!("HTTP proxy listens on {}, proxy auto-config is at http://{}/proxy.pac", &listen, &listen);
+    let chain = ChainRef::new(context);
+    let filter = Arc::new(BlockchainFilter::new(chain.clone()));
+    let _ = thread::Builder::new().name(String::from("http-proxy")).spawn(move || {
+        for stream in listener.incoming().flatten() {
+            let chain = chain.clone();
+            let filter = Arc::clone(&filter);
+            let listen = listen.clone();
+            let _ = thread::Builder::new().name(String::from("http-proxy-client")).spawn(move || {
+                if let Err(e) = handle_client(stream, &chain, &filter, &listen) {
+                    debug!("Error in proxy connection: {}", e);
+                }
+            });
+        }
+    });
+}
+
+/// Makes proxy auto-config file that sends names of these zones to the proxy, and everything else directly
+pub fn make_pac(zones: &[String], proxy: &str) -> String {
+    let mut conditions: Vec<String> = zones.iter()
+        .filter(|zone| !zone.is_empty())
+        .map(|zone| format!("dnsDomainIs(host, \".{}\")", zone))
+        .collect();
+    if conditions.is_empty() {
+        conditions.push(String::from("false"));
+    }
+    format!("function FindProxyForURL(url, host) {{\n    host = host.toLowerCase();\n    if ({}) {{\n        return \"PROXY {}\";\n    }}\n    return \"DIRECT\";\n}}\n", conditions.join(" ||\n        "), proxy)
+}
+
+fn handle_client(stream: TcpStream, chain: &ChainRef, filter: &BlockchainFilter, listen: &str) -> io::Result<()> {
+    let mut writer = stream.try_clone()?;
+    let mut reader = BufReader::new(stream);
+    let head = read_head(&mut reader)?;
+    let mut parts = head[0].split_whitespace();
+    let (method, target, version) = match (parts.next(), parts.next(), parts.next()) {
+        (Some(method), Some(target), Some(version)) => (method.to_owned(), target.to_owned(), version.to_owned()),
+        _ => return Err(Error::new(ErrorKind::InvalidData, "wrong request line"))
+    };
+
+    if method == "GET" && target == "/proxy.pac" {
+        let zones: Vec<String> = chain.read(|chain| chain.get_zones().iter().map(|zone| zone.name.clone()).collect());
+        // Browser knows how it reached us, the listen address can be 0.0.0.0
+        let proxy = head.iter()
+            .find_map(|line| line.split_once(':').filter(|(name, _)| name.eq_ignore_ascii_case("host")).map(|(_, value)| value.trim()))
+            .unwrap_or(listen);
+        let pac = make_pac(&zones, proxy);
+        write!(writer, "HTTP/1.1 200 OK\r\nContent-Type: application/x-ns-proxy-autoconfig\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}", pac.len(), pac)?;
+        return Ok(());
+    }
+
+    let (host, port, path) = match parse_target(&method, &target) {
+        Some(target) => target,
+        None => return answer(&mut writer, "400 Bad Request")
+    };
+    if !chain.is_available_zone(&get_domain_zone(&host)) {
+        return answer(&mut writer, "403 Forbidden");
+    }
+    let addr = match resolve(filter, &host, port) {
+        Some(addr) => addr,
+        None => return answer(&mut writer, "502 Bad Gateway")
+    };
+    debug!("Proxying {} {} to {}", &method, &target, &addr);
+    let mut remote = match TcpStream::connect_timeout(&addr, CONNECT_TIMEOUT) {
+        Ok(remote) => remote,
+        Err(_) => return answer(&mut writer, "504 Gateway Timeout")
+    };
+
+    if method == "CONNECT" {
+        writer.write_all(b"HTTP/1.1 200 Connection established\r\n\r\n")?;
+    } else {
+        // Sites get the request in origin form, without proxy headers
+        let mut request = format!("{} {} {}\r\n", method, path, version);
+        for line in head.iter().skip(1) {
+            if !line.to_lowercase().starts_with("proxy-") {
+                request.push_str(line);
+                request.push_str("\r\n");
+            }
+        }
+        request.push_str("\r\n");
+        remote.write_all(request.as_bytes())?;
+    }
+    // Some bytes can be already read by our reader
+    remote.write_all(reader.buffer())?;
+    let mut remote_reader = remote.try_clone()?;
+    let mut client = reader.into_inner();
+    let upload = thread::spawn(move || {
+        let _ = io::copy(&mut client, &mut remote);
+        let _ = remote.shutdown(Shutdown::Write);
+    });
+    let _ = io::copy(&mut remote_reader, &mut writer);
+    let _ = writer.shutdown(Shutdown::Both);
+    let _ = upload.join();
+    Ok(())
+}
+
+/// Reads request line and headers, without empty line at the end
+fn read_head<R: BufRead>(reader: &mut R) -> io::Result<Vec<String>> {
+    let mut head = Vec::new();
+    loop {
+        let mut line = String::new();
+        if reader.read_line(&mut line)? == 0 {
+            return Err(Error::new(ErrorKind::UnexpectedEof, "no end of headers"));
+        }
+        let line = line.trim_end();
+        if line.is_empty() {
+            break;
+        }
+        if head.len() >= MAX_HEAD_LINES {
+            return Err(Error::new(ErrorKind::InvalidData, "too many headers"));
+        }
+        head.push(line.to_owned());
+    }
+    if head.is_empty() {
+        return Err(Error::new(ErrorKind::InvalidData, "empty request"));
+    }
+    Ok(head)
+}
+
+/// Gets host, port and path from `host:port` of CONNECT, or from absolute URL of other methods
+fn parse_target(method: &str, target: &str) -> Option<(String, u16, String)> {
+    let (authority, path, default_port) = if method == "CONNECT" {
+        (target, "/", 443)
+    } else {
+        let rest = target.strip_prefix("http://")?;
+        match rest.find('/') {
+            Some(pos) => (&rest[..pos], &rest[pos..], 80),
+            None => (rest, "/", 80)
+        }
+    };
+    let (host, port) = match authority.rsplit_once(':') {
+        Some((host, port)) => (host, port.parse().ok()?),
+        None => (authority, default_port)
+    };
+    let host = host.trim_end_matches('.').to_lowercase();
+    if host.is_empty() {
+        return None;
+    }
+    Some((host, port, path.to_owned()))
+}
+
+/// Resolves ALFIS name from blockchain, following CNAME records to other names
+fn resolve(filter: &BlockchainFilter, host: &str, port: u16) -> Option<SocketAddr> {
+    let mut host = host.to_owned();
+    for _ in 0..MAX_ALIASES {
+        let mut alias = None;
+        for qtype in [QueryType::A, QueryType::AAAA] {
+            let packet = match filter.lookup(&host, qtype) {
+                Some(packet) => packet,
+                // It is not ALFIS name, the system knows it better
+                None => return (host.as_str(), port).to_socket_addrs().ok()?.next()
+            };
+            for record in packet.answers {
+                match record {
+                    DnsRecord::A { addr, .. } => return Some(SocketAddr::new(IpAddr::V4(addr), port)),
+                    DnsRecord::AAAA { addr, .. } => return Some(SocketAddr::new(IpAddr::V6(addr), port)),
+                    DnsRecord::CNAME { host, .. } => alias = Some(host),
+                    _ => {}
+                }
+            }
+        }
+        host = alias?;
+    }
+    None
+}
+
+fn answer(writer: &mut TcpStream, status: &str) -> io::Result<()> {
+    write!(writer, "HTTP/1.1 {}\r\nContent-Length: 0\r\nConnection: close\r\n\r\n", status)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::proxy::{make_pac, parse_target};
+
+    #[test]
+    fn test_parse_target() {
+        assert_eq!(parse_target("CONNECT", "example.ygg:443"), Some((String::from("example.ygg"), 443, String::from("/"))));
+        assert_eq!(parse_target("GET", "http://Example.ygg/page?x=1"), Some((String::from("example.ygg"), 80, String::from("/page?x=1"))));
+        assert_eq!(parse_target("GET", "http://example.ygg:8080"), Some((String::from("example.ygg"), 8080, String::from("/"))));
+        assert_eq!(parse_target("GET", "/local"), None);
+        assert_eq!(parse_target("CONNECT", "example.ygg:port"), None);
+    }
+
+    #[test]
+    fn test_pac() {
+        let pac = make_pac(&[String::from("ygg"), String::from("anon")], "127.0.0.1:4247");
+        assert!(pac.contains("dnsDomainIs(host, \".ygg\") ||\n        dnsDomainIs(host, \".anon\")"));
+        assert!(pac.contains("return \"PROXY 127.0.0.1:4247\";"));
+    }
+}
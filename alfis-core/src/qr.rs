@@ -0,0 +1,56 @@
+//! Texts for QR codes: public keys of the new owner for domain transfers, and domain transactions
+//! that are exported for a friend to mine. Scanners give the same text back, so it is parsed here too.
+
+use crate::{from_hex, Bytes, Transaction};
+
+/// Start of the text with public keys, the signing and encryption keys follow in hex, separated by colon
+pub const KEY_PREFIX: &str = "alfis:key:";
+/// Key length in bytes, for both signing and encryption keys
+const KEY_LENGTH: usize = 32;
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum QrPayload {
+    Key { signing: Bytes, encryption: Bytes },
+    Transaction(Transaction)
+}
+
+impl QrPayload {
+    pub fn to_text(&self) -> String {
+        match self {
+            QrPayload::Key { signing, encryption } => format!("{}{}:{}", KEY_PREFIX, signing.to_string(), encryption.to_string()),
+            QrPayload::Transaction(transaction) => transaction.to_string()
+        }
+    }
+
+    /// Parses text from QR code, or pasted by user
+    pub fn parse(text: &str) -> Option<QrPayload> {
+        let text = text.trim();
+        if let Some(keys) = text.strip_prefix(KEY_PREFIX) {
+            let (signing, encryption) = keys.split_once(':')?;
+            let signing = from_hex(signing).ok().filter(|key| key.len() == KEY_LENGTH)?;
+            let encryption = from_hex(encryption).ok().filter(|key| key.len() == KEY_LENGTH)?;
+            return Some(QrPayload::Key { signing: Bytes::from_bytes(&signing), encryption: Bytes::from_bytes(&encryption) });
+        }
+        Transaction::from_untrusted_json(text).ok().map(QrPayload::Transaction)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::qr::QrPayload;
+    use crate::{Bytes, Transaction};
+
+    #[test]
+    fn test_payload() {
+        let key = QrPayload::Key { signing: Bytes::from_bytes(&[1u8; 32]), encryption: Bytes::from_bytes(&[2u8; 32]) };
+        let text = key.to_text();
+        assert!(text.starts_with("alfis:key:0101"));
+        assert_eq!(QrPayload::parse(&format!(" {}\n", &text)), Some(key));
+        assert_eq!(QrPayload::parse("alfis:key:0101:0202"), None);
+
+        let transaction = Transaction::from_str(String::from("example.ygg"), String::from("domain"), String::from("{}"), Bytes::from_bytes(&[1u8; 32]), Bytes::from_bytes(&[2u8; 32]));
+        let payload = QrPayload::Transaction(transaction);
+        assert_eq!(QrPayload::parse(&payload.to_text()), Some(payload));
+        assert_eq!(QrPayload::parse("something else"), None);
+    }
+}
@@ -0,0 +1,36 @@
+//! Telling service managers about our state, by the protocol of `sd_notify(3)`.
+//! It is a datagram with lines like `READY=1` sent to the socket from `NOTIFY_SOCKET` environment variable.
+
+#[allow(unused_imports)]
+use log::{debug, error, info, trace, warn};
+
+/// Service is started and works
+pub const READY: &str = "READY=1";
+/// Service is shutting down
+pub const STOPPING: &str = "STOPPING=1";
+/// Service loaded its config again
+pub const RELOADING: &str = "RELOADING=1";
+
+/// Sends state to systemd if we are started by it, does nothing otherwise
+#[cfg(unix)]
+pub fn notify(state: &str) {
+    use std::env;
+    use std::os::unix::net::UnixDatagram;
+
+    let path = match env::var("NOTIFY_SOCKET") {
+        Ok(path) => path,
+        Err(_) => return
+    };
+    // Sockets in abstract namespace are not supported by std
+    if path.starts_with('@') {
+        debug!("Unable to notify service manager by abstract socket {}", &path);
+        return;
+    }
+    let result = UnixDatagram::unbound().and_then(|socket| socket.send_to(state.as_bytes(), &path));
+    if let Err(e) = result {
+        warn!("Unable to notify service manager: {}", e);
+    }
+}
+
+#[cfg(not(unix))]
+pub fn notify(_state: &str) {}
@@ -0,0 +1,564 @@
+use std::cmp::Ordering;
+use std::fs;
+use std::fs::File;
+use std::io::Read;
+use std::path::Path;
+
+#[allow(unused_imports)]
+use log::{debug, error, info, trace, warn, LevelFilter};
+use serde::{Deserialize, Serialize};
+
+use crate::commons::*;
+use crate::Bytes;
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Settings {
+    #[serde(default)]
+    pub origin: String,
+    #[serde(default)]
+    pub chain: ChainKind,
+    #[serde(default = "default_key_files")]
+    pub key_files: Vec<String>,
+    #[serde(default)]
+    pub default_key: String,
+    #[serde(default)]
+    pub use_keychain: bool,
+    #[serde(default)]
+    pub watch_keys: Vec<String>,
+    #[serde(default = "default_check_blocks")]
+    pub check_blocks: u64,
+    #[serde(default)]
+    pub prune_blocks: u64,
+    #[serde(default)]
+    pub data_dir: String,
+    #[serde(default)]
+    pub language: String,
+    #[serde(default)]
+    pub net: Net,
+    #[serde(default)]
+    pub dns: Dns,
+    #[serde(default)]
+    pub mining: Mining,
+    #[serde(default)]
+    pub export: Export,
+    #[serde(default)]
+    pub backup: Backup,
+    #[serde(default)]
+    pub api: Api,
+    #[serde(default)]
+    pub http_proxy: HttpProxy,
+    #[serde(default)]
+    pub log: Log,
+    #[serde(default)]
+    pub notifications: Notifications,
+    #[serde(default)]
+    pub webhooks: Vec<Webhook>
+}
+
+impl Settings {
+    pub fn load(filename: &str) -> Option<Settings> {
+        match File::open(filename) {
+            Ok(mut file) => {
+                let mut text = String::new();
+                file.read_to_string(&mut text).unwrap();
+                if let Ok(settings) = toml::from_str(&text) {
+                    return Some(settings);
+                }
+                None
+            }
+            Err(..) => None
+        }
+    }
+
+    /// Returns names of options that differ in `other` and can't be changed without restart
+    pub fn restart_needed(&self, other: &Settings) -> Vec<&'static str> {
+        let mut result = Vec::new();
+        let checks = [
+            ("origin", self.origin != other.origin),
+            ("chain", self.chain != other.chain),
+            ("data_dir", self.data_dir != other.data_dir),
+            ("prune_blocks", self.prune_blocks != other.prune_blocks),
+            ("key_files", self.key_files != other.key_files),
+            ("net.listen", self.net.listen != other.net.listen),
+            ("dns.listen", self.dns.listen != other.dns.listen),
+            ("dns.threads", self.dns.threads != other.dns.threads),
+            ("dns.doq_listen", self.dns.doq_listen != other.dns.doq_listen),
+            ("mining.threads", self.mining.threads != other.mining.threads),
+            ("mining.getwork_listen", self.mining.getwork_listen != other.mining.getwork_listen),
+            ("api.listen", self.api.listen != other.api.listen),
+            ("api.control_socket", self.api.control_socket != other.api.control_socket),
+            ("api.query_stats", self.api.query_stats != other.api.query_stats),
+            ("http_proxy.listen", self.http_proxy.listen != other.http_proxy.listen),
+            ("log", self.log.level != other.log.level || self.log.modules != other.log.modules || self.log.file != other.log.file || self.log.json != other.log.json)
+        ];
+        for (name, changed) in checks {
+            if changed {
+                result.push(name);
+            }
+        }
+        result
+    }
+
+    pub fn save(&self, filename: &str) -> bool {
+        match toml::to_string(&self) {
+            Ok(text) => match fs::write(filename, text) {
+                Ok(_) => true,
+                Err(e) => {
+                    error!("Error saving settings to {}: {}", filename, e);
+                    false
+                }
+            },
+            Err(e) => {
+                error!("Error serializing settings: {}", e);
+                false
+            }
+        }
+    }
+
+    /// Returns public keys from `watch_keys`, wrong keys are skipped
+    pub fn get_watch_keys(&self) -> Vec<Bytes> {
+        self.watch_keys.iter()
+            .filter_map(|key| match crate::from_hex(key) {
+                Ok(key) if key.len() == 32 => Some(Bytes::new(key)),
+                _ => {
+                    warn!("Wrong public key in watch_keys: {}", key);
+                    None
+                }
+            })
+            .collect()
+    }
+
+    /// Changes options that are left default for the main chain to the defaults of our chain,
+    /// so that test chain never touches the main one and both nodes can run together
+    pub fn apply_chain_defaults(&mut self) {
+        let (dns_port, data_dir) = match self.chain {
+            ChainKind::Main => return,
+            ChainKind::Testnet => (TESTNET_DNS_PORT, TESTNET_DATA_DIR),
+            ChainKind::Regtest => (REGTEST_DNS_PORT, REGTEST_DATA_DIR)
+        };
+        if self.origin == MAIN_ORIGIN {
+            self.origin = String::from(TESTNET_ORIGIN);
+        }
+        if self.chain == ChainKind::Regtest {
+            // Regtest node works alone
+            self.net.peers.clear();
+            self.net.lan_discovery = false;
+        } else if self.net.peers == Net::default().peers {
+            self.net.peers = TESTNET_PEERS.iter().map(|peer| peer.to_string()).collect();
+        }
+        // Seeds give nodes of the main chain
+        self.net.dns_seeds.clear();
+        self.net.listen = replace_port(&self.net.listen, LISTEN_PORT, TESTNET_LISTEN_PORT);
+        self.dns.listen = replace_port(&self.dns.listen, 53, dns_port);
+        self.data_dir = Path::new(&self.data_dir).join(data_dir).to_string_lossy().to_string();
+    }
+
+    /// How many last blocks keep their transactions, 0 means that all blocks are kept
+    pub fn get_prune_blocks(&self) -> u64 {
+        match self.prune_blocks {
+            0 => 0,
+            blocks => blocks.max(PRUNE_BLOCKS_MIN)
+        }
+    }
+
+    pub fn get_origin(&self) -> Bytes {
+        if self.origin.eq("") {
+            return Bytes::zero32();
+        }
+        let origin = crate::from_hex(&self.origin).expect("Wrong origin in settings");
+        Bytes::from_bytes(origin.as_slice())
+    }
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            origin: String::from(MAIN_ORIGIN),
+            chain: ChainKind::Main,
+            key_files: default_key_files(),
+            default_key: String::new(),
+            use_keychain: false,
+            watch_keys: Vec::new(),
+            check_blocks: default_check_blocks(),
+            prune_blocks: 0,
+            data_dir: String::new(),
+            language: String::new(),
+            net: Net::default(),
+            dns: Default::default(),
+            mining: Mining::default(),
+            export: Export::default(),
+            backup: Backup::default(),
+            api: Api::default(),
+            http_proxy: HttpProxy::default(),
+            log: Log::default(),
+            notifications: Notifications::default(),
+            webhooks: Vec::new()
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Dns {
+    #[serde(default = "default_listen_dns")]
+    pub listen: String,
+    #[serde(default = "default_threads")]
+    pub threads: usize,
+    pub forwarders: Vec<String>,
+    #[serde(default = "default_dns_bootstraps")]
+    pub bootstraps: Vec<String>,
+    #[serde(default)]
+    pub hosts: Vec<String>,
+    #[serde(default)]
+    pub routes: Vec<Route>,
+    #[serde(default)]
+    pub reverse: bool,
+    #[serde(default)]
+    pub doq_listen: String,
+    #[serde(default)]
+    pub tls_cert: String,
+    #[serde(default)]
+    pub tls_key: String
+}
+
+impl Default for Dns {
+    fn default() -> Self {
+        Dns {
+            listen: String::from("127.0.0.1:53"),
+            threads: 20,
+            forwarders: vec![String::from("94.140.14.14:53"), String::from("94.140.15.15:53")],
+            bootstraps: default_dns_bootstraps(),
+            hosts: Vec::new(),
+            routes: Vec::new(),
+            reverse: false,
+            doq_listen: String::new(),
+            tls_cert: String::new(),
+            tls_key: String::new()
+        }
+    }
+}
+
+/// Zone that is resolved by its own forwarders, empty list of forwarders means recursive resolution
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct Route {
+    pub zone: String,
+    #[serde(default)]
+    pub forwarders: Vec<String>
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Mining {
+    #[serde(default)]
+    pub threads: usize,
+    #[serde(default)]
+    pub lower: bool,
+    #[serde(default = "default_cpu_limit")]
+    pub cpu_limit: u8,
+    #[serde(default)]
+    pub start_hour: u8,
+    #[serde(default)]
+    pub end_hour: u8,
+    #[serde(default)]
+    pub only_on_ac: bool,
+    #[serde(default)]
+    pub getwork_listen: String
+}
+
+impl Default for Mining {
+    fn default() -> Self {
+        Mining { threads: 0, lower: false, cpu_limit: default_cpu_limit(), start_hour: 0, end_hour: 0, only_on_ac: false, getwork_listen: String::new() }
+    }
+}
+
+impl Mining {
+    /// Checks if the mining is allowed by schedule at given hour and power state
+    pub fn is_allowed(&self, hour: u8, on_ac: bool) -> bool {
+        if self.only_on_ac && !on_ac {
+            return false;
+        }
+        match self.start_hour.cmp(&self.end_hour) {
+            Ordering::Equal => true,
+            Ordering::Less => hour >= self.start_hour && hour < self.end_hour,
+            // Schedule goes over midnight, like from 22 to 7
+            Ordering::Greater => hour >= self.start_hour || hour < self.end_hour
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Export {
+    #[serde(default)]
+    pub file: String,
+    #[serde(default = "default_export_format")]
+    pub format: String,
+    #[serde(default)]
+    pub zone: String,
+    #[serde(default)]
+    pub domains: Vec<String>
+}
+
+impl Default for Export {
+    fn default() -> Self {
+        Export { file: String::new(), format: default_export_format(), zone: String::new(), domains: Vec::new() }
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Backup {
+    #[serde(default)]
+    pub dir: String,
+    #[serde(default)]
+    pub secondary: String,
+    #[serde(default = "default_backup_period")]
+    pub period: u64,
+    #[serde(default = "default_backup_keep")]
+    pub keep: usize,
+    #[serde(default = "default_backup_warn_days")]
+    pub warn_days: u64
+}
+
+impl Default for Backup {
+    fn default() -> Self {
+        Backup { dir: String::new(), secondary: String::new(), period: default_backup_period(), keep: default_backup_keep(), warn_days: default_backup_warn_days() }
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Notifications {
+    #[serde(default = "default_notify")]
+    pub enabled: bool,
+    #[serde(default = "default_notify")]
+    pub domains: bool,
+    #[serde(default = "default_notify")]
+    pub conflicts: bool,
+    #[serde(default = "default_notify_behind")]
+    pub behind_blocks: u64
+}
+
+impl Default for Notifications {
+    fn default() -> Self {
+        Notifications { enabled: default_notify(), domains: default_notify(), conflicts: default_notify(), behind_blocks: default_notify_behind() }
+    }
+}
+
+/// URL that gets events as HTTP POST with JSON, `events` are their types like in `/events` of API
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Webhook {
+    pub url: String,
+    #[serde(default = "default_webhook_events")]
+    pub events: Vec<String>
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Log {
+    #[serde(default = "default_log_level")]
+    pub level: String,
+    #[serde(default)]
+    pub modules: Vec<String>,
+    #[serde(default)]
+    pub file: String,
+    #[serde(default)]
+    pub json: bool,
+    #[serde(default = "default_log_max_size")]
+    pub max_size: u64,
+    #[serde(default = "default_log_keep")]
+    pub keep: usize
+}
+
+impl Default for Log {
+    fn default() -> Self {
+        Log { level: default_log_level(), modules: Vec::new(), file: String::new(), json: false, max_size: default_log_max_size(), keep: default_log_keep() }
+    }
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct Api {
+    #[serde(default)]
+    pub listen: String,
+    #[serde(default)]
+    pub control_socket: String,
+    #[serde(default)]
+    pub explorer: bool,
+    #[serde(default)]
+    pub query_stats: bool
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct HttpProxy {
+    #[serde(default)]
+    pub listen: String
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Net {
+    #[serde(default)]
+    pub peers: Vec<String>,
+    #[serde(default)]
+    pub dns_seeds: Vec<String>,
+    #[serde(default = "default_listen")]
+    pub listen: String,
+    #[serde(default)]
+    pub public: bool,
+    #[serde(default)]
+    pub yggdrasil_only: bool,
+    #[serde(default)]
+    pub proxy: String,
+    #[serde(default)]
+    pub upnp: bool,
+    #[serde(default = "default_max_inbound")]
+    pub max_inbound: usize,
+    #[serde(default = "default_max_outbound")]
+    pub max_outbound: usize,
+    #[serde(default = "default_max_per_ip")]
+    pub max_per_ip: usize,
+    #[serde(default)]
+    pub lan_discovery: bool,
+    #[serde(default)]
+    pub upload_limit: u64,
+    #[serde(default)]
+    pub download_limit: u64
+}
+
+impl Default for Net {
+    fn default() -> Self {
+        Net {
+            peers: vec![String::from("test-ip4.alfis.name:4244"), String::from("test-ip6.alfis.name:4244")],
+            dns_seeds: Vec::new(),
+            listen: String::from("[::]:4244"),
+            public: true,
+            yggdrasil_only: false,
+            proxy: String::new(),
+            upnp: false,
+            max_inbound: default_max_inbound(),
+            max_outbound: default_max_outbound(),
+            max_per_ip: default_max_per_ip(),
+            lan_discovery: false,
+            upload_limit: 0,
+            download_limit: 0
+        }
+    }
+}
+
+/// Changes the port of address if it is `from`
+fn replace_port(addr: &str, from: u16, to: u16) -> String {
+    match addr.strip_suffix(&format!(":{}", from)) {
+        Some(host) => format!("{}:{}", host, to),
+        None => addr.to_owned()
+    }
+}
+
+fn default_listen() -> String {
+    String::from("[::]:4244")
+}
+
+fn default_max_inbound() -> usize {
+    64
+}
+
+fn default_max_outbound() -> usize {
+    MAX_NODES
+}
+
+fn default_max_per_ip() -> usize {
+    4
+}
+
+fn default_listen_dns() -> String {
+    String::from("0.0.0.0:53")
+}
+
+fn default_threads() -> usize {
+    100
+}
+
+fn default_cpu_limit() -> u8 {
+    100
+}
+
+fn default_export_format() -> String {
+    String::from("hosts")
+}
+
+fn default_backup_period() -> u64 {
+    24
+}
+
+fn default_backup_keep() -> usize {
+    10
+}
+
+fn default_backup_warn_days() -> u64 {
+    7
+}
+
+fn default_notify() -> bool {
+    true
+}
+
+fn default_notify_behind() -> u64 {
+    10
+}
+
+fn default_log_level() -> String {
+    String::from("info")
+}
+
+fn default_log_max_size() -> u64 {
+    10
+}
+
+fn default_log_keep() -> usize {
+    5
+}
+
+fn default_webhook_events() -> Vec<String> {
+    vec![String::from("domain_conflict"), String::from("domain_expiring")]
+}
+
+fn default_check_blocks() -> u64 {
+    8
+}
+
+fn default_key_files() -> Vec<String> {
+    vec![
+        String::from("key1.toml"),
+        String::from("key2.toml"),
+        String::from("key3.toml"),
+        String::from("key4.toml"),
+        String::from("key5.toml"),
+    ]
+}
+
+fn default_dns_bootstraps() -> Vec<String> {
+    vec![String::from("9.9.9.9:53"), String::from("94.140.14.14:53")]
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::commons::*;
+    use crate::Settings;
+
+    #[test]
+    fn test_testnet_defaults() {
+        let mut settings = Settings::default();
+        settings.dns.listen = String::from("127.0.0.1:5300");
+        settings.chain = ChainKind::Testnet;
+        settings.apply_chain_defaults();
+        assert_eq!(settings.origin, TESTNET_ORIGIN);
+        assert_eq!(settings.net.peers, TESTNET_PEERS);
+        assert_eq!(settings.net.listen, "[::]:4344");
+        // Changed options stay
+        assert_eq!(settings.dns.listen, "127.0.0.1:5300");
+        assert_eq!(settings.data_dir, TESTNET_DATA_DIR);
+
+        let mut settings = Settings::default();
+        settings.chain = ChainKind::Regtest;
+        settings.apply_chain_defaults();
+        assert!(settings.net.peers.is_empty());
+        assert_eq!(settings.dns.listen, "127.0.0.1:5354");
+
+        let mut settings = Settings::default();
+        settings.apply_chain_defaults();
+        assert_eq!(settings.origin, MAIN_ORIGIN);
+        assert_eq!(settings.net.listen, "[::]:4244");
+    }
+}
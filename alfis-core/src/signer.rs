@@ -0,0 +1,51 @@
+//! Signing of blocks behind a trait, so the keys can live outside of ALFIS:
+//! in hardware tokens, PKCS#11 modules or remote signing agents.
+
+use std::fmt::Debug;
+
+use crate::Bytes;
+
+/// Owner of ed25519 key that can sign blocks with it
+pub trait Signer: Send + Sync + Debug {
+    /// Public key to check signatures, it goes to `pub_key` of blocks
+    fn public_key(&self) -> Bytes;
+
+    /// Signs the message, returns None if the device is not available or refused to sign
+    fn sign(&self, message: &[u8]) -> Option<Bytes>;
+
+    /// Path of key file to load this signer again after restart, empty if there is no such file
+    fn key_file(&self) -> &str {
+        ""
+    }
+
+    /// Offline signers can't sign right away, mined blocks are saved to be signed on another computer
+    fn is_offline(&self) -> bool {
+        false
+    }
+}
+
+/// Public key of the owner that keeps the secret key offline, see `watch_keys` setting
+#[derive(Clone, Debug)]
+pub struct WatchKey {
+    public: Bytes
+}
+
+impl WatchKey {
+    pub fn new(public: Bytes) -> Self {
+        WatchKey { public }
+    }
+}
+
+impl Signer for WatchKey {
+    fn public_key(&self) -> Bytes {
+        self.public.clone()
+    }
+
+    fn sign(&self, _message: &[u8]) -> Option<Bytes> {
+        None
+    }
+
+    fn is_offline(&self) -> bool {
+        true
+    }
+}
@@ -0,0 +1,271 @@
+//! Simulation of several nodes in memory to test consensus.
+//! Nodes mine blocks, lose connections to each other, get invalid blocks, and when all of them are connected again
+//! they must end up with the same chain. Nodes handle blocks by `p2p::network::process_block`, but messages go through queues.
+//! Mining real blocks is too slow for tests, so "mined" blocks are taken from a copy of the chain in `tests/blockchain.db`.
+
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::fs;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+
+use crate::blockchain::types::BlockQuality;
+use crate::p2p::network::{process_block, BlockOutcome};
+use crate::{Block, Bytes, Chain, Settings};
+
+/// Recorded chain that nodes "mine"
+pub const SIM_DB: &str = "./tests/blockchain.db";
+/// Protection from endless message loops
+const MAX_ROUNDS: usize = 10_000;
+/// Every simulation gets its own directory, tests run in parallel
+static SIM_COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Action {
+    /// Node mines the next block and sends it to its peers
+    Mine(usize),
+    /// Node sends a broken copy of the next block to its peers
+    SendInvalid(usize),
+    /// These nodes lose connections with all others
+    Partition(Vec<usize>),
+    /// All nodes are connected again
+    Reconnect,
+    /// All messages are delivered and nodes sync with their peers
+    Deliver
+}
+
+enum Packet {
+    Block(Block),
+    GetBlock(u64)
+}
+
+struct Envelope {
+    from: usize,
+    to: usize,
+    packet: Packet
+}
+
+pub struct Node {
+    pub chain: Chain,
+    future_blocks: HashMap<u64, Block>,
+    seen_blocks: HashSet<Bytes>,
+    /// Peers that sent us bad blocks
+    pub bad_peers: HashSet<usize>
+}
+
+impl Node {
+    fn new(settings: &Settings) -> Self {
+        Node { chain: Chain::new(settings, ":memory:"), future_blocks: HashMap::new(), seen_blocks: HashSet::new(), bad_peers: HashSet::new() }
+    }
+
+    /// Handles block from peer, returns a request to send back
+    fn handle_block(&mut self, from: usize, block: Block) -> Option<Packet> {
+        if !self.seen_blocks.insert(block.hash.clone()) {
+            return None;
+        }
+        match process_block(&mut self.chain, &mut self.future_blocks, &mut self.seen_blocks, block) {
+            BlockOutcome::Added | BlockOutcome::Replaced | BlockOutcome::Ignored => None,
+            BlockOutcome::Bad => {
+                self.bad_peers.insert(from);
+                None
+            }
+            BlockOutcome::Rewind(index) => Some(Packet::GetBlock(index)),
+            BlockOutcome::OurBlock(block) => Some(Packet::Block(block))
+        }
+    }
+}
+
+pub struct Sim {
+    pub nodes: Vec<Node>,
+    recorded: Chain,
+    /// Group of every node, nodes in different groups can't talk
+    groups: Vec<usize>,
+    queue: VecDeque<Envelope>,
+    /// Directory with the copy of recorded chain, it is removed with the simulation
+    dir: PathBuf
+}
+
+impl Sim {
+    pub fn new(count: usize) -> Self {
+        let settings = Settings::default();
+        let nodes = (0..count).map(|_| Node::new(&settings)).collect();
+        let id = SIM_COUNTER.fetch_add(1, Ordering::SeqCst);
+        let dir = std::env::temp_dir().join(format!("alfis-sim-{}-{}", std::process::id(), id));
+        fs::create_dir_all(&dir).expect("Unable to create directory for simulation");
+        let db = dir.join("blockchain.db");
+        fs::copy(SIM_DB, &db).expect("Unable to copy recorded chain");
+        let recorded = Chain::new(&settings, &db.to_string_lossy());
+        Sim { nodes, recorded, groups: vec![0; count], queue: VecDeque::new(), dir }
+    }
+
+    /// Height of the recorded chain, nodes can't mine more
+    pub fn max_height(&self) -> u64 {
+        self.recorded.get_height()
+    }
+
+    pub fn run(&mut self, actions: &[Action]) {
+        for action in actions {
+            self.apply(action);
+        }
+    }
+
+    pub fn apply(&mut self, action: &Action) {
+        match action {
+            Action::Mine(node) => {
+                if let Some(block) = self.next_block(*node) {
+                    let node_ref = &mut self.nodes[*node];
+                    node_ref.seen_blocks.insert(block.hash.clone());
+                    assert!(node_ref.chain.check_new_block(&block) == BlockQuality::Good, "Mined block {} is not good", block.index);
+                    node_ref.chain.add_block(block.clone());
+                    self.broadcast(*node, block);
+                }
+            }
+            Action::SendInvalid(node) => {
+                if let Some(mut block) = self.next_block(*node) {
+                    let mut signature = block.signature.to_vec();
+                    signature[0] ^= 0xFF;
+                    block.signature = Bytes::new(signature);
+                    self.broadcast(*node, block);
+                }
+            }
+            Action::Partition(nodes) => {
+                let group = self.groups.iter().max().unwrap() + 1;
+                for node in nodes {
+                    self.groups[*node] = group;
+                }
+            }
+            Action::Reconnect => self.groups.iter_mut().for_each(|group| *group = 0),
+            Action::Deliver => self.deliver()
+        }
+    }
+
+    /// Delivers messages and syncs connected nodes until nothing changes
+    pub fn deliver(&mut self) {
+        for _ in 0..MAX_ROUNDS {
+            while let Some(Envelope { from, to, packet }) = self.queue.pop_front() {
+                // Messages in flight are lost when connection breaks
+                if !self.connected(from, to) {
+                    continue;
+                }
+                let answer = match packet {
+                    Packet::Block(block) => self.nodes[to].handle_block(from, block),
+                    Packet::GetBlock(index) => self.nodes[to].chain.get_block(index).map(Packet::Block)
+                };
+                if let Some(packet) = answer {
+                    self.queue.push_back(Envelope { from: to, to: from, packet });
+                }
+            }
+            // Nodes tell their heights to each other, and lagging ones ask for the next block
+            for from in 0..self.nodes.len() {
+                for to in 0..self.nodes.len() {
+                    let (height, peer_height) = (self.nodes[to].chain.get_height(), self.nodes[from].chain.get_height());
+                    if self.connected(from, to) && peer_height > height {
+                        self.queue.push_back(Envelope { from: to, to: from, packet: Packet::GetBlock(height + 1) });
+                    }
+                }
+            }
+            if self.queue.is_empty() {
+                return;
+            }
+        }
+        panic!("Nodes didn't stop talking after {} rounds", MAX_ROUNDS);
+    }
+
+    /// Checks that all nodes have the same chain without invalid blocks
+    pub fn assert_converged(&self) {
+        let first = &self.nodes[0].chain;
+        for (i, node) in self.nodes.iter().enumerate() {
+            assert_eq!(node.chain.get_height(), first.get_height(), "Node {} has other height", i);
+            assert_eq!(node.chain.get_last_hash(), first.get_last_hash(), "Node {} has other last block", i);
+            for index in 1..=node.chain.get_height() {
+                let (block, recorded) = (node.chain.get_block(index).unwrap(), self.recorded.get_block(index).unwrap());
+                assert!(block.hash == recorded.hash && block.signature == recorded.signature, "Node {} has invalid block {}", i, index);
+            }
+        }
+    }
+
+    /// Random actions, but nodes mine only on top of the highest chain, as all blocks are from one recorded chain
+    pub fn random_actions(&self, seed: u64, count: usize) -> Vec<Action> {
+        let mut rng = StdRng::seed_from_u64(seed);
+        let nodes = self.nodes.len();
+        (0..count)
+            .map(|_| match rng.gen_range(0..10) {
+                0..=4 => Action::Mine(rng.gen_range(0..nodes)),
+                5 => Action::SendInvalid(rng.gen_range(0..nodes)),
+                6 => Action::Partition((0..nodes).filter(|_| rng.gen_bool(0.5)).collect()),
+                7 => Action::Reconnect,
+                _ => Action::Deliver
+            })
+            .collect()
+    }
+
+    fn next_block(&self, node: usize) -> Option<Block> {
+        self.recorded.get_block(self.nodes[node].chain.get_height() + 1)
+    }
+
+    fn connected(&self, from: usize, to: usize) -> bool {
+        from != to && self.groups[from] == self.groups[to]
+    }
+
+    fn broadcast(&mut self, from: usize, block: Block) {
+        for to in 0..self.nodes.len() {
+            if self.connected(from, to) {
+                self.queue.push_back(Envelope { from, to, packet: Packet::Block(block.clone()) });
+            }
+        }
+    }
+}
+
+impl Drop for Sim {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.dir);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::sim::{Action, Sim};
+
+    #[test]
+    fn test_partition_and_reconnect() {
+        let mut sim = Sim::new(3);
+        let mut actions: Vec<Action> = (0..10).map(|_| Action::Mine(0)).collect();
+        actions.push(Action::Deliver);
+        actions.push(Action::Partition(vec![2]));
+        actions.extend((0..5).map(|_| Action::Mine(1)));
+        actions.push(Action::Deliver);
+        sim.run(&actions);
+        assert_eq!(sim.nodes[0].chain.get_height(), 15);
+        assert_eq!(sim.nodes[2].chain.get_height(), 10);
+
+        sim.run(&[Action::Reconnect, Action::Deliver]);
+        sim.assert_converged();
+        assert_eq!(sim.nodes[2].chain.get_height(), 15);
+    }
+
+    #[test]
+    fn test_invalid_blocks() {
+        let mut sim = Sim::new(2);
+        sim.run(&[Action::Mine(0), Action::SendInvalid(0), Action::Deliver]);
+        assert!(sim.nodes[1].bad_peers.contains(&0));
+        assert_eq!(sim.nodes[1].chain.get_height(), 1);
+        sim.run(&[Action::Mine(0), Action::Deliver]);
+        sim.assert_converged();
+        assert_eq!(sim.nodes[1].chain.get_height(), 2);
+    }
+
+    #[test]
+    fn test_random_actions() {
+        for seed in 0..4 {
+            let mut sim = Sim::new(4);
+            let actions = sim.random_actions(seed, 60);
+            sim.run(&actions);
+            sim.run(&[Action::Reconnect, Action::Deliver]);
+            sim.assert_converged();
+            let mined = actions.iter().filter(|action| matches!(action, Action::Mine(_))).count() as u64;
+            assert!(sim.nodes[0].chain.get_height() <= mined.min(sim.max_height()));
+        }
+    }
+}
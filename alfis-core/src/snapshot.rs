@@ -0,0 +1,107 @@
+//! Snapshot of node state, saved to data directory periodically and on exit.
+//! Domains, peers and bans are kept in the database, and blocks are checked when they come,
+//! so on start we only need to know that the database is the same as when the node stopped.
+//! If the last block in database is the one from snapshot, last blocks are not checked again.
+
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+use chrono::Utc;
+#[allow(unused_imports)]
+use log::{debug, error, info, trace, warn};
+use serde::{Deserialize, Serialize};
+
+use crate::{Bytes, Chain, Context, Settings};
+
+/// File with snapshot in data directory
+pub const SNAPSHOT_FILE: &str = "snapshot.json";
+/// How often we save snapshot while working
+const SNAPSHOT_PERIOD: Duration = Duration::from_secs(300);
+
+#[derive(Serialize, Deserialize, Debug, Default, PartialEq)]
+pub struct Snapshot {
+    /// New versions can know bad blocks that old versions have taken
+    pub version: String,
+    pub height: u64,
+    pub hash: Bytes,
+    pub timestamp: i64
+}
+
+impl Snapshot {
+    pub fn take(context: &Context) -> Self {
+        Snapshot {
+            version: context.app_version.clone(),
+            height: context.chain.get_height(),
+            hash: context.chain.get_last_hash(),
+            timestamp: Utc::now().timestamp()
+        }
+    }
+
+    pub fn load(path: &Path) -> Option<Self> {
+        let data = fs::read_to_string(path).ok()?;
+        serde_json::from_str(&data).ok()
+    }
+
+    /// Writes to temporary file first, so that the snapshot is never half written
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        let temp = path.with_extension("tmp");
+        fs::write(&temp, serde_json::to_string(self)?)?;
+        fs::rename(&temp, path)
+    }
+
+    /// True if the chain is the same as when the snapshot was taken by this version
+    pub fn is_current(&self, chain: &Chain, version: &str) -> bool {
+        self.height > 0 && self.version == version && self.height == chain.get_height() && self.hash == chain.get_last_hash()
+    }
+}
+
+pub fn get_snapshot_path(settings: &Settings) -> PathBuf {
+    Path::new(&settings.data_dir).join(SNAPSHOT_FILE)
+}
+
+/// Saves snapshot to data directory
+pub fn save_snapshot(context: &Context) {
+    let path = get_snapshot_path(&context.settings);
+    if let Err(e) = Snapshot::take(context).save(&path) {
+        warn!("Error saving snapshot to {}: {}", path.display(), e);
+    }
+}
+
+/// Starts a thread that saves snapshots periodically, so that they are fresh after crashes too
+pub fn start_snapshots(context: Arc<Mutex<Context>>) {
+    let _ = thread::Builder::new().name(String::from("snapshot")).spawn(move || loop {
+        thread::sleep(SNAPSHOT_PERIOD);
+        save_snapshot(&context.lock().unwrap());
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+    use std::fs;
+
+    use crate::snapshot::Snapshot;
+    use crate::{Chain, Context, Settings};
+
+    #[test]
+    fn test_snapshot() {
+        let settings = Settings::default();
+        let chain = Chain::new(&settings, "./tests/blockchain.db");
+        let context = Context::new(String::from("0.8.0"), settings, Vec::new(), chain);
+        let snapshot = Snapshot::take(&context);
+        assert!(snapshot.is_current(&context.chain, "0.8.0"));
+        assert!(!snapshot.is_current(&context.chain, "0.8.1"));
+
+        let path = env::temp_dir().join(format!("alfis-snapshot-{}.json", std::process::id()));
+        snapshot.save(&path).unwrap();
+        assert_eq!(Snapshot::load(&path), Some(snapshot));
+        let _ = fs::remove_file(&path);
+
+        let empty = Chain::new(&Settings::default(), ":memory:");
+        assert!(!Snapshot::take(&context).is_current(&empty, "0.8.0"));
+    }
+}
@@ -0,0 +1,186 @@
+//! Search of keys with public key that starts with some pattern.
+//! Candidates are made from random seed and counter, so the search can be stopped and resumed
+//! from the state file without checking the same keys again.
+
+use std::fs;
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use blakeout::Blakeout;
+use ed25519_dalek::{PublicKey, SecretKey};
+#[allow(unused_imports)]
+use log::{debug, error, info, trace, warn};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::blockchain::hash_utils::key_hash_difficulty;
+use crate::{from_hex, to_hex, Keystore};
+
+/// Domain separation for candidates of vanity keys
+const VANITY_KEY_CONTEXT: &[u8] = b"ALFIS-VANITY-KEY";
+/// How often we report speed and save the state
+const REPORT_PERIOD: Duration = Duration::from_secs(10);
+
+/// What we want from the key
+#[derive(Clone, Debug, PartialEq)]
+pub struct VanityCriterion {
+    /// Beginning of public key in hex, upper case
+    pub prefix: String,
+    /// Minimal strength of the key, see `check_public_key_strength`
+    pub strength: u32
+}
+
+impl VanityCriterion {
+    /// Returns None if prefix is not a hex string
+    pub fn new(prefix: &str, strength: u32) -> Option<Self> {
+        let prefix = prefix.to_uppercase();
+        if !prefix.chars().all(|c| c.is_ascii_hexdigit()) || prefix.len() > 64 {
+            return None;
+        }
+        Some(VanityCriterion { prefix, strength })
+    }
+
+    /// How many keys we need to check in average to find one
+    pub fn expected_keys(&self) -> f64 {
+        16f64.powi(self.prefix.len() as i32) * 2f64.powi(self.strength as i32)
+    }
+
+    fn matches(&self, public: &PublicKey, digest: &mut Blakeout) -> bool {
+        if !to_hex(public.as_bytes()).starts_with(&self.prefix) {
+            return false;
+        }
+        if self.strength == 0 {
+            return true;
+        }
+        digest.reset();
+        digest.update(public.as_bytes());
+        key_hash_difficulty(digest.result()) >= self.strength
+    }
+}
+
+/// Progress of search, all counters below `counter` are already checked
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+struct VanityState {
+    seed: String,
+    prefix: String,
+    strength: u32,
+    counter: u64
+}
+
+impl VanityState {
+    fn load(path: &str, criterion: &VanityCriterion) -> Option<Self> {
+        let state: VanityState = serde_json::from_str(&fs::read_to_string(path).ok()?).ok()?;
+        if state.prefix != criterion.prefix || state.strength != criterion.strength {
+            return None;
+        }
+        Some(state)
+    }
+
+    fn save(&self, path: &str) {
+        if let Err(e) = fs::write(path, serde_json::to_string(&self).unwrap()) {
+            warn!("Error saving vanity search state to {}: {}", path, e);
+        }
+    }
+}
+
+/// Searches for the key in `threads` threads. If `state_file` has the state of search with the same criterion
+/// it continues from there, and the state is saved there periodically and when the search is stopped.
+pub fn find_vanity_key(criterion: &VanityCriterion, threads: usize, state_file: &str, running: Arc<AtomicBool>) -> Option<Keystore> {
+    let threads = threads.max(1) as u64;
+    let state = match VanityState::load(state_file, criterion) {
+        Some(state) => {
+            info!("Resuming search of key from {} checked keys", state.counter);
+            state
+        }
+        None => {
+            let seed: [u8; 32] = rand::random();
+            VanityState { seed: to_hex(&seed), prefix: criterion.prefix.clone(), strength: criterion.strength, counter: 0 }
+        }
+    };
+    let seed = from_hex(&state.seed).ok()?;
+    let start = state.counter;
+    let found = Arc::new(AtomicU64::new(u64::MAX));
+    // Next counter of every thread, all counters below the smallest of them are checked
+    let progress: Arc<Vec<AtomicU64>> = Arc::new((0..threads).map(|t| AtomicU64::new(start + t)).collect());
+    let live = Arc::new(AtomicUsize::new(threads as usize));
+    let mut handles = Vec::new();
+    for t in 0..threads {
+        let criterion = criterion.clone();
+        let seed = seed.clone();
+        let found = Arc::clone(&found);
+        let progress = Arc::clone(&progress);
+        let running = Arc::clone(&running);
+        let live = Arc::clone(&live);
+        handles.push(thread::spawn(move || {
+            let mut digest = Blakeout::default();
+            let mut counter = start + t;
+            while counter < found.load(Ordering::Relaxed) && running.load(Ordering::Relaxed) {
+                let secret = SecretKey::from_bytes(&vanity_secret(&seed, counter)).unwrap();
+                if criterion.matches(&PublicKey::from(&secret), &mut digest) {
+                    found.fetch_min(counter, Ordering::SeqCst);
+                    break;
+                }
+                counter += threads;
+                progress[t as usize].store(counter, Ordering::Relaxed);
+            }
+            live.fetch_sub(1, Ordering::SeqCst);
+        }));
+    }
+
+    let mut state = state;
+    let mut last = (Instant::now(), start);
+    while live.load(Ordering::SeqCst) > 0 {
+        thread::sleep(Duration::from_millis(100));
+        if last.0.elapsed() >= REPORT_PERIOD {
+            state.counter = progress.iter().map(|p| p.load(Ordering::Relaxed)).min().unwrap_or(start);
+            let speed = (state.counter - last.1) / last.0.elapsed().as_secs().max(1);
+            info!("Checked {} keys, {} keys/s, {:.0} keys needed in average", state.counter, speed, criterion.expected_keys());
+            state.save(state_file);
+            last = (Instant::now(), state.counter);
+        }
+    }
+    for handle in handles {
+        let _ = handle.join();
+    }
+
+    let counter = found.load(Ordering::SeqCst);
+    if counter == u64::MAX {
+        state.counter = progress.iter().map(|p| p.load(Ordering::Relaxed)).min().unwrap_or(start);
+        state.save(state_file);
+        return None;
+    }
+    let _ = fs::remove_file(state_file);
+    Some(Keystore::from_random_bytes(&vanity_secret(&seed, counter)))
+}
+
+/// Makes candidate for secret key
+fn vanity_secret(seed: &[u8], counter: u64) -> [u8; 32] {
+    let mut digest = Sha256::new();
+    digest.update(VANITY_KEY_CONTEXT);
+    digest.update(seed);
+    digest.update(counter.to_le_bytes());
+    digest.finalize().into()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::AtomicBool;
+    use std::sync::Arc;
+
+    use crate::vanity::{find_vanity_key, VanityCriterion};
+
+    #[test]
+    fn test_vanity_key() {
+        assert!(VanityCriterion::new("alfis", 0).is_none());
+        let criterion = VanityCriterion::new("a1", 0).unwrap();
+        assert_eq!(criterion.prefix, "A1");
+        assert_eq!(criterion.expected_keys(), 256.0);
+
+        let state_file = std::env::temp_dir().join("alfis-vanity-test.json");
+        let keystore = find_vanity_key(&criterion, 2, &state_file.to_string_lossy(), Arc::new(AtomicBool::new(true))).unwrap();
+        assert!(keystore.get_public().to_string().starts_with("A1"));
+        assert!(!state_file.exists());
+    }
+}
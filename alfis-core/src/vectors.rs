@@ -0,0 +1,222 @@
+//! Test vectors of serialization, hashing and signing: inputs with the bytes, hashes and signatures
+//! that must come out of them. Other implementations of ALFIS, and this one after refactoring,
+//! can check that they make the same blocks by verifying the same file of vectors.
+//! Every vector has all its inputs, so a file made by another program can be verified as well.
+
+use std::fs;
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+use crate::blockchain::hash_utils::{block_version, hash_identity, pow_hash};
+use crate::{Block, Bytes, Keystore, Transaction, CLASS_DOMAIN};
+
+pub const VECTORS_VERSION: u32 = 1;
+/// Secret keys of vectors, they are public and must never be used for anything else
+const SECRETS: [[u8; 32]; 2] = [[0x11; 32], [0xA5; 32]];
+const IDENTITIES: [&str; 3] = ["", "example.ygg", "тест.anon"];
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct VectorSet {
+    pub version: u32,
+    #[serde(default)]
+    pub identities: Vec<IdentityVector>,
+    #[serde(default)]
+    pub transactions: Vec<TransactionVector>,
+    #[serde(default)]
+    pub blocks: Vec<BlockVector>
+}
+
+/// Hash of identity, or confirmation if it is hashed with the key
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct IdentityVector {
+    pub identity: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub key: Option<Bytes>,
+    pub hash: Bytes
+}
+
+/// Transaction of domain with its JSON, as it is stored in blocks and database
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct TransactionVector {
+    pub domain: String,
+    pub transaction: Transaction,
+    pub json: String
+}
+
+/// Signed block with its serialized forms. The `block` has the expected hash and signature.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct BlockVector {
+    /// Secret key that signs the block
+    pub secret: Bytes,
+    pub block: Block,
+    /// Compact bytes of the block without hash and signature, they are hashed by PoW algorithm
+    pub hashed: Bytes,
+    /// Compact bytes of the block without signature, they are signed
+    pub signed: Bytes,
+    /// CBOR of the whole block, as it is sent to other nodes
+    pub cbor: Bytes
+}
+
+impl VectorSet {
+    /// Makes vectors from the fixed inputs, they are the same every time
+    pub fn generate() -> Self {
+        let keys: Vec<Keystore> = SECRETS.iter().map(|secret| Keystore::from_random_bytes(secret)).collect();
+
+        let mut identities = Vec::new();
+        for identity in IDENTITIES.iter() {
+            identities.push(IdentityVector { identity: identity.to_string(), key: None, hash: hash_identity(identity, None) });
+            for keystore in &keys {
+                let key = keystore.get_public();
+                let hash = hash_identity(identity, Some(&key));
+                identities.push(IdentityVector { identity: identity.to_string(), key: Some(key), hash });
+            }
+        }
+
+        let mut transactions = Vec::new();
+        for (domain, keystore) in IDENTITIES.iter().skip(1).zip(keys.iter()) {
+            // Domain data is encrypted with random nonce, so the data here is fixed
+            let data = String::from(r#"{"encrypted":"","zone":"ygg","records":[{"type":"A","domain":"@","addr":"10.0.0.1","ttl":3600}]}"#);
+            let transaction = Transaction::from_str(domain.to_string(), CLASS_DOMAIN.to_owned(), data, keystore.get_public(), keystore.get_encryption_public());
+            let json = transaction.to_string();
+            transactions.push(TransactionVector { domain: domain.to_string(), transaction, json });
+        }
+
+        let origin = Transaction::origin(hash_identity("ygg", None), keys[0].get_public(), keys[0].get_encryption_public());
+        let domain = transactions[0].transaction.clone();
+        // Blocks of both PoW algorithms, with and without transaction
+        let blocks = vec![
+            make_block(&keys[0], 1, false, Bytes::default(), Some(origin)),
+            make_block(&keys[1], 2, false, Bytes::new(vec![0x42; 32]), None),
+            make_block(&keys[1], 3, true, Bytes::new(vec![0x24; 32]), Some(domain))
+        ];
+
+        VectorSet { version: VECTORS_VERSION, identities, transactions, blocks }
+    }
+
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let json = fs::read_to_string(path).map_err(|e| e.to_string())?;
+        serde_json::from_str(&json).map_err(|e| e.to_string())
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), String> {
+        let json = serde_json::to_string_pretty(self).map_err(|e| e.to_string())?;
+        fs::write(path, json).map_err(|e| e.to_string())
+    }
+
+    /// Checks every vector, returns how many were checked or the first that failed
+    pub fn verify(&self) -> Result<usize, String> {
+        if self.version != VECTORS_VERSION {
+            return Err(format!("Unsupported version of vectors {}", self.version));
+        }
+        for (i, vector) in self.identities.iter().enumerate() {
+            if hash_identity(&vector.identity, vector.key.as_ref()) != vector.hash {
+                return Err(format!("Identity {} '{}': wrong hash", i, vector.identity));
+            }
+        }
+        for (i, vector) in self.transactions.iter().enumerate() {
+            verify_transaction(vector).map_err(|e| format!("Transaction {} '{}': {}", i, vector.domain, e))?;
+        }
+        for (i, vector) in self.blocks.iter().enumerate() {
+            verify_block(vector).map_err(|e| format!("Block {} (index {}): {}", i, vector.block.index, e))?;
+        }
+        Ok(self.identities.len() + self.transactions.len() + self.blocks.len())
+    }
+}
+
+fn make_block(keystore: &Keystore, index: u64, argon2: bool, prev_block_hash: Bytes, transaction: Option<Transaction>) -> BlockVector {
+    let mut block = Block::new(transaction, keystore.get_public(), prev_block_hash, 20);
+    block.index = index;
+    block.timestamp = 1_600_000_000 + index as i64;
+    block.version = block_version(argon2);
+    block.random = 0x1234_5678;
+    block.nonce = index * 1000;
+    let hashed = Bytes::new(block.as_bytes_compact());
+    block.hash = pow_hash(block.version, &hashed);
+    let signed = Bytes::new(block.as_bytes_compact());
+    block.signature = Bytes::from_bytes(&keystore.sign(&signed));
+    let cbor = Bytes::new(block.as_bytes());
+    BlockVector { secret: keystore.get_private(), block, hashed, signed, cbor }
+}
+
+fn verify_transaction(vector: &TransactionVector) -> Result<(), &'static str> {
+    if vector.transaction.to_string() != vector.json {
+        return Err("wrong JSON");
+    }
+    if Transaction::from_json(&vector.json).as_ref() != Some(&vector.transaction) {
+        return Err("JSON is parsed to other transaction");
+    }
+    if !vector.transaction.check_identity(&vector.domain) {
+        return Err("wrong identity or confirmation");
+    }
+    Ok(())
+}
+
+fn verify_block(vector: &BlockVector) -> Result<(), &'static str> {
+    let block = &vector.block;
+    if block.as_bytes() != vector.cbor.as_slice() {
+        return Err("wrong CBOR");
+    }
+    match Block::from_bytes(&vector.cbor) {
+        Ok(parsed) if &parsed == block => {}
+        _ => return Err("CBOR is parsed to other block")
+    }
+    let mut copy = block.clone();
+    copy.signature = Bytes::default();
+    let signed = copy.as_bytes_compact();
+    copy.hash = Bytes::default();
+    let hashed = copy.as_bytes_compact();
+    if hashed != vector.hashed.as_slice() || alfis_verify::compact_bytes(&(&copy).into()) != hashed {
+        return Err("wrong compact bytes for hash");
+    }
+    if signed != vector.signed.as_slice() {
+        return Err("wrong compact bytes for signature");
+    }
+    if pow_hash(block.version, &hashed) != block.hash {
+        return Err("wrong hash");
+    }
+    if vector.secret.length() != 32 {
+        return Err("wrong secret key");
+    }
+    let keystore = Keystore::from_random_bytes(&vector.secret);
+    if keystore.get_public() != block.pub_key {
+        return Err("public key is not of this secret key");
+    }
+    // Ed25519 signatures don't have randomness, so they must be the same
+    if keystore.sign(&signed)[..] != block.signature[..] {
+        return Err("wrong signature");
+    }
+    if !alfis_verify::check_block_signature(&block.into()) {
+        return Err("signature is not valid");
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::vectors::VectorSet;
+
+    #[test]
+    fn test_vectors() {
+        let vectors = VectorSet::generate();
+        assert_eq!(vectors, VectorSet::generate());
+        let count = vectors.identities.len() + vectors.transactions.len() + vectors.blocks.len();
+        assert_eq!(vectors.verify(), Ok(count));
+
+        let json = serde_json::to_string(&vectors).unwrap();
+        let parsed: VectorSet = serde_json::from_str(&json).unwrap();
+        assert_eq!(parsed.verify(), Ok(count));
+
+        let mut broken = vectors.clone();
+        broken.identities[1].hash = broken.identities[0].hash.clone();
+        assert!(broken.verify().is_err());
+
+        let mut broken = vectors.clone();
+        broken.transactions[0].json = broken.transactions[0].json.replace("10.0.0.1", "10.0.0.2");
+        assert!(broken.verify().is_err());
+
+        let mut broken = vectors;
+        broken.blocks[1].block.nonce += 1;
+        assert!(broken.verify().is_err());
+    }
+}
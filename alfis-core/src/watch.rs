@@ -0,0 +1,152 @@
+//! Watching for expiration of our domains, including the domains of keys from `watch_keys` setting,
+//! which secret keys are kept on another computer. We also tell when other key changes some of these domains.
+
+use std::sync::mpsc;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+use chrono::Utc;
+#[allow(unused_imports)]
+use log::{debug, error, info, trace, warn};
+
+use crate::event::Event;
+use crate::eventbus::{post, register};
+use crate::{Bytes, Context, CLASS_DOMAIN};
+
+/// We warn about domains that expire sooner than this
+const EXPIRATION_WARNING: i64 = 86400 * 30;
+/// How often we check our domains
+const CHECK_PERIOD: Duration = Duration::from_secs(3600);
+/// Older blocks are from synchronization, their conflicts are history
+const CONFLICT_MAX_AGE: i64 = 86400;
+
+/// Starts a thread that checks expiration of our domains every hour, and a thread that checks new blocks for conflicts
+pub fn start_domain_watcher(context: Arc<Mutex<Context>>) {
+    let watcher_context = Arc::clone(&context);
+    let _ = thread::Builder::new().name(String::from("watcher")).spawn(move || loop {
+        // We let the node to sync a bit before the first check
+        thread::sleep(Duration::from_secs(60));
+        let expiring = {
+            let context = watcher_context.lock().unwrap();
+            get_expiring_domains(&context, Utc::now().timestamp())
+        };
+        for (identity, owner, expire) in expiring {
+            warn!("Domain {} of key {} expires at {}, renew it!", &identity, &owner, expire);
+            post(Event::DomainExpiring { identity, owner, expire });
+        }
+        thread::sleep(CHECK_PERIOD);
+    });
+
+    // Listeners of event bus must not lock the context, so blocks are checked in other thread
+    let (sender, receiver) = mpsc::channel();
+    let sender = Mutex::new(sender);
+    register(move |_uuid, e| {
+        match e {
+            Event::NewBlock { index, .. } => sender.lock().unwrap().send(index).is_ok(),
+            Event::ActionQuit => false,
+            _ => true
+        }
+    });
+    let _ = thread::Builder::new().name(String::from("conflicts")).spawn(move || {
+        for index in receiver {
+            let conflict = {
+                let context = context.lock().unwrap();
+                find_conflict(&context, index, Utc::now().timestamp())
+            };
+            if let Some((identity, owner, key)) = conflict {
+                warn!("Domain {} of key {} was changed by key {} in block {}!", &identity, &owner, &key, index);
+                post(Event::DomainConflict { index, identity, owner, key });
+            }
+        }
+    });
+}
+
+/// Returns (identity, owner, other key) if domain of our key was changed in this block by other key
+fn find_conflict(context: &Context, index: u64, now: i64) -> Option<(String, String, String)> {
+    let block = context.chain.get_block(index)?;
+    let transaction = block.transaction.as_ref()?;
+    if transaction.class != CLASS_DOMAIN || block.timestamp < now - CONFLICT_MAX_AGE {
+        return None;
+    }
+    let (previous, _state) = context.chain.get_identity_transaction_and_state(&transaction.identity, index, block.timestamp);
+    let owner = previous?.signing;
+    let mut keys: Vec<Bytes> = context.get_keystores().iter().map(|k| k.get_public()).collect();
+    keys.extend(context.settings.get_watch_keys());
+    // Friends can mine transactions of owners, so only the key that signs the transaction matters
+    let signing = block.get_signing()?;
+    if keys.contains(&owner) && signing != &owner {
+        return Some((transaction.identity.to_string(), owner.to_string(), signing.to_string()));
+    }
+    None
+}
+
+/// Name of our domain that was changed by other key in this block, if we have the key of its owner to decrypt it
+pub fn get_conflict_domain_name(context: &Context, index: u64) -> Option<String> {
+    let block = context.chain.get_block(index)?;
+    let transaction = block.transaction.as_ref()?;
+    let (previous, _state) = context.chain.get_identity_transaction_and_state(&transaction.identity, index, block.timestamp);
+    let previous = previous?;
+    let keystore = context.get_keystore_by_public(&previous.signing)?;
+    let data = previous.get_domain_data()?;
+    String::from_utf8(keystore.decrypt(data.encrypted.as_slice()).to_vec()).ok().filter(|name| !name.is_empty())
+}
+
+/// Returns (identity, owner, expiration time) of domains that expire soon
+fn get_expiring_domains(context: &Context, now: i64) -> Vec<(String, String, i64)> {
+    let mut keys: Vec<Bytes> = context.get_keystores().iter().map(|k| k.get_public()).collect();
+    keys.extend(context.settings.get_watch_keys());
+    let mut result = Vec::new();
+    for key in keys {
+        for (identity, (timestamp, _data)) in context.chain.get_owned_domains(&key) {
+            let expire = timestamp + context.chain.get_kind().domain_lifetime();
+            if is_expiring(expire, now) {
+                result.push((identity.to_string(), key.to_string(), expire));
+            }
+        }
+    }
+    result
+}
+
+fn is_expiring(expire: i64, now: i64) -> bool {
+    expire > now && expire - now < EXPIRATION_WARNING
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::watch::{find_conflict, is_expiring};
+    use crate::{Block, Bytes, Chain, Context, Keystore, Settings, Transaction, CLASS_DOMAIN};
+
+    #[test]
+    fn test_is_expiring() {
+        let now = 1_700_000_000;
+        assert!(is_expiring(now + 86400, now));
+        assert!(!is_expiring(now + 86400 * 60, now));
+        // Already expired domains are not ours anymore
+        assert!(!is_expiring(now - 1, now));
+    }
+
+    #[test]
+    fn test_find_conflict() {
+        let owner = Keystore::from_random_bytes(&[1u8; 32]);
+        let friend = Keystore::from_random_bytes(&[2u8; 32]);
+        let other = Keystore::from_random_bytes(&[3u8; 32]);
+        let now = 1_700_000_000;
+        let settings = Settings::default();
+        let mut chain = Chain::new(&settings, ":memory:");
+        let data = String::from(r#"{"encrypted":"","zone":"ygg","records":[]}"#);
+        // Owner mines the domain, then the friend mines its renewal, then the friend mines it for other key
+        for (index, miner, signing) in [(1, &owner, &owner), (2, &friend, &owner), (3, &friend, &other)] {
+            let transaction = Transaction::from_str(String::from("example.ygg"), CLASS_DOMAIN.to_owned(), data.clone(), signing.get_public(), signing.get_encryption_public());
+            let mut block = Block::new(Some(transaction), miner.get_public(), Bytes::default(), 20);
+            block.index = index;
+            block.timestamp = now;
+            chain.add_block(block);
+        }
+        let context = Context::new(String::from("test"), settings, vec![owner.clone()], chain);
+        assert_eq!(find_conflict(&context, 2, now), None);
+        let (_identity, key, changed_by) = find_conflict(&context, 3, now).unwrap();
+        assert_eq!(key, owner.get_public().to_string());
+        assert_eq!(changed_by, other.get_public().to_string());
+    }
+}
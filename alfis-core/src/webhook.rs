@@ -0,0 +1,118 @@
+//! Webhooks from `[[webhooks]]` settings: events of the node are sent to their URLs as HTTP POST with JSON,
+//! the same objects that `/events` of API gives. So operators can get alerts in chats and monitoring.
+
+use std::collections::HashMap;
+use std::sync::mpsc;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+use chrono::Utc;
+#[allow(unused_imports)]
+use log::{debug, error, info, trace, warn};
+
+use crate::api::websocket::ApiEvent;
+use crate::event::Event;
+use crate::eventbus::register;
+use crate::settings::Webhook;
+use crate::Context;
+
+/// How long we wait for the server of webhook
+const TIMEOUT: Duration = Duration::from_secs(10);
+/// Expiring domains are reported every hour, but we send them once a day
+const EXPIRING_PERIOD: i64 = 86400;
+
+/// Starts a thread that sends events to webhooks, the list of webhooks is taken from settings for every event
+pub fn start_webhooks(context: Arc<Mutex<Context>>) {
+    // Listeners of event bus must not lock the context
+    let (sender, receiver) = mpsc::channel();
+    let sender = Mutex::new(sender);
+    register(move |_uuid, e| {
+        if matches!(e, Event::ActionQuit) {
+            return false;
+        }
+        match ApiEvent::from_event(&e) {
+            Some(event) => sender.lock().unwrap().send(event).is_ok(),
+            None => true
+        }
+    });
+    let _ = thread::Builder::new().name(String::from("webhooks")).spawn(move || {
+        let mut expiring_sent: HashMap<String, i64> = HashMap::new();
+        for event in receiver {
+            let webhooks = context.lock().unwrap().settings.webhooks.clone();
+            let webhooks: Vec<Webhook> = webhooks.into_iter().filter(|webhook| webhook.events.contains(&event_type(&event))).collect();
+            if webhooks.is_empty() {
+                continue;
+            }
+            if let ApiEvent::DomainExpiring { identity, .. } = &event {
+                let now = Utc::now().timestamp();
+                match expiring_sent.get(identity) {
+                    Some(time) if now - time < EXPIRING_PERIOD => continue,
+                    _ => { expiring_sent.insert(identity.clone(), now); }
+                }
+            }
+            let body = serde_json::to_string(&event).unwrap();
+            for webhook in webhooks {
+                match post_json(&webhook.url, &body) {
+                    Ok(status) if (200..300).contains(&status) => debug!("Webhook {} got {}", &webhook.url, &body),
+                    Ok(status) => warn!("Webhook {} answered with status {}", &webhook.url, status),
+                    Err(e) => warn!("Error sending event to webhook {}: {}", &webhook.url, e)
+                }
+            }
+        }
+    });
+}
+
+/// Returns the value of `type` field of this event, like "domain_expiring"
+fn event_type(event: &ApiEvent) -> String {
+    serde_json::to_value(event).ok().and_then(|value| value["type"].as_str().map(|s| s.to_owned())).unwrap_or_default()
+}
+
+/// Sends JSON by HTTP POST, returns status of response
+#[cfg(feature = "doh")]
+fn post_json(url: &str, body: &str) -> Result<u16, String> {
+    let result = ureq::post(url)
+        .timeout(TIMEOUT)
+        .set("Content-Type", "application/json")
+        .send_string(body);
+    match result {
+        Ok(response) => Ok(response.status()),
+        Err(ureq::Error::Status(status, _)) => Ok(status),
+        Err(e) => Err(e.to_string())
+    }
+}
+
+/// Sends JSON by HTTP POST, returns status of response. Only plain HTTP is supported without TLS of "doh" feature
+#[cfg(not(feature = "doh"))]
+fn post_json(url: &str, body: &str) -> Result<u16, String> {
+    use std::io::{Read, Write};
+    use std::net::{TcpStream, ToSocketAddrs};
+
+    let rest = url.strip_prefix("http://").ok_or("only http:// URLs are supported without \"doh\" feature")?;
+    let (host, path) = match rest.find('/') {
+        Some(pos) => (&rest[..pos], &rest[pos..]),
+        None => (rest, "/")
+    };
+    let addr = if host.contains(':') { host.to_owned() } else { format!("{}:80", host) };
+    let addr = addr.to_socket_addrs().map_err(|e| e.to_string())?.next().ok_or("unable to resolve host")?;
+    let mut stream = TcpStream::connect_timeout(&addr, TIMEOUT).map_err(|e| e.to_string())?;
+    stream.set_read_timeout(Some(TIMEOUT)).map_err(|e| e.to_string())?;
+    let request = format!("POST {} HTTP/1.1\r\nHost: {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}", path, host, body.len(), body);
+    stream.write_all(request.as_bytes()).map_err(|e| e.to_string())?;
+    let mut text = String::new();
+    stream.read_to_string(&mut text).map_err(|e| e.to_string())?;
+    text.split_whitespace().nth(1).and_then(|status| status.parse().ok()).ok_or_else(|| String::from("wrong response"))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::api::websocket::ApiEvent;
+    use crate::webhook::event_type;
+
+    #[test]
+    fn test_event_type() {
+        let event = ApiEvent::DomainConflict { index: 5, identity: String::from("AA"), owner: String::from("BB"), key: String::from("CC") };
+        assert_eq!(event_type(&event), "domain_conflict");
+        assert_eq!(event_type(&ApiEvent::SyncFinished), "sync_finished");
+    }
+}
@@ -0,0 +1,4 @@
+//! C library with functions of `alfis_core::ffi`, built by `cargo build -p alfis-ffi --release`.
+//! Functions are declared in `alfis-core/include/alfis.h`.
+
+pub use alfis_core::ffi::*;
@@ -0,0 +1,75 @@
+//! Compact form of blocks to hash and sign them. It is the same as bincode gives for blocks of `alfis-core`:
+//! numbers in little endian, strings with u64 length before them, hashes and keys as upper case hex strings,
+//! and empty (zero) fields are skipped at all.
+
+use alloc::vec::Vec;
+
+use crate::{Block, Transaction};
+
+const HEX: &[u8; 16] = b"0123456789ABCDEF";
+
+/// Serializes block to the same bytes that were hashed and signed
+pub fn compact_bytes(block: &Block) -> Vec<u8> {
+    let mut buf = Vec::with_capacity(256);
+    buf.extend_from_slice(&block.index.to_le_bytes());
+    buf.extend_from_slice(&block.timestamp.to_le_bytes());
+    buf.extend_from_slice(&block.version.to_le_bytes());
+    buf.extend_from_slice(&block.difficulty.to_le_bytes());
+    buf.extend_from_slice(&block.random.to_le_bytes());
+    buf.extend_from_slice(&block.nonce.to_le_bytes());
+    write_hex(&mut buf, &block.hash);
+    write_hex(&mut buf, &block.prev_block_hash);
+    write_hex(&mut buf, &block.pub_key);
+    write_hex(&mut buf, &block.signature);
+    if let Some(transaction) = &block.transaction {
+        buf.push(1);
+        write_transaction(&mut buf, transaction);
+    }
+    buf
+}
+
+fn write_transaction(buf: &mut Vec<u8>, transaction: &Transaction) {
+    write_str(buf, transaction.class.as_bytes());
+    write_hex(buf, &transaction.identity);
+    write_hex(buf, &transaction.confirmation);
+    write_hex(buf, &transaction.signing);
+    write_hex(buf, &transaction.encryption);
+    if !transaction.data.is_empty() {
+        write_str(buf, transaction.data.as_bytes());
+    }
+}
+
+fn write_str(buf: &mut Vec<u8>, data: &[u8]) {
+    buf.extend_from_slice(&(data.len() as u64).to_le_bytes());
+    buf.extend_from_slice(data);
+}
+
+/// Writes bytes as hex string, if they are not all zeroes
+fn write_hex(buf: &mut Vec<u8>, data: &[u8]) {
+    if data.iter().all(|x| *x == 0) {
+        return;
+    }
+    buf.extend_from_slice(&(data.len() as u64 * 2).to_le_bytes());
+    for x in data {
+        buf.push(HEX[(x >> 4) as usize]);
+        buf.push(HEX[(x & 0x0F) as usize]);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::vec;
+
+    use crate::{compact_bytes, Block};
+
+    #[test]
+    fn test_compact_bytes() {
+        let block = Block { index: 1, difficulty: 16, hash: vec![0u8; 32], pub_key: vec![0xAB, 0x01], ..Block::default() };
+        let bytes = compact_bytes(&block);
+        // Six numbers, zero hash is skipped, then key as hex
+        assert_eq!(bytes.len(), 8 + 8 + 4 + 4 + 4 + 8 + 8 + 4);
+        assert_eq!(&bytes[..8], &1u64.to_le_bytes());
+        assert_eq!(&bytes[36..44], &4u64.to_le_bytes());
+        assert_eq!(&bytes[44..], b"AB01");
+    }
+}
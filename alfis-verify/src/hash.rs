@@ -0,0 +1,131 @@
+use alloc::vec::Vec;
+use core::convert::TryInto;
+
+use argon2::{Algorithm, Argon2, Params, Version};
+use blakeout::Blakeout;
+use ed25519_dalek::{PublicKey, Signature, Verifier};
+use sha2::{Digest, Sha256};
+
+use crate::{ARGON2_MEMORY, BLOCK_FLAG_ARGON2};
+
+/// Salt for Argon2id, it has to be the same for everyone
+const ARGON2_SALT: &[u8] = b"ALFIS-POW";
+
+/// Hashes block data by PoW algorithm of this block version
+pub fn pow_hash(version: u32, data: &[u8]) -> Vec<u8> {
+    if version & BLOCK_FLAG_ARGON2 != 0 {
+        argon2_data(data)
+    } else {
+        blakeout_data(data)
+    }
+}
+
+/// Hashes data by Blakeout
+pub fn blakeout_data(data: &[u8]) -> Vec<u8> {
+    let mut digest = Blakeout::default();
+    digest.update(data);
+    Vec::from(digest.result())
+}
+
+/// Hashes data by Argon2id, it needs `ARGON2_MEMORY` KiB of memory for every hash
+pub fn argon2_data(data: &[u8]) -> Vec<u8> {
+    let params = Params::new(ARGON2_MEMORY, 1, 1, Some(32)).expect("Wrong Argon2 params");
+    let argon = Argon2::new(Algorithm::Argon2id, Version::V0x13, params);
+    let mut hash = [0u8; 32];
+    argon.hash_password_into(data, ARGON2_SALT, &mut hash).expect("Error hashing by Argon2");
+    Vec::from(&hash[..])
+}
+
+/// Hashes data by Sha256 algorithm
+#[inline]
+pub fn hash_sha256(data: &[u8]) -> Vec<u8> {
+    let mut digest = Sha256::default();
+    digest.update(data);
+    Vec::from(&digest.finalize()[..])
+}
+
+/// Hashes some identity (domain in case of DNS). If you give it a public key, it will hash with it as well,
+/// that is how confirmation field of transaction is made.
+pub fn hash_identity(identity: &str, key: Option<&[u8]>) -> Vec<u8> {
+    let mut base = hash_sha256(identity.as_bytes());
+    match key {
+        None => hash_sha256(&base),
+        Some(key) => {
+            base.extend_from_slice(key);
+            hash_sha256(&base)
+        }
+    }
+}
+
+/// Checks ed25519 signature, wrong keys and signatures are just not valid
+pub fn check_signature(message: &[u8], public_key: &[u8], signature: &[u8]) -> bool {
+    let key = match PublicKey::from_bytes(public_key) {
+        Ok(key) => key,
+        Err(_) => return false
+    };
+    match Signature::from_bytes(signature) {
+        Ok(signature) => key.verify(message, &signature).is_ok(),
+        Err(_) => false
+    }
+}
+
+/// Returns hash difficulty (sum of zeroes from start and end)
+#[inline]
+pub fn hash_difficulty(hash: &[u8]) -> u32 {
+    let bytes: [u8; 8] = hash[..8].try_into().unwrap();
+    let int_start = u64::from_be_bytes(bytes);
+    let bytes: [u8; 8] = hash[hash.len() - 8..].try_into().unwrap();
+    let int_end = u64::from_be_bytes(bytes);
+    int_start.leading_zeros() + int_end.trailing_zeros()
+}
+
+/// Returns hash difficulty for keys (only from the start)
+#[inline]
+pub fn key_hash_difficulty(hash: &[u8]) -> u32 {
+    let bytes: [u8; 8] = hash[..8].try_into().unwrap();
+    let int = u64::from_be_bytes(bytes);
+    int.leading_zeros()
+}
+
+/// Checks that the key was really mined by ALFIS, it is needed to own domains
+pub fn check_public_key_strength(key: &[u8], strength: u32) -> bool {
+    key.len() >= 8 && key_hash_difficulty(&blakeout_data(key)) >= strength
+}
+
+/// There is no default PartialEq implementation for arrays > 32 in size
+pub fn same_hash(left: &[u8], right: &[u8]) -> bool {
+    if left.len() != right.len() {
+        return false;
+    }
+    // We iterate whole slices to eliminate timing attacks
+    let mut result = true;
+    for (x, y) in left.iter().zip(right) {
+        if x != y {
+            result = false;
+        }
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{hash_difficulty, hash_identity, hash_sha256, key_hash_difficulty};
+
+    #[test]
+    fn test_identity() {
+        let base = hash_sha256(b"example.ygg");
+        assert_eq!(hash_identity("example.ygg", None), hash_sha256(&base));
+        let mut buf = base.clone();
+        buf.extend_from_slice(b"key");
+        assert_eq!(hash_identity("example.ygg", Some(b"key")), hash_sha256(&buf));
+    }
+
+    #[test]
+    fn test_difficulty() {
+        let mut hash = [0xFFu8; 32];
+        hash[0] = 0;
+        hash[31] = 0x80;
+        assert_eq!(hash_difficulty(&hash), 8 + 7);
+        assert_eq!(key_hash_difficulty(&hash), 8);
+    }
+}
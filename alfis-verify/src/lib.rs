@@ -0,0 +1,64 @@
+//! Verification of ALFIS blocks and domain transactions, small enough to be compiled to WebAssembly.
+//! The node uses it to check every block, and browser extensions can use it to check blocks of some domain
+//! that they got from untrusted gateway, by [`verify_domain_proof`].
+//! The code itself uses only `core` and `alloc`, but Blakeout needs `std`, so the target must have it,
+//! like `wasm32-unknown-unknown` does.
+
+extern crate alloc;
+
+pub use compact::compact_bytes;
+pub use hash::*;
+pub use proof::*;
+
+pub mod compact;
+pub mod hash;
+pub mod proof;
+
+use alloc::string::String;
+use alloc::vec::Vec;
+
+pub const CHAIN_VERSION: u32 = 1;
+/// Blocks with this flag in version are hashed by Argon2id instead of Blakeout.
+/// The node allows it only when the `argon2` rule is activated by miners, this crate doesn't know the state of rules.
+pub const BLOCK_FLAG_ARGON2: u32 = 1 << 16;
+/// Bits of version that miners set to signal readiness for new rules, they don't change how the block is checked
+pub const BLOCK_SIGNAL_MASK: u32 = 0xFF << 20;
+/// Memory for one Argon2id hash in KiB, the same as Blakeout uses
+pub const ARGON2_MEMORY: u32 = 2048;
+
+pub const DOMAIN_DIFFICULTY: u32 = 24;
+pub const SIGNER_DIFFICULTY: u32 = 16;
+pub const KEYSTORE_DIFFICULTY: u32 = 23;
+/// Difficulty of domains is adjusted by up to this count of bits
+pub const DIFFICULTY_MAX_ADJUST: u32 = 2;
+/// Max discount of difficulty for domains that are already in blockchain
+pub const MAX_DOMAIN_DISCOUNT: u32 = 8;
+
+pub const CLASS_DOMAIN: &str = "domain";
+
+/// Block as it is in blockchain, but with raw bytes instead of `Bytes`
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Block {
+    pub index: u64,
+    pub timestamp: i64,
+    pub version: u32,
+    pub difficulty: u32,
+    pub random: u32,
+    pub nonce: u64,
+    pub hash: Vec<u8>,
+    pub prev_block_hash: Vec<u8>,
+    pub pub_key: Vec<u8>,
+    pub signature: Vec<u8>,
+    pub transaction: Option<Transaction>
+}
+
+/// Transaction as it is in blockchain, `data` is JSON with domain records
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Transaction {
+    pub class: String,
+    pub identity: Vec<u8>,
+    pub confirmation: Vec<u8>,
+    pub signing: Vec<u8>,
+    pub encryption: Vec<u8>,
+    pub data: String
+}
@@ -0,0 +1,129 @@
+//! Checks of blocks that don't need the rest of blockchain. The node does these checks and many more,
+//! like ownership of domains and timing of blocks, but those need the whole chain.
+
+use crate::hash::*;
+use crate::*;
+
+/// The lowest difficulty a domain block can have, after adjustment and discount for old domains
+pub const MIN_DOMAIN_DIFFICULTY: u32 = DOMAIN_DIFFICULTY - DIFFICULTY_MAX_ADJUST - MAX_DOMAIN_DISCOUNT;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ProofError {
+    /// There are no blocks in the proof
+    Empty,
+    /// Unsupported version
+    Version,
+    /// Block difficulty is lower than any block of this kind can have
+    LowDifficulty,
+    /// Hash doesn't have zeroes that block difficulty needs
+    WeakHash,
+    /// Hash is not the hash of block data
+    WrongHash,
+    /// Signature is not made by the key of block
+    WrongSignature,
+    /// Key of block is not mined by ALFIS
+    WeakKey,
+    /// First block has no transaction of this domain
+    WrongDomain,
+    /// Blocks are not one after another
+    Broken
+}
+
+/// Checks block's hash by PoW algorithm of its version
+pub fn check_block_hash(block: &Block) -> bool {
+    let mut copy = block.clone();
+    copy.hash.clear();
+    copy.signature.clear();
+    same_hash(&pow_hash(block.version, &compact_bytes(&copy)), &block.hash)
+}
+
+/// Checks block's signature, it is made for the block with hash but without signature
+pub fn check_block_signature(block: &Block) -> bool {
+    let mut copy = block.clone();
+    copy.signature.clear();
+    check_signature(&compact_bytes(&copy), &block.pub_key, &block.signature)
+}
+
+/// Checks that transaction is for this domain, and that confirmation is made with its signing key
+pub fn check_identity(transaction: &Transaction, domain: &str) -> bool {
+    same_hash(&transaction.identity, &hash_identity(domain, None)) &&
+        same_hash(&transaction.confirmation, &hash_identity(domain, Some(&transaction.signing)))
+}
+
+/// Checks everything in the block that can be checked without blockchain
+pub fn verify_block(block: &Block) -> Result<(), ProofError> {
+    if block.version & !(BLOCK_FLAG_ARGON2 | BLOCK_SIGNAL_MASK) > CHAIN_VERSION {
+        return Err(ProofError::Version);
+    }
+    let min_difficulty = match block.transaction {
+        None => SIGNER_DIFFICULTY,
+        Some(_) => MIN_DOMAIN_DIFFICULTY
+    };
+    if block.difficulty < min_difficulty {
+        return Err(ProofError::LowDifficulty);
+    }
+    if block.hash.len() < 16 || hash_difficulty(&block.hash) < block.difficulty {
+        return Err(ProofError::WeakHash);
+    }
+    if !check_block_hash(block) {
+        return Err(ProofError::WrongHash);
+    }
+    if !check_block_signature(block) {
+        return Err(ProofError::WrongSignature);
+    }
+    if block.transaction.is_some() && !check_public_key_strength(&block.pub_key, KEYSTORE_DIFFICULTY) {
+        return Err(ProofError::WeakKey);
+    }
+    Ok(())
+}
+
+/// Checks proof of domain: the first block must have transaction of this domain,
+/// and the next blocks must follow it one by one, they confirm that the block is in the chain.
+/// Returns the transaction, its `data` has records of the domain.
+///
+/// It can't tell if this is the last transaction of the domain, or if it is from the same chain that the node has,
+/// so compare the hash of the last block with the one from several gateways or your own node.
+pub fn verify_domain_proof<'a>(domain: &str, blocks: &'a [Block]) -> Result<&'a Transaction, ProofError> {
+    let first = blocks.first().ok_or(ProofError::Empty)?;
+    let transaction = match &first.transaction {
+        Some(transaction) if transaction.class == CLASS_DOMAIN && check_identity(transaction, domain) => transaction,
+        _ => return Err(ProofError::WrongDomain)
+    };
+    for block in blocks {
+        verify_block(block)?;
+    }
+    for pair in blocks.windows(2) {
+        if pair[1].index != pair[0].index + 1 || !same_hash(&pair[1].prev_block_hash, &pair[0].hash) {
+            return Err(ProofError::Broken);
+        }
+    }
+    Ok(transaction)
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::string::String;
+    use alloc::vec;
+
+    use crate::{check_identity, hash_identity, verify_domain_proof, Block, ProofError, Transaction, CLASS_DOMAIN};
+
+    #[test]
+    fn test_identity() {
+        let signing = vec![1u8; 32];
+        let transaction = Transaction {
+            class: String::from(CLASS_DOMAIN),
+            identity: hash_identity("example.ygg", None),
+            confirmation: hash_identity("example.ygg", Some(&signing)),
+            signing,
+            ..Transaction::default()
+        };
+        assert!(check_identity(&transaction, "example.ygg"));
+        assert!(!check_identity(&transaction, "example2.ygg"));
+
+        let block = Block { index: 10, difficulty: 24, transaction: Some(transaction), ..Block::default() };
+        assert_eq!(verify_domain_proof("example.ygg", &[]), Err(ProofError::Empty));
+        assert_eq!(verify_domain_proof("other.ygg", &[block.clone()]), Err(ProofError::WrongDomain));
+        // It is not mined, so the hash can't be good
+        assert_eq!(verify_domain_proof("example.ygg", &[block]), Err(ProofError::WeakHash));
+    }
+}
@@ -0,0 +1,468 @@
+//! Commands like `alfis lookup example.ygg`. They talk to the running node by its API if it is enabled in config,
+//! and work with the database directly when the node is stopped.
+
+use std::fs;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::path::Path;
+use std::process::exit;
+use std::sync::{Arc, Mutex};
+
+use alfis_core::api::get_domain_info;
+use alfis_core::api::http::send_request;
+use alfis_core::backup::NodeArchive;
+use alfis_core::blockchain::transaction::DomainData;
+use alfis_core::blockchain::types::MineResult;
+use alfis_core::control::{send_command, ControlAnswer, ControlCommand};
+use alfis_core::dns::protocol::DnsRecord;
+use alfis_core::import::{import_names, ImportFormat};
+use alfis_core::keystore::{check_public_key_strength, key_fingerprint};
+use alfis_core::light::{LightClient, LightError, HEADERS_FILE};
+use alfis_core::lock::{DataLock, LockError};
+use alfis_core::miner::make_renewal_transaction;
+use alfis_core::vectors::VectorSet;
+use alfis_core::{check_domain, from_hex, get_domain_zone, Bytes, Chain, Context, Settings, Transaction, CLASS_DOMAIN, DB_NAME, KEYSTORE_DIFFICULTY};
+use chrono::{Local, TimeZone};
+use getopts::Matches;
+
+pub const COMMANDS_USAGE: &str = "Commands:
+    lookup NAME                         Show owner, state and records of the domain
+    lookup NAME --light HOST:PORT       Check the domain by its proof from API of a full node, keeping only headers of blocks
+    list                                List domains of loaded keys and of keys from watch_keys
+    register NAME --records FILE        Mine new domain, or renew your domain, with DNS records from JSON file
+    transfer NAME SIGNING ENCRYPTION    Give your domain to the owner of these public keys
+    import FILE --zone ZONE             Claim names from Namecoin export (name_list JSON) or zone file, the node must be stopped
+    peer add HOST:PORT                  Add bootstrap node to config, and connect to it if the node is running
+    chain verify                        Check all blocks in the database, the node must be stopped
+    backup create FILE                  Save config, key files and known peers to one encrypted file, without the chain
+    backup restore FILE                 Restore config, key files and known peers from this file on a new computer
+    vectors generate FILE               Save test vectors of hashing, signing and serialization of blocks to the file
+    vectors verify FILE                 Check that this build makes the same hashes, signatures and bytes as in the file
+    status                              Show chain, sync, peers, mining, DNS and database of running node, it needs control_socket in config
+    mining pause|resume                 Pause or resume mining of running node
+    reload                              Make running node load its config again
+    route set ZONE FORWARDER...         Make running node resolve the zone by these forwarders, until reload or restart
+    route clear ZONE                    Remove the route of the zone from running node
+    generate                            Make running node in regtest mode mine the next block from its queue";
+
+/// Runs the command from free arguments. Returns transactions if the node is not running,
+/// and we need to start it to mine them. If `running` the node works with our data directory now.
+pub fn run_command(opt_matches: &Matches, context: &Arc<Mutex<Context>>, config_name: &str, running: bool) -> Vec<Transaction> {
+    let args: Vec<&str> = opt_matches.free.iter().map(|arg| arg.as_str()).collect();
+    let api = get_api_addr(&context.lock().unwrap().settings);
+    match args.as_slice() {
+        ["lookup", name] => match opt_matches.opt_str("light") {
+            Some(addr) => light_lookup(context, name, &addr),
+            None => lookup(context, api, name)
+        },
+        ["list"] => list(context),
+        ["register", name] => match opt_matches.opt_str("records") {
+            Some(records) => return send_transaction(context, api, running, register(context, name, &records)).into_iter().collect(),
+            None => fail("Records of the domain are needed, give them by --records FILE")
+        },
+        ["transfer", name, signing, encryption] => return send_transaction(context, api, running, transfer(context, name, signing, encryption)).into_iter().collect(),
+        ["import", file] => return import(context, running, file, opt_matches.opt_str("zone")),
+        ["peer", "add", addr] => add_peer(context, addr, config_name),
+        ["chain", "verify"] => verify_chain(context, running),
+        ["backup", "create", file] => create_backup(context, config_name, file),
+        ["status"] => control(context, ControlCommand::Status),
+        ["mining", "pause"] => control(context, ControlCommand::PauseMining),
+        ["mining", "resume"] => control(context, ControlCommand::ResumeMining),
+        ["reload"] => control(context, ControlCommand::ReloadConfig),
+        ["route", "set", zone, forwarders @ ..] if !forwarders.is_empty() => {
+            let forwarders = forwarders.iter().map(|forwarder| forwarder.to_string()).collect();
+            control(context, ControlCommand::SetRoute { zone: zone.to_string(), forwarders })
+        }
+        ["route", "clear", zone] => control(context, ControlCommand::ClearRoute { zone: zone.to_string() }),
+        ["generate"] => control(context, ControlCommand::GenerateBlock),
+        _ => fail(&format!("Unknown command '{}'\n\n{}", args.join(" "), COMMANDS_USAGE))
+    }
+    Vec::new()
+}
+
+fn fail(message: &str) -> ! {
+    println!("{}", message);
+    exit(1);
+}
+
+/// Returns address of API to connect to, if it is enabled
+fn get_api_addr(settings: &Settings) -> Option<SocketAddr> {
+    let mut addr: SocketAddr = settings.api.listen.parse().ok()?;
+    if addr.ip().is_unspecified() {
+        let ip = match addr {
+            SocketAddr::V4(_) => IpAddr::V4(Ipv4Addr::LOCALHOST),
+            SocketAddr::V6(_) => IpAddr::V6(Ipv6Addr::LOCALHOST)
+        };
+        addr.set_ip(ip);
+    }
+    Some(addr)
+}
+
+fn format_time(timestamp: i64) -> String {
+    Local.timestamp_opt(timestamp, 0).unwrap().format("%Y-%m-%d %H:%M").to_string()
+}
+
+fn lookup(context: &Arc<Mutex<Context>>, api: Option<SocketAddr>, name: &str) {
+    let name = name.to_lowercase();
+    if !check_domain(&name, true) {
+        fail(&format!("Wrong domain name '{}'", &name));
+    }
+    if let Some(addr) = api {
+        if let Ok((status, body)) = send_request(&addr, "GET", &format!("/domains/{}", &name), "") {
+            match serde_json::from_str::<serde_json::Value>(&body) {
+                Ok(value) if status == 200 => println!("{}", serde_json::to_string_pretty(&value).unwrap()),
+                _ => fail(&format!("Domain {} is not found", &name))
+            }
+            return;
+        }
+    }
+    match get_domain_info(&context.lock().unwrap().chain, &name) {
+        Some(info) => println!("{}", serde_json::to_string_pretty(&info).unwrap()),
+        None => fail(&format!("Domain {} is not found", &name))
+    }
+}
+
+/// Syncs headers of blocks from a full node, and checks the proof of domain by them
+fn light_lookup(context: &Arc<Mutex<Context>>, name: &str, addr: &str) {
+    let name = name.to_lowercase();
+    if !check_domain(&name, true) {
+        fail(&format!("Wrong domain name '{}'", &name));
+    }
+    let addr: SocketAddr = match addr.parse() {
+        Ok(addr) => addr,
+        Err(_) => fail(&format!("Wrong address of full node '{}'", addr))
+    };
+    let (origin, path) = {
+        let context = context.lock().unwrap();
+        (context.settings.get_origin(), Path::new(&context.settings.data_dir).join(HEADERS_FILE))
+    };
+    let mut client = LightClient::load(origin, addr, &path);
+    match client.sync() {
+        Ok(height) => println!("Headers of {} blocks are checked", height),
+        Err(e) => fail(&format!("Unable to sync headers: {}", e))
+    }
+    if let Err(e) = client.save(&path) {
+        println!("Unable to save headers to {}: {}", path.display(), e);
+    }
+    match client.lookup(&name) {
+        Ok(transaction) => {
+            let info = serde_json::json!({ "name": &name, "owner": &transaction.signing, "data": transaction.get_domain_data() });
+            println!("{}", serde_json::to_string_pretty(&info).unwrap());
+        }
+        Err(LightError::NotFound) => fail(&format!("Domain {} is not found", &name)),
+        Err(e) => fail(&format!("Proof of domain {} is not good: {}", &name, e))
+    }
+}
+
+fn list(context: &Arc<Mutex<Context>>) {
+    let context = context.lock().unwrap();
+    let lifetime = context.chain.get_kind().domain_lifetime();
+    for keystore in context.get_keystores() {
+        println!("Key {} ({}):", keystore.get_name(), keystore.get_fingerprint());
+        for (_identity, (domain, timestamp, _data)) in context.chain.get_my_domains(Some(keystore)) {
+            println!("    {} until {}", domain, format_time(timestamp + lifetime));
+        }
+    }
+    // We can't decrypt names of these domains without secret keys
+    for key in context.settings.get_watch_keys() {
+        println!("Watched key {} ({}):", key.to_string(), key_fingerprint(&key));
+        for (identity, (timestamp, _data)) in context.chain.get_owned_domains(&key) {
+            println!("    {} until {}", identity.to_string(), format_time(timestamp + lifetime));
+        }
+    }
+}
+
+/// Makes transaction of new domain, or renewal of our domain, with records from file
+fn register(context: &Arc<Mutex<Context>>, name: &str, records_file: &str) -> Transaction {
+    let name = name.to_lowercase();
+    if !check_domain(&name, true) {
+        fail(&format!("Wrong domain name '{}'", &name));
+    }
+    let records: Vec<DnsRecord> = match fs::read_to_string(records_file).ok().and_then(|text| serde_json::from_str(&text).ok()) {
+        Some(records) => records,
+        None => fail(&format!("Unable to load list of DNS records from {}", records_file))
+    };
+    let context = context.lock().unwrap();
+    let keystore = match context.get_keystore() {
+        Some(keystore) => keystore,
+        None => fail("There is no loaded key to own the domain")
+    };
+    // Info and contacts of our domain stay the same
+    let (info, contacts) = match context.chain.get_domain_transaction_and_state(&name).0.and_then(|transaction| transaction.get_domain_data()) {
+        Some(data) => (data.info, data.contacts),
+        None => (String::new(), Vec::new())
+    };
+    let data = DomainData::new(Bytes::default(), get_domain_zone(&name), info, records, contacts);
+    let transaction = Transaction::from_domain_data(CLASS_DOMAIN, &name, data, keystore.get_public(), keystore.get_encryption_public());
+    match context.chain.can_mine_transaction(&transaction, &keystore.get_public()) {
+        MineResult::Fine => transaction,
+        result => fail(&format!("Domain {} can't be mined: {:?}", &name, result))
+    }
+}
+
+/// Makes transaction that moves our domain to other keys
+fn transfer(context: &Arc<Mutex<Context>>, name: &str, signing: &str, encryption: &str) -> Transaction {
+    let name = name.to_lowercase();
+    let (signing, encryption) = match (from_hex(signing), from_hex(encryption)) {
+        (Ok(signing), Ok(encryption)) if signing.len() == 32 && encryption.len() == 32 => (Bytes::new(signing), Bytes::new(encryption)),
+        _ => fail("Public keys must be 32 bytes in hex")
+    };
+    if !check_public_key_strength(&signing, KEYSTORE_DIFFICULTY) {
+        fail("This signing key is not strong enough, it is not from ALFIS");
+    }
+    let context = context.lock().unwrap();
+    let (transaction, owner) = match make_renewal_transaction(&context.chain, &name, Some((signing.clone(), encryption))) {
+        Ok(result) => result,
+        Err(result) => fail(&format!("Domain {} can't be transferred: {:?}", &name, result))
+    };
+    match context.get_keystore() {
+        Some(keystore) if keystore.get_public() == owner => {
+            println!("Transferring domain {} to {}", &name, key_fingerprint(&signing));
+            transaction
+        }
+        _ => fail("The domain is not owned by the current key, select its key by --default-key")
+    }
+}
+
+/// Gives the transaction to the running node, or returns it back to start the node
+fn send_transaction(context: &Arc<Mutex<Context>>, api: Option<SocketAddr>, running: bool, transaction: Transaction) -> Option<Transaction> {
+    if let Some(addr) = api {
+        match send_request(&addr, "POST", "/transactions", &transaction.to_string()) {
+            Ok((202, _)) => {
+                println!("Running node has started mining");
+                exit(0);
+            }
+            Ok((_, body)) => fail(&format!("Running node can't mine it: {}", body)),
+            Err(_) => {}
+        }
+    }
+    if running {
+        forward_transactions(context, vec![transaction]);
+        return None;
+    }
+    println!("Node is not running, starting it to mine the domain");
+    Some(transaction)
+}
+
+/// Gives transactions to the node that works with our data directory, it can't be started again
+fn forward_transactions(context: &Arc<Mutex<Context>>, transactions: Vec<Transaction>) {
+    if context.lock().unwrap().settings.api.control_socket.is_empty() {
+        fail("Node is running already, set control_socket in [api] section of config to give it domains to mine");
+    }
+    control(context, ControlCommand::MineTransactions { transactions });
+}
+
+/// Makes transactions for names from Namecoin export or zone file, that are free or already ours
+fn import(context: &Arc<Mutex<Context>>, running: bool, file: &str, zone: Option<String>) -> Vec<Transaction> {
+    let zone = match zone {
+        Some(zone) => zone.to_lowercase(),
+        None => fail("Give the zone of ALFIS for imported names by --zone ZONE")
+    };
+    let text = match fs::read_to_string(file) {
+        Ok(text) => text,
+        Err(e) => fail(&format!("Unable to read {}: {}", file, e))
+    };
+    let format = if text.trim_start().starts_with('[') { ImportFormat::Namecoin } else { ImportFormat::Zone };
+    let context = context.lock().unwrap();
+    if !context.chain.is_available_zone(&zone) {
+        fail(&format!("There is no zone '{}' in ALFIS", &zone));
+    }
+    let yggdrasil_only = context.chain.get_zones().iter().any(|z| z.name == zone && z.yggdrasil);
+    let names = match import_names(&text, format, &zone, yggdrasil_only) {
+        Ok(names) => names,
+        Err(e) => fail(&e)
+    };
+    let keystore = match context.get_keystore() {
+        Some(keystore) => keystore,
+        None => fail("There is no loaded key to own the domains")
+    };
+    let mut transactions = Vec::new();
+    for name in names {
+        for (record, e) in &name.skipped {
+            println!("Record {:?} of {} is skipped: {}", record.get_querytype(), &name.name, e.message);
+        }
+        let data = DomainData::new(Bytes::default(), zone.clone(), String::new(), name.records, name.contacts);
+        let transaction = Transaction::from_domain_data(CLASS_DOMAIN, &name.name, data, keystore.get_public(), keystore.get_encryption_public());
+        match context.chain.can_mine_transaction(&transaction, &keystore.get_public()) {
+            MineResult::Fine | MineResult::Cooldown { .. } => {
+                println!("Domain {} can be claimed", &name.name);
+                transactions.push(transaction);
+            }
+            MineResult::NotOwned => println!("Domain {} is owned by other key, skipping it", &name.name),
+            result => println!("Domain {} can't be mined: {:?}, skipping it", &name.name, result)
+        }
+    }
+    if transactions.is_empty() {
+        fail("There are no names to claim");
+    }
+    // Running node takes only one transaction by API, so we give all of them through control socket
+    if running {
+        forward_transactions(context, transactions);
+        return Vec::new();
+    }
+    println!("Starting the node to mine {} domains, new domains are mined one per day", transactions.len());
+    transactions
+}
+
+fn add_peer(context: &Arc<Mutex<Context>>, addr: &str, config_name: &str) {
+    match addr.rsplit_once(':') {
+        Some((host, port)) if !host.is_empty() && port.parse::<u16>().is_ok() => {}
+        _ => fail("Address of the node must be like 'host:port'")
+    }
+    let mut context = context.lock().unwrap();
+    if context.settings.net.peers.iter().any(|peer| peer == addr) {
+        println!("Node {} is already in {}", addr, config_name);
+        return;
+    }
+    context.settings.net.peers.push(addr.to_owned());
+    if !context.settings.save(config_name) {
+        exit(1);
+    }
+    let socket = &context.settings.api.control_socket;
+    let command = ControlCommand::AddPeer { addr: addr.to_owned() };
+    if !socket.is_empty() && matches!(send_command(socket, &command), Ok(ControlAnswer::Result { result: true })) {
+        println!("Node {} is added to {}, running node is connecting to it", addr, config_name);
+    } else {
+        println!("Node {} is added to {}, it will be used after restart", addr, config_name);
+    }
+}
+
+/// Bans or unbans the node by control socket, when the running node holds the database
+pub fn ban_on_running_node(settings: &Settings, ip: &str, ban: bool) {
+    let socket = &settings.api.control_socket;
+    if socket.is_empty() {
+        fail("The node is running, set control_socket in [api] section of config to ban nodes while it works");
+    }
+    let command = match ban {
+        true => ControlCommand::Ban { ip: ip.to_owned() },
+        false => ControlCommand::Unban { ip: ip.to_owned() }
+    };
+    match (send_command(socket, &command), ban) {
+        (Ok(ControlAnswer::Result { result: true }), true) => println!("Node {} is banned", ip),
+        (Ok(ControlAnswer::Result { result: true }), false) => println!("Node {} is unbanned", ip),
+        (Ok(ControlAnswer::Result { result: false }), _) => println!("Node {} was not banned", ip),
+        (Ok(ControlAnswer::Error { error }), _) => fail(&format!("Running node has answered: {}", error)),
+        (Ok(_), _) => fail("Running node has given wrong answer"),
+        (Err(e), _) => fail(&format!("Unable to connect to running node by {}: {}", socket, e))
+    }
+}
+
+/// Sends the command to running node by control socket and prints its answer
+fn control(context: &Arc<Mutex<Context>>, command: ControlCommand) {
+    let socket = context.lock().unwrap().settings.api.control_socket.clone();
+    if socket.is_empty() {
+        fail("Set control_socket in [api] section of config to control running node");
+    }
+    match send_command(&socket, &command) {
+        Ok(ControlAnswer::Status(status)) => {
+            println!("ALFIS {}", status.version);
+            println!("Blocks: {}, last hash: {}", status.blocks, status.hash.to_string());
+            match status.syncing {
+                Some(height) if height > status.blocks => println!("Sync: downloading blocks up to {}", height),
+                _ => println!("Sync: done")
+            }
+            println!("Domains: {}, keys: {}, nodes: {}", status.domains, status.keys, status.nodes);
+            let mining = match (status.mining, status.paused) {
+                (true, true) => "paused",
+                (true, false) => "working",
+                (false, _) => "stopped"
+            };
+            println!("Mining: {}, {} H/s, blocks in queue: {}", mining, status.hashrate, status.mempool);
+            println!("DNS: {:.1} queries/s, cache hits {:.0}%", status.dns_queries_per_sec, status.dns_cache_hit_rate * 100.0);
+            println!("Database: {:.1} MB", status.db_size as f64 / 1_048_576.0);
+        }
+        Ok(ControlAnswer::Result { result: true }) => println!("Done"),
+        Ok(ControlAnswer::Result { result: false }) => fail("Running node has not done it"),
+        Ok(ControlAnswer::Error { error }) => fail(&format!("Running node has answered: {}", error)),
+        Err(e) => fail(&format!("Unable to connect to running node by {}: {}", &socket, e))
+    }
+}
+
+fn create_backup(context: &Arc<Mutex<Context>>, config_name: &str, file: &str) {
+    if Path::new(file).exists() {
+        fail(&format!("File {} exists already", file));
+    }
+    let password = rpassword::prompt_password("Enter password for backup: ").unwrap_or_default();
+    if password.is_empty() {
+        fail("Backup needs a password, it has your keys");
+    }
+    if rpassword::prompt_password("Repeat password: ").unwrap_or_default() != password {
+        fail("Passwords don't match");
+    }
+    let archive = match NodeArchive::collect(&context.lock().unwrap(), config_name) {
+        Ok(archive) => archive,
+        Err(e) => fail(&format!("Unable to read files for backup: {}", e))
+    };
+    if let Err(e) = archive.save(Path::new(file), &password) {
+        fail(&format!("Unable to save backup to {}: {}", file, e));
+    }
+    println!("Saved config, {} key files and {} peers to {}", archive.keys.len(), archive.peers.len(), file);
+}
+
+/// Restores node from backup. It runs before the config is loaded, as there is no config on a new computer.
+pub fn restore_backup(file: &str, config_name: &str) {
+    let password = rpassword::prompt_password(format!("Enter password for {}: ", file)).unwrap_or_default();
+    let archive = match NodeArchive::load(Path::new(file), &password) {
+        Ok(archive) => archive,
+        Err(e) => fail(&format!("Unable to load backup from {}: {}", file, e))
+    };
+    let settings: Settings = match toml::from_str(&archive.config) {
+        Ok(settings) => settings,
+        Err(e) => fail(&format!("Config in backup is damaged: {}", e))
+    };
+    if !settings.data_dir.is_empty() {
+        if let Err(e) = fs::create_dir_all(&settings.data_dir) {
+            fail(&format!("Unable to create data directory {}: {}", &settings.data_dir, e));
+        }
+    }
+    let _lock = match DataLock::acquire(&settings.data_dir) {
+        Ok(lock) => lock,
+        Err(LockError::Busy) => fail("Stop the node first"),
+        Err(e) => fail(&format!("Unable to lock data directory {}: {}", &settings.data_dir, e))
+    };
+    let keys = match archive.restore_files(config_name) {
+        Ok(keys) => keys,
+        Err(e) => fail(&format!("Unable to restore files: {}", e))
+    };
+    let db_path = Path::new(&settings.data_dir).join(DB_NAME);
+    Chain::new(&settings, &db_path.to_string_lossy()).restore_peers(&archive.peers);
+    println!("Restored config {}, {} key files and {} peers from backup of {}", config_name, keys, archive.peers.len(), format_time(archive.timestamp));
+    println!("Start the node to download the chain");
+}
+
+pub fn generate_vectors(file: &str) {
+    if Path::new(file).exists() {
+        fail(&format!("File {} exists already", file));
+    }
+    let vectors = VectorSet::generate();
+    if let Err(e) = vectors.save(Path::new(file)) {
+        fail(&format!("Unable to save vectors to {}: {}", file, e));
+    }
+    println!("Saved {} identities, {} transactions and {} blocks to {}", vectors.identities.len(), vectors.transactions.len(), vectors.blocks.len(), file);
+}
+
+pub fn verify_vectors(file: &str) {
+    let vectors = match VectorSet::load(Path::new(file)) {
+        Ok(vectors) => vectors,
+        Err(e) => fail(&format!("Unable to load vectors from {}: {}", file, e))
+    };
+    match vectors.verify() {
+        Ok(count) => println!("All {} vectors are good", count),
+        Err(e) => fail(&format!("Vectors don't match: {}", e))
+    }
+}
+
+fn verify_chain(context: &Arc<Mutex<Context>>, running: bool) {
+    // The node adds blocks all the time, and we can remove bad blocks here
+    if running {
+        fail("Stop the node first, it checks the last blocks on start anyway");
+    }
+    let mut context = context.lock().unwrap();
+    let height = context.chain.get_height();
+    context.chain.check_chain(height);
+    let checked = context.chain.get_height();
+    if checked == height {
+        println!("All {} blocks are good", height);
+    } else {
+        println!("Blocks after {} were bad and are removed, they will be downloaded again", checked);
+    }
+}
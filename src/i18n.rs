@@ -0,0 +1,113 @@
+//! Translations of the interface. Every language is a JSON file in `webview/lang` with texts by their ids,
+//! the texts that are not translated yet are taken from English. Texts can have `{name}` placeholders.
+
+use std::collections::HashMap;
+use std::env;
+use std::sync::Mutex;
+
+#[allow(unused_imports)]
+use log::{debug, error, info, trace, warn};
+use serde::Serialize;
+
+const DEFAULT_LANGUAGE: &str = "en";
+
+/// Codes, names and files of all languages
+const LANGUAGES: &[(&str, &str, &str)] = &[
+    ("en", "English", include_str!("webview/lang/en.json")),
+    ("ru", "Русский", include_str!("webview/lang/ru.json"))
+];
+
+/// Texts of the selected language, with English ones for missing texts
+static STRINGS: Mutex<Option<HashMap<String, String>>> = Mutex::new(None);
+
+#[derive(Serialize)]
+struct LanguageForJS {
+    code: &'static str,
+    name: &'static str
+}
+
+/// Selects language by its code, the empty code means language of the system.
+/// If there is no such language English is used.
+pub fn set_language(code: &str) {
+    let code = match code.is_empty() {
+        true => system_language(),
+        false => code.to_lowercase()
+    };
+    let &(code, _, file) = LANGUAGES.iter()
+        .find(|(c, _, _)| *c == code)
+        .unwrap_or(&LANGUAGES[0]);
+    let mut strings = parse(LANGUAGES[0].2);
+    strings.extend(parse(file));
+    *STRINGS.lock().unwrap() = Some(strings);
+    info!("Using language '{}'", code);
+}
+
+/// Gets translated text by its id, or the id itself if there is no such text
+pub fn tr(id: &str) -> String {
+    let mut strings = STRINGS.lock().unwrap();
+    let strings = strings.get_or_insert_with(|| parse(LANGUAGES[0].2));
+    match strings.get(id) {
+        Some(text) => text.clone(),
+        None => {
+            warn!("No text for '{}'", id);
+            id.to_owned()
+        }
+    }
+}
+
+/// Gets translated text and puts values to its placeholders
+pub fn tr_args(id: &str, args: &[(&str, &str)]) -> String {
+    let mut text = tr(id);
+    for (name, value) in args {
+        text = text.replace(&format!("{{{}}}", name), value);
+    }
+    text
+}
+
+/// All texts of selected language for the scripts of the interface
+pub fn strings_json() -> String {
+    let mut strings = STRINGS.lock().unwrap();
+    let strings = strings.get_or_insert_with(|| parse(LANGUAGES[0].2));
+    serde_json::to_string(&*strings).unwrap()
+}
+
+/// Codes and names of available languages
+pub fn languages_json() -> String {
+    let languages: Vec<LanguageForJS> = LANGUAGES.iter().map(|(code, name, _)| LanguageForJS { code, name }).collect();
+    serde_json::to_string(&languages).unwrap()
+}
+
+/// Takes language from locale variables, like "ru" from "ru_RU.UTF-8"
+fn system_language() -> String {
+    ["LC_ALL", "LC_MESSAGES", "LANG"].iter()
+        .filter_map(|name| env::var(name).ok())
+        .find(|value| !value.is_empty() && value != "C" && value != "POSIX")
+        .and_then(|value| value.split(|c| c == '_' || c == '.' || c == '-').next().map(|code| code.to_lowercase()))
+        .unwrap_or_else(|| String::from(DEFAULT_LANGUAGE))
+}
+
+fn parse(file: &str) -> HashMap<String, String> {
+    serde_json::from_str(file).expect("Wrong language file")
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::i18n::{parse, LANGUAGES};
+
+    /// Every translation has only texts that English has, with the same placeholders
+    #[test]
+    fn test_languages() {
+        let english = parse(LANGUAGES[0].2);
+        let placeholders = |text: &str| -> Vec<String> {
+            let mut result: Vec<String> = text.split('{').skip(1).filter_map(|part| part.split_once('}')).map(|(name, _)| name.to_owned()).collect();
+            result.sort();
+            result
+        };
+        for (code, _, file) in LANGUAGES.iter().skip(1) {
+            for (id, text) in parse(file) {
+                let original = english.get(&id).unwrap_or_else(|| panic!("Unknown text '{}' in language '{}'", &id, code));
+                assert_eq!(placeholders(&text), placeholders(original), "Wrong placeholders of '{}' in language '{}'", &id, code);
+            }
+        }
+    }
+}
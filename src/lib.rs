@@ -1,25 +0,0 @@
-pub use blockchain::block::Block;
-pub use blockchain::transaction::Transaction;
-pub use commons::simplebus::*;
-
-pub use crate::blockchain::Chain;
-pub use crate::bytes::Bytes;
-pub use crate::commons::*;
-pub use crate::context::Context;
-pub use crate::keystore::Keystore;
-pub use crate::miner::Miner;
-pub use crate::p2p::Network;
-pub use crate::settings::Settings;
-
-pub mod blockchain;
-pub mod bytes;
-pub mod commons;
-pub mod context;
-pub mod crypto;
-pub mod dns;
-pub mod dns_utils;
-pub mod event;
-pub mod keystore;
-pub mod miner;
-pub mod p2p;
-pub mod settings;
@@ -3,34 +3,59 @@
 // See https://msdn.microsoft.com/en-us/library/4cc7ya5b.aspx for more details.
 #![windows_subsystem = "windows"]
 
+use std::cmp::{max, min};
+use std::net::IpAddr;
 use std::path::Path;
 use std::sync::{Arc, Mutex};
 use std::time::Duration;
 use std::{env, thread};
 
+use chrono::Utc;
 use getopts::{Matches, Options};
 #[allow(unused_imports)]
 use log::{debug, error, info, trace, warn, LevelFilter};
-use simplelog::{ColorChoice, CombinedLogger, ConfigBuilder, format_description, LevelPadding, TerminalMode, TermLogger, WriteLogger};
+use simplelog::{ColorChoice, ConfigBuilder, format_description, LevelPadding, SharedLogger, TerminalMode, TermLogger, WriteLogger};
 #[cfg(windows)]
 use winapi::um::wincon::{AttachConsole, FreeConsole, ATTACH_PARENT_PROCESS};
-extern crate lazy_static;
 
-use std::fs::{File, OpenOptions};
-use std::io::{Seek, SeekFrom, Write};
+use std::fs::{self, File};
+use std::io::{self, Write};
 use std::process::exit;
+use std::str::FromStr;
 use std::sync::atomic::{AtomicBool, Ordering};
 
-use alfis::event::Event;
-use alfis::eventbus::{post, register};
-use alfis::keystore::create_key;
-use alfis::{dns_utils, Block, Bytes, Chain, Context, Keystore, Miner, Network, Settings, Transaction, ALFIS_DEBUG, ALFIS_TRACE, DB_NAME, ORIGIN_DIFFICULTY};
-
+use alfis_core::blockchain::hash_utils::expected_hashes;
+use alfis_core::blockchain::types::BlockQuality;
+use alfis_core::event::Event;
+use alfis_core::export::{self, ExportFormat};
+use alfis_core::eventbus::{post, register};
+use alfis_core::keystore::{create_key, derive_domain_key, key_fingerprint};
+use alfis_core::lock::{DataLock, LockError};
+use alfis_core::logger::{JsonLogger, LogFilter, Logger, RotatingFile};
+use alfis_core::snapshot::{get_snapshot_path, Snapshot};
+use alfis_core::vanity::{find_vanity_key, VanityCriterion};
+use alfis_core::{api, backup, control, dns_utils, from_hex, getwork, keychain, miner, proxy, service, settings, snapshot, watch, webhook, Block, Bytes, Chain, ChainKind, Context, Keystore, Miner, Network, Settings, Transaction, ALFIS_DEBUG, ALFIS_TRACE, BAN_TIME_SEC, DB_NAME, KEYSTORE_DIFFICULTY};
+
+mod cli;
+#[cfg(feature = "webgui")]
+mod i18n;
+#[cfg(feature = "webgui")]
+mod notifications;
+#[cfg(feature = "webgui")]
+mod tray;
 #[cfg(feature = "webgui")]
 mod web_ui;
+#[cfg(feature = "webgui")]
+mod wizard;
 
 const SETTINGS_FILENAME: &str = "alfis.toml";
 const LOG_TARGET_MAIN: &str = "alfis::Main";
+/// How long we measure mining speed for every count of threads
+const BENCHMARK_SECONDS: u64 = 10;
+/// Environment variable with password for encrypted key files
+const KEY_PASSWORD_ENV: &str = "ALFIS_KEY_PASSWORD";
+/// Set when we run as a service, then nobody can answer our questions
+static DAEMON: AtomicBool = AtomicBool::new(false);
 
 fn main() {
     #[allow(unused_assignments, unused_mut)]
@@ -50,10 +75,17 @@ fn main() {
     let mut opts = Options::new();
     opts.optflag("h", "help", "Print this help menu");
     opts.optflag("n", "nogui", "Run without graphic user interface (default for no gui builds)");
+    opts.optflag("", "daemon", "Run as a service: without GUI and without questions in console, encrypted keys need ALFIS_KEY_PASSWORD or keychain");
+    opts.optopt("", "pid-file", "Write process id to file, for init systems", "FILE");
     opts.optflag("v", "version", "Print version and exit");
     opts.optflag("d", "debug", "Show debug messages, more than usual");
     opts.optflag("t", "trace", "Show trace messages, more than debug");
     opts.optflag("b", "blocks", "List blocks from DB and exit");
+    opts.optflag("", "check-db", "Check database and all blocks in it, remove broken blocks and exit");
+    opts.optflag("", "no-network", "Run without connecting to other nodes, only DNS and local blockchain will work");
+    opts.optflag("", "testnet", "Work with test chain: it has lower difficulty, short lifetime of domains, its own nodes, ports and data directory");
+    opts.optflag("", "regtest", "Work with local chain for tests without network, blocks are mined by 'generate' command");
+    opts.optflag("", "benchmark", "Measure mining speed with different count of threads and exit");
     opts.optflag("g", "generate", "Generate new config file. Generated config will be printed to console.");
     opts.optopt("k", "gen-key", "Generate new keys and save them to file.", "FILE");
     opts.optopt("l", "log", "Write log to file", "FILE");
@@ -61,6 +93,30 @@ fn main() {
     opts.optopt("c", "config", "Path to config file", "FILE");
     opts.optopt("w", "work-dir", "Path to working directory", "DIRECTORY");
     opts.optopt("u", "upgrade", "Path to config file that you want to upgrade. Upgraded config will be printed to console.", "FILE");
+    opts.optopt("e", "export", "Export names of loaded keys and export.domains from config in given format (hosts, dnsmasq or unbound) to console and exit", "FORMAT");
+    opts.optopt("", "export-zone", "Export names only from this zone", "ZONE");
+    opts.optopt("", "ban", "Ban node with this IP-address for a day and exit", "IP");
+    opts.optopt("", "unban", "Remove ban of node with this IP-address and exit", "IP");
+    opts.optflag("", "list-keys", "List key files from config and exit");
+    opts.optopt("", "add-key", "Add key file to config and exit", "FILE");
+    opts.optopt("", "remove-key", "Remove key file from config and exit", "FILE");
+    opts.optopt("", "default-key", "Set the key to select at start (name or path) and exit", "NAME");
+    opts.optopt("", "show-mnemonic", "Print backup words of key file and exit", "FILE");
+    opts.optopt("", "restore-from-mnemonic", "Restore keys from backup words and save them to file", "FILE");
+    opts.optopt("", "export-key", "Print key file as encrypted JSON container and exit", "FILE");
+    opts.optopt("", "import-key", "Import keys from JSON container, save them to file with the same name and .toml extension and exit", "FILE");
+    opts.optopt("", "vanity", "Search for the key with public key that starts with this hex, save it to vanity-PREFIX.toml and exit", "PREFIX");
+    opts.optopt("", "fingerprint", "Print fingerprint of public key in hex and exit", "KEY");
+    opts.optopt("", "offline-renew", "Mine renewal of domain owned by a key from watch_keys, to sign it on another computer", "DOMAIN");
+    opts.optopt("", "sign-block", "Sign mined block of offline key by a key from config and exit", "FILE");
+    opts.optopt("", "import-block", "Add signed block from file to blockchain at start", "FILE");
+    opts.optopt("", "domain-key", "Derive the key for domain from current key, save it to DOMAIN.toml and exit", "DOMAIN");
+    opts.optopt("", "rotate-key", "Move your domain to the key from --new-key, if the current key may be exposed", "DOMAIN");
+    opts.optopt("", "new-key", "Key file for --rotate-key", "FILE");
+    opts.optopt("", "mine-transaction", "Mine domain from file that was exported by a friend", "FILE");
+    opts.optopt("", "records", "JSON file with DNS records for 'register' command", "FILE");
+    opts.optopt("", "zone", "Zone of ALFIS for names of 'import' command", "ZONE");
+    opts.optopt("", "light", "API address of full node for 'lookup' command, to check the domain like a light client", "HOST:PORT");
 
     let opt_matches = match opts.parse(&args[1..]) {
         Ok(m) => m,
@@ -68,8 +124,8 @@ fn main() {
     };
 
     if opt_matches.opt_present("h") {
-        let brief = format!("Usage: {} [options]", program);
-        println!("{}", opts.usage(&brief));
+        let brief = format!("Usage: {} [options] [command]", program);
+        println!("{}\n{}", opts.usage(&brief), cli::COMMANDS_USAGE);
         exit(0);
     }
 
@@ -96,8 +152,11 @@ fn main() {
         }
     };
 
+    // Commands ask passwords in console, and if they start the node it works without GUI
+    let daemon = opt_matches.opt_present("daemon");
+    DAEMON.store(daemon, Ordering::Relaxed);
     #[cfg(feature = "webgui")]
-    let no_gui = opt_matches.opt_present("n");
+    let no_gui = opt_matches.opt_present("n") || daemon || !opt_matches.free.is_empty();
     #[cfg(not(feature = "webgui"))]
     let no_gui = true;
 
@@ -109,7 +168,38 @@ fn main() {
         Some(path) => path
     };
 
-    setup_logger(&opt_matches, console_attached);
+    // Restoring from backup writes the config, so it goes before loading of it.
+    // Test vectors need neither config nor database.
+    match opt_matches.free.iter().map(|arg| arg.as_str()).collect::<Vec<_>>().as_slice() {
+        ["backup", "restore", file] => {
+            cli::restore_backup(file, &config_name);
+            exit(0);
+        }
+        ["vectors", "generate", file] => {
+            cli::generate_vectors(file);
+            exit(0);
+        }
+        ["vectors", "verify", file] => {
+            cli::verify_vectors(file);
+            exit(0);
+        }
+        _ => {}
+    }
+
+    // On the first start user sets up the node in a wizard, it writes the config
+    #[cfg(feature = "webgui")]
+    if !no_gui && !Path::new(&config_name).exists() && !wizard::run_wizard(&config_name) {
+        exit(0);
+    }
+    let mut settings = Settings::load(&config_name).unwrap_or_else(|| panic!("Cannot load settings from {}!", &config_name));
+    if opt_matches.opt_present("testnet") {
+        settings.chain = ChainKind::Testnet;
+    }
+    if opt_matches.opt_present("regtest") {
+        settings.chain = ChainKind::Regtest;
+    }
+    settings.apply_chain_defaults();
+    setup_logger(&opt_matches, &settings.log, console_attached);
     if let Some(status) = opt_matches.opt_str("s") {
         register(move |_, event| {
             // TODO optimize for same data
@@ -128,10 +218,44 @@ fn main() {
     }
 
     info!(target: LOG_TARGET_MAIN, "Starting ALFIS {}", env!("CARGO_PKG_VERSION"));
-
-    let settings = Settings::load(&config_name).unwrap_or_else(|| panic!("Cannot load settings from {}!", &config_name));
     debug!(target: LOG_TARGET_MAIN, "Loaded settings: {:?}", &settings);
-    let chain: Chain = Chain::new(&settings, DB_NAME);
+    if manage_keys(&opt_matches, &mut settings, &config_name) {
+        return;
+    }
+    if !settings.data_dir.is_empty() {
+        if let Err(e) = fs::create_dir_all(&settings.data_dir) {
+            error!(target: LOG_TARGET_MAIN, "Unable to create data directory {}: {}", &settings.data_dir, e);
+            exit(1);
+        }
+    }
+    // Two nodes with one database break it, so commands only talk to the running node then
+    let mut data_lock = match DataLock::acquire(&settings.data_dir) {
+        Ok(lock) => Some(lock),
+        Err(LockError::Busy) if talks_to_running_node(&opt_matches) => None,
+        Err(e) => {
+            error!(target: LOG_TARGET_MAIN, "Unable to lock data directory '{}': {}. Stop the other node or set another data_dir in config", &settings.data_dir, e);
+            exit(1);
+        }
+    };
+    // Pid file belongs to the node that holds the lock, it is removed with the lock
+    if let (Some(lock), Some(path)) = (data_lock.as_mut(), opt_matches.opt_str("pid-file")) {
+        if let Err(e) = lock.write_pid_file(&path) {
+            error!(target: LOG_TARGET_MAIN, "Error writing process id to {}: {}", &path, e);
+            exit(1);
+        }
+    }
+    let db_path = Path::new(&settings.data_dir).join(DB_NAME);
+    // The running node writes the database, commands only read it then
+    let chain: Chain = match data_lock {
+        Some(_) => Chain::new(&settings, &db_path.to_string_lossy()),
+        None => match Chain::open_read_only(&settings, &db_path.to_string_lossy()) {
+            Ok(chain) => chain,
+            Err(e) => {
+                error!(target: LOG_TARGET_MAIN, "Unable to open database {}: {}", db_path.display(), e);
+                exit(1);
+            }
+        }
+    };
     if opt_matches.opt_present("b") {
         for i in 1..(chain.get_height() + 1) {
             if let Some(block) = chain.get_block(i) {
@@ -140,17 +264,72 @@ fn main() {
         }
         return;
     }
+    if opt_matches.opt_present("check-db") {
+        let mut chain = chain;
+        if !chain.check_db(u64::MAX, true) {
+            error!(target: LOG_TARGET_MAIN, "Database {} is damaged, delete it or restore it from backup", db_path.display());
+            exit(1);
+        }
+        info!(target: LOG_TARGET_MAIN, "Database is fine, blockchain height is {}", chain.get_height());
+        return;
+    }
+    if opt_matches.opt_present("benchmark") {
+        run_benchmark(&chain);
+        return;
+    }
+    if let Some(prefix) = opt_matches.opt_str("vanity") {
+        run_vanity(&prefix, settings.mining.threads);
+        return;
+    }
+    if let Some(ip) = opt_matches.opt_str("ban") {
+        if ip.parse::<IpAddr>().is_err() {
+            println!("Wrong IP-address '{}'", &ip);
+            exit(1);
+        }
+        // Running node has the database, it bans the node and closes connections to it
+        if data_lock.is_none() {
+            cli::ban_on_running_node(&settings, &ip, true);
+            return;
+        }
+        chain.add_ban(&ip, Utc::now().timestamp() + BAN_TIME_SEC, "manual");
+        println!("Node {} is banned", &ip);
+        return;
+    }
+    if let Some(ip) = opt_matches.opt_str("unban") {
+        if data_lock.is_none() {
+            cli::ban_on_running_node(&settings, &ip, false);
+            return;
+        }
+        match chain.remove_ban(&ip) {
+            true => println!("Node {} is unbanned", &ip),
+            false => println!("Node {} was not banned", &ip)
+        }
+        return;
+    }
     info!("Blocks count: {}, domains count: {}, users count: {}", chain.get_height(), chain.get_domains_count(), chain.get_users_count());
     let settings_copy = settings.clone();
     let mut keys = Vec::new();
     if !settings.key_files.is_empty() {
         for name in &settings.key_files {
-            match Keystore::from_file(name, "") {
+            let encrypted = Keystore::is_encrypted_file(name);
+            let saved = match encrypted && settings.use_keychain {
+                true => keychain::get_password(name),
+                false => None
+            };
+            let password = match (encrypted, &saved) {
+                (true, Some(password)) => password.clone(),
+                (true, None) => get_key_password(&format!("Enter password for key file {}", name), no_gui),
+                (false, _) => String::new()
+            };
+            match Keystore::from_file(name, &password) {
                 None => {
                     warn!("Error loading keyfile from {}", name);
                 }
                 Some(keystore) => {
-                    info!("Successfully loaded keyfile {}", name);
+                    info!("Successfully loaded keyfile {}, fingerprint: {}", name, keystore.get_fingerprint());
+                    if encrypted && settings.use_keychain && saved.is_none() {
+                        keychain::save_password(name, &password);
+                    }
                     keys.push(keystore);
                 }
             }
@@ -159,8 +338,64 @@ fn main() {
     let context = Context::new(env!("CARGO_PKG_VERSION").to_owned(), settings, keys, chain);
     let context: Arc<Mutex<Context>> = Arc::new(Mutex::new(context));
 
+    // If we just need to export names
+    if let Some(format) = opt_matches.opt_str("e") {
+        match ExportFormat::from_str(&format) {
+            Ok(format) => {
+                let zone = opt_matches.opt_str("export-zone").unwrap_or_default();
+                print!("{}", export::export(&context.lock().unwrap(), format, &zone));
+                exit(0);
+            }
+            Err(e) => {
+                println!("{}", e);
+                exit(1);
+            }
+        }
+    }
+
+    // If we need to run a command like `alfis lookup example.ygg`
+    let mut command_transactions = Vec::new();
+    if !opt_matches.free.is_empty() {
+        command_transactions = cli::run_command(&opt_matches, &context, &config_name, data_lock.is_none());
+        if command_transactions.is_empty() || data_lock.is_none() {
+            exit(0);
+        }
+    }
+
+    // If we need to sign a block that was mined for us on another computer
+    if let Some(path) = opt_matches.opt_str("sign-block") {
+        sign_block(&context, &path);
+        exit(0);
+    }
+
+    // If we just need to derive the key for some domain
+    if let Some(domain) = opt_matches.opt_str("domain-key") {
+        let (master, threads) = {
+            let context = context.lock().unwrap();
+            (context.get_keystore().cloned(), context.settings.mining.threads)
+        };
+        let master = match master {
+            Some(master) => master,
+            None => {
+                println!("There is no loaded key to derive domain key from");
+                exit(1);
+            }
+        };
+        let threads = if threads == 0 { num_cpus::get() } else { threads };
+        info!(target: LOG_TARGET_MAIN, "Deriving key for domain {}...", &domain);
+        let domain = domain.to_lowercase();
+        if let Some(mut keystore) = derive_domain_key(&master, &domain, threads, Arc::new(AtomicBool::new(true))) {
+            let password = get_key_password("Enter password to encrypt new keys (leave empty for no encryption)", true);
+            let filename = format!("{}.toml", &domain);
+            keystore.save(&filename, &password);
+            println!("Key for domain {} saved to {}, public key: {}", &domain, &filename, keystore.get_public().to_string());
+        }
+        exit(0);
+    }
+
     // If we just need to generate keys
     if let Some(filename) = opt_matches.opt_str("k") {
+        let password = get_key_password("Enter password to encrypt new keys (leave empty for no encryption)", true);
         info!(target: LOG_TARGET_MAIN, "Generating keys...");
         let mining = Arc::new(AtomicBool::new(true));
         let mining_copy = Arc::clone(&mining);
@@ -171,9 +406,10 @@ fn main() {
                 let context_copy = Arc::clone(&context_copy);
                 let mining_copy = Arc::clone(&mining_copy);
                 let filename = filename.clone();
+                let password = password.clone();
                 thread::spawn(move || {
                     if let Some(keystore) = context_copy.lock().unwrap().get_keystore_mut() {
-                        keystore.save(&filename, "");
+                        keystore.save(&filename, &password);
                         mining_copy.store(false, Ordering::Relaxed);
                     }
                 });
@@ -193,7 +429,16 @@ fn main() {
     }
 
     if let Ok(mut context) = context.lock() {
-        context.chain.check_chain(settings_copy.check_blocks);
+        if !context.chain.check_db(settings_copy.check_blocks, false) {
+            error!(target: LOG_TARGET_MAIN, "Database is damaged, delete it or restore it from backup, blocks will be downloaded again");
+            exit(1);
+        }
+        match Snapshot::load(&get_snapshot_path(&settings_copy)) {
+            Some(snapshot) if snapshot.is_current(&context.chain, &context.app_version) => {
+                info!(target: LOG_TARGET_MAIN, "Database has not changed since the node stopped, last blocks are not checked");
+            }
+            _ => context.chain.check_chain(settings_copy.check_blocks)
+        }
         match context.chain.get_block(1) {
             None => {
                 info!(target: LOG_TARGET_MAIN, "No blocks found in DB");
@@ -210,21 +455,86 @@ fn main() {
         true
     };
 
+    export::start_exporter(Arc::clone(&context));
+    watch::start_domain_watcher(Arc::clone(&context));
+    webhook::start_webhooks(Arc::clone(&context));
+    backup::start_backups(Arc::clone(&context));
+    snapshot::start_snapshots(Arc::clone(&context));
+    if let Some(path) = opt_matches.opt_str("import-block") {
+        import_block(&context, &path);
+    }
+
     let mut miner_obj = Miner::new(Arc::clone(&context));
+    miner_obj.resume_jobs();
+    if let Some(domain) = opt_matches.opt_str("rotate-key") {
+        rotate_key(&mut miner_obj, &domain.to_lowercase(), opt_matches.opt_str("new-key"), no_gui);
+    }
+    if let Some(domain) = opt_matches.opt_str("offline-renew") {
+        let result = miner_obj.renew_offline(&domain.to_lowercase());
+        info!("Renewal of domain {} for offline key: {:?}", &domain, result);
+    }
+    if let Some(path) = opt_matches.opt_str("mine-transaction") {
+        match fs::read_to_string(&path).ok().and_then(|json| Transaction::from_untrusted_json(&json).ok()) {
+            Some(transaction) => {
+                let owner = key_fingerprint(&transaction.signing);
+                let result = miner_obj.add_transaction(transaction);
+                info!("Mining domain from '{}' for owner {}: {:?}", &path, &owner, result);
+            }
+            None => error!("Unable to load domain transaction from '{}'", &path)
+        }
+    }
+    if !command_transactions.is_empty() {
+        let results = miner_obj.add_transactions(command_transactions);
+        info!("Mining domains from command line: {:?}", results);
+    }
     miner_obj.start_mining_thread();
     let miner: Arc<Mutex<Miner>> = Arc::new(Mutex::new(miner_obj));
-
-    let mut network = Network::new(Arc::clone(&context));
-    let network = thread::Builder::new().name(String::from("Network")).spawn(move || {
-        // Give UI some time to appear :)
-        thread::sleep(Duration::from_millis(1000));
-        network.start();
-    }).expect("Could not start network thread!");
+    getwork::start_getwork_server(Arc::clone(&context));
+    api::start_api_server(Arc::clone(&context), Arc::clone(&miner));
+    control::start_control_socket(Arc::clone(&context), Arc::clone(&miner), &config_name);
+    proxy::start_http_proxy(Arc::clone(&context));
+    reload_on_signal(Arc::clone(&context), &config_name);
+
+    let regtest = context.lock().unwrap().chain.get_kind().is_on_demand();
+    let network = if opt_matches.opt_present("no-network") || regtest {
+        info!(target: LOG_TARGET_MAIN, "Network is disabled, working offline");
+        let context = Arc::clone(&context);
+        thread::spawn(move || {
+            // Give UI some time to appear and show what we have
+            thread::sleep(Duration::from_millis(1000));
+            let context = context.lock().unwrap();
+            let (blocks, domains, keys) = (context.chain.get_height(), context.chain.get_domains_count(), context.chain.get_users_count());
+            post(Event::NetworkStatus { blocks, domains, keys, nodes: 0 });
+        });
+        None
+    } else {
+        let mut network = Network::new(Arc::clone(&context));
+        let network = thread::Builder::new().name(String::from("Network")).spawn(move || {
+            // Give UI some time to appear :)
+            thread::sleep(Duration::from_millis(1000));
+            network.start();
+        }).expect("Could not start network thread!");
+        Some(network)
+    };
 
     create_genesis_if_needed(&context, &miner);
     if no_gui {
+        if daemon && !dns_server_ok {
+            // Let the service manager restart us when the port is free
+            error!(target: LOG_TARGET_MAIN, "Error starting DNS-server, exiting");
+            exit(1);
+        }
+        log_events();
         print_my_domains(&context);
-        let _ = network.join();
+        quit_on_signal();
+        service::notify(service::READY);
+        match network {
+            Some(network) => {
+                let _ = network.join();
+            }
+            // DNS server works in its own threads, we just wait
+            None => wait_for_quit()
+        }
     } else {
         if !dns_server_ok {
             thread::spawn(|| {
@@ -233,8 +543,11 @@ fn main() {
             });
         }
         #[cfg(feature = "webgui")]
-        web_ui::run_interface(Arc::clone(&context), miner);
+        notifications::start_notifications(Arc::clone(&context));
+        #[cfg(feature = "webgui")]
+        web_ui::run_interface(Arc::clone(&context), miner, &config_name);
     }
+    shutdown(&context);
 
     // Without explicitly detaching the console cmd won't redraw it's prompt.
     #[cfg(windows)]
@@ -243,20 +556,117 @@ fn main() {
     }
 }
 
+/// Commands and bans can talk to running node, without taking its data directory
+fn talks_to_running_node(opt_matches: &Matches) -> bool {
+    if opt_matches.opt_present("check-db") {
+        return false;
+    }
+    !opt_matches.free.is_empty() || opt_matches.opt_present("ban") || opt_matches.opt_present("unban")
+}
+
+/// Starts coordinated shutdown on SIGTERM or Ctrl+C, the second signal stops the node at once
+#[cfg(unix)]
+fn quit_on_signal() {
+    use signal_hook::consts::{SIGINT, SIGTERM};
+    use signal_hook::iterator::Signals;
+
+    let mut signals = match Signals::new([SIGTERM, SIGINT]) {
+        Ok(signals) => signals,
+        Err(e) => {
+            warn!(target: LOG_TARGET_MAIN, "Unable to handle SIGTERM: {}", e);
+            return;
+        }
+    };
+    thread::spawn(move || {
+        let mut quitting = false;
+        for _ in signals.forever() {
+            if quitting {
+                warn!(target: LOG_TARGET_MAIN, "Stopping without waiting");
+                exit(1);
+            }
+            quitting = true;
+            info!(target: LOG_TARGET_MAIN, "Shutting down");
+            service::notify(service::STOPPING);
+            post(Event::ActionQuit);
+        }
+    });
+}
+
+#[cfg(not(unix))]
+fn quit_on_signal() {}
+
+/// Blocks until somebody posts `ActionQuit`
+fn wait_for_quit() {
+    let quit = Arc::new(AtomicBool::new(false));
+    let quit_copy = Arc::clone(&quit);
+    let main_thread = thread::current();
+    register(move |_uuid, e| {
+        if e == Event::ActionQuit {
+            quit_copy.store(true, Ordering::SeqCst);
+            main_thread.unpark();
+            return false;
+        }
+        true
+    });
+    while !quit.load(Ordering::SeqCst) {
+        thread::park();
+    }
+}
+
+/// Waits for the block that is being added to the DB, then we can exit safely.
+/// Network and DNS servers stop by `ActionQuit` themselves.
+fn shutdown(context: &Arc<Mutex<Context>>) {
+    let context = context.lock().unwrap();
+    if context.miner_state.mining {
+        warn!(target: LOG_TARGET_MAIN, "Mining is interrupted, it will not continue after restart");
+    }
+    snapshot::save_snapshot(&context);
+    info!(target: LOG_TARGET_MAIN, "Stopped at block {}", context.chain.get_height());
+}
+
+/// Reloads settings from config file on SIGHUP, as usual for services
+#[cfg(unix)]
+fn reload_on_signal(context: Arc<Mutex<Context>>, config_name: &str) {
+    use signal_hook::consts::SIGHUP;
+    use signal_hook::iterator::Signals;
+
+    let mut signals = match Signals::new([SIGHUP]) {
+        Ok(signals) => signals,
+        Err(e) => {
+            warn!(target: LOG_TARGET_MAIN, "Unable to handle SIGHUP: {}", e);
+            return;
+        }
+    };
+    let config_name = config_name.to_owned();
+    thread::spawn(move || {
+        for _ in signals.forever() {
+            service::notify(service::RELOADING);
+            context.lock().unwrap().reload_settings(&config_name);
+            service::notify(service::READY);
+        }
+    });
+}
+
+#[cfg(not(unix))]
+fn reload_on_signal(_context: Arc<Mutex<Context>>, _config_name: &str) {}
+
 /// Sets up logger in accordance with command line options
-fn setup_logger(opt_matches: &Matches, console_attached: bool) {
-    let mut level = LevelFilter::Info;
+fn setup_logger(opt_matches: &Matches, settings: &settings::Log, console_attached: bool) {
+    let mut filter = match LogFilter::parse(&settings.level, &settings.modules) {
+        Ok(filter) => filter,
+        Err(e) => {
+            println!("Error in [log] section of config: {}", e);
+            exit(1);
+        }
+    };
     if opt_matches.opt_present("d") || env::var(ALFIS_DEBUG).is_ok() {
-        level = LevelFilter::Debug;
+        filter.set_level(LevelFilter::Debug);
     }
     if opt_matches.opt_present("t") || env::var(ALFIS_TRACE).is_ok() {
-        level = LevelFilter::Trace;
+        filter.set_level(LevelFilter::Trace);
     }
     let mut builder = ConfigBuilder::new();
-    builder.add_filter_ignore_str("mio::poll")
-        .add_filter_ignore_str("rustls::client")
-        .add_filter_ignore_str("ureq::")
-        .set_thread_level(LevelFilter::Error)
+    builder.set_thread_level(LevelFilter::Error)
         .set_location_level(LevelFilter::Off)
         .set_target_level(LevelFilter::Error)
         .set_level_padding(LevelPadding::Right)
@@ -267,35 +677,227 @@ fn setup_logger(opt_matches: &Matches, console_attached: bool) {
         Err(config) => config
     };
     let config = config.build();
-    match opt_matches.opt_str("l") {
-        None => {
-            if console_attached {
-                if let Err(e) = TermLogger::init(level, config, TerminalMode::Stdout, ColorChoice::Auto) {
-                    println!("Unable to initialize logger!\n{}", e);
-                }
+
+    // Levels are checked by the filter, outputs write everything they get
+    let mut outputs: Vec<Box<dyn SharedLogger>> = Vec::new();
+    if console_attached {
+        match settings.json {
+            true => outputs.push(JsonLogger::new(io::stdout())),
+            false => outputs.push(TermLogger::new(LevelFilter::Trace, config.clone(), TerminalMode::Stdout, ColorChoice::Auto))
+        }
+    }
+    let path = opt_matches.opt_str("l").unwrap_or_else(|| settings.file.clone());
+    if !path.is_empty() {
+        let file = match RotatingFile::open(&path, settings.max_size * 1024 * 1024, settings.keep) {
+            Ok(file) => file,
+            Err(e) => {
+                println!("Could not open log file '{}' for writing!\n{}", &path, e);
+                exit(1);
             }
+        };
+        match settings.json {
+            true => outputs.push(JsonLogger::new(file)),
+            false => outputs.push(WriteLogger::new(LevelFilter::Trace, config, file))
         }
-        Some(path) => {
-            let file = match OpenOptions::new().write(true).create(true).open(&path) {
-                Ok(mut file) => {
-                    file.seek(SeekFrom::End(0)).unwrap();
-                    file
+    }
+    if let Err(e) = Logger::init(filter, outputs) {
+        println!("Unable to initialize logger!\n{}", e);
+    }
+}
+
+/// Gets password for key files from environment, or asks user for it
+fn get_key_password(message: &str, no_gui: bool) -> String {
+    if let Ok(password) = env::var(KEY_PASSWORD_ENV) {
+        return password;
+    }
+    if DAEMON.load(Ordering::Relaxed) {
+        warn!(target: LOG_TARGET_MAIN, "Running as daemon, can't ask: {}", message);
+        return String::new();
+    }
+    #[cfg(feature = "webgui")]
+    if !no_gui {
+        return web_ui::ask_password(message).unwrap_or_default();
+    }
+    #[cfg(not(feature = "webgui"))]
+    let _ = no_gui;
+    rpassword::prompt_password(format!("{}: ", message)).unwrap_or_default()
+}
+
+/// Manages key files and key files in config, returns true if some of these commands was given
+fn manage_keys(opt_matches: &Matches, settings: &mut Settings, config_name: &str) -> bool {
+    if opt_matches.opt_present("list-keys") {
+        for name in &settings.key_files {
+            let default = if name == &settings.default_key { " (default)" } else { "" };
+            let encrypted = if Keystore::is_encrypted_file(name) { " (encrypted)" } else { "" };
+            match Keystore::read_public(name) {
+                Some(public) => println!("{}{}{} {} {}", name, default, encrypted, public.to_string(), key_fingerprint(&public)),
+                None => println!("{}{} (not found)", name, default)
+            }
+        }
+        return true;
+    }
+    if let Some(key) = opt_matches.opt_str("fingerprint") {
+        match from_hex(&key) {
+            Ok(key) if key.len() == 32 => println!("{}", key_fingerprint(&key)),
+            _ => println!("Wrong public key")
+        }
+        return true;
+    }
+    if let Some(path) = opt_matches.opt_str("add-key") {
+        if !settings.key_files.contains(&path) {
+            settings.key_files.push(path.clone());
+        }
+        if settings.save(config_name) {
+            println!("Key file {} added", &path);
+        }
+        return true;
+    }
+    if let Some(path) = opt_matches.opt_str("remove-key") {
+        let count = settings.key_files.len();
+        settings.key_files.retain(|name| name != &path);
+        if settings.key_files.len() == count {
+            println!("There is no key file {} in config", &path);
+        } else if settings.save(config_name) {
+            println!("Key file {} removed", &path);
+        }
+        return true;
+    }
+    if let Some(path) = opt_matches.opt_str("show-mnemonic") {
+        let password = match Keystore::is_encrypted_file(&path) {
+            true => get_key_password(&format!("Enter password for key file {}", &path), true),
+            false => String::new()
+        };
+        match Keystore::from_file(&path, &password) {
+            Some(keystore) => {
+                println!("{}", keystore.get_mnemonic());
+                if !keystore.is_mnemonic_complete() {
+                    println!("This is an old key, its encryption key can't be restored from these words, keep the key file too!");
                 }
-                Err(e) => {
-                    println!("Could not open log file '{}' for writing!\n{}", &path, e);
-                    exit(1);
+            }
+            None => println!("Unable to load key file {}", &path)
+        }
+        return true;
+    }
+    if let Some(path) = opt_matches.opt_str("export-key") {
+        let password = match Keystore::is_encrypted_file(&path) {
+            true => get_key_password(&format!("Enter password for key file {}", &path), true),
+            false => String::new()
+        };
+        match Keystore::from_file(&path, &password) {
+            Some(keystore) => {
+                let password = rpassword::prompt_password("Enter password for exported keys: ").unwrap_or_default();
+                if password.is_empty() {
+                    println!("Exported keys need a password");
+                } else {
+                    println!("{}", keystore.export_json(&password));
                 }
-            };
-            if console_attached {
-                CombinedLogger::init(vec![
-                    TermLogger::new(level, config.clone(), TerminalMode::Stdout, ColorChoice::Auto),
-                    WriteLogger::new(level, config, file),
-                ])
-                .unwrap();
-            } else if let Err(e) = WriteLogger::init(level, config, file) {
-                println!("Unable to initialize logger!\n{}", e);
             }
+            None => println!("Unable to load key file {}", &path)
+        }
+        return true;
+    }
+    if let Some(path) = opt_matches.opt_str("import-key") {
+        let json = fs::read_to_string(&path).unwrap_or_default();
+        let password = rpassword::prompt_password(format!("Enter password for {}: ", &path)).unwrap_or_default();
+        match Keystore::import_json(&json, &password) {
+            Some(mut keystore) => {
+                let filename = Path::new(&path).with_extension("toml").to_string_lossy().to_string();
+                let password = get_key_password("Enter password to encrypt keys (leave empty for no encryption)", true);
+                keystore.save(&filename, &password);
+                println!("Keys imported and saved to {}", &filename);
+            }
+            None => println!("Unable to import keys, the file is wrong or the password doesn't fit")
         }
+        return true;
+    }
+    if let Some(path) = opt_matches.opt_str("restore-from-mnemonic") {
+        let words = rpassword::prompt_password("Enter 24 backup words: ").unwrap_or_default();
+        match Keystore::from_mnemonic(&words) {
+            Some(mut keystore) => {
+                let password = get_key_password("Enter password to encrypt keys (leave empty for no encryption)", true);
+                keystore.save(&path, &password);
+                println!("Keys restored and saved to {}", &path);
+            }
+            None => println!("Wrong backup words!")
+        }
+        return true;
+    }
+    if let Some(name) = opt_matches.opt_str("default-key") {
+        settings.default_key = name.clone();
+        if settings.save(config_name) {
+            println!("Default key is set to {}", &name);
+        }
+        return true;
+    }
+    false
+}
+
+/// Starts mining of domain transfer to the new key
+fn rotate_key(miner: &mut Miner, domain: &str, new_key: Option<String>, no_gui: bool) {
+    let path = match new_key {
+        Some(path) => path,
+        None => {
+            error!("You need to give the new key with --new-key option, you can generate it with --gen-key");
+            return;
+        }
+    };
+    let password = match Keystore::is_encrypted_file(&path) {
+        true => get_key_password(&format!("Enter password for key file {}", &path), no_gui),
+        false => String::new()
+    };
+    match Keystore::from_file(&path, &password) {
+        Some(keystore) => {
+            let result = miner.rotate_key(domain, &keystore);
+            info!("Moving domain {} to key {} ({}): {:?}", domain, &path, keystore.get_fingerprint(), result);
+        }
+        None => error!("Unable to load new key from '{}'", &path)
+    }
+}
+
+/// Signs block from file by loaded key that mined it, and saves it to file with "signed-" prefix
+fn sign_block(context: &Arc<Mutex<Context>>, path: &str) {
+    let mut block = match fs::read_to_string(path).ok().and_then(|json| serde_json::from_str::<Block>(&json).ok()) {
+        Some(block) => block,
+        None => {
+            println!("Unable to load block from {}", path);
+            return;
+        }
+    };
+    let keystore = match context.lock().unwrap().get_keystore_by_public(&block.pub_key) {
+        Some(keystore) => keystore.clone(),
+        None => {
+            println!("The key {} of this block is not loaded", block.pub_key.to_string());
+            return;
+        }
+    };
+    if let Some(transaction) = &block.transaction {
+        println!("Block {} is for domain owned by {}", block.index, key_fingerprint(&transaction.signing));
+    }
+    block.signature = Bytes::from_bytes(&keystore.sign(&block.as_bytes_compact()));
+    let file_name = Path::new(path).file_name().map(|name| name.to_string_lossy().replace("unsigned-", "")).unwrap_or_default();
+    let file_name = format!("signed-{}", file_name);
+    match fs::write(&file_name, serde_json::to_string_pretty(&block).unwrap()) {
+        Ok(_) => println!("Signed block is saved to {}, import it by 'alfis --import-block {}'", &file_name, &file_name),
+        Err(e) => println!("Error saving signed block: {}", e)
+    }
+}
+
+/// Adds signed block from file to our chain, it will be sent to other nodes as usual
+fn import_block(context: &Arc<Mutex<Context>>, path: &str) {
+    let block = match fs::read_to_string(path).ok().and_then(|json| serde_json::from_str::<Block>(&json).ok()) {
+        Some(block) => block,
+        None => {
+            error!("Unable to load block from {}", path);
+            return;
+        }
+    };
+    let mut context = context.lock().unwrap();
+    match context.chain.check_new_block(&block) {
+        BlockQuality::Good => {
+            info!("Block {} from {} is added", block.index, path);
+            context.chain.add_block(block);
+        }
+        _ => error!("Block from {} can't be added, maybe other blocks were added after it was mined", path)
     }
 }
 
@@ -306,6 +908,73 @@ fn print_my_domains(context: &Arc<Mutex<Context>>) {
     debug!("Domains: {:?}", &domains);
 }
 
+/// Searches for the key with public key that starts with `prefix`, the search can be stopped and resumed later
+fn run_vanity(prefix: &str, threads: usize) {
+    let criterion = match VanityCriterion::new(prefix, KEYSTORE_DIFFICULTY) {
+        Some(criterion) => criterion,
+        None => {
+            println!("Prefix must be in hex, like 'A1F15'");
+            return;
+        }
+    };
+    let threads = if threads == 0 { num_cpus::get() } else { threads };
+    let state_file = format!("vanity-{}.json", &criterion.prefix);
+    info!(target: LOG_TARGET_MAIN, "Searching for key {}..., it needs {:.0} keys in average", &criterion.prefix, criterion.expected_keys());
+    match find_vanity_key(&criterion, threads, &state_file, Arc::new(AtomicBool::new(true))) {
+        Some(mut keystore) => {
+            let password = get_key_password("Enter password to encrypt new keys (leave empty for no encryption)", true);
+            let filename = format!("vanity-{}.toml", &criterion.prefix);
+            keystore.save(&filename, &password);
+            println!("Key {} saved to {}", keystore.get_public().to_string(), &filename);
+        }
+        None => println!("Search stopped, progress is saved to {}", &state_file)
+    }
+}
+
+/// Measures mining speed and shows how long it would take to mine a domain
+fn run_benchmark(chain: &Chain) {
+    let index = chain.get_height() + 1;
+    let difficulty = chain.get_domain_difficulty(index);
+    let version = chain.get_block_version(index);
+    let expected = expected_hashes(difficulty);
+    let cpus = num_cpus::get();
+    let mut threads = 1;
+    println!("Domain difficulty is {}, it needs {} hashes in average", difficulty, expected);
+    loop {
+        let speed = miner::benchmark(version, threads, Duration::from_secs(BENCHMARK_SECONDS));
+        let time = expected / max(speed, 1);
+        println!("{} threads: {} H/s, a domain would take about {} minutes", threads, speed, time / 60);
+        if threads >= cpus {
+            break;
+        }
+        threads = min(threads * 2, cpus);
+    }
+}
+
+/// Shows what is going on in console when there is no GUI
+fn log_events() {
+    register(|_uuid, e| {
+        match e {
+            Event::PeerConnected { addr, inbound } => {
+                let direction = if inbound { "from" } else { "to" };
+                info!(target: LOG_TARGET_MAIN, "Connected {} {}", direction, addr);
+            }
+            Event::PeerDisconnected { addr } => info!(target: LOG_TARGET_MAIN, "Disconnected from {}", addr),
+            Event::NewBlock { index, hash } => debug!(target: LOG_TARGET_MAIN, "New block {} with hash {}", index, hash),
+            Event::DomainConfirmed { index, identity } => {
+                info!(target: LOG_TARGET_MAIN, "Domain {} from block {} is confirmed", identity, index);
+            }
+            Event::MinerProgress { speed, target_diff, eta } => {
+                info!(target: LOG_TARGET_MAIN, "Mining speed {} H/s, difficulty {}, expected time {} seconds", speed, target_diff, eta);
+            }
+            Event::SyncFinished => info!(target: LOG_TARGET_MAIN, "Synchronization finished"),
+            Event::Error { text } => error!(target: LOG_TARGET_MAIN, "{}", text),
+            _ => {}
+        }
+        true
+    });
+}
+
 /// Creates genesis (origin) block if `origin` is empty in config and we don't have any blocks in DB
 fn create_genesis_if_needed(context: &Arc<Mutex<Context>>, miner: &Arc<Mutex<Miner>>) {
     // If there is no origin in settings and no blockchain in DB, generate genesis block
@@ -316,7 +985,7 @@ fn create_genesis_if_needed(context: &Arc<Mutex<Context>>, miner: &Arc<Mutex<Min
         if let Some(keystore) = context.get_keystore() {
             // If blockchain is empty, we are going to mine a Genesis block
             let transaction = Transaction::origin(Chain::get_zones_hash(), keystore.get_public(), keystore.get_encryption_public());
-            let block = Block::new(Some(transaction), keystore.get_public(), Bytes::default(), ORIGIN_DIFFICULTY);
+            let block = Block::new(Some(transaction), keystore.get_public(), Bytes::default(), context.chain.get_kind().origin_difficulty());
             miner.lock().unwrap().add_block(block, keystore.clone());
         }
     }
@@ -324,7 +993,7 @@ fn create_genesis_if_needed(context: &Arc<Mutex<Context>>, miner: &Arc<Mutex<Min
 
 #[cfg(test)]
 mod tests {
-    use alfis::dns::protocol::{DnsRecord, TransientTtl};
+    use alfis_core::dns::protocol::{DnsRecord, TransientTtl};
 
     #[test]
     fn record_to_string() {
@@ -0,0 +1,174 @@
+//! Desktop notifications about important events: our domain was mined and confirmed, our domain was changed
+//! by other key, or the node fell behind the network. They are configured in `[notifications]` of settings.
+
+#[cfg(feature = "notifications")]
+pub use self::desktop::start_notifications;
+#[cfg(not(feature = "notifications"))]
+pub use self::stub::start_notifications;
+
+#[cfg(feature = "notifications")]
+mod desktop {
+    use std::sync::mpsc;
+    use std::sync::{Arc, Mutex};
+    use std::thread;
+
+    use alfis_core::event::Event;
+    use alfis_core::eventbus::register;
+    use alfis_core::watch::get_conflict_domain_name;
+    use alfis_core::{from_hex, Bytes, Context, Transaction};
+    use chrono::Utc;
+    #[allow(unused_imports)]
+    use log::{debug, error, info, trace, warn};
+    use notify_rust::Notification;
+
+    use crate::i18n::{tr, tr_args};
+
+    /// Older blocks are from synchronization, we don't notify about them
+    const BLOCK_MAX_AGE: i64 = 86400;
+
+    /// Tells once when the node is behind the network by `blocks` or more, until it is synchronized again
+    #[derive(Debug, Default)]
+    struct Lag {
+        synced: bool,
+        notified: bool
+    }
+
+    impl Lag {
+        /// Returns count of blocks we are behind, if it is time to notify about it
+        fn update(&mut self, event: &Event, blocks: u64) -> Option<u64> {
+            match event {
+                Event::SyncFinished => {
+                    self.synced = true;
+                    self.notified = false;
+                    None
+                }
+                // Right after start we are always behind, that is not interesting
+                Event::SyncProgress { have, height } if self.synced && !self.notified && blocks > 0 && height.saturating_sub(*have) >= blocks => {
+                    self.notified = true;
+                    Some(height - have)
+                }
+                _ => None
+            }
+        }
+    }
+
+    /// Starts a thread that shows notifications, settings are checked for every event, so changes apply at once
+    pub fn start_notifications(context: Arc<Mutex<Context>>) {
+        // Listeners of event bus must not lock the context
+        let (sender, receiver) = mpsc::channel();
+        let sender = Mutex::new(sender);
+        register(move |_uuid, e| {
+            match e {
+                Event::DomainConfirmed { .. } | Event::DomainConflict { .. } | Event::SyncProgress { .. } | Event::SyncFinished => sender.lock().unwrap().send(e).is_ok(),
+                Event::ActionQuit => false,
+                _ => true
+            }
+        });
+        let _ = thread::Builder::new().name(String::from("notifications")).spawn(move || {
+            let mut lag = Lag::default();
+            for event in receiver {
+                let text = {
+                    let context = context.lock().unwrap();
+                    let settings = &context.settings.notifications;
+                    let behind = lag.update(&event, settings.behind_blocks);
+                    if !settings.enabled {
+                        continue;
+                    }
+                    match event {
+                        Event::DomainConfirmed { index, .. } if settings.domains => confirmed_text(&context, index),
+                        Event::DomainConflict { index, identity, key, .. } if settings.conflicts => Some(conflict_text(&context, index, &identity, &key)),
+                        _ => behind.map(|blocks| tr_args("notify.behind", &[("blocks", &blocks.to_string())]))
+                    }
+                };
+                if let Some(text) = text {
+                    show(&text);
+                }
+            }
+        });
+    }
+
+    /// Text about our domain that got enough signing blocks
+    fn confirmed_text(context: &Context, index: u64) -> Option<String> {
+        let block = context.chain.get_block(index)?;
+        if block.timestamp < Utc::now().timestamp() - BLOCK_MAX_AGE {
+            return None;
+        }
+        let transaction = block.transaction.as_ref()?;
+        if !is_ours(context, &transaction.signing) {
+            return None;
+        }
+        let name = domain_name(context, transaction);
+        Some(tr_args("notify.domain_confirmed", &[("domain", &name)]))
+    }
+
+    /// Text about our domain that was changed by other key
+    fn conflict_text(context: &Context, index: u64, identity: &str, key: &str) -> String {
+        let name = get_conflict_domain_name(context, index).unwrap_or_else(|| identity.chars().take(16).collect());
+        let key = from_hex(key).map(|key| short(&Bytes::new(key))).unwrap_or_default();
+        tr_args("notify.domain_conflict", &[("domain", &name), ("key", &key)])
+    }
+
+    fn is_ours(context: &Context, owner: &Bytes) -> bool {
+        context.get_keystore_by_public(owner).is_some() || context.settings.get_watch_keys().contains(owner)
+    }
+
+    /// Decrypted name of domain if we have its key, or the beginning of its identity hash
+    fn domain_name(context: &Context, transaction: &Transaction) -> String {
+        let keystore = context.get_keystore_by_public(&transaction.signing);
+        let name = match (keystore, transaction.get_domain_data()) {
+            (Some(keystore), Some(data)) => String::from_utf8(keystore.decrypt(data.encrypted.as_slice()).to_vec()).unwrap_or_default(),
+            _ => String::new()
+        };
+        match name.is_empty() {
+            true => short(&transaction.identity),
+            false => name
+        }
+    }
+
+    fn short(bytes: &Bytes) -> String {
+        bytes.to_string().chars().take(16).collect()
+    }
+
+    fn show(text: &str) {
+        debug!("Showing notification: {}", text);
+        let result = Notification::new()
+            .appname("ALFIS")
+            .summary(&tr("notify.title"))
+            .body(text)
+            .show();
+        if let Err(e) = result {
+            warn!("Error showing notification: {}", e);
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use alfis_core::event::Event;
+
+        use super::Lag;
+
+        #[test]
+        fn test_lag() {
+            let mut lag = Lag::default();
+            // Synchronization at start
+            assert_eq!(lag.update(&Event::SyncProgress { have: 0, height: 1000 }, 10), None);
+            assert_eq!(lag.update(&Event::SyncFinished, 10), None);
+            assert_eq!(lag.update(&Event::SyncProgress { have: 995, height: 1000 }, 10), None);
+            assert_eq!(lag.update(&Event::SyncProgress { have: 990, height: 1010 }, 10), Some(20));
+            // Only once until we catch up
+            assert_eq!(lag.update(&Event::SyncProgress { have: 991, height: 1010 }, 10), None);
+            assert_eq!(lag.update(&Event::SyncFinished, 10), None);
+            assert_eq!(lag.update(&Event::SyncProgress { have: 1010, height: 1030 }, 0), None);
+            assert_eq!(lag.update(&Event::SyncProgress { have: 1010, height: 1030 }, 10), Some(20));
+        }
+    }
+}
+
+#[cfg(not(feature = "notifications"))]
+mod stub {
+    use std::sync::{Arc, Mutex};
+
+    use alfis_core::Context;
+
+    pub fn start_notifications(_context: Arc<Mutex<Context>>) {}
+}
@@ -0,0 +1,269 @@
+//! Icon in system tray with the status of the node. When it is shown, closing the main window
+//! leaves the node working in background, the window can be opened again from the tray menu.
+
+/// What user wants from the tray menu
+#[allow(dead_code)]
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum TrayAction {
+    Open,
+    Quit
+}
+
+#[cfg(feature = "tray")]
+pub use self::icon::Tray;
+#[cfg(not(feature = "tray"))]
+pub use self::stub::Tray;
+
+#[cfg(feature = "tray")]
+mod icon {
+    use std::sync::{Arc, Mutex};
+    use std::thread;
+    use std::time::Duration;
+
+    use alfis_core::event::Event;
+    use alfis_core::eventbus::{post, register};
+    use alfis_core::Context;
+    #[allow(unused_imports)]
+    use log::{debug, error, info, trace, warn};
+    use tray_icon::menu::{Menu, MenuEvent, MenuItem, PredefinedMenuItem};
+    use tray_icon::{Icon, TrayIcon, TrayIconBuilder};
+
+    use super::TrayAction;
+    use crate::i18n::{tr, tr_args};
+
+    const ICON_SIZE: u32 = 32;
+    /// How often we look for tray events when the main window is closed
+    const WAIT_PAUSE: Duration = Duration::from_millis(50);
+
+    /// Status of the node, it is updated by events
+    #[derive(Clone, Debug, Default, PartialEq)]
+    struct NodeStatus {
+        syncing: bool,
+        have: u64,
+        height: u64,
+        nodes: usize,
+        mining: bool,
+        paused: bool
+    }
+
+    impl NodeStatus {
+        fn text(&self) -> String {
+            let state = if self.mining && self.paused {
+                tr("status.mining_paused")
+            } else if self.mining {
+                tr("status.mining")
+            } else if self.syncing {
+                tr_args("status.synchronizing", &[("have", &self.have.to_string()), ("height", &self.height.to_string())])
+            } else {
+                tr("status.idle")
+            };
+            tr_args("tray.status", &[("state", &state), ("nodes", &self.nodes.to_string())])
+        }
+
+        /// Color of the icon: blue when mining, yellow when syncing or alone, green when all is fine
+        fn color(&self) -> [u8; 3] {
+            if self.mining && !self.paused {
+                [0x32, 0x73, 0xdc]
+            } else if self.syncing || self.nodes == 0 {
+                [0xff, 0xb7, 0x0f]
+            } else {
+                [0x48, 0xc7, 0x74]
+            }
+        }
+    }
+
+    pub struct Tray {
+        context: Arc<Mutex<Context>>,
+        icon: TrayIcon,
+        status_item: MenuItem,
+        open_item: MenuItem,
+        pause_item: MenuItem,
+        copy_item: MenuItem,
+        quit_item: MenuItem,
+        status: Arc<Mutex<NodeStatus>>,
+        shown: NodeStatus,
+        clipboard: Option<arboard::Clipboard>
+    }
+
+    impl Tray {
+        /// Shows the icon in tray, returns None if there is no tray in this system
+        pub fn new(context: Arc<Mutex<Context>>) -> Option<Tray> {
+            #[cfg(target_os = "linux")]
+            if let Err(e) = gtk::init() {
+                warn!("Unable to show tray icon: {}", e);
+                return None;
+            }
+            let status_item = MenuItem::new(tr("status.idle"), false, None);
+            let open_item = MenuItem::new(tr("tray.open"), true, None);
+            let pause_item = MenuItem::new(tr("tray.pause"), false, None);
+            let copy_item = MenuItem::new(tr("tray.copy_key"), true, None);
+            let quit_item = MenuItem::new(tr("tray.quit"), true, None);
+            let menu = Menu::new();
+            let separator = PredefinedMenuItem::separator();
+            if let Err(e) = menu.append_items(&[&status_item, &separator, &open_item, &pause_item, &copy_item, &quit_item]) {
+                warn!("Unable to make tray menu: {}", e);
+                return None;
+            }
+            let shown = NodeStatus::default();
+            let icon = TrayIconBuilder::new()
+                .with_menu(Box::new(menu))
+                .with_tooltip(&format!("ALFIS: {}", shown.text()))
+                .with_icon(make_icon(shown.color()))
+                .build();
+            let icon = match icon {
+                Ok(icon) => icon,
+                Err(e) => {
+                    warn!("Unable to show tray icon: {}", e);
+                    return None;
+                }
+            };
+
+            let status = Arc::new(Mutex::new(NodeStatus::default()));
+            let status_copy = Arc::clone(&status);
+            register(move |_uuid, e| {
+                let mut status = status_copy.lock().unwrap();
+                match e {
+                    Event::SyncProgress { have, height } => {
+                        status.syncing = true;
+                        status.have = have;
+                        status.height = height;
+                    }
+                    Event::SyncFinished => status.syncing = false,
+                    Event::NetworkStatus { nodes, .. } => status.nodes = nodes,
+                    Event::MinerStarted | Event::KeyGeneratorStarted => {
+                        status.mining = true;
+                        status.paused = false;
+                    }
+                    Event::MinerStopped { .. } | Event::KeyGeneratorStopped => status.mining = false,
+                    Event::MinerPaused { paused } => status.paused = paused,
+                    Event::ActionQuit => return false,
+                    _ => {}
+                }
+                true
+            });
+
+            let clipboard = arboard::Clipboard::new().map_err(|e| warn!("Clipboard is not available: {}", e)).ok();
+            Some(Tray { context, icon, status_item, open_item, pause_item, copy_item, quit_item, status, shown, clipboard })
+        }
+
+        /// Updates the icon by node status and handles clicks in the menu
+        pub fn poll(&mut self) -> Option<TrayAction> {
+            let status = self.status.lock().unwrap().clone();
+            if status != self.shown {
+                let text = status.text();
+                let _ = self.icon.set_tooltip(Some(format!("ALFIS: {}", &text)));
+                let _ = self.icon.set_icon(Some(make_icon(status.color())));
+                self.status_item.set_text(&text);
+                self.pause_item.set_enabled(status.mining);
+                self.pause_item.set_text(tr(if status.paused { "tray.resume" } else { "tray.pause" }));
+                self.shown = status;
+            }
+
+            while let Ok(event) = MenuEvent::receiver().try_recv() {
+                if event.id == *self.open_item.id() {
+                    return Some(TrayAction::Open);
+                } else if event.id == *self.quit_item.id() {
+                    return Some(TrayAction::Quit);
+                } else if event.id == *self.pause_item.id() {
+                    post(if self.shown.paused { Event::ActionResumeMining } else { Event::ActionPauseMining });
+                } else if event.id == *self.copy_item.id() {
+                    self.copy_public_key();
+                }
+            }
+            None
+        }
+
+        /// Keeps the node working while the main window is closed, returns false if user wants to quit
+        pub fn wait_for_open(&mut self) -> bool {
+            info!("Main window is closed, ALFIS works in tray");
+            loop {
+                pump_events();
+                match self.poll() {
+                    Some(TrayAction::Open) => return true,
+                    Some(TrayAction::Quit) => return false,
+                    None => thread::sleep(WAIT_PAUSE)
+                }
+            }
+        }
+
+        fn copy_public_key(&mut self) {
+            let public = match self.context.lock().unwrap().get_keystore() {
+                Some(keystore) => keystore.get_public().to_string(),
+                None => return
+            };
+            // Clipboard keeps the text only while it is alive on some systems, so we don't drop it
+            if let Some(clipboard) = self.clipboard.as_mut() {
+                if let Err(e) = clipboard.set_text(public) {
+                    warn!("Unable to copy public key: {}", e);
+                }
+            }
+        }
+    }
+
+    /// Makes round icon of this color
+    fn make_icon(color: [u8; 3]) -> Icon {
+        let mut rgba = Vec::with_capacity((ICON_SIZE * ICON_SIZE * 4) as usize);
+        let center = ICON_SIZE as f32 / 2.0 - 0.5;
+        let radius = ICON_SIZE as f32 / 2.0 - 1.0;
+        for y in 0..ICON_SIZE {
+            for x in 0..ICON_SIZE {
+                let distance = ((x as f32 - center).powi(2) + (y as f32 - center).powi(2)).sqrt();
+                // Smooth edge of one pixel
+                let alpha = (radius - distance + 0.5).clamp(0.0, 1.0);
+                rgba.extend_from_slice(&color);
+                rgba.push((alpha * 255.0) as u8);
+            }
+        }
+        Icon::from_rgba(rgba, ICON_SIZE, ICON_SIZE).expect("Wrong tray icon")
+    }
+
+    /// Tray needs the event loop of the system, the main window runs it when it is open
+    #[cfg(target_os = "linux")]
+    fn pump_events() {
+        while gtk::events_pending() {
+            gtk::main_iteration_do(false);
+        }
+    }
+
+    #[cfg(windows)]
+    fn pump_events() {
+        use winapi::um::winuser::{DispatchMessageW, PeekMessageW, TranslateMessage, MSG, PM_REMOVE};
+
+        unsafe {
+            let mut msg: MSG = std::mem::zeroed();
+            while PeekMessageW(&mut msg, std::ptr::null_mut(), 0, 0, PM_REMOVE) != 0 {
+                TranslateMessage(&msg);
+                DispatchMessageW(&msg);
+            }
+        }
+    }
+
+    #[cfg(not(any(target_os = "linux", windows)))]
+    fn pump_events() {}
+}
+
+/// Without "tray" feature there is no icon, and closing the window stops the node
+#[cfg(not(feature = "tray"))]
+mod stub {
+    use std::sync::{Arc, Mutex};
+
+    use alfis_core::Context;
+
+    use super::TrayAction;
+
+    pub struct Tray;
+
+    impl Tray {
+        pub fn new(_context: Arc<Mutex<Context>>) -> Option<Tray> {
+            None
+        }
+
+        pub fn poll(&mut self) -> Option<TrayAction> {
+            None
+        }
+
+        pub fn wait_for_open(&mut self) -> bool {
+            false
+        }
+    }
+}
@@ -4,76 +4,136 @@ extern crate serde_json;
 extern crate tinyfiledialogs as tfd;
 extern crate web_view;
 
+use std::fs;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::{Arc, Mutex, MutexGuard};
 use std::thread;
 use std::time::{Duration, Instant};
 
-use alfis::blockchain::transaction::DomainData;
-use alfis::blockchain::types::MineResult;
-use alfis::commons::*;
-use alfis::crypto::CryptoBox;
-use alfis::dns::protocol::DnsRecord;
-use alfis::event::Event;
-use alfis::eventbus::{post, register};
-use alfis::miner::Miner;
-use alfis::{keystore, Block, Bytes, Context, Keystore, Transaction};
+use alfis_core::blockchain::transaction::DomainData;
+use alfis_core::blockchain::types::MineResult;
+use alfis_core::commons::*;
+use alfis_core::dns::protocol::DnsRecord;
+use alfis_core::event::Event;
+use alfis_core::eventbus::{post, register};
+use alfis_core::miner::Miner;
+use alfis_core::qr::QrPayload;
+use alfis_core::watch::get_conflict_domain_name;
+use alfis_core::keystore::key_fingerprint;
+use alfis_core::{keychain, keystore, Block, Bytes, Context, Keystore, Transaction};
 use chrono::{DateTime, Local, Utc};
 #[allow(unused_imports)]
 use log::{debug, error, info, trace, warn, LevelFilter};
+use qrcode::render::svg;
+use qrcode::QrCode;
 use serde::{Deserialize, Serialize};
 use web_view::Content;
 use Cmd::*;
 
 use self::web_view::{Handle, WebView};
+use crate::i18n::{self, tr, tr_args};
+use crate::tray::{Tray, TrayAction};
 
-pub fn run_interface(context: Arc<Mutex<Context>>, miner: Arc<Mutex<Miner>>) {
+pub fn run_interface(context: Arc<Mutex<Context>>, miner: Arc<Mutex<Miner>>, config_name: &str) {
+    let title = format!("ALFIS {}", env!("CARGO_PKG_VERSION"));
+    i18n::set_language(&context.lock().unwrap().settings.language);
+    let mut tray = Tray::new(Arc::clone(&context));
+    loop {
+        // Listeners of events that this window registers stop when it is closed
+        let window_open = Arc::new(AtomicBool::new(true));
+        let mut interface = build_interface(&title, &context, &miner, config_name, &window_open);
+        let quit = run_interface_loop(&mut interface, &mut tray);
+        window_open.store(false, Ordering::Relaxed);
+        drop(interface);
+        if quit {
+            break;
+        }
+        // The window is closed to the tray, the node keeps working until user opens the window or quits
+        let reopen = match tray.as_mut() {
+            Some(tray) => tray.wait_for_open(),
+            None => false
+        };
+        if !reopen {
+            break;
+        }
+    }
+    info!("Interface closed, exiting");
+    post(Event::ActionQuit);
+    thread::sleep(Duration::from_millis(100));
+}
+
+fn build_interface<'a>(title: &'a str, context: &'a Arc<Mutex<Context>>, miner: &'a Arc<Mutex<Miner>>, config_name: &'a str, window_open: &'a Arc<AtomicBool>) -> WebView<'a, ()> {
     let file_content = include_str!("webview/index.html");
     let mut styles = inline_style(include_str!("webview/bulma.css"));
     styles.push_str(&inline_style(include_str!("webview/styles.css")));
     styles.push_str(&inline_style(include_str!("webview/busy_indicator.css")));
-    let scripts = inline_script(include_str!("webview/scripts.js"));
+    // Texts are needed before the page is shown, so they are given right in the page
+    let language = escape_js(&context.lock().unwrap().settings.language);
+    let mut scripts = inline_script(&format!("var strings = {}; var languages = {}; var language = '{}';", i18n::strings_json(), i18n::languages_json(), language));
+    scripts.push_str(&inline_script(include_str!("webview/scripts.js")));
 
     let html = Content::Html(file_content.to_owned().replace("{styles}", &styles).replace("{scripts}", &scripts));
-    let title = format!("ALFIS {}", env!("CARGO_PKG_VERSION"));
-    let mut interface = web_view::builder()
-        .title(&title)
+    web_view::builder()
+        .title(title)
         .content(html)
         .size(1023, 720)
         .min_size(773, 350)
         .resizable(true)
         .debug(false)
         .user_data(())
-        .invoke_handler(|web_view, arg| {
+        .invoke_handler(move |web_view, arg| {
             debug!("Command {}", arg);
             match serde_json::from_str(arg).unwrap() {
-                Loaded => { action_loaded(&context, web_view); }
-                LoadKey => { action_load_key(&context, web_view); }
-                CreateKey => { keystore::create_key(Arc::clone(&context)); }
-                SaveKey => { action_save_key(&context); }
-                SelectKey { index } => { action_select_key(&context, web_view, index); }
-                CheckRecord { data } => { action_check_record(web_view, data); }
-                CheckDomain { name } => { action_check_domain(&context, web_view, name); }
+                Loaded => { action_loaded(context, web_view, window_open); }
+                LoadKey => { action_load_key(context, web_view); }
+                CreateKey => { keystore::create_key(Arc::clone(context)); }
+                SaveKey => { action_save_key(context); }
+                SelectKey { index } => { action_select_key(context, web_view, index); }
+                Fingerprint { key } => {
+                    let _ = web_view.eval(&format!("ownerFingerprint('{}');", fingerprint(&key)));
+                }
+                RemoveKey { index } => { action_remove_key(context, web_view, index); }
+                ShowMnemonic => { action_show_mnemonic(context); }
+                ExportKey => { action_export_key(context, web_view); }
+                RestoreKey => { action_restore_key(context, web_view); }
+                RotateKey { name } => { action_rotate_key(context, miner, web_view, name); }
+                DeriveDomainKey => {
+                    if let Some(domain) = tfd::input_box(&tr("dialog.domain_key"), &tr("dialog.domain_key_text"), "") {
+                        if !domain.trim().is_empty() {
+                            keystore::create_domain_key(Arc::clone(context), domain.trim());
+                        }
+                    }
+                }
+                CheckRecord { data, zone } => { action_check_record(context, web_view, data, zone); }
+                CheckDomain { name } => { action_check_domain(context, web_view, name); }
                 MineDomain { name, data, signing, encryption, renewal } => {
-                    action_create_domain(Arc::clone(&context), Arc::clone(&miner), web_view, name, data, signing, encryption, renewal);
+                    action_create_domain(Arc::clone(context), Arc::clone(miner), web_view, name, data, signing, encryption, renewal);
                 }
                 TransferDomain { .. } => {}
+                ExportDomain { name, data } => { action_export_domain(context, web_view, name, data); }
+                ExportDomainQr { name, data } => { action_export_domain_qr(context, web_view, name, data); }
+                MineTransaction => { action_mine_transaction(miner, web_view); }
+                MineTransactionQr => { action_mine_transaction_qr(miner, web_view); }
+                ShowKeyQr => { action_show_key_qr(context, web_view); }
+                PasteOwnerQr => { action_paste_owner_qr(web_view); }
+                SetLanguage { code } => { action_set_language(context, web_view, config_name, code); }
                 StopMining => { post(Event::ActionStopMining); }
+                PauseMining => { post(Event::ActionPauseMining); }
+                ResumeMining => { post(Event::ActionResumeMining); }
                 Open { link } => {
                     if open::that(&link).is_err() {
-                        show_warning(web_view, "Something wrong, I can't open the link 😢");
+                        show_warning(web_view, &tr("warning.open_link"));
                     }
                 }
             }
             Ok(())
         })
         .build()
-        .expect("Error building GUI");
-
-    run_interface_loop(&mut interface);
+        .expect("Error building GUI")
 }
 
-/// Indefinitely loops through WebView steps
-fn run_interface_loop(interface: &mut WebView<()>) {
+/// Indefinitely loops through WebView steps, returns true if user wants to quit, and false if the window is closed to the tray
+fn run_interface_loop(interface: &mut WebView<()>, tray: &mut Option<Tray>) -> bool {
     // We use this ugly loop to lower CPU usage a lot.
     // If we use .run() or only .step() in a loop without sleeps it will try
     // to support 60FPS and uses more CPU than it should.
@@ -81,22 +141,21 @@ fn run_interface_loop(interface: &mut WebView<()>) {
     let mut start = Instant::now();
     loop {
         match interface.step() {
-            None => {
-                info!("Interface closed, exiting");
-                post(Event::ActionQuit);
-                thread::sleep(Duration::from_millis(100));
-                break;
-            }
+            None => return tray.is_none(),
             Some(result) => {
                 match result {
                     Ok(_) => {}
                     Err(_) => {
                         error!("Something wrong with webview, exiting");
-                        break;
+                        return true;
                     }
                 }
             }
         }
+        if let Some(TrayAction::Quit) = tray.as_mut().and_then(|tray| tray.poll()) {
+            interface.exit();
+            return true;
+        }
         if start.elapsed().as_millis() > 1 {
             thread::sleep(pause);
             start = Instant::now();
@@ -104,21 +163,32 @@ fn run_interface_loop(interface: &mut WebView<()>) {
     }
 }
 
-fn action_check_record(web_view: &mut WebView<()>, data: String) {
-    match serde_json::from_str::<DnsRecord>(&data) {
-        Ok(record) => {
-            if let Some(string) = record.get_data() {
-                if string.len() > MAX_DATA_LEN {
-                    web_view.eval("recordOkay(false)").expect("Error evaluating!");
-                } else {
-                    web_view.eval("recordOkay(true)").expect("Error evaluating!");
-                }
-            }
-        }
+fn action_check_record(context: &Arc<Mutex<Context>>, web_view: &mut WebView<()>, data: String, zone: String) {
+    let record = match serde_json::from_str::<DnsRecord>(&data) {
+        Ok(record) => record,
         Err(e) => {
-            web_view.eval("recordOkay(false)").expect("Error evaluating!");
-            dbg!(e);
+            debug!("Wrong record {}: {}", &data, e);
+            web_view.eval(&format!("recordError('data', '{}')", escape_js(&tr("record.invalid")))).expect("Error evaluating!");
+            return;
         }
+    };
+    let yggdrasil_only = context.lock().unwrap().chain.get_zones().iter().any(|z| z.name == zone && z.yggdrasil);
+    match check_record(&record, yggdrasil_only) {
+        Ok(()) => web_view.eval("recordOkay(true)").expect("Error evaluating!"),
+        Err(e) => web_view.eval(&format!("recordError('{}', '{}')", e.field, escape_js(&e.message))).expect("Error evaluating!")
+    }
+}
+
+/// Changes language of the interface and saves it to settings, empty code means language of the system
+fn action_set_language(context: &Arc<Mutex<Context>>, web_view: &mut WebView<()>, config_name: &str, code: String) {
+    i18n::set_language(&code);
+    let mut c = context.lock().unwrap();
+    c.settings.language = code.clone();
+    let saved = c.settings.save(config_name);
+    drop(c);
+    let _ = web_view.eval(&format!("languageChanged('{}', '{}');", escape_js(&i18n::strings_json()), escape_js(&code)));
+    if !saved {
+        show_warning(web_view, &tr("warning.save_settings"));
     }
 }
 
@@ -138,7 +208,7 @@ fn action_save_key(context: &Arc<Mutex<Context>>) {
     if !context.lock().unwrap().has_keys() {
         return;
     }
-    let result = tfd::save_file_dialog_with_filter("Save keys file", "", &["*.toml"], "Key files (*.toml)");
+    let result = tfd::save_file_dialog_with_filter(&tr("dialog.save_key"), "", &["*.toml"], &format!("{} (*.toml)", tr("dialog.key_files")));
     match result {
         None => {}
         Some(mut new_path) => {
@@ -146,10 +216,14 @@ fn action_save_key(context: &Arc<Mutex<Context>>) {
                 new_path.push_str(".toml");
             }
             let path = new_path.clone();
+            let password = ask_password(&tr("dialog.password_new")).unwrap_or_default();
+            if !password.is_empty() && context.lock().unwrap().settings.use_keychain {
+                keychain::save_password(&new_path, &password);
+            }
             if let Some(keystore) = context.lock().unwrap().get_keystore_mut() {
                 let public = keystore.get_public().to_string();
                 let hash = keystore.get_hash().to_string();
-                keystore.save(&new_path, "");
+                keystore.save(&new_path, &password);
                 info!("Key file saved to {}", &path);
                 post(Event::KeySaved { path, public, hash });
             }
@@ -157,6 +231,11 @@ fn action_save_key(context: &Arc<Mutex<Context>>) {
     }
 }
 
+/// Shows dialog to enter password for key file
+pub fn ask_password(message: &str) -> Option<String> {
+    tfd::password_box("ALFIS", message)
+}
+
 fn action_select_key(context: &Arc<Mutex<Context>>, web_view: &mut WebView<()>, index: usize) {
     if context.lock().unwrap().select_key_by_index(index) {
         let (path, public, hash) = {
@@ -171,16 +250,151 @@ fn action_select_key(context: &Arc<Mutex<Context>>, web_view: &mut WebView<()>,
     }
 }
 
+/// Unloads the key, it is not deleted from disk
+fn action_remove_key(context: &Arc<Mutex<Context>>, web_view: &mut WebView<()>, index: usize) {
+    let mut c = context.lock().unwrap();
+    if let Some(keystore) = c.remove_keystore(index) {
+        info!("Key {} removed", keystore.get_name());
+        send_keys_to_ui(&c, &web_view.handle());
+        match c.get_keystore() {
+            Some(keystore) => {
+                let path = keystore.get_path().to_owned();
+                let public = keystore.get_public().to_string();
+                let hash = keystore.get_hash().to_string();
+                post(Event::KeyLoaded { path, public, hash });
+            }
+            None => {
+                let _ = web_view.eval("keystoreChanged('', '', '', '');");
+            }
+        }
+    }
+}
+
+fn action_show_mnemonic(context: &Arc<Mutex<Context>>) {
+    let keystore = match context.lock().unwrap().get_keystore() {
+        Some(keystore) => keystore.clone(),
+        None => return
+    };
+    let mut text = tr_args("dialog.mnemonic_text", &[("words", &keystore.get_mnemonic())]);
+    if !keystore.is_mnemonic_complete() {
+        text.push_str("\n\n");
+        text.push_str(&tr("dialog.mnemonic_old"));
+    }
+    tfd::message_box_ok(&tr("dialog.mnemonic"), &text, tfd::MessageBoxIcon::Info);
+}
+
+fn action_export_key(context: &Arc<Mutex<Context>>, web_view: &mut WebView<()>) {
+    let keystore = match context.lock().unwrap().get_keystore() {
+        Some(keystore) => keystore.clone(),
+        None => return
+    };
+    let mut path = match tfd::save_file_dialog_with_filter(&tr("dialog.export_key"), "", &["*.json"], &format!("{} (*.json)", tr("dialog.key_containers"))) {
+        Some(path) => path,
+        None => return
+    };
+    if !path.ends_with(".json") {
+        path.push_str(".json");
+    }
+    let password = ask_password(&tr("dialog.password_export")).unwrap_or_default();
+    if password.is_empty() {
+        show_warning(web_view, &tr("warning.export_password"));
+        return;
+    }
+    match fs::write(&path, keystore.export_json(&password)) {
+        Ok(_) => event_info(web_view, &tr("event.keys_exported")),
+        Err(e) => {
+            error!("Error exporting keys to {}: {}", &path, e);
+            show_warning(web_view, &tr("warning.save_key"));
+        }
+    }
+}
+
+fn action_restore_key(context: &Arc<Mutex<Context>>, web_view: &mut WebView<()>) {
+    let words = match tfd::input_box(&tr("dialog.restore_key"), &tr("dialog.restore_key_text"), "") {
+        Some(words) => words,
+        None => return
+    };
+    match Keystore::from_mnemonic(&words) {
+        Some(keystore) => {
+            let public = keystore.get_public().to_string();
+            let hash = keystore.get_hash().to_string();
+            let mut c = context.lock().unwrap();
+            if !c.select_key_by_public(&keystore.get_public()) {
+                c.add_keystore(keystore);
+            }
+            drop(c);
+            post(Event::KeyLoaded { path: String::new(), public, hash });
+            event_info(web_view, &tr("event.key_restored"));
+        }
+        None => show_warning(web_view, &tr("warning.wrong_words"))
+    }
+}
+
+/// Moves the domain to another key, if current key may be exposed
+fn action_rotate_key(context: &Arc<Mutex<Context>>, miner: &Arc<Mutex<Miner>>, web_view: &mut WebView<()>, name: String) {
+    let file_name = match tfd::open_file_dialog(&tr("dialog.rotate_key"), "", Some((&["*.key", "*.toml"], &tr("dialog.key_files")))) {
+        Some(file_name) => file_name,
+        None => return
+    };
+    let password = match Keystore::is_encrypted_file(&file_name) {
+        true => ask_password(&tr_args("dialog.password_key", &[("file", &file_name)])).unwrap_or_default(),
+        false => String::new()
+    };
+    let keystore = match Keystore::from_file(&file_name, &password) {
+        Some(keystore) => keystore,
+        None => {
+            show_warning(web_view, &tr("warning.load_key"));
+            return;
+        }
+    };
+    let name = name.to_lowercase();
+    match miner.lock().unwrap().rotate_key(&name, &keystore) {
+        MineResult::Fine => {
+            // We keep the new key loaded, but the old one stays selected until the domain is moved
+            let mut c = context.lock().unwrap();
+            if c.get_keystore_by_public(&keystore.get_public()).is_none() {
+                c.keystores.push(keystore);
+                send_keys_to_ui(&c, &web_view.handle());
+            }
+            drop(c);
+            let _ = web_view.eval("domainMiningStarted();");
+            event_info(web_view, &tr_args("event.rotation_started", &[("domain", &name)]));
+        }
+        MineResult::WrongName => show_warning(web_view, &tr("warning.domain_not_found")),
+        MineResult::NotOwned => show_warning(web_view, &tr("warning.owner_not_loaded")),
+        _ => show_warning(web_view, &tr("warning.wrong_data_move"))
+    }
+}
+
 fn action_load_key(context: &Arc<Mutex<Context>>, web_view: &mut WebView<()>) {
-    let result = tfd::open_file_dialog("Open keys file", "", Some((&["*.key", "*.toml"], "Key files")));
+    let result = tfd::open_file_dialog(&tr("dialog.open_key"), "", Some((&["*.key", "*.toml", "*.json"], &tr("dialog.key_files"))));
     match result {
         None => {}
         Some(file_name) => {
-            match Keystore::from_file(&file_name, "") {
+            let keystore = if file_name.ends_with(".json") {
+                let json = fs::read_to_string(&file_name).unwrap_or_default();
+                let password = ask_password(&tr_args("dialog.password_file", &[("file", &file_name)])).unwrap_or_default();
+                Keystore::import_json(&json, &password)
+            } else {
+                let use_keychain = context.lock().unwrap().settings.use_keychain;
+                let password = match Keystore::is_encrypted_file(&file_name) {
+                    true => match use_keychain.then(|| keychain::get_password(&file_name)).flatten() {
+                        Some(password) => password,
+                        None => ask_password(&tr_args("dialog.password_key", &[("file", &file_name)])).unwrap_or_default()
+                    },
+                    false => String::new()
+                };
+                let keystore = Keystore::from_file(&file_name, &password);
+                if keystore.is_some() && use_keychain && !password.is_empty() {
+                    keychain::save_password(&file_name, &password);
+                }
+                keystore
+            };
+            match keystore {
                 None => {
                     error!("Error loading keystore '{}'!", &file_name);
-                    show_warning(web_view, "Error loading key!<br>Key cannot be loaded or its difficulty is not enough.");
-                    event_fail(web_view, &format!("Error loading key from \\'{}\\'!", &file_name));
+                    show_warning(web_view, &tr("warning.load_key"));
+                    event_fail(web_view, &tr_args("event.load_key_failed", &[("file", &file_name)]));
                 }
                 Some(keystore) => {
                     info!("Loaded keystore with keys: {:?}, {:?}", &keystore.get_public(), &keystore.get_encryption_public());
@@ -200,21 +414,20 @@ fn action_load_key(context: &Arc<Mutex<Context>>, web_view: &mut WebView<()>) {
     }
 }
 
-fn action_loaded(context: &Arc<Mutex<Context>>, web_view: &mut WebView<()>) {
+fn action_loaded(context: &Arc<Mutex<Context>>, web_view: &mut WebView<()>, window_open: &Arc<AtomicBool>) {
     info!("Interface loaded");
     web_view.eval("showMiningIndicator(false, false);").expect("Error evaluating!");
     let handle: Handle<()> = web_view.handle();
-    let threads = context.lock().unwrap().settings.mining.threads;
-    let threads = match threads {
-        0 => num_cpus::get(),
-        _ => threads
-    };
-    let status = Arc::new(Mutex::new(UiStatus::new(threads)));
+    let status = Arc::new(Mutex::new(UiStatus::new()));
     let context_copy = Arc::clone(context);
     let c = context.lock().unwrap();
+    let window_open = Arc::clone(window_open);
 
     register(move |_uuid, e| {
         //debug!("Got event from bus {:?}", &e);
+        if !window_open.load(Ordering::Relaxed) {
+            return false;
+        }
         let status = Arc::clone(&status);
         let handle = handle.clone();
         let context_copy = Arc::clone(&context_copy);
@@ -225,108 +438,135 @@ fn action_loaded(context: &Arc<Mutex<Context>>, web_view: &mut WebView<()>) {
                 Event::KeyCreated { path, public, hash } => {
                     load_domains(&mut context, &handle);
                     send_keys_to_ui(&context, &handle);
-                    event_handle_luck(&handle, "Key successfully created! Don\\'t forget to save it!");
-                    let mut s = format!("keystoreChanged('{}', '{}', '{}');", &path, &public, &hash);
-                    s.push_str(" showSuccess('New key mined successfully! Save it to a safe place!')");
+                    event_handle_luck(&handle, &tr("event.key_created"));
+                    let mut s = format!("keystoreChanged('{}', '{}', '{}', '{}');", escape_js(&path), &public, &hash, fingerprint(&public));
+                    s.push_str(&format!(" showSuccess('{}')", escape_js(&tr("success.key_created"))));
                     s
                 }
                 Event::KeyLoaded { path, public, hash } |
                 Event::KeySaved { path, public, hash } => {
                     load_domains(&mut context, &handle);
                     send_keys_to_ui(&context, &handle);
-                    format!("keystoreChanged('{}', '{}', '{}');", &path, &public, &hash)
+                    format!("keystoreChanged('{}', '{}', '{}', '{}');", escape_js(&path), &public, &hash, fingerprint(&public))
                 }
                 Event::MinerStarted | Event::KeyGeneratorStarted => {
                     status.mining = true;
                     status.max_diff = 0;
-                    event_handle_info(&handle, "Mining started");
-                    String::from("setLeftStatusBarText('Mining...'); showMiningIndicator(true, false);")
+                    event_handle_info(&handle, &tr("event.mining_started"));
+                    format!("{} showMiningIndicator(true, false);", set_status(&tr("status.mining")))
                 }
                 Event::MinerStopped { success, full } => {
                     status.mining = false;
                     status.max_diff = 0;
                     let mut s = if status.syncing {
-                        String::from("setLeftStatusBarText('Syncing...'); showMiningIndicator(true, true);")
+                        format!("{} showMiningIndicator(true, true);", set_status(&tr("status.syncing")))
                     } else {
-                        String::from("setLeftStatusBarText('Idle'); showMiningIndicator(false, false);")
+                        format!("{} showMiningIndicator(false, false);", set_status(&tr("status.idle")))
                     };
                     if full {
                         match success {
                             true => {
                                 load_domains(&mut context, &handle);
-                                event_handle_luck(&handle, "Mining is successful!");
-                                s.push_str(" showSuccess('Block successfully mined!')");
+                                event_handle_luck(&handle, &tr("event.mining_success"));
+                                s.push_str(&format!(" showSuccess('{}')", escape_js(&tr("success.block_mined"))));
                             }
                             false => {
-                                event_handle_info(&handle, "Mining finished without result.");
-                                s.push_str(" showWarning('Mining unsuccessful, sorry.')");
+                                event_handle_info(&handle, &tr("event.mining_failed"));
+                                s.push_str(&format!(" showWarning('{}')", escape_js(&tr("warning.mining_failed"))));
                             }
                         }
                     }
                     s
                 }
-                Event::MinerStats { thread, speed, max_diff, target_diff } => {
+                Event::MinerStats { max_diff, .. } => {
                     if status.max_diff < max_diff {
                         status.max_diff = max_diff;
                     }
-                    status.set_thread_speed(thread, speed);
-                    if thread as usize == threads - 1 {
-                        format!("setLeftStatusBarText('Mining speed {} H/s, max found difficulty {}/{}.'); showMiningIndicator(true, false);", status.get_speed(), status.max_diff, target_diff)
+                    String::new()
+                }
+                Event::MinerProgress { speed, target_diff, eta } => {
+                    let text = tr_args("status.mining_speed", &[
+                        ("speed", &speed.to_string()),
+                        ("difficulty", &status.max_diff.to_string()),
+                        ("target", &target_diff.to_string()),
+                        ("time", &format_cooldown(eta as i64))
+                    ]);
+                    format!("{} showMiningIndicator(true, false);", set_status(&text))
+                }
+                Event::MinerPaused { paused } => {
+                    if paused {
+                        event_handle_info(&handle, &tr("status.mining_paused"));
+                        format!("{} setMiningPaused(true);", set_status(&tr("status.mining_paused")))
                     } else {
-                        String::new()
+                        event_handle_info(&handle, &tr("event.mining_resumed"));
+                        format!("{} setMiningPaused(false);", set_status(&tr("status.mining")))
                     }
                 }
                 Event::KeyGeneratorStopped => {
                     status.mining = false;
                     if status.syncing {
-                        String::from("setLeftStatusBarText('Syncing...'); showMiningIndicator(true, true);")
+                        format!("{} showMiningIndicator(true, true);", set_status(&tr("status.syncing")))
                     } else {
-                        String::from("setLeftStatusBarText('Idle'); showMiningIndicator(false, false);")
+                        format!("{} showMiningIndicator(false, false);", set_status(&tr("status.idle")))
                     }
                 }
-                Event::Syncing { have, height } => {
+                Event::SyncProgress { have, height } => {
                     status.syncing = true;
                     status.synced_blocks = have;
                     if height != status.sync_height {
-                        event_handle_info(&handle, "Syncing started...");
+                        event_handle_info(&handle, &tr("event.sync_started"));
                         status.sync_height = height;
                     }
                     if status.mining {
-                        String::from("setLeftStatusBarText('Mining...'); showMiningIndicator(true, false);")
+                        format!("{} showMiningIndicator(true, false);", set_status(&tr("status.mining")))
                     } else {
-                        format!("setLeftStatusBarText('Synchronizing {}/{}'); showMiningIndicator(true, true);", have, height)
+                        let text = tr_args("status.synchronizing", &[("have", &have.to_string()), ("height", &height.to_string())]);
+                        format!("{} showMiningIndicator(true, true);", set_status(&text))
                     }
                 }
                 Event::SyncFinished => {
                     load_domains(&mut context, &handle);
-                    event_handle_info(&handle, "Syncing finished.");
+                    event_handle_info(&handle, &tr("event.sync_finished"));
                     status.syncing = false;
                     if status.mining {
-                        String::from("setLeftStatusBarText('Mining...'); showMiningIndicator(true, false);")
+                        format!("{} showMiningIndicator(true, false);", set_status(&tr("status.mining")))
                     } else {
-                        String::from("setLeftStatusBarText('Idle'); showMiningIndicator(false, false);")
+                        format!("{} showMiningIndicator(false, false);", set_status(&tr("status.idle")))
                     }
                 }
                 Event::NetworkStatus { blocks, domains, keys, nodes } => {
                     if status.mining || status.syncing || nodes < 3 {
                         format!("setStats({}, {}, {}, {});", blocks, domains, keys, nodes)
                     } else {
-                        format!("setLeftStatusBarText('Idle'); setStats({}, {}, {}, {});", blocks, domains, keys, nodes)
+                        format!("{} setStats({}, {}, {}, {});", set_status(&tr("status.idle")), blocks, domains, keys, nodes)
                     }
                 }
                 Event::BlockchainChanged { index } => {
                     debug!("Current blockchain height is {}", index);
-                    event_handle_info(&handle, &format!("Blockchain changed, current block count is {} now.", index));
+                    event_handle_info(&handle, &tr_args("event.blockchain_changed", &[("count", &index.to_string())]));
                     String::new() // Nothing
                 }
-                Event::Error { text } => format!("showError('{}')", &text),
+                Event::Error { text } => format!("showError('{}')", escape_js(&text)),
+                Event::DomainExpiring { expire, .. } => {
+                    let days = (expire - Utc::now().timestamp()) / 86400;
+                    format!("showWarning('{}')", escape_js(&tr_args("warning.domain_expiring", &[("days", &days.to_string())])))
+                }
+                Event::ClockSkew { offset } => {
+                    format!("showWarning('{}')", escape_js(&tr_args("warning.clock_skew", &[("seconds", &offset.abs().to_string())])))
+                }
+                Event::DomainConflict { index, identity, key, .. } => {
+                    let name = get_conflict_domain_name(&context, index).unwrap_or(identity);
+                    let text = tr_args("conflict.text", &[("domain", &name), ("key", &key), ("index", &index.to_string())]);
+                    format!("showConflict('{}')", escape_js(&text))
+                }
                 _ => String::new()
             };
 
             if !eval.is_empty() {
-                handle.dispatch(move |web_view| {
-                    web_view.eval(&eval.replace("\\", "\\\\"))
-                }).expect("Error dispatching!");
+                // The window can be closed already
+                let _ = handle.dispatch(move |web_view| {
+                    web_view.eval(&eval)
+                });
             }
         });
         true
@@ -352,7 +592,7 @@ fn action_loaded(context: &Arc<Mutex<Context>>, web_view: &mut WebView<()>) {
     if let Err(e) = web_view.eval(&command) {
         error!("Error evaluating stats: {}", e);
     }
-    event_info(web_view, "Application loaded");
+    event_info(web_view, &tr("event.loaded"));
 }
 
 fn load_domains(context: &mut MutexGuard<Context>, handle: &Handle<()>) {
@@ -364,7 +604,7 @@ fn load_domains(context: &mut MutexGuard<Context>, handle: &Handle<()>) {
     for (_identity, (domain, timestamp, data)) in domains {
         let d = serde_json::to_string(&data).unwrap();
         let d = d.replace("'", "\\'").replace("\\n", "\\\\n");
-        let command = format!("addMyDomain('{}', {}, {}, '{}');", &domain, timestamp, timestamp + DOMAIN_LIFETIME, &d);
+        let command = format!("addMyDomain('{}', {}, {}, '{}');", &domain, timestamp, timestamp + context.chain.get_kind().domain_lifetime(), &d);
         let _ = handle.dispatch(move |web_view|{
             web_view.eval(&command)
         });
@@ -372,6 +612,33 @@ fn load_domains(context: &mut MutexGuard<Context>, handle: &Handle<()>) {
     let _ = handle.dispatch(move |web_view|{
         web_view.eval("refreshMyDomains();")
     });
+    load_portfolio(context, handle);
+}
+
+/// Sends domains of all loaded keys to "My domains" tab
+fn load_portfolio(context: &MutexGuard<Context>, handle: &Handle<()>) {
+    let height = context.chain.get_height();
+    let active = context.get_keystore().map(|keystore| keystore.get_public());
+    let mut domains = Vec::new();
+    for keystore in context.get_keystores() {
+        let public = keystore.get_public();
+        for domain in context.chain.get_domains_by_owner(&public) {
+            let name = String::from_utf8(keystore.decrypt(domain.data.encrypted.as_slice()).to_vec()).unwrap_or_default();
+            domains.push(PortfolioDomainForJS {
+                name: if name.is_empty() { tr("portfolio.unknown") } else { name },
+                key: keystore.get_name(),
+                active: active.as_ref() == Some(&public),
+                confirmations: height.saturating_sub(domain.index) + 1,
+                renewed: domain.renewed,
+                expire: domain.renewed + context.chain.get_kind().domain_lifetime(),
+                data: domain.data
+            });
+        }
+    }
+    let json = serde_json::to_string(&domains).unwrap().replace('\\', "\\\\").replace('\'', "\\'");
+    let _ = handle.dispatch(move |web_view| {
+        web_view.eval(&format!("portfolioChanged('{}');", json))
+    });
 }
 
 fn send_keys_to_ui(context: &MutexGuard<Context>, handle: &Handle<()>) {
@@ -380,17 +647,18 @@ fn send_keys_to_ui(context: &MutexGuard<Context>, handle: &Handle<()>) {
         for key in context.get_keystores() {
             let path = key.get_path().replace("\\", "/");
             let parts: Vec<&str> = path.rsplitn(2, '/').collect();
-            keys.push(KeysForJS { file_name: parts[0].to_owned(), public: key.get_public().to_string() });
+            keys.push(KeysForJS { file_name: parts[0].to_owned(), name: key.get_name(), public: key.get_public().to_string(), fingerprint: key.get_fingerprint() });
         }
         keys
     };
-    if !keys.is_empty() {
-        let index = context.get_active_key_index();
-        let _ = handle.dispatch(move |web_view| {
-            let command = format!("keysChanged('{}'); keySelected({});", serde_json::to_string(&keys).unwrap(), index);
-            web_view.eval(&command)
-        });
-    }
+    let index = match keys.is_empty() {
+        true => -1,
+        false => context.get_active_key_index() as i64
+    };
+    let _ = handle.dispatch(move |web_view| {
+        let command = format!("keysChanged('{}'); keySelected({});", serde_json::to_string(&keys).unwrap(), index);
+        web_view.eval(&command)
+    });
 }
 
 fn action_create_domain(context: Arc<Mutex<Context>>, miner: Arc<Mutex<Miner>>, web_view: &mut WebView<()>, name: String, data: String, signing: String, encryption: String, renewal: bool) {
@@ -398,22 +666,26 @@ fn action_create_domain(context: Arc<Mutex<Context>>, miner: Arc<Mutex<Miner>>,
     let c = Arc::clone(&context);
     let context = context.lock().unwrap();
     if !context.has_keys() {
-        show_warning(web_view, "You don't have keys loaded!<br>Load or mine the keys and try again.");
+        show_warning(web_view, &tr("warning.no_keys"));
         let _ = web_view.eval("domainMiningUnavailable();");
         return;
     }
     if context.chain.is_waiting_signers() {
-        show_warning(web_view, "Waiting for last full block to be signed. Try again later.");
+        show_warning(web_view, &tr("warning.waiting_signers"));
         let _ = web_view.eval("domainMiningUnavailable();");
         info!("Waiting for last full block to be signed. Try again later.");
         return;
     }
-    let keystore = context.get_keystore().unwrap().clone();
+    // If the domain is already ours we mine it with the key that owns it, not with the selected one
+    let keystore = match context.chain.get_domain_transaction_and_state(&name).0 {
+        Some(transaction) => context.get_keystore_by_public(&transaction.signing).or_else(|| context.get_keystore()).unwrap().clone(),
+        None => context.get_keystore().unwrap().clone()
+    };
     let pub_key = keystore.get_public();
     let data = match serde_json::from_str::<DomainData>(&data) {
         Ok(data) => data,
         Err(e) => {
-            show_warning(web_view, "Something wrong with domain data. I cannot mine it.");
+            show_warning(web_view, &tr("warning.wrong_data_mine"));
             let _ = web_view.eval("domainMiningUnavailable();");
             warn!("Error parsing data: {}", e);
             return;
@@ -421,7 +693,12 @@ fn action_create_domain(context: Arc<Mutex<Context>>, miner: Arc<Mutex<Miner>>,
     };
     info!("Parsed domain data:\n{:#?}", &data);
     if data.records.len() > MAX_RECORDS {
-        show_warning(web_view, "Too many records. Mining more than 30 records not allowed.");
+        show_warning(web_view, &tr_args("warning.too_many_records", &[("count", &MAX_RECORDS.to_string())]));
+        let _ = web_view.eval("domainMiningUnavailable();");
+        return;
+    }
+    if data.comments.iter().any(|comment| check_record_comment(comment).is_err()) {
+        show_warning(web_view, &tr_args("warning.wrong_comment", &[("length", &MAX_COMMENT_LEN.to_string())]));
         let _ = web_view.eval("domainMiningUnavailable();");
         return;
     }
@@ -431,7 +708,7 @@ fn action_create_domain(context: Arc<Mutex<Context>>, miner: Arc<Mutex<Miner>>,
         if z.name == data.zone && z.yggdrasil {
             for record in &data.records {
                 if !is_yggdrasil_record(record) {
-                    show_warning(web_view, &format!("Zone {} is Yggdrasil only, you cannot use IPs from clearnet!", &data.zone));
+                    show_warning(web_view, &tr_args("warning.yggdrasil_only", &[("zone", &data.zone)]));
                     let _ = web_view.eval("domainMiningUnavailable();");
                     return;
                 }
@@ -445,52 +722,191 @@ fn action_create_domain(context: Arc<Mutex<Context>>, miner: Arc<Mutex<Miner>>,
     };
     match context.chain.can_mine_domain(context.chain.get_height(), &name, &pub_key) {
         MineResult::Fine => {
+            let difficulty = context.chain.get_domain_difficulty(context.chain.get_height() + 1);
             drop(context);
-            create_domain(c, miner, CLASS_DOMAIN, &name, data, DOMAIN_DIFFICULTY, &keystore, signing, encryption, renewal);
+            create_domain(c, miner, CLASS_DOMAIN, &name, data, difficulty, &keystore, signing, encryption, renewal);
             let _ = web_view.eval("domainMiningStarted();");
-            event_info(web_view, &format!("Mining of domain \\'{}\\' has started", &name));
+            event_info(web_view, &tr_args("event.domain_mining_started", &[("domain", &name)]));
         }
         MineResult::WrongName => {
-            show_warning(web_view, "You can't mine this domain!");
+            show_warning(web_view, &tr("warning.wrong_name"));
             let _ = web_view.eval("domainMiningUnavailable();");
         }
         MineResult::WrongData => {
-            show_warning(web_view, "You have an error in records!");
+            show_warning(web_view, &tr("warning.wrong_records"));
             let _ = web_view.eval("domainMiningUnavailable();");
         }
         MineResult::WrongKey => {
-            show_warning(web_view, "You can't mine with current key!");
+            show_warning(web_view, &tr("warning.wrong_key"));
             let _ = web_view.eval("domainMiningUnavailable();");
         }
         MineResult::WrongZone => {
-            show_warning(web_view, "You can't mine domain in this zone!");
+            show_warning(web_view, &tr("warning.wrong_zone"));
             let _ = web_view.eval("domainMiningUnavailable();");
         }
         MineResult::NotOwned => {
-            show_warning(web_view, "This domain is already taken, and it is not yours!");
+            show_warning(web_view, &tr("warning.not_owned"));
             let _ = web_view.eval("domainMiningUnavailable();");
         }
         MineResult::Cooldown { time } => {
-            event_info(web_view, &format!("You have cooldown {}!", format_cooldown(time)));
-            show_warning(web_view, &format!("You have cooldown {}!", format_cooldown(time)));
+            let text = tr_args("warning.cooldown", &[("time", &format_cooldown(time))]);
+            event_info(web_view, &text);
+            show_warning(web_view, &text);
+            let _ = web_view.eval("domainMiningUnavailable();");
+        }
+        MineResult::Limited => {
+            show_warning(web_view, &tr("warning.registration_limit"));
             let _ = web_view.eval("domainMiningUnavailable();");
         }
     }
 }
 
+/// Saves domain transaction owned by our current key, so that somebody else can mine it for us
+fn action_export_domain(context: &Arc<Mutex<Context>>, web_view: &mut WebView<()>, name: String, data: String) {
+    let transaction = match make_domain_transaction(context, web_view, &name, &data) {
+        Some(transaction) => transaction,
+        None => return
+    };
+    if let Some(mut path) = tfd::save_file_dialog_with_filter(&tr("dialog.save_domain"), "", &["*.json"], &format!("{} (*.json)", tr("dialog.domain_files"))) {
+        if !path.ends_with(".json") {
+            path.push_str(".json");
+        }
+        match fs::write(&path, transaction.to_string()) {
+            Ok(_) => {
+                info!("Domain transaction saved to {}", &path);
+                event_info(web_view, &tr_args("event.domain_saved", &[("domain", &name)]));
+            }
+            Err(e) => {
+                error!("Error saving domain transaction to {}: {}", &path, e);
+                show_warning(web_view, &tr("warning.save_domain"));
+            }
+        }
+    }
+}
+
+/// Shows the same transaction as [action_export_domain] saves, but as QR code
+fn action_export_domain_qr(context: &Arc<Mutex<Context>>, web_view: &mut WebView<()>, name: String, data: String) {
+    if let Some(transaction) = make_domain_transaction(context, web_view, &name, &data) {
+        show_qr(web_view, &tr_args("qr.domain", &[("domain", &name)]), &QrPayload::Transaction(transaction).to_text());
+    }
+}
+
+/// Makes domain transaction signed by nobody yet, a friend will mine it with their key
+fn make_domain_transaction(context: &Arc<Mutex<Context>>, web_view: &mut WebView<()>, name: &str, data: &str) -> Option<Transaction> {
+    let keystore = match context.lock().unwrap().get_keystore() {
+        Some(keystore) => keystore.clone(),
+        None => {
+            show_warning(web_view, &tr("warning.no_keys"));
+            return None;
+        }
+    };
+    let data = match serde_json::from_str::<DomainData>(data) {
+        Ok(data) => data,
+        Err(e) => {
+            show_warning(web_view, &tr("warning.wrong_data_export"));
+            warn!("Error parsing data: {}", e);
+            return None;
+        }
+    };
+    Some(Transaction::from_domain_data(CLASS_DOMAIN, name, data, keystore.get_public(), keystore.get_encryption_public()))
+}
+
+/// Loads domain transaction of somebody else and mines it with our current key
+fn action_mine_transaction(miner: &Arc<Mutex<Miner>>, web_view: &mut WebView<()>) {
+    let path = match tfd::open_file_dialog(&tr("dialog.open_domain"), "", Some((&["*.json"], &tr("dialog.domain_files")))) {
+        Some(path) => path,
+        None => return
+    };
+    let transaction = match fs::read_to_string(&path).ok().and_then(|json| Transaction::from_untrusted_json(&json).ok()) {
+        Some(transaction) => transaction,
+        None => {
+            show_warning(web_view, &tr("warning.load_domain"));
+            return;
+        }
+    };
+    mine_transaction(miner, web_view, transaction);
+}
+
+/// Mines domain transaction of somebody else from the text of QR code
+fn action_mine_transaction_qr(miner: &Arc<Mutex<Miner>>, web_view: &mut WebView<()>) {
+    let text = match tfd::input_box(&tr("dialog.mine_friend"), &tr("dialog.mine_friend_text"), "") {
+        Some(text) => text,
+        None => return
+    };
+    match QrPayload::parse(&text) {
+        Some(QrPayload::Transaction(transaction)) => mine_transaction(miner, web_view, transaction),
+        _ => show_warning(web_view, &tr("warning.not_qr_domain"))
+    }
+}
+
+fn mine_transaction(miner: &Arc<Mutex<Miner>>, web_view: &mut WebView<()>, transaction: Transaction) {
+    match miner.lock().unwrap().add_transaction(transaction) {
+        MineResult::Fine => {
+            let _ = web_view.eval("domainMiningStarted();");
+            event_info(web_view, &tr("event.friend_mining_started"));
+        }
+        MineResult::WrongKey => show_warning(web_view, &tr("warning.no_keys")),
+        MineResult::WrongZone => show_warning(web_view, &tr("warning.wrong_zone")),
+        MineResult::NotOwned => show_warning(web_view, &tr("warning.not_owned_friend")),
+        MineResult::Cooldown { time } => show_warning(web_view, &tr_args("warning.cooldown", &[("time", &format_cooldown(time))])),
+        MineResult::Limited => show_warning(web_view, &tr("warning.registration_limit")),
+        _ => show_warning(web_view, &tr("warning.wrong_data_mine"))
+    }
+}
+
+/// Shows public keys of current key as QR code, to get domains transferred to it
+fn action_show_key_qr(context: &Arc<Mutex<Context>>, web_view: &mut WebView<()>) {
+    let payload = match context.lock().unwrap().get_keystore() {
+        Some(keystore) => QrPayload::Key { signing: keystore.get_public(), encryption: keystore.get_encryption_public() },
+        None => {
+            show_warning(web_view, &tr("warning.no_keys"));
+            return;
+        }
+    };
+    show_qr(web_view, &tr("qr.keys"), &payload.to_text());
+}
+
+/// Takes keys of the new owner from the text of QR code
+fn action_paste_owner_qr(web_view: &mut WebView<()>) {
+    let text = match tfd::input_box(&tr("dialog.new_owner"), &tr("dialog.new_owner_text"), "") {
+        Some(text) => text,
+        None => return
+    };
+    match QrPayload::parse(&text) {
+        Some(QrPayload::Key { signing, encryption }) => {
+            let _ = web_view.eval(&format!("setOwnerKeys('{}', '{}');", signing.to_string(), encryption.to_string()));
+        }
+        _ => show_warning(web_view, &tr("warning.not_qr_keys"))
+    }
+}
+
+fn show_qr(web_view: &mut WebView<()>, title: &str, text: &str) {
+    let code = match QrCode::new(text.as_bytes()) {
+        Ok(code) => code,
+        Err(e) => {
+            warn!("Unable to make QR code: {}", e);
+            show_warning(web_view, &tr("warning.qr_too_big"));
+            return;
+        }
+    };
+    let image = code.render::<svg::Color>().min_dimensions(256, 256).build();
+    let escape = |s: &str| escape_js(s).replace('\n', " ");
+    let _ = web_view.eval(&format!("showQr('{}', '{}', '{}');", escape(title), escape(&image), escape(text)));
+}
+
 fn format_cooldown(time: i64) -> String {
     if time <= 60 {
-        return format!("{} seconds", time);
+        return tr_args("time.seconds", &[("count", &time.to_string())]);
     }
     let minutes = time / 60;
     if minutes <= 60 {
-        return format!("{} minutes", minutes);
+        return tr_args("time.minutes", &[("count", &minutes.to_string())]);
     }
-    format!("{} hours", minutes / 60)
+    tr_args("time.hours", &[("count", &(minutes / 60).to_string())])
 }
 
 fn show_warning(web_view: &mut WebView<()>, text: &str) {
-    let str = text.replace('\'', "\\'");
+    let str = escape_js(text);
     match web_view.eval(&format!("showWarning('{}');", &str)) {
         Ok(_) => {}
         Err(_) => { warn!("Error showing warning!"); }
@@ -499,7 +915,7 @@ fn show_warning(web_view: &mut WebView<()>, text: &str) {
 
 #[allow(dead_code)]
 fn show_success(web_view: &mut WebView<()>, text: &str) {
-    let str = text.replace('\'', "\\'");
+    let str = escape_js(text);
     match web_view.eval(&format!("showSuccess('{}');", &str)) {
         Ok(_) => {}
         Err(_) => { warn!("Error showing success!"); }
@@ -555,27 +971,30 @@ fn event_handle_luck(handle: &Handle<()>, message: &str) {
 
 #[allow(dead_code)]
 fn format_event(kind: &str, time: DateTime<Local>, message: &str) -> String {
-    format!("addEvent('{}', '{}', '{}');", kind, time.format("%d.%m.%y %X"), message)
+    format!("addEvent('{}', '{}', '{}');", kind, time.format("%d.%m.%y %X"), escape_js(message))
 }
 
 fn format_event_now(kind: &str, message: &str) -> String {
-    let time = Local::now();
-    format!("addEvent('{}', '{}', '{}');", kind, time.format("%d.%m.%y %X"), message)
+    format_event(kind, Local::now(), message)
 }
 
-#[allow(clippy::too_many_arguments)]
-fn create_domain(context: Arc<Mutex<Context>>, miner: Arc<Mutex<Miner>>, class: &str, name: &str, mut data: DomainData, difficulty: u32, keystore: &Keystore, signing: Bytes, encryption: Bytes, renewal: bool) {
-    let name = name.to_owned();
-    let encrypted = CryptoBox::encrypt(encryption.as_slice(), name.as_bytes()).expect("Error encrypting domain name!");
-    data.encrypted = Bytes::from_bytes(&encrypted);
+/// Makes text safe to put in quotes of JavaScript string
+pub fn escape_js(text: &str) -> String {
+    text.replace('\\', "\\\\").replace('\'', "\\'")
+}
 
-    let data = serde_json::to_string(&data).unwrap();
+fn set_status(text: &str) -> String {
+    format!("setLeftStatusBarText('{}');", escape_js(text))
+}
+
+#[allow(clippy::too_many_arguments)]
+fn create_domain(context: Arc<Mutex<Context>>, miner: Arc<Mutex<Miner>>, class: &str, name: &str, data: DomainData, difficulty: u32, keystore: &Keystore, signing: Bytes, encryption: Bytes, renewal: bool) {
     let (signing, encryption) = if signing.is_empty() || encryption.is_empty() {
         (keystore.get_public(), keystore.get_encryption_public())
     } else {
         (signing, encryption)
     };
-    let transaction = Transaction::from_str(name, class.to_owned(), data, signing, encryption);
+    let transaction = Transaction::from_domain_data(class, name, data, signing, encryption);
     // If this domain is already in blockchain we approve slightly smaller difficulty
     let height = context.lock().unwrap().chain.get_height();
     let discount = context.lock().unwrap().chain.get_identity_discount(&transaction.identity, renewal, height, Utc::now().timestamp());
@@ -591,11 +1010,27 @@ pub enum Cmd {
     CreateKey,
     SaveKey,
     SelectKey { index: usize },
-    CheckRecord { data: String },
+    RemoveKey { index: usize },
+    Fingerprint { key: String },
+    ShowMnemonic,
+    ExportKey,
+    RestoreKey,
+    DeriveDomainKey,
+    RotateKey { name: String },
+    CheckRecord { data: String, zone: String },
     CheckDomain { name: String },
     MineDomain { name: String, data: String, signing: String, encryption: String, renewal: bool },
     TransferDomain { name: String, owner: String },
+    ExportDomain { name: String, data: String },
+    ExportDomainQr { name: String, data: String },
+    MineTransaction,
+    MineTransactionQr,
+    ShowKeyQr,
+    PasteOwnerQr,
+    SetLanguage { code: String },
     StopMining,
+    PauseMining,
+    ResumeMining,
     Open { link: String }
 }
 
@@ -604,35 +1039,46 @@ struct UiStatus {
     pub syncing: bool,
     pub synced_blocks: u64,
     pub sync_height: u64,
-    pub max_diff: u32,
-    pub speed: Vec<u64>
+    pub max_diff: u32
 }
 
 impl UiStatus {
-    fn new(threads: usize) -> Self {
-        let speed =vec![0; threads];
-        UiStatus { mining: false, syncing: false, synced_blocks: 0, sync_height: 0, max_diff: 0, speed }
-    }
-
-    fn set_thread_speed(&mut self, thread: u32, speed: u64) {
-        self.speed[thread as usize] = speed;
-    }
-
-    fn get_speed(&self) -> u64 {
-        self.speed.iter().sum()
+    fn new() -> Self {
+        UiStatus { mining: false, syncing: false, synced_blocks: 0, sync_height: 0, max_diff: 0 }
     }
 }
 
 #[derive(Serialize)]
 struct KeysForJS {
     file_name: String,
-    public: String
+    name: String,
+    public: String,
+    fingerprint: String
+}
+
+#[derive(Serialize)]
+struct PortfolioDomainForJS {
+    name: String,
+    key: String,
+    active: bool,
+    confirmations: u64,
+    renewed: i64,
+    expire: i64,
+    data: DomainData
+}
+
+/// Makes fingerprint of public key in hex, or empty string if it is not a key
+fn fingerprint(public: &str) -> String {
+    match from_hex(public) {
+        Ok(key) if key.len() == 32 => key_fingerprint(&key),
+        _ => String::new()
+    }
 }
 
-fn inline_style(s: &str) -> String {
+pub fn inline_style(s: &str) -> String {
     format!(r#"<style type="text/css">{}</style>"#, s)
 }
 
-fn inline_script(s: &str) -> String {
+pub fn inline_script(s: &str) -> String {
     format!(r#"<script type="text/javascript">{}</script>"#, s)
 }
\ No newline at end of file
@@ -1,4 +1,6 @@
 var recordsBuffer = [];
+// Comments to records with the same index
+var commentsBuffer = [];
 var ownerSigning = "";
 var ownerEncryption = "";
 var availableZones = [];
@@ -8,6 +10,11 @@ var currentDomain = "";
 var currentSelectedKey = -1;
 var keysLoaded = [];
 var stateMining = false;
+var statePaused = false;
+var portfolioJson = "[]";
+// Same as MAX_RECORD_TTL and MAX_COMMENT_LEN in alfis-core
+var MAX_RECORD_TTL = 604800;
+var MAX_COMMENT_LEN = 100;
 
 document.addEventListener('click', function (event) {
     closeDropdowns();
@@ -23,20 +30,77 @@ function closeDropdowns(except) {
     }
 }
 
-function addRecord(record) {
+// Gets translated text by its id, and puts values from args to its {name} placeholders
+function tr(id, args) {
+    var text = strings[id];
+    if (typeof text === 'undefined') {
+        return id;
+    }
+    for (var name in args) {
+        text = text.replace("{" + name + "}", args[name]);
+    }
+    return text;
+}
+
+// Puts translated texts to all elements that have ids of texts
+function translatePage() {
+    var elements = document.querySelectorAll("[data-i18n]");
+    for (var i = 0; i < elements.length; i++) {
+        elements[i].innerHTML = tr(elements[i].getAttribute("data-i18n"));
+    }
+    elements = document.querySelectorAll("[data-i18n-title]");
+    for (var i = 0; i < elements.length; i++) {
+        elements[i].title = tr(elements[i].getAttribute("data-i18n-title"));
+    }
+    elements = document.querySelectorAll("[data-i18n-placeholder]");
+    for (var i = 0; i < elements.length; i++) {
+        elements[i].placeholder = tr(elements[i].getAttribute("data-i18n-placeholder"));
+    }
+}
+
+function refreshLanguages() {
+    var buf = "<option value=\"\">" + tr("help.language_system") + "</option>";
+    languages.forEach(function(value, index, array) {
+        buf += "<option value=\"{code}\">{name}</option>".replace("{code}", value.code).replace("{name}", value.name);
+    });
+    var select = document.getElementById("language");
+    select.innerHTML = buf;
+    select.value = language;
+}
+
+function setLanguage(code) {
+    external.invoke(JSON.stringify({cmd: 'setLanguage', code: code}));
+}
+
+function languageChanged(json, code) {
+    strings = JSON.parse(json);
+    language = code;
+    translatePage();
+    refreshLanguages();
+    refreshKeysMenu();
+    refreshZonesList();
+    refreshRecordsList();
+    refreshMyDomains();
+    portfolioChanged(portfolioJson);
+    setMiningPaused(statePaused);
+}
+
+function addRecord(record, comment) {
     recordsBuffer.push(record);
+    commentsBuffer[recordsBuffer.length - 1] = comment || "";
     refreshRecordsList();
 }
 
 function delRecord(index) {
     recordsBuffer.splice(index, 1);
+    commentsBuffer.splice(index, 1);
     refreshRecordsList();
 }
 
 function refreshRecordsList() {
     var buf = "";
     if (recordsBuffer.length > 0) {
-        buf = "<label class=\"label\">Records:</label>\n";
+        buf = "<label class=\"label\">" + tr("domains.records") + "</label>\n";
     }
     function getInput(text) {
         if (typeof text === "string"){
@@ -61,7 +125,7 @@ function refreshRecordsList() {
                "<input class=\"input\" type=\"text\" value=\"{1}\" readonly>" +
                "<input class=\"input ml-3 has-text-centered\" type=\"text\" size=\"6\" style=\"width: 20%;\" value=\"{2}\" readonly>" +
                "<input class=\"input ml-3 has-text-centered\" type=\"text\" size=\"6\" style=\"width: 20%;\" value=\"{3}\" readonly>" +
-               "<input class=\"input ml-3\" type=\"text\" value=\"{4}\" readonly>" +
+               "<input class=\"input ml-3\" type=\"text\" value=\"{4}\" title=\"{6}\" readonly>" +
                "<button class=\"button is-danger is-outlined ml-3\" id=\"record_delete\" onclick=\"delRecord({5});\">" +
                "  <span class=\"icon is-small\">" +
                "    <svg viewBox=\"0 0 24 24\" style=\"width: 20px; height: 20px;\"><path d=\"M22.54 16.88L20.41 19L22.54 21.12L21.12 22.54L19 20.41L16.88 22.54L15.47 21.12L17.59 19L15.47 16.88L16.88 15.47L19 17.59L21.12 15.46L22.54 16.88M12 13C10.9 13 10 13.9 10 15S10.9 17 12 17 14 16.1 14 15 13.1 13 12 13M13.35 21H5.5C4.58 21 3.81 20.38 3.58 19.54L1.04 10.27C1 10.18 1 10.09 1 10C1 9.45 1.45 9 2 9H6.79L11.17 2.45C11.36 2.16 11.68 2 12 2S12.64 2.16 12.83 2.44L17.21 9H22C22.55 9 23 9.45 23 10L22.97 10.27L22 13.81C21.43 13.5 20.79 13.24 20.12 13.11L20.7 11H3.31L5.5 19H13C13 19.7 13.13 20.37 13.35 21M9.2 9H14.8L12 4.8L9.2 9Z\"></path></svg>" +
@@ -72,7 +136,8 @@ function refreshRecordsList() {
                    .replace("{2}", value.type)
                    .replace("{3}", value.ttl)
                    .replace("{4}", data)
-                   .replace("{5}", index);
+                   .replace("{5}", index)
+                   .replace("{6}", (commentsBuffer[index] || "").replace(/"/g, "&quot;"));
     }
 
     recordsBuffer.forEach(makeRecord);
@@ -82,7 +147,9 @@ function refreshRecordsList() {
 function showNewRecordDialog() {
     var button_positive = document.getElementById("new_record_positive_button");
     button_positive.onclick = function() {
-        checkRecord(getRecordFromDialog());
+        if (previewRecord()) {
+            checkRecord(getRecordFromDialog());
+        }
     };
 
     var button_negative = document.getElementById("new_record_negative_button");
@@ -92,15 +159,115 @@ function showNewRecordDialog() {
         refreshRecordsList();
     }
 
+    recordTypeChanged();
     var dialog = document.getElementById("new_record_dialog");
     dialog.className = "modal is-active";
 }
 
+// Ids of label texts of record fields for every type, fields without label are hidden
+var recordForms = {
+    "A": { data: "record.ipv4", placeholder: "1.2.3.4" },
+    "AAAA": { data: "record.ipv6", placeholder: "200:1234::1" },
+    "CNAME": { data: "record.host", placeholder: "www.example.ygg" },
+    "NS": { data: "record.name_server", placeholder: "ns1.example.ygg" },
+    "MX": { priority: "record.priority", data: "record.mail_server", placeholder: "mail.example.ygg" },
+    "SRV": { priority: "record.priority", weight: "record.weight", port: "record.port", data: "record.host", placeholder: "xmpp.example.ygg" },
+    "TXT": { data: "record.text", placeholder: "v=spf1 mx -all" },
+    "TLSA": { priority: "record.usage", weight: "record.selector", port: "record.matching_type", data: "record.certificate", placeholder: "8F00B2..." }
+};
+
+function recordTypeChanged() {
+    var form = recordForms[document.getElementById("record_type").value];
+    ["priority", "weight", "port"].forEach(function(field) {
+        var column = document.getElementById("record_" + field + "_column");
+        if (typeof form[field] !== 'undefined') {
+            column.style.display = 'block';
+            document.getElementById("record_" + field + "_label").innerHTML = tr(form[field]);
+        } else {
+            column.style.display = 'none';
+        }
+    });
+    document.getElementById("record_data_label").innerHTML = tr(form.data);
+    document.getElementById("record_data").placeholder = form.placeholder;
+    previewRecord();
+}
+
+// Marks wrong field of the record dialog
+function markRecordField(field, message) {
+    var id = field == "type" ? "record_type_select" : "record_" + field;
+    document.getElementById(id).classList.add("is-danger");
+    document.getElementById("record_error").innerHTML = message;
+}
+
+function recordError(field, message) {
+    markRecordField(field, message);
+    showWarning(message);
+}
+
+// Checks fields of the record dialog and shows domain data with this record, returns false if some field is wrong
+function previewRecord() {
+    ["name", "type_select", "ttl", "priority", "weight", "port", "data", "comment"].forEach(function(field) {
+        document.getElementById("record_" + field).classList.remove("is-danger");
+    });
+    document.getElementById("record_error").innerHTML = "";
+
+    var type = document.getElementById("record_type").value;
+    var form = recordForms[type];
+    var name = document.getElementById("record_name").value;
+    var data = document.getElementById("record_data").value.trim();
+    var ttl = document.getElementById("record_ttl").value;
+    var comment = document.getElementById("record_comment").value.trim();
+    var error = null;
+    if (name == "") {
+        error = ["name", tr("record.error_name")];
+    } else if (!/^[0-9]+$/.test(ttl) || parseInt(ttl) <= 0 || parseInt(ttl) > MAX_RECORD_TTL) {
+        error = ["ttl", tr("record.error_ttl", {max: MAX_RECORD_TTL})];
+    } else if (unescape(encodeURIComponent(comment)).length > MAX_COMMENT_LEN) {
+        error = ["comment", tr("record.error_comment", {length: MAX_COMMENT_LEN})];
+    }
+    ["priority", "weight", "port"].forEach(function(field) {
+        var value = document.getElementById("record_" + field).value;
+        if (error == null && typeof form[field] !== 'undefined' && (!/^[0-9]+$/.test(value) || parseInt(value) > 65535)) {
+            error = [field, tr("record.error_number", {field: tr(form[field])})];
+        }
+    });
+    if (error == null) {
+        if (data == "") {
+            error = ["data", tr("record.error_empty", {field: tr(form.data)})];
+        } else if (type == "A" && !/^(\d{1,3}\.){3}\d{1,3}$/.test(data)) {
+            error = ["data", tr("record.error_ipv4")];
+        } else if (type == "AAAA" && (data.indexOf(":") < 0 || !/^[0-9a-fA-F:.]+$/.test(data))) {
+            error = ["data", tr("record.error_ipv6")];
+        } else if (type == "TLSA" && !/^([0-9a-fA-F]{2})+$/.test(data)) {
+            error = ["data", tr("record.error_hex")];
+        }
+    }
+    if (error != null) {
+        markRecordField(error[0], error[1]);
+        document.getElementById("record_preview").innerHTML = "";
+        document.getElementById("record_size").innerHTML = "";
+        return false;
+    }
+
+    var domainData = {};
+    domainData.zone = typeof currentZone !== 'undefined' ? currentZone.name : "";
+    domainData.records = recordsBuffer.concat([getRecordFromDialog()]);
+    var comments = getComments(commentsBuffer.slice(0, recordsBuffer.length).concat([comment]));
+    if (comments.length > 0) {
+        domainData.comments = comments;
+    }
+    var json = JSON.stringify(domainData);
+    var size = unescape(encodeURIComponent(json)).length;
+    document.getElementById("record_preview").innerText = JSON.stringify(domainData, null, 2);
+    document.getElementById("record_size").innerHTML = tr("record.size", {size: size});
+    return true;
+}
+
 function getRecordFromDialog() {
     var record_name = document.getElementById("record_name").value.toLowerCase();
     var record_type = document.getElementById("record_type").value;
     var record_ttl = parseInt(document.getElementById("record_ttl").value);
-    var record_data = document.getElementById("record_data").value;
+    var record_data = document.getElementById("record_data").value.trim();
     if (record_type == "CNAME" || record_type == "NS") {
         return { type: record_type, domain: record_name, ttl: record_ttl, host: record_data }
     } else if (record_type == "MX") {
@@ -166,7 +333,7 @@ function refreshMyDomains() {
                 }
             });
         } else {
-            tags = tag.replace("{domain}", "No records").replace("{ip}", "");
+            tags = tag.replace("{domain}", tr("domains.no_records")).replace("{ip}", "");
         }
         rows = rows + row.replace("{title}", title).replace("{domain}", title).replace("{tags}", tags).replace("{date1}", start).replace("{date2}", expire);
     });
@@ -178,6 +345,39 @@ function refreshMyDomains() {
     }
 }
 
+function portfolioChanged(json) {
+    portfolioJson = json;
+    var domains = JSON.parse(json);
+    var now = Date.now() / 1000;
+    domains.sort(function(a, b) {
+        return a.expire - b.expire;
+    });
+    var rows = "";
+    domains.forEach(function(value, index, array) {
+        var counts = {};
+        var records = value.data.records || [];
+        records.forEach(function(v, i, a) {
+            counts[v.type] = (counts[v.type] || 0) + 1;
+        });
+        var summary = Object.keys(counts).map(function(type) {
+            return '<span class="tag">' + counts[type] + ' ' + type + '</span>';
+        }).join("");
+        if (summary == "") {
+            summary = '<span class="tag">' + tr("domains.no_records") + '</span>';
+        }
+        var days = Math.floor((value.expire - now) / 86400);
+        var renew = days >= 0 ? tr("portfolio.days", {days: days}) : tr("portfolio.expired");
+        var renewClass = days < 30 ? "has-text-danger" : "";
+        var row = value.active ? '<tr class="is-clickable" onclick="editDomain(\'' + value.name + '\', event);">' : '<tr title="' + tr("portfolio.select_key") + '">';
+        row += '<td class="has-text-weight-semibold">' + value.name + '</td><td>' + value.key + '</td><td><div class="tags">' + summary + '</div></td>';
+        row += '<td>' + value.confirmations + '</td><td class="' + renewClass + '" title="' + tr("portfolio.expires", {date: formatDate(new Date(value.expire * 1000))}) + '">' + renew + '</td></tr>';
+        rows = rows + row;
+    });
+    document.getElementById("portfolio").innerHTML = rows;
+    document.getElementById("portfolio_table").style.display = rows != "" ? 'table' : 'none';
+    document.getElementById("portfolio_empty").style.display = rows != "" ? 'none' : 'block';
+}
+
 function editDomain(domain, event) {
     myDomains.forEach(function(value, index, array) {
         if (domain != value.name) {
@@ -186,9 +386,11 @@ function editDomain(domain, event) {
         var title = value.name;
         var domain_data = JSON.parse(value.data);
         recordsBuffer = [];
+        commentsBuffer = [];
         if (typeof domain_data.records !== 'undefined') {
             domain_data.records.forEach(function(v, i, a) {
                 recordsBuffer.push(v);
+                commentsBuffer.push(typeof domain_data.comments !== 'undefined' && i < domain_data.comments.length ? domain_data.comments[i] : "");
             });
         }
         currentDomain = title.replace("." + domain_data.zone, "");
@@ -221,6 +423,9 @@ function onLoad() {
         };
     }
 
+    translatePage();
+    refreshLanguages();
+    setLeftStatusBarText(tr("status.no_connection"));
     external.invoke(JSON.stringify({cmd: 'loaded'}));
 }
 
@@ -269,16 +474,17 @@ function saveKey() {
 }
 
 function checkRecord(data) {
-    external.invoke(JSON.stringify({cmd: 'checkRecord', data: JSON.stringify(data)}));
+    var zone = typeof currentZone !== 'undefined' ? currentZone.name : "";
+    external.invoke(JSON.stringify({cmd: 'checkRecord', data: JSON.stringify(data), zone: zone}));
 }
 
 function recordOkay(okay) {
     if (okay) {
-        addRecord(getRecordFromDialog()); // It will refresh list
+        addRecord(getRecordFromDialog(), document.getElementById("record_comment").value.trim()); // It will refresh list
         var dialog = document.getElementById("new_record_dialog");
         dialog.className = "modal";
     } else {
-        showWarning('Record is not valid!');
+        showWarning(tr("record.invalid"));
     }
 }
 
@@ -292,20 +498,97 @@ function closeDialog(name) {
 
 function createDomain() {
     if (typeof currentZone == 'undefined') {
-        showWarning("Select a domain zone first");
+        showWarning(tr("domain.select_zone_first"));
+        return;
+    }
+    var domain = getDomainName();
+    var data = getDomainData();
+    var renewal = document.getElementById("renewal").checked;
+    external.invoke(JSON.stringify({cmd: 'mineDomain', name: domain, data: data, signing: ownerSigning, encryption: ownerEncryption, renewal: renewal}));
+}
+
+function exportDomain() {
+    if (typeof currentZone == 'undefined') {
+        showWarning(tr("domain.select_zone_first"));
         return;
     }
+    var domain = getDomainName();
+    var data = getDomainData();
+    external.invoke(JSON.stringify({cmd: 'exportDomain', name: domain, data: data}));
+}
+
+function rotateKey() {
+    if (typeof currentZone == 'undefined') {
+        showWarning(tr("domain.select_zone_first"));
+        return;
+    }
+    external.invoke(JSON.stringify({cmd: 'rotateKey', name: getDomainName()}));
+}
+
+function mineTransaction() {
+    external.invoke(JSON.stringify({cmd: 'mineTransaction'}));
+}
+
+function exportDomainQr() {
+    if (typeof currentZone == 'undefined') {
+        showWarning(tr("domain.select_zone_first"));
+        return;
+    }
+    external.invoke(JSON.stringify({cmd: 'exportDomainQr', name: getDomainName(), data: getDomainData()}));
+}
+
+function mineTransactionQr() {
+    external.invoke(JSON.stringify({cmd: 'mineTransactionQr'}));
+}
+
+function showKeyQr() {
+    external.invoke(JSON.stringify({cmd: 'showKeyQr'}));
+}
+
+function pasteOwnerQr() {
+    external.invoke(JSON.stringify({cmd: 'pasteOwnerQr'}));
+}
+
+function setOwnerKeys(signing, encryption) {
+    var field = document.getElementById("owner_signing");
+    field.value = signing;
+    document.getElementById("owner_encryption").value = encryption;
+    onOwnerChange(field);
+}
+
+function showQr(title, image, text) {
+    document.getElementById("qr_title").innerText = title;
+    document.getElementById("qr_image").innerHTML = image;
+    document.getElementById("qr_text").value = text;
+    document.getElementById("qr_dialog").className = "modal is-active";
+}
+
+function getDomainName() {
     var new_domain = document.getElementById("new_domain").value.toLowerCase();
-    var domain = new_domain + "." + currentZone.name;
+    return new_domain + "." + currentZone.name;
+}
+
+function getDomainData() {
     var data = {};
     data.encrypted = "";
     data.zone = currentZone.name;
     data.info = document.getElementById("info_text").value;
     data.records = recordsBuffer;
+    var comments = getComments(commentsBuffer.slice(0, recordsBuffer.length));
+    if (comments.length > 0) {
+        data.comments = comments;
+    }
     data.contacts = getContacts();
-    var renewal = document.getElementById("renewal").checked;
-    data = JSON.stringify(data);
-    external.invoke(JSON.stringify({cmd: 'mineDomain', name: domain, data: data, signing: ownerSigning, encryption: ownerEncryption, renewal: renewal}));
+    return JSON.stringify(data);
+}
+
+// Comments of records without empty ones at the end, they are not needed in the block
+function getComments(comments) {
+    var result = comments.map(function(comment) { return comment || ""; });
+    while (result.length > 0 && result[result.length - 1] == "") {
+        result.pop();
+    }
+    return result;
 }
 
 function getContacts() {
@@ -407,6 +690,23 @@ function isValidOwner(text) {
     return regexp.test(text);
 }
 
+function onOwnerChange(element) {
+    if (isValidOwner(element.value)) {
+        external.invoke(JSON.stringify({cmd: 'fingerprint', key: element.value}));
+    } else {
+        ownerFingerprint("");
+    }
+}
+
+function ownerFingerprint(fingerprint) {
+    var help = document.getElementById("owner_fingerprint");
+    if (fingerprint == "") {
+        help.innerHTML = "";
+    } else {
+        help.innerHTML = tr("owner.fingerprint", {fingerprint: fingerprint});
+    }
+}
+
 function ownerPositiveButton() {
     var signing = document.getElementById("owner_signing").value;
     var encryption = document.getElementById("owner_encryption").value;
@@ -415,7 +715,7 @@ function ownerPositiveButton() {
             ownerSigning = signing;
             ownerEncryption = encryption;
         } else {
-            alert("Wrong owner '{}'!".replace("{}", value));
+            alert(tr("owner.wrong"));
             wrong = true;
             return;
         }
@@ -489,6 +789,12 @@ function showError(text) {
     }
 }
 
+// Our domain was changed by other key, it must not be missed
+function showConflict(text) {
+    document.getElementById("conflict_text").innerHTML = text;
+    document.getElementById("conflict_dialog").className = "modal is-active";
+}
+
 function showSuccess(text) {
     var warning = document.getElementById("notification_success");
     var message = document.getElementById("success_text");
@@ -511,6 +817,13 @@ function showMiningIndicator(visible, blue) {
         add = " busy_blue";
         stateMining = false;
     }
+    var pause = document.getElementById("mining_pause_parent");
+    if (visible && stateMining) {
+        pause.className = "level-item";
+    } else {
+        pause.className = "level-item is-hidden";
+        setMiningPaused(false);
+    }
     if (visible) {
         indicator.className = 'busy_indicator' + add;
         parent.style.display = 'flex';
@@ -527,12 +840,26 @@ function showMiningIndicator(visible, blue) {
 
 function miningIndicatorClick(element) {
     if (stateMining) {
-        showModalDialog("Do you really want to stop mining?", function() {
+        showModalDialog(tr("mining.stop_question"), function() {
             external.invoke(JSON.stringify({cmd: 'stopMining'}));
         });
     }
 }
 
+function miningPauseClick() {
+    if (statePaused) {
+        external.invoke(JSON.stringify({cmd: 'resumeMining'}));
+    } else {
+        external.invoke(JSON.stringify({cmd: 'pauseMining'}));
+    }
+}
+
+function setMiningPaused(paused) {
+    statePaused = paused;
+    var button = document.getElementById("mining_pause_button");
+    button.innerHTML = paused ? tr("status.resume") : tr("status.pause");
+}
+
 function setLeftStatusBarText(text) {
     var bar = document.getElementById("status_bar_left");
     bar.innerHTML = text;
@@ -561,9 +888,19 @@ function addEvent(type, time, message) {
     tab_events.innerHTML = tab_events.innerHTML + buf;
 }
 
-function keystoreChanged(path, pub_key, hash) {
+function keystoreChanged(path, pub_key, hash, fingerprint) {
+    document.getElementById("key_fingerprint").innerHTML = fingerprint;
+    if (pub_key == '') {
+        document.getElementById("public_key").value = "";
+        document.getElementById("save_key").disabled = true;
+        document.getElementById("new_domain").disabled = true;
+        clearMyDomains();
+        refreshMyDomains();
+        portfolioChanged('[]');
+        return;
+    }
     if (path == '') {
-        path = "In memory";
+        path = tr("key.in_memory");
     }
     var public_key_field = document.getElementById("public_key");
     public_key_field.value = pub_key;
@@ -634,9 +971,9 @@ function changeZone(zone, event) {
 function refreshKeysMenu() {
     var buf = "";
     keysLoaded.forEach(function(value, index, array) {
-        var file_name = value.file_name;
+        var file_name = value.name;
         if (file_name == "") {
-            file_name = "[Not saved]";
+            file_name = tr("key.not_saved");
         }
         var public = value.public;
 
@@ -648,18 +985,67 @@ function refreshKeysMenu() {
             .replace("{id}", index)
             .replace("{index}", index)
             .replace("{class}", add_class)
-            .replace("{title}", public)
+            .replace("{title}", public + "\n" + value.fingerprint)
             .replace("{name}", file_name);
     });
+    buf += "<hr class=\"dropdown-divider\">";
+    if (currentSelectedKey >= 0) {
+        buf += keyMenuItem("showMnemonic", "key.show_words");
+        buf += keyMenuItem("exportKey", "key.export");
+        buf += keyMenuItem("removeKey", "key.remove");
+    }
+    if (currentSelectedKey >= 0) {
+        buf += keyMenuItem("deriveDomainKey", "key.derive");
+    }
+    buf += keyMenuItem("restoreKey", "key.restore");
     var links = document.getElementById("keys_links");
     links.innerHTML = buf;
+    var cur_name = document.getElementById("keys_current_name");
+    if (currentSelectedKey < 0 || currentSelectedKey >= keysLoaded.length) {
+        cur_name.innerHTML = tr("key.no_keys");
+    } else if (keysLoaded[currentSelectedKey].name == "") {
+        cur_name.innerHTML = tr("key.not_saved");
+    } else {
+        cur_name.innerHTML = keysLoaded[currentSelectedKey].name;
+    }
+}
+
+function keyMenuItem(action, id) {
+    return "<a class=\"dropdown-item\" onclick=\"{action}(event);\" title=\"{title}\">{text}</a>"
+        .replace("{action}", action)
+        .replace("{title}", tr(id + "_title"))
+        .replace("{text}", tr(id));
+}
+
+function showMnemonic(event) {
+    event.stopPropagation();
+    closeDropdowns();
+    external.invoke(JSON.stringify({cmd: 'showMnemonic'}));
+}
+
+function exportKey(event) {
+    event.stopPropagation();
+    closeDropdowns();
+    external.invoke(JSON.stringify({cmd: 'exportKey'}));
+}
+
+function restoreKey(event) {
+    event.stopPropagation();
+    closeDropdowns();
+    external.invoke(JSON.stringify({cmd: 'restoreKey'}));
+}
+
+function deriveDomainKey(event) {
+    event.stopPropagation();
+    closeDropdowns();
+    external.invoke(JSON.stringify({cmd: 'deriveDomainKey'}));
+}
+
+function removeKey(event) {
+    event.stopPropagation();
+    closeDropdowns();
     if (currentSelectedKey >= 0) {
-        var cur_name = document.getElementById("keys_current_name");
-        if (keysLoaded[currentSelectedKey].file_name == "") {
-            cur_name.innerHTML = "[Not saved]";
-        } else {
-            cur_name.innerHTML = keysLoaded[currentSelectedKey].file_name;
-        }
+        external.invoke(JSON.stringify({cmd: 'removeKey', index: currentSelectedKey}));
     }
 }
 
@@ -0,0 +1,211 @@
+var steps = ["step_welcome", "step_key", "step_data", "step_dns", "step_resolver", "step_test"];
+var currentStep = 0;
+var keyMiningNow = false;
+var keyText = "";
+
+// Gets translated text by its id, and puts values from args to its {name} placeholders
+function tr(id, args) {
+    var text = strings[id];
+    if (typeof text === 'undefined') {
+        return id;
+    }
+    for (var name in args) {
+        text = text.replace("{" + name + "}", args[name]);
+    }
+    return text;
+}
+
+// Puts translated texts to all elements that have ids of texts
+function translatePage() {
+    var elements = document.querySelectorAll("[data-i18n]");
+    for (var i = 0; i < elements.length; i++) {
+        elements[i].innerHTML = tr(elements[i].getAttribute("data-i18n"));
+    }
+    document.getElementById("data_dir").placeholder = tr("wizard.data_default", {dir: workDir});
+}
+
+function refreshLanguages() {
+    var buf = "<option value=\"\">" + tr("help.language_system") + "</option>";
+    languages.forEach(function(value, index, array) {
+        buf += "<option value=\"{code}\">{name}</option>".replace("{code}", value.code).replace("{name}", value.name);
+    });
+    var select = document.getElementById("language");
+    select.innerHTML = buf;
+    select.value = language;
+}
+
+function setLanguage(code) {
+    external.invoke(JSON.stringify({cmd: 'setLanguage', code: code}));
+}
+
+function languageChanged(json, code) {
+    strings = JSON.parse(json);
+    language = code;
+    translatePage();
+    refreshLanguages();
+    refreshResolver();
+    if (keyText != "") {
+        document.getElementById("key_ready").innerHTML = keyText;
+    }
+}
+
+function showStep(index) {
+    document.getElementById(steps[currentStep]).classList.add("is-hidden");
+    currentStep = index;
+    document.getElementById(steps[currentStep]).classList.remove("is-hidden");
+    document.getElementById("wizard_progress").value = currentStep + 1;
+    document.getElementById("back_button").disabled = currentStep == 0;
+    var last = currentStep == steps.length - 1;
+    document.getElementById("next_button").classList.toggle("is-hidden", last);
+    document.getElementById("finish_button").classList.toggle("is-hidden", !last);
+    if (steps[currentStep] == "step_resolver") {
+        refreshResolver();
+    }
+}
+
+function nextStep() {
+    if (keyMiningNow) {
+        showError(tr("wizard.key_wait"));
+        return;
+    }
+    showStep(currentStep + 1);
+}
+
+function previousStep() {
+    showStep(currentStep - 1);
+}
+
+function showError(text) {
+    var error = document.getElementById("notification_error");
+    error.innerHTML = text;
+    error.classList.remove("is-hidden");
+    setTimeout(function() {
+        error.classList.add("is-hidden");
+    }, 5000);
+}
+
+function generateKey() {
+    external.invoke(JSON.stringify({cmd: 'generateKey'}));
+}
+
+function stopKey() {
+    external.invoke(JSON.stringify({cmd: 'stopKey'}));
+    keyMining(false);
+}
+
+function importKey() {
+    external.invoke(JSON.stringify({cmd: 'importKey'}));
+}
+
+function keyMining(mining) {
+    keyMiningNow = mining;
+    document.getElementById("key_mining").classList.toggle("is-hidden", !mining);
+    document.getElementById("key_buttons").classList.toggle("is-hidden", mining);
+}
+
+function keyReady(path, key) {
+    keyMining(false);
+    keyText = tr("wizard.key_ready", {file: path, key: key});
+    var ready = document.getElementById("key_ready");
+    ready.innerHTML = keyText;
+    ready.classList.remove("is-hidden");
+}
+
+function browseDataDir() {
+    external.invoke(JSON.stringify({cmd: 'browseDataDir'}));
+}
+
+function dataDirSelected(path) {
+    document.getElementById("data_dir").value = path;
+}
+
+function dnsChanged() {
+    document.getElementById("dns_result").innerHTML = "";
+}
+
+function checkDns() {
+    var listen = document.getElementById("dns_listen").value;
+    external.invoke(JSON.stringify({cmd: 'checkDns', listen: listen}));
+}
+
+function dnsChecked(ok, text) {
+    var result = document.getElementById("dns_result");
+    result.className = ok ? "help is-success" : "help is-danger";
+    result.innerHTML = text;
+}
+
+// Shows how to set our DNS server as resolver of the system, it works only on port 53
+function refreshResolver() {
+    var listen = document.getElementById("dns_listen").value.trim();
+    var pos = listen.lastIndexOf(":");
+    var address = listen.substring(0, pos).replace("[", "").replace("]", "");
+    var port = listen.substring(pos + 1);
+    if (address == "0.0.0.0") {
+        address = "127.0.0.1";
+    } else if (address == "::") {
+        address = "::1";
+    }
+    var command = document.getElementById("resolver_command");
+    if (port != "53") {
+        document.getElementById("resolver_text").innerHTML = tr("wizard.resolver_port");
+        command.classList.add("is-hidden");
+        return;
+    }
+    document.getElementById("resolver_text").innerHTML = tr("wizard.resolver_" + system);
+    var commands = {
+        windows: "netsh interface ip set dns name=\"Ethernet\" static " + address,
+        macos: "networksetup -setdnsservers Wi-Fi " + address,
+        linux: "echo \"nameserver " + address + "\" | sudo tee /etc/resolv.conf"
+    };
+    if (typeof commands[system] === 'undefined') {
+        command.classList.add("is-hidden");
+    } else {
+        command.textContent = commands[system];
+        command.classList.remove("is-hidden");
+    }
+}
+
+function testConnection() {
+    document.getElementById("test_button").classList.add("is-loading");
+    document.getElementById("test_results").innerHTML = "";
+    document.getElementById("test_summary").innerHTML = "";
+    external.invoke(JSON.stringify({cmd: 'testConnection'}));
+}
+
+function connectionTested(json) {
+    document.getElementById("test_button").classList.remove("is-loading");
+    var results = JSON.parse(json);
+    var buf = "";
+    var good = 0;
+    results.forEach(function(value, index, array) {
+        var state = value.ok ? tr("wizard.test_ok") : tr("wizard.test_failed");
+        buf += "<li>" + value.peer + ": " + state + "</li>";
+        if (value.ok) {
+            good += 1;
+        }
+    });
+    document.getElementById("test_results").innerHTML = buf;
+    document.getElementById("test_summary").innerHTML = good > 0 ? tr("wizard.test_good") : tr("wizard.test_bad");
+}
+
+function finish() {
+    var data_dir = document.getElementById("data_dir").value;
+    var dns_listen = document.getElementById("dns_listen").value;
+    external.invoke(JSON.stringify({cmd: 'finish', data_dir: data_dir, dns_listen: dns_listen}));
+}
+
+function onLoad() {
+    // Workaround for Arch Linux Webkit
+    // https://github.com/Boscop/web-view/issues/212#issuecomment-671055663
+    if (typeof window.external == 'undefined' || typeof window.external.invoke == 'undefined') {
+        window.external = {
+            invoke: function(x) {
+                window.webkit.messageHandlers.external.postMessage(x);
+            }
+        };
+    }
+
+    translatePage();
+    refreshLanguages();
+    showStep(0);
+}
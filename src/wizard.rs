@@ -0,0 +1,327 @@
+//! Setup on the first start in GUI: the key, data directory, DNS listen address and the resolver of the system.
+//! It is shown when there is no config file yet, and writes the config from `alfis.toml` template with comments.
+
+extern crate tinyfiledialogs as tfd;
+extern crate web_view;
+
+use std::env;
+use std::fs;
+use std::net::{SocketAddr, TcpStream, ToSocketAddrs, UdpSocket};
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use alfis_core::keystore::mine_key;
+use alfis_core::{Keystore, Settings};
+#[allow(unused_imports)]
+use log::{debug, error, info, trace, warn};
+use serde::{Deserialize, Serialize};
+use web_view::{Content, WebView};
+use Cmd::*;
+
+use crate::i18n::{self, tr, tr_args};
+use crate::web_ui::{ask_password, escape_js, inline_script, inline_style};
+
+const TEMPLATE: &str = include_str!("../alfis.toml");
+const DEFAULT_KEY_FILE: &str = "key1.toml";
+/// How long we wait for every bootstrap node in connectivity test
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// What user has chosen so far
+#[derive(Default)]
+struct Choices {
+    language: String,
+    key_file: Option<String>,
+    mining: Option<Arc<AtomicBool>>,
+    done: bool
+}
+
+/// Result of connectivity test for one bootstrap node
+#[derive(Serialize)]
+struct PeerTest {
+    peer: String,
+    ok: bool
+}
+
+/// Shows the setup window and writes config to `config_name`, returns false if user closed the window
+pub fn run_wizard(config_name: &str) -> bool {
+    i18n::set_language("");
+    let mut styles = inline_style(include_str!("webview/bulma.css"));
+    styles.push_str(&inline_style(include_str!("webview/styles.css")));
+    let work_dir = env::current_dir().map(|dir| dir.to_string_lossy().to_string()).unwrap_or_default();
+    let mut scripts = inline_script(&format!(
+        "var strings = {}; var languages = {}; var language = ''; var system = '{}'; var workDir = '{}';",
+        i18n::strings_json(), i18n::languages_json(), system_name(), escape_js(&work_dir)
+    ));
+    scripts.push_str(&inline_script(include_str!("webview/wizard.js")));
+    let html = Content::Html(include_str!("webview/wizard.html").replace("{styles}", &styles).replace("{scripts}", &scripts));
+    let config_name = config_name.to_owned();
+    let mut interface = web_view::builder()
+        .title("ALFIS")
+        .content(html)
+        .size(800, 600)
+        .min_size(640, 480)
+        .resizable(true)
+        .debug(false)
+        .user_data(Choices::default())
+        .invoke_handler(move |web_view, arg| {
+            debug!("Command {}", arg);
+            match serde_json::from_str(arg).unwrap() {
+                SetLanguage { code } => {
+                    i18n::set_language(&code);
+                    web_view.user_data_mut().language = code.clone();
+                    web_view.eval(&format!("languageChanged('{}', '{}');", escape_js(&i18n::strings_json()), escape_js(&code)))?;
+                }
+                GenerateKey => action_generate_key(web_view),
+                StopKey => {
+                    if let Some(mining) = web_view.user_data_mut().mining.take() {
+                        mining.store(false, Ordering::SeqCst);
+                    }
+                }
+                ImportKey => action_import_key(web_view),
+                BrowseDataDir => {
+                    if let Some(path) = tfd::select_folder_dialog(&tr("wizard.data_dir"), &work_dir) {
+                        web_view.eval(&format!("dataDirSelected('{}');", escape_js(&path)))?;
+                    }
+                }
+                CheckDns { listen } => {
+                    let (ok, text) = check_listen(&listen);
+                    web_view.eval(&format!("dnsChecked({}, '{}');", ok, escape_js(&text)))?;
+                }
+                TestConnection => action_test_connection(web_view),
+                Finish { data_dir, dns_listen } => {
+                    let config = make_config(&web_view.user_data().language, web_view.user_data().key_file.as_deref(), &data_dir, &dns_listen);
+                    match fs::write(&config_name, config) {
+                        Ok(_) => {
+                            info!("Settings are saved to {}", &config_name);
+                            web_view.user_data_mut().done = true;
+                            web_view.exit();
+                        }
+                        Err(e) => {
+                            error!("Error saving settings to {}: {}", &config_name, e);
+                            let text = tr_args("wizard.save_failed", &[("file", &config_name), ("error", &e.to_string())]);
+                            web_view.eval(&format!("showError('{}');", escape_js(&text)))?;
+                        }
+                    }
+                }
+            }
+            Ok(())
+        })
+        .build()
+        .expect("Error building GUI");
+
+    // The same loop with pauses as in main window, to lower CPU usage
+    let pause = Duration::from_millis(25);
+    let mut start = Instant::now();
+    loop {
+        match interface.step() {
+            None => break,
+            Some(Ok(_)) => {}
+            Some(Err(_)) => {
+                error!("Something wrong with webview, exiting");
+                break;
+            }
+        }
+        if start.elapsed().as_millis() > 1 {
+            thread::sleep(pause);
+            start = Instant::now();
+        }
+    }
+    let choices = interface.into_inner();
+    if let Some(mining) = choices.mining {
+        mining.store(false, Ordering::SeqCst);
+    }
+    choices.done
+}
+
+/// Asks where to save new key and mines it in background, the page gets `keyReady()` when it is saved
+fn action_generate_key(web_view: &mut WebView<Choices>) {
+    let mut path = match tfd::save_file_dialog_with_filter(&tr("dialog.save_key"), DEFAULT_KEY_FILE, &["*.toml"], &format!("{} (*.toml)", tr("dialog.key_files"))) {
+        Some(path) => path,
+        None => return
+    };
+    if !path.ends_with(".toml") {
+        path.push_str(".toml");
+    }
+    let password = ask_password(&tr("dialog.password_new")).unwrap_or_default();
+    let mining = Arc::new(AtomicBool::new(true));
+    web_view.user_data_mut().mining = Some(Arc::clone(&mining));
+    let _ = web_view.eval("keyMining(true);");
+    let handle = web_view.handle();
+    thread::spawn(move || {
+        let result = mine_key(num_cpus::get(), mining).map(|mut keystore| {
+            keystore.save(&path, &password);
+            info!("Key mined and saved to {}, public key: {}", &path, keystore.get_public().to_string());
+            keystore.get_public().to_string()
+        });
+        // The window can be closed already
+        let _ = handle.dispatch(move |web_view| {
+            web_view.user_data_mut().mining = None;
+            match result {
+                Some(public) => {
+                    web_view.eval(&format!("keyReady('{}', '{}');", escape_js(&path), public))?;
+                    web_view.user_data_mut().key_file = Some(path);
+                }
+                None => web_view.eval("keyMining(false);")?
+            }
+            Ok(())
+        });
+    });
+}
+
+/// Takes existing key file, the keys from JSON container are saved to key file with the same name
+fn action_import_key(web_view: &mut WebView<Choices>) {
+    let path = match tfd::open_file_dialog(&tr("dialog.open_key"), "", Some((&["*.key", "*.toml", "*.json"], &tr("dialog.key_files")))) {
+        Some(path) => path,
+        None => return
+    };
+    let result = if path.ends_with(".json") {
+        let json = fs::read_to_string(&path).unwrap_or_default();
+        let password = ask_password(&tr_args("dialog.password_file", &[("file", &path)])).unwrap_or_default();
+        Keystore::import_json(&json, &password).map(|mut keystore| {
+            let path = Path::new(&path).with_extension("toml").to_string_lossy().to_string();
+            let password = ask_password(&tr("dialog.password_new")).unwrap_or_default();
+            keystore.save(&path, &password);
+            (path, keystore)
+        })
+    } else {
+        let password = match Keystore::is_encrypted_file(&path) {
+            true => ask_password(&tr_args("dialog.password_key", &[("file", &path)])).unwrap_or_default(),
+            false => String::new()
+        };
+        Keystore::from_file(&path, &password).map(|keystore| (path.clone(), keystore))
+    };
+    match result {
+        Some((path, keystore)) => {
+            info!("Using key file {}", &path);
+            let _ = web_view.eval(&format!("keyReady('{}', '{}');", escape_js(&path), keystore.get_public().to_string()));
+            web_view.user_data_mut().key_file = Some(path);
+        }
+        None => {
+            warn!("Error loading key file {}", &path);
+            let _ = web_view.eval(&format!("showError('{}');", escape_js(&tr("warning.load_key"))));
+        }
+    }
+}
+
+/// Connects to bootstrap nodes from template in background, the page gets `connectionTested()` with results
+fn action_test_connection(web_view: &mut WebView<Choices>) {
+    let handle = web_view.handle();
+    thread::spawn(move || {
+        let peers = toml::from_str::<Settings>(TEMPLATE).map(|settings| settings.net.peers).unwrap_or_default();
+        let results: Vec<PeerTest> = peers.into_iter()
+            .map(|peer| {
+                let ok = peer.to_socket_addrs()
+                    .map(|addrs| addrs.into_iter().any(|addr| TcpStream::connect_timeout(&addr, CONNECT_TIMEOUT).is_ok()))
+                    .unwrap_or(false);
+                PeerTest { peer, ok }
+            })
+            .collect();
+        let json = serde_json::to_string(&results).unwrap();
+        let _ = handle.dispatch(move |web_view| web_view.eval(&format!("connectionTested('{}');", escape_js(&json))));
+    });
+}
+
+/// Checks that DNS server will be able to listen on this address, returns the result and its description
+fn check_listen(listen: &str) -> (bool, String) {
+    let addr: SocketAddr = match listen.trim().parse() {
+        Ok(addr) => addr,
+        Err(_) => return (false, tr("wizard.dns_wrong"))
+    };
+    match UdpSocket::bind(addr) {
+        Ok(_) if addr.port() == 53 => (true, tr("wizard.dns_ok")),
+        Ok(_) => (true, tr("wizard.dns_ok_port")),
+        Err(e) => (false, tr_args("wizard.dns_busy", &[("error", &e.to_string())]))
+    }
+}
+
+/// Name of the system for instructions about resolver
+fn system_name() -> &'static str {
+    if cfg!(target_os = "windows") {
+        "windows"
+    } else if cfg!(target_os = "macos") {
+        "macos"
+    } else if cfg!(target_os = "linux") {
+        "linux"
+    } else {
+        "other"
+    }
+}
+
+/// Makes config text from template with chosen options
+fn make_config(language: &str, key_file: Option<&str>, data_dir: &str, dns_listen: &str) -> String {
+    let string = |s: &str| toml::Value::String(s.trim().to_owned()).to_string();
+    let mut values = vec![
+        ("language", string(language)),
+        ("data_dir", string(data_dir)),
+        ("dns.listen", string(dns_listen))
+    ];
+    if let Some(key_file) = key_file {
+        values.push(("key_files", toml::Value::Array(vec![toml::Value::String(key_file.to_owned())]).to_string()));
+    }
+    fill_template(TEMPLATE, &values)
+}
+
+/// Sets values of options in config text and keeps its comments.
+/// Options are named like "data_dir" or "dns.listen", values are in TOML already.
+fn fill_template(template: &str, values: &[(&str, String)]) -> String {
+    let mut section = String::new();
+    let mut result = String::new();
+    for line in template.lines() {
+        let trimmed = line.trim();
+        let mut replaced = false;
+        if trimmed.starts_with('[') {
+            section = trimmed.trim_matches(|c| c == '[' || c == ']').to_owned();
+        } else if let Some((name, _)) = trimmed.split_once('=').filter(|_| !trimmed.starts_with('#')) {
+            let name = name.trim();
+            let option = match section.is_empty() {
+                true => name.to_owned(),
+                false => format!("{}.{}", &section, name)
+            };
+            if let Some((_, value)) = values.iter().find(|(o, _)| *o == option) {
+                result.push_str(&format!("{} = {}\n", name, value));
+                replaced = true;
+            }
+        }
+        if !replaced {
+            result.push_str(line);
+            result.push('\n');
+        }
+    }
+    result
+}
+
+#[derive(Deserialize)]
+#[serde(tag = "cmd", rename_all = "camelCase")]
+enum Cmd {
+    SetLanguage { code: String },
+    GenerateKey,
+    StopKey,
+    ImportKey,
+    BrowseDataDir,
+    CheckDns { listen: String },
+    TestConnection,
+    Finish { data_dir: String, dns_listen: String }
+}
+
+#[cfg(test)]
+mod tests {
+    use alfis_core::Settings;
+
+    use crate::wizard::make_config;
+
+    #[test]
+    fn test_make_config() {
+        let config = make_config("ru", Some("my key.toml"), "C:\\alfis", "127.0.0.1:5353");
+        let settings: Settings = toml::from_str(&config).unwrap();
+        assert_eq!(settings.language, "ru");
+        assert_eq!(settings.key_files, vec![String::from("my key.toml")]);
+        assert_eq!(settings.data_dir, "C:\\alfis");
+        assert_eq!(settings.dns.listen, "127.0.0.1:5353");
+        // Listen address of the network is not touched, and comments are kept
+        assert_eq!(settings.net.listen, "[::]:4244");
+        assert!(config.contains("# Your DNS resolver will be listening on this address"));
+    }
+}